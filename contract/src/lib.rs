@@ -10,14 +10,23 @@ use solana_program::{
     declare_id,
     entrypoint,
     entrypoint::ProgramResult,
-    program::{invoke, invoke_signed},
+    incinerator,
+    instruction::{AccountMeta, Instruction},
+    keccak,
+    msg,
+    program::{invoke, invoke_signed, set_return_data, MAX_RETURN_DATA},
+    program_option::COption,
     program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
+    system_instruction,
     system_program,
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
-use spl_associated_token_account::get_associated_token_address;
-use spl_token::state::{Account as TokenAccount, Mint};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
 use thiserror::Error;
 
 declare_id!("8DbZKwhFKq1Zi7HGSKfs6AsqS5CLWNCPZkQFuMKsntVt");
@@ -30,30 +39,110 @@ pub fn process_instruction(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    let instruction = EscrowInstruction::try_from_slice(instruction_data)
-        .map_err(|_| EscrowError::InvalidInstructionData)?;
+    let instruction = EscrowInstruction::unpack(instruction_data)?;
 
     match instruction {
-        EscrowInstruction::InitializeListing {
-            listing_id,
-            price_per_token,
-            quantity,
-            allow_partial,
-            fee_payment_method,
-            x402_payload,
-        } => initialize_listing(
-            program_id,
-            accounts,
-            listing_id,
-            price_per_token,
-            quantity,
-            allow_partial,
-            fee_payment_method,
-            x402_payload,
-        ),
+        EscrowInstruction::InitializeListing(args) => initialize_listing(program_id, accounts, args),
         EscrowInstruction::DepositTokens => deposit_tokens(program_id, accounts),
-        EscrowInstruction::Purchase { quantity } => purchase_tokens(program_id, accounts, quantity),
-        EscrowInstruction::CancelListing => cancel_listing(program_id, accounts),
+        EscrowInstruction::Purchase { quantity } => {
+            purchase_tokens(program_id, accounts, PurchaseSize::Exact(quantity), PurchaseOptions::default())
+        }
+        EscrowInstruction::CancelListing => cancel_listing(program_id, accounts, false),
+        EscrowInstruction::ReadListing => read_listing(program_id, accounts),
+        EscrowInstruction::SetPriceSchedule { price_steps } => {
+            set_price_schedule(program_id, accounts, price_steps)
+        }
+        EscrowInstruction::PurchaseFraction { bps } => {
+            purchase_tokens(program_id, accounts, PurchaseSize::FractionBps(bps), PurchaseOptions::default())
+        }
+        EscrowInstruction::DryRunInitialize(args) => dry_run_initialize(program_id, accounts, args),
+        EscrowInstruction::InitializeConfig { default_treasury } => {
+            initialize_config(program_id, accounts, default_treasury)
+        }
+        EscrowInstruction::SetTreasury { treasury } => set_treasury(program_id, accounts, treasury),
+        EscrowInstruction::ReleaseFeeEscrow => release_fee_escrow(program_id, accounts),
+        EscrowInstruction::SweepVaultLamports => sweep_vault_lamports(program_id, accounts),
+        EscrowInstruction::AddFeeExempt { seller } => add_fee_exempt(program_id, accounts, seller),
+        EscrowInstruction::RemoveFeeExempt { seller } => remove_fee_exempt(program_id, accounts, seller),
+        EscrowInstruction::RegisterInterest => register_interest(program_id, accounts),
+        EscrowInstruction::SetBasketLegs { legs } => set_basket_legs(program_id, accounts, legs),
+        EscrowInstruction::PurchaseCreatingSellerQuote { quantity } => {
+            let options = PurchaseOptions { create_seller_quote: true, ..PurchaseOptions::default() };
+            purchase_tokens(program_id, accounts, PurchaseSize::Exact(quantity), options)
+        }
+        EscrowInstruction::RegisterClaim { desired } => register_claim(program_id, accounts, desired),
+        EscrowInstruction::SettleClaims => settle_claims(program_id, accounts),
+        EscrowInstruction::SetAllowedBuyer { buyer } => set_allowed_buyer(program_id, accounts, buyer),
+        EscrowInstruction::PayInstallment { quote_amount } => pay_installment(program_id, accounts, quote_amount),
+        EscrowInstruction::SetMaxActiveListings { max_active_listings } => {
+            set_max_active_listings(program_id, accounts, max_active_listings)
+        }
+        EscrowInstruction::PlaceBid { amount } => place_bid(program_id, accounts, amount),
+        EscrowInstruction::FinalizeAuction => finalize_auction(program_id, accounts),
+        EscrowInstruction::PurchaseUpTo { max_quantity } => {
+            purchase_tokens(program_id, accounts, PurchaseSize::UpTo(max_quantity), PurchaseOptions::default())
+        }
+        EscrowInstruction::ForceCancel => cancel_listing(program_id, accounts, true),
+        EscrowInstruction::SetFeeMint => set_fee_mint(program_id, accounts),
+        EscrowInstruction::ReconcileListing => reconcile_listing(program_id, accounts),
+        EscrowInstruction::GetTimeRemaining => get_time_remaining(program_id, accounts),
+        EscrowInstruction::PurchaseWithRelayerTip { quantity, tip_lamports } => {
+            let options = PurchaseOptions { relayer_tip: Some(tip_lamports), ..PurchaseOptions::default() };
+            purchase_tokens(program_id, accounts, PurchaseSize::Exact(quantity), options)
+        }
+        EscrowInstruction::EnableStatusHistory => enable_status_history(program_id, accounts),
+        EscrowInstruction::PurchaseWithDelegate { quantity } => {
+            let options = PurchaseOptions { delegated: true, ..PurchaseOptions::default() };
+            purchase_tokens(program_id, accounts, PurchaseSize::Exact(quantity), options)
+        }
+        EscrowInstruction::ProposeAdmin { new_admin } => propose_admin(program_id, accounts, new_admin),
+        EscrowInstruction::AcceptAdmin => accept_admin(program_id, accounts),
+        EscrowInstruction::CommitPurchase { commitment } => commit_purchase(program_id, accounts, commitment),
+        EscrowInstruction::RevealPurchase { quantity, nonce } => reveal_purchase(program_id, accounts, quantity, nonce),
+        EscrowInstruction::SetMaxListingQuantity { max_listing_quantity } => {
+            set_max_listing_quantity(program_id, accounts, max_listing_quantity)
+        }
+        EscrowInstruction::UnfreezeListing => unfreeze_listing(program_id, accounts),
+        EscrowInstruction::EnableSwap { counterparty } => enable_swap(program_id, accounts, counterparty),
+        EscrowInstruction::DepositSwapQuote => deposit_swap_quote(program_id, accounts),
+        EscrowInstruction::SettleSwap => settle_swap(program_id, accounts),
+        EscrowInstruction::CancelSwap => cancel_swap(program_id, accounts),
+        EscrowInstruction::SimulatePurchase { quantity } => simulate_purchase(program_id, accounts, quantity),
+        EscrowInstruction::SetBurnBps { burn_bps } => set_burn_bps(program_id, accounts, burn_bps),
+        EscrowInstruction::SetAllowlistRoot { root } => set_allowlist_root(program_id, accounts, root),
+        EscrowInstruction::PurchaseWithProof { quantity, proof } => {
+            let options = PurchaseOptions { allowlist_proof: &proof, ..PurchaseOptions::default() };
+            purchase_tokens(program_id, accounts, PurchaseSize::Exact(quantity), options)
+        }
+        EscrowInstruction::ExpireListing => expire_listing(program_id, accounts),
+        EscrowInstruction::BatchCancel => batch_cancel(program_id, accounts),
+        EscrowInstruction::ReadGlobalStats => read_global_stats(program_id, accounts),
+        EscrowInstruction::SetCancellationFee { cancellation_fee } => {
+            set_cancellation_fee(program_id, accounts, cancellation_fee)
+        }
+        EscrowInstruction::EnableProceedsVault => enable_proceeds_vault(program_id, accounts),
+        EscrowInstruction::ClaimProceeds => claim_proceeds(program_id, accounts),
+        EscrowInstruction::PurchaseWithReceipt { quantity } => {
+            let options = PurchaseOptions { with_receipt: true, ..PurchaseOptions::default() };
+            purchase_tokens(program_id, accounts, PurchaseSize::Exact(quantity), options)
+        }
+        EscrowInstruction::SetMinListingQuantity { min_listing_quantity } => {
+            set_min_listing_quantity(program_id, accounts, min_listing_quantity)
+        }
+        EscrowInstruction::PurchaseWithReferencePrice { quantity, reference_price } => {
+            let options = PurchaseOptions { reference_price: Some(reference_price), ..PurchaseOptions::default() };
+            purchase_tokens(program_id, accounts, PurchaseSize::Exact(quantity), options)
+        }
+        EscrowInstruction::SetSplFeeAmount { spl_fee_amount } => {
+            set_spl_fee_amount(program_id, accounts, spl_fee_amount)
+        }
+        EscrowInstruction::CloseClaim => close_claim(program_id, accounts),
+        EscrowInstruction::ApproveDelegate { delegate, quantity, max_price } => {
+            approve_delegate(program_id, accounts, delegate, quantity, max_price)
+        }
+        EscrowInstruction::SetNativeFeeLamports { native_fee_lamports } => {
+            set_native_fee_lamports(program_id, accounts, native_fee_lamports)
+        }
     }
 }
 
@@ -61,29 +150,754 @@ pub fn process_instruction(
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub enum EscrowInstruction {
     /// Initialize a new listing. Expects the listing account to be already created.
-    InitializeListing {
-        /// External identifier supplied by the client (e.g. auto increment, timestamp).
-        listing_id: u64,
-        /// Price per base token in quote token units.
-        price_per_token: u64,
-        /// Total amount of base tokens available for sale.
-        quantity: u64,
-        /// Whether the listing can be partially filled.
-        allow_partial: bool,
-        /// Fee payment method (0 = NativeSol, 1 = X402).
-        fee_payment_method: u8,
-        /// x402 payment proof payload (base64-encoded, optional).
-        x402_payload: Option<String>,
-    },
+    ///
+    /// Accounts: seller (signer, writable), listing, vault authority PDA, vault ATA,
+    /// base mint, quote mint, system program (optional when no SOL moves and no
+    /// account is created), config PDA (optional; without it no fee is charged).
+    /// Once the config is initialized, the quote mint's treasury route PDA, the
+    /// resolved treasury wallet (writable) and the seller's fee exemption PDA
+    /// follow; the config's flat NativeSol fee in lamports is transferred to that
+    /// wallet unless the seller is exempt. When the config
+    /// caps active listings, the seller stats PDA (writable) comes next.
+    /// `SplToken` fees then pass the seller's and the treasury's fee-mint token
+    /// accounts and the token program, plus the fee mint (writable) when the
+    /// config burns part of the fee; NativeSol fees sent to the treasury pass
+    /// the incinerator (writable) instead. The global stats PDA (writable) may
+    /// be passed last to count the listing and its fee.
+    InitializeListing(InitializeListingArgs),
     /// Move seller tokens into the escrow vault, activating the listing.
+    /// Tranche listings append a vault authority/vault pair per extra tranche.
     DepositTokens,
     /// Allow a buyer to take `quantity` tokens from the listing.
+    ///
+    /// Accounts: buyer (signer, payer), listing, seller quote account, buyer quote
+    /// account, buyer base account, vault authority PDA, vault ATA, token program,
+    /// fills ledger PDA, system program. The price schedule PDA and buyer
+    /// attestation follow when the listing uses them. On tranche listings the
+    /// vault authority and vault may belong to any unlocked tranche. Listings
+    /// counted toward the seller's cap pass the seller stats PDA right after the
+    /// system program. The global stats PDA (writable) may be passed last to
+    /// count the fill's volume and taker fee.
     Purchase {
         /// Number of base tokens to purchase.
         quantity: u64,
     },
-    /// Seller cancels the listing, retrieving any remaining tokens.
+    /// Seller cancels the listing, retrieving any remaining tokens. Cancelling a
+    /// listing that was never funded marks it `Cancelled` and refunds its rent to
+    /// the seller, closing the account.
+    /// Listings with an escrowed fee also pass the fee escrow PDA and treasury
+    /// wallet; the unfilled share of the fee goes back to the seller.
+    ///
+    /// The signer may be the seller or the listing's cancel authority; when the
+    /// cancel authority signs, the seller wallet (writable) follows the token
+    /// program so refunds still reach the seller. Listings counted toward the
+    /// seller's cap then pass the seller stats PDA. Cancelling an active listing
+    /// with no fills that owes a `cancellation_fee` passes the treasury wallet
+    /// (writable) and system program last; the seller pays the fee and must
+    /// sign, co-signing when the cancel authority cancels.
+    /// Listings accruing proceeds pass the quote vault and seller quote account
+    /// before those, and any `proceeds_accrued` is paid out to the seller.
     CancelListing,
+    /// Write the Borsh-serialized listing to program return data so other
+    /// programs can read it via CPI without decoding the raw account.
+    ReadListing,
+    /// Freeze a step-price schedule for a listing that is still awaiting deposit.
+    /// Purchases are then priced across the steps instead of at `price_per_token`.
+    SetPriceSchedule {
+        /// `(cumulative threshold, price)` pairs with strictly increasing thresholds;
+        /// units up to each threshold are sold at that step's price.
+        price_steps: Vec<(u64, u64)>,
+    },
+    /// Buy a fraction of the remaining quantity, computed on-chain. Takes the
+    /// same accounts as `Purchase`.
+    PurchaseFraction {
+        /// Share of the remaining quantity in basis points (1..=10_000).
+        bps: u16,
+    },
+    /// Run every `InitializeListing` validation without writing any state and
+    /// return the listing fee it would charge (`u64`, little endian) as program
+    /// return data.
+    /// Accounts: seller, vault authority PDA, vault ATA, base mint, quote mint,
+    /// [config PDA, treasury route PDA, treasury, fee exemption PDA,
+    /// [seller stats PDA]].
+    DryRunInitialize(InitializeListingArgs),
+    /// Create the global config PDA; the signer becomes the admin.
+    /// Accounts: admin (signer, payer), config PDA, system program.
+    InitializeConfig {
+        /// Treasury receiving fees for quote mints without a dedicated route.
+        default_treasury: Pubkey,
+    },
+    /// Admin routes listing fees for a quote mint to a dedicated treasury.
+    /// Accounts: admin (signer, payer), config PDA, treasury route PDA, quote mint,
+    /// system program.
+    SetTreasury {
+        /// Wallet receiving fees for listings quoted in this mint.
+        treasury: Pubkey,
+    },
+    /// Pay out the escrowed fee of a completed listing to its treasury and
+    /// return the escrow rent to the seller.
+    /// Accounts: listing, fee escrow PDA, seller (writable), treasury wallet (writable).
+    ReleaseFeeEscrow,
+    /// Return lamports stranded in a vault authority PDA to the seller once the
+    /// listing is completed or cancelled.
+    /// Accounts: seller (signer, writable), listing, vault authority PDA (writable),
+    /// system program.
+    SweepVaultLamports,
+    /// Admin exempts a seller from listing fees.
+    /// Accounts: admin (signer, payer), config PDA, fee exemption PDA, system program.
+    AddFeeExempt {
+        /// Seller to exempt.
+        seller: Pubkey,
+    },
+    /// Admin revokes a seller's fee exemption, reclaiming its rent.
+    /// Accounts: admin (signer, writable), config PDA, fee exemption PDA.
+    RemoveFeeExempt {
+        /// Seller whose exemption is removed.
+        seller: Pubkey,
+    },
+    /// Anyone signals interest in an open listing, paying `INTEREST_FEE_LAMPORTS`
+    /// to the seller to deter spam.
+    /// Accounts: caller (signer, writable), listing (writable), seller (writable), system program.
+    RegisterInterest,
+    /// Turn a listing awaiting deposit into a basket: extra base mints sold
+    /// together with the listing's own base mint for a single quote payment.
+    /// Accounts: seller (signer, payer), listing, basket PDA, system program.
+    SetBasketLegs {
+        /// Additional legs, each with its own mint and fixed quantity.
+        legs: Vec<BasketLeg>,
+    },
+    /// `Purchase` that first creates the seller's quote ATA if it is missing,
+    /// the buyer paying its rent. Accounts: as `Purchase` (seller quote ATA
+    /// writable), with seller wallet, quote mint and associated token program
+    /// inserted after the system program.
+    PurchaseCreatingSellerQuote {
+        /// Number of base tokens to purchase.
+        quantity: u64,
+    },
+    /// Buyer registers intent to buy `desired` base units in a later
+    /// `SettleClaims`. No tokens move; the listing's vault authority is
+    /// approved as delegate over the buyer's quote account for the cost, so the
+    /// account may not already approve another delegate. The first claim opens
+    /// a round that takes claims for `CLAIM_WINDOW_SECS`.
+    /// Accounts: buyer (signer, payer), listing (writable), vault authority PDA,
+    /// claim PDA, buyer quote account (writable), token program, system program.
+    RegisterClaim {
+        /// Base units the buyer wants.
+        desired: u64,
+    },
+    /// Seller allocates the remaining quantity across the round's claims, pro
+    /// rata against all of them when oversubscribed, and settles the ones
+    /// passed. Claims whose approval no longer covers their share are closed
+    /// unfilled. Only in the `CLAIM_WINDOW_SECS` after registration closes.
+    /// Accounts: seller (signer, writable), listing, vault authority PDA, vault
+    /// ATA, seller quote account, token program, fills ledger PDA, system
+    /// program, [seller stats PDA for counted listings], then per claim: claim
+    /// PDA, buyer wallet (writable), buyer quote account, buyer base account.
+    SettleClaims,
+    /// Seller changes or clears the only buyer allowed to purchase, on a listing
+    /// awaiting deposit or active.
+    /// Accounts: seller (signer), listing.
+    SetAllowedBuyer {
+        /// New private-deal buyer, or `None` to open the listing to anyone.
+        buyer: Option<Pubkey>,
+    },
+    /// Buyer pays part of a deal and immediately receives the base tokens that
+    /// payment covers at `price_per_token`; a payment covering more than what
+    /// remains only charges for the remainder.
+    /// Accounts: buyer (signer, payer), listing, seller quote account, buyer
+    /// quote account, buyer base account, vault authority PDA, vault ATA, token
+    /// program, installment PDA, fills ledger PDA, system program, [seller stats
+    /// PDA for counted listings].
+    PayInstallment {
+        /// Quote units offered in this installment.
+        quote_amount: u64,
+    },
+    /// Admin caps how many listings a seller may have open at once.
+    /// Accounts: admin (signer), config PDA.
+    SetMaxActiveListings {
+        /// New cap; 0 removes the limit.
+        max_active_listings: u32,
+    },
+    /// Bid on an active auction listing. The bid is locked in the bid vault and
+    /// the previous high bid, if any, is refunded.
+    /// Accounts: bidder (signer), listing, bidder quote account, bid vault (the
+    /// vault authority's quote ATA), vault authority PDA, token program, then the
+    /// previous high bidder's quote account when there is one.
+    PlaceBid {
+        /// Total quote units offered for the whole quantity.
+        amount: u64,
+    },
+    /// Settle an auction after it closes: the seller receives the high bid and
+    /// the high bidder the base tokens. Anyone may call it.
+    /// Accounts: caller (signer, payer), listing, vault authority PDA, vault ATA,
+    /// bid vault, seller quote account, winner base account, token program, fills
+    /// ledger PDA, system program, [seller stats PDA for counted listings].
+    FinalizeAuction,
+    /// Buy `min(max_quantity, remaining)` instead of failing when less is left,
+    /// and return the quantity bought (`u64`, little endian) as program return
+    /// data. Takes the same accounts as `Purchase`.
+    PurchaseUpTo {
+        /// Most base tokens to buy.
+        max_quantity: u64,
+    },
+    /// Config admin cancels any listing, e.g. for a compliance takedown; unsold
+    /// tokens still go back to the seller. Logs a `ForceCancelled` event.
+    /// Accounts: admin (signer), listing, vault authority PDA, vault ATA, seller
+    /// base token account, token program, seller wallet (writable), config PDA,
+    /// then the same trailing accounts as `CancelListing`.
+    ForceCancel,
+    /// Admin designates the mint charged by `FeePaymentMethod::SplToken`.
+    /// Accounts: admin (signer), config PDA, fee mint.
+    SetFeeMint,
+    /// Compare the listing's vault balances with its recorded fills. If more
+    /// base has left the vaults than `filled` accounts for, the listing is
+    /// moved to `Frozen`. Anyone may call it.
+    /// Accounts: listing, [status history PDA if enabled], then the vault ATA of
+    /// each tranche in order.
+    ReconcileListing,
+    /// Write the seconds left until the listing's auction closes to return data,
+    /// as a little-endian `u64` (0 once closed or for listings with no deadline).
+    /// Accounts: listing.
+    GetTimeRemaining,
+    /// Same as `Purchase`, with the buyer also tipping the relayer that pays the
+    /// transaction fee. `tip_lamports` may be at most `MAX_RELAYER_TIP_LAMPORTS`.
+    /// Accounts: as `Purchase` with the buyer writable, plus the relayer wallet
+    /// (writable) right after the system program.
+    PurchaseWithRelayerTip {
+        /// Amount of base tokens to purchase.
+        quantity: u64,
+        /// Lamports the buyer pays the relayer.
+        tip_lamports: u64,
+    },
+    /// Seller starts recording the listing's status transitions in a status
+    /// history PDA (`["status_history", listing]`), seeded with the current status.
+    /// Accounts: seller (signer, payer), listing, status history PDA, system program.
+    EnableStatusHistory,
+    /// Same as `Purchase`, but paid through the buyer's `ApproveDelegate`
+    /// delegation, so the buyer need not sign. The submitter must be the
+    /// delegate it records (or the buyer), and the fill must stay within its
+    /// quantity and max price. Accounts: as `Purchase` with the buyer not
+    /// signing, plus the submitter (signer, payer) and the delegation PDA
+    /// (writable) right after the system program.
+    PurchaseWithDelegate {
+        /// Amount of base tokens to purchase.
+        quantity: u64,
+    },
+    /// Admin nominates a successor, who must confirm with `AcceptAdmin`.
+    /// Accounts: admin (signer), config PDA.
+    ProposeAdmin {
+        /// Key that may accept the admin role.
+        new_admin: Pubkey,
+    },
+    /// Proposed admin takes over the config.
+    /// Accounts: proposed admin (signer), config PDA.
+    AcceptAdmin,
+    /// First half of a commit-reveal purchase: the buyer records a hash of the
+    /// fill (see `purchase_commitment_hash`) without revealing its size.
+    /// Accounts: buyer (signer, payer), listing, commitment PDA, system program.
+    CommitPurchase {
+        /// `purchase_commitment_hash(buyer, quantity, nonce)`.
+        commitment: [u8; 32],
+    },
+    /// Second half of a commit-reveal purchase: at least `MIN_REVEAL_DELAY`
+    /// seconds after committing, executes the committed purchase and closes the
+    /// commitment, returning its rent to the buyer.
+    /// Accounts: commitment PDA, then the same accounts as `Purchase`.
+    RevealPurchase {
+        /// Amount of base tokens to purchase.
+        quantity: u64,
+        /// Secret salt hashed into the commitment.
+        nonce: u64,
+    },
+    /// Admin caps the base `quantity` a listing may be created with.
+    /// Accounts: admin (signer), config PDA.
+    SetMaxListingQuantity {
+        /// New cap in base units; 0 removes the limit.
+        max_listing_quantity: u64,
+    },
+    /// Admin returns a frozen listing to `Active` once its vaults again hold
+    /// at least the unfilled quantity.
+    /// Accounts: admin (signer), config PDA, listing, [status history PDA if
+    /// enabled], then the vault ATA of each tranche in order.
+    UnfreezeListing,
+    /// Seller turns an unfunded, all-or-nothing listing into a two-sided swap
+    /// with `counterparty`, who escrows the full quote with `DepositSwapQuote`.
+    /// Swaps settle only through `SettleSwap`; `Purchase` and its variants are
+    /// rejected. Accounts: seller (signer), listing.
+    EnableSwap {
+        /// Only party that may deposit quote and receive the base.
+        counterparty: Pubkey,
+    },
+    /// Counterparty locks the quote for the whole quantity in the quote vault
+    /// (the vault authority's quote ATA), before or after the seller deposits.
+    /// Accounts: counterparty (signer), listing, counterparty quote account,
+    /// quote vault, token program.
+    DepositSwapQuote,
+    /// Once both sides are deposited, pays the quote vault to the seller and the
+    /// base vault to the counterparty in one instruction. Only the seller or the
+    /// counterparty may call it.
+    /// Accounts: seller or counterparty (signer, payer), listing, vault authority PDA, vault ATA,
+    /// quote vault, seller quote account, counterparty base account, token
+    /// program, fills ledger PDA, system program.
+    SettleSwap,
+    /// Seller or counterparty calls off an unsettled swap: each side's deposit
+    /// goes back to whoever made it. An unfunded listing is marked `Cancelled`
+    /// and its rent refunded to the seller.
+    /// Accounts: seller or counterparty (signer), listing, seller (writable),
+    /// vault authority PDA, vault ATA, seller base account, quote vault,
+    /// counterparty quote account, token program.
+    CancelSwap,
+    /// Run the `Purchase` pricing, fee and fill-limit checks for `quantity`
+    /// without moving tokens, and write a Borsh `SimulatedPurchase` to return
+    /// data. Buyer-specific checks (allowlist, attestation, balance) and tranche
+    /// locks are not simulated.
+    /// Accounts: listing, then the price schedule PDA or oracle, as for `Purchase`.
+    SimulatePurchase {
+        /// Amount of base tokens to price.
+        quantity: u64,
+    },
+    /// Admin sets the share of each listing fee that is burned instead of
+    /// paid to the treasury.
+    /// Accounts: admin (signer), config PDA.
+    SetBurnBps {
+        /// Burned share in basis points; 0 sends the whole fee to the treasury.
+        burn_bps: u16,
+    },
+    /// Seller restricts a listing awaiting deposit or active to the buyers in
+    /// a Merkle tree built with `allowlist_leaf` and `allowlist_node`.
+    /// Accounts: seller (signer), listing.
+    SetAllowlistRoot {
+        /// Tree root, or `None` to drop the allowlist.
+        root: Option<[u8; 32]>,
+    },
+    /// `Purchase` by a buyer proving membership in the listing's allowlist.
+    /// Accounts: same as `Purchase`.
+    PurchaseWithProof {
+        /// Amount of base tokens to purchase.
+        quantity: u64,
+        /// Sibling hashes from the buyer's leaf up to the root.
+        proof: Vec<[u8; 32]>,
+    },
+    /// Anyone closes a listing still awaiting deposit after its
+    /// `deposit_deadline`, marking it `Cancelled` and refunding its rent to the
+    /// seller.
+    /// Accounts: caller (signer), listing, seller (writable), [seller stats PDA
+    /// for counted listings], [status history PDA], [fee escrow PDA and
+    /// treasury wallet for escrowed fees].
+    ExpireListing,
+    /// Seller cancels up to `MAX_BATCH_LEGS` of their listings at once,
+    /// skipping any already completed, cancelled or closed. Listings that
+    /// owe a cancellation fee must go through `CancelListing`.
+    /// Accounts: seller (signer, writable), token program, then per listing:
+    /// listing, vault authority PDA, vault ATA, seller base token account,
+    /// [seller stats PDA for counted listings].
+    BatchCancel,
+    /// Write the Borsh-serialized `GlobalStats` to program return data.
+    /// Accounts: global stats PDA.
+    ReadGlobalStats,
+    /// Admin sets the lamports paid to the treasury for cancelling an active
+    /// listing that never had a fill. Listings keep the fee set when they were
+    /// created.
+    /// Accounts: admin (signer), config PDA.
+    SetCancellationFee {
+        /// Fee in lamports; 0 disables it.
+        cancellation_fee: u64,
+    },
+    /// Seller switches purchases to pay into a quote vault (the vault
+    /// authority's quote ATA, created if missing) instead of their own account.
+    /// Accounts: seller (signer, payer), listing, vault authority PDA, quote
+    /// vault, quote mint, system program, token program, associated token program.
+    EnableProceedsVault,
+    /// Seller sweeps the proceeds accrued in the quote vault to their quote account.
+    /// Accounts: seller (signer), listing, vault authority PDA, quote vault,
+    /// seller quote account, token program.
+    ClaimProceeds,
+    /// `Purchase` that also creates a `Receipt` PDA for the buyer, who pays its rent.
+    /// Accounts: as `Purchase`, plus the receipt PDA (`["receipt", listing,
+    /// buyer, fill_index]`, writable) right after the system program.
+    PurchaseWithReceipt {
+        /// Amount of base tokens to purchase.
+        quantity: u64,
+    },
+    /// Admin sets the smallest base `quantity` a fungible listing may be created with.
+    /// Accounts: admin (signer), config PDA.
+    SetMinListingQuantity {
+        /// New minimum in base units; 0 removes it.
+        min_listing_quantity: u64,
+    },
+    /// `Purchase` that fails with `SlippageExceeded` if the oracle price has moved
+    /// more than the listing's `max_slippage_bps` from the buyer's reference.
+    /// Accounts: as `Purchase`.
+    PurchaseWithReferencePrice {
+        /// Amount of base tokens to purchase.
+        quantity: u64,
+        /// Spread-adjusted per-token price the buyer expects, e.g. from `SimulatePurchase`.
+        reference_price: u64,
+    },
+    /// Admin sets the flat listing fee charged by `FeePaymentMethod::SplToken`,
+    /// in the fee mint's atomic units.
+    /// Accounts: admin (signer), config PDA.
+    SetSplFeeAmount {
+        /// Fee in fee-mint units; 0 stops offering `SplToken` fees.
+        spl_fee_amount: u64,
+    },
+    /// Buyer withdraws a claim, revoking the vault authority's approval on the
+    /// quote account if it is still the delegate, and reclaiming the rent. Allowed while the round takes claims, once its
+    /// settlement window has lapsed, or when the listing is no longer active.
+    /// Accounts: buyer (signer, writable), listing (writable), claim PDA, buyer
+    /// quote account (writable), token program.
+    CloseClaim,
+    /// Buyer lets `delegate` buy up to `quantity` base units at no more than
+    /// `max_price` per token with `PurchaseWithDelegate`, approving the
+    /// delegation PDA (not the vault authority) over their quote account for
+    /// that cost plus any taker fee. Fails with `QuoteDelegateInUse` if the
+    /// account already approves another key, such as a pending claim. A
+    /// `quantity` of 0 revokes the PDA's approval, if still in place, and
+    /// closes the PDA.
+    /// Accounts: buyer (signer, payer), listing, delegation PDA, buyer quote
+    /// account (writable), token program, system program.
+    ApproveDelegate {
+        /// Only key, besides the buyer, that may submit the purchases.
+        delegate: Pubkey,
+        /// Base units the delegate may buy in total.
+        quantity: u64,
+        /// Highest per-token price, in the units of `price_per_token`.
+        max_price: u64,
+    },
+    /// Admin sets the flat listing fee charged by `FeePaymentMethod::NativeSol`,
+    /// in lamports.
+    /// Accounts: admin (signer), config PDA.
+    SetNativeFeeLamports {
+        /// Fee in lamports; 0 makes NativeSol listings free.
+        native_fee_lamports: u64,
+    },
+}
+
+impl EscrowInstruction {
+    /// Decode instruction data: a one-byte code followed by the variant's Borsh-encoded fields.
+    ///
+    /// Codes are fixed per instruction so new variants only ever take the next free byte; the first
+    /// four keep the indices they had when the enum was decoded implicitly.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        let (&code, mut rest) = data.split_first().ok_or(EscrowError::InvalidInstructionData)?;
+        let input = &mut rest;
+        let instruction = match code {
+            0 => Self::InitializeListing(decode(input)?),
+            1 => Self::DepositTokens,
+            2 => Self::Purchase { quantity: decode(input)? },
+            3 => Self::CancelListing,
+            4 => Self::ReadListing,
+            5 => Self::SetPriceSchedule {
+                price_steps: decode(input)?,
+            },
+            6 => Self::PurchaseFraction { bps: decode(input)? },
+            7 => Self::DryRunInitialize(decode(input)?),
+            8 => Self::InitializeConfig {
+                default_treasury: decode(input)?,
+            },
+            9 => Self::SetTreasury { treasury: decode(input)? },
+            10 => Self::ReleaseFeeEscrow,
+            11 => Self::SweepVaultLamports,
+            12 => Self::AddFeeExempt { seller: decode(input)? },
+            13 => Self::RemoveFeeExempt { seller: decode(input)? },
+            14 => Self::RegisterInterest,
+            15 => Self::SetBasketLegs { legs: decode(input)? },
+            16 => Self::PurchaseCreatingSellerQuote { quantity: decode(input)? },
+            17 => Self::RegisterClaim { desired: decode(input)? },
+            18 => Self::SettleClaims,
+            19 => Self::SetAllowedBuyer { buyer: decode(input)? },
+            20 => Self::PayInstallment {
+                quote_amount: decode(input)?,
+            },
+            21 => Self::SetMaxActiveListings {
+                max_active_listings: decode(input)?,
+            },
+            22 => Self::PlaceBid { amount: decode(input)? },
+            23 => Self::FinalizeAuction,
+            24 => Self::PurchaseUpTo {
+                max_quantity: decode(input)?,
+            },
+            25 => Self::ForceCancel,
+            26 => Self::SetFeeMint,
+            27 => Self::ReconcileListing,
+            28 => Self::GetTimeRemaining,
+            29 => Self::PurchaseWithRelayerTip {
+                quantity: decode(input)?,
+                tip_lamports: decode(input)?,
+            },
+            30 => Self::EnableStatusHistory,
+            31 => Self::PurchaseWithDelegate { quantity: decode(input)? },
+            32 => Self::ProposeAdmin { new_admin: decode(input)? },
+            33 => Self::AcceptAdmin,
+            34 => Self::CommitPurchase { commitment: decode(input)? },
+            35 => Self::RevealPurchase {
+                quantity: decode(input)?,
+                nonce: decode(input)?,
+            },
+            36 => Self::SetMaxListingQuantity {
+                max_listing_quantity: decode(input)?,
+            },
+            37 => Self::UnfreezeListing,
+            38 => Self::EnableSwap {
+                counterparty: decode(input)?,
+            },
+            39 => Self::DepositSwapQuote,
+            40 => Self::SettleSwap,
+            41 => Self::CancelSwap,
+            42 => Self::SimulatePurchase { quantity: decode(input)? },
+            43 => Self::SetBurnBps { burn_bps: decode(input)? },
+            44 => Self::SetAllowlistRoot { root: decode(input)? },
+            45 => Self::PurchaseWithProof {
+                quantity: decode(input)?,
+                proof: decode(input)?,
+            },
+            46 => Self::ExpireListing,
+            47 => Self::BatchCancel,
+            48 => Self::ReadGlobalStats,
+            49 => Self::SetCancellationFee { cancellation_fee: decode(input)? },
+            50 => Self::EnableProceedsVault,
+            51 => Self::ClaimProceeds,
+            52 => Self::PurchaseWithReceipt { quantity: decode(input)? },
+            53 => Self::SetMinListingQuantity { min_listing_quantity: decode(input)? },
+            54 => Self::PurchaseWithReferencePrice { quantity: decode(input)?, reference_price: decode(input)? },
+            55 => Self::SetSplFeeAmount { spl_fee_amount: decode(input)? },
+            56 => Self::CloseClaim,
+            57 => Self::ApproveDelegate {
+                delegate: decode(input)?,
+                quantity: decode(input)?,
+                max_price: decode(input)?,
+            },
+            58 => Self::SetNativeFeeLamports { native_fee_lamports: decode(input)? },
+            _ => return Err(EscrowError::InvalidInstructionData.into()),
+        };
+        if !input.is_empty() {
+            return Err(EscrowError::InvalidInstructionData.into());
+        }
+        Ok(instruction)
+    }
+}
+
+/// Borsh-decode one instruction field, mapping failures to `InvalidInstructionData`.
+fn decode<T: BorshDeserialize>(input: &mut &[u8]) -> Result<T, ProgramError> {
+    T::deserialize(input).map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+/// Denominator for basis-point amounts.
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Price of one whole base token in whole quote tokens, as a reduced
+/// `(numerator, denominator)` pair: `(5, 4)` renders as "1.25 quote per token".
+///
+/// `price_per_token` is already quoted in quote units per whole base token, so
+/// the base decimals cancel out; the parameter is kept so callers can pass a
+/// listing's fields as-is. Quote decimals past what a `u64` denominator can hold
+/// (more than 19) drop the excess precision from the numerator.
+pub fn display_price(price_per_token: u64, _base_decimals: u8, quote_decimals: u8) -> (u64, u64) {
+    let mut numerator = price_per_token;
+    let mut denominator = 1u64;
+    for _ in 0..quote_decimals {
+        if numerator.is_multiple_of(10) {
+            numerator /= 10;
+        } else if let Some(scaled) = denominator.checked_mul(10) {
+            denominator = scaled;
+        } else {
+            numerator /= 10;
+        }
+    }
+    if numerator == 0 {
+        return (0, 1);
+    }
+    // Only factors of 2 and 5 can still be shared with a power-of-ten denominator.
+    for factor in [2, 5] {
+        while numerator.is_multiple_of(factor) && denominator.is_multiple_of(factor) {
+            numerator /= factor;
+            denominator /= factor;
+        }
+    }
+    (numerator, denominator)
+}
+
+/// Globally unique handle for a listing: the Keccak hash of the seller, the
+/// client-chosen `listing_id` and the creation timestamp, all little-endian.
+pub fn canonical_listing_id(seller: &Pubkey, listing_id: u64, created_ts: i64) -> [u8; 32] {
+    keccak::hashv(&[seller.as_ref(), &listing_id.to_le_bytes(), &created_ts.to_le_bytes()]).to_bytes()
+}
+
+/// How a purchase specifies the base quantity to buy.
+enum PurchaseSize {
+    /// Exactly this many base units.
+    Exact(u64),
+    /// This share of the remaining quantity, in basis points, rounded down.
+    FractionBps(u16),
+    /// At most this many base units, clamped to the remaining quantity.
+    UpTo(u64),
+}
+
+impl PurchaseSize {
+    /// Resolve to a base quantity against the listing's remaining inventory.
+    fn resolve(self, remaining: u64) -> Result<u64, ProgramError> {
+        let quantity = match self {
+            PurchaseSize::Exact(quantity) => quantity,
+            PurchaseSize::FractionBps(bps) => {
+                if bps == 0 || u64::from(bps) > BPS_DENOMINATOR {
+                    return Err(EscrowError::InvalidBasisPoints.into());
+                }
+                let quantity = u128::from(remaining) * u128::from(bps) / u128::from(BPS_DENOMINATOR);
+                u64::try_from(quantity).map_err(|_| EscrowError::AmountOverflow)?
+            }
+            PurchaseSize::UpTo(max_quantity) => max_quantity.min(remaining),
+        };
+        if quantity == 0 {
+            return Err(EscrowError::AmountOverflow.into());
+        }
+        Ok(quantity)
+    }
+}
+
+/// What a purchase variant adds to a plain `Purchase`.
+#[derive(Default)]
+struct PurchaseOptions<'a> {
+    /// Create the seller's quote ATA if it is missing.
+    create_seller_quote: bool,
+    /// Lamports the buyer tips the fee payer.
+    relayer_tip: Option<u64>,
+    /// Spend through the buyer's delegation instead of the buyer's signature.
+    delegated: bool,
+    /// Mint a receipt PDA for the fill.
+    with_receipt: bool,
+    /// Merkle proof of the buyer's place in the listing's allowlist.
+    allowlist_proof: &'a [[u8; 32]],
+    /// Per-token price the buyer expects, checked against the slippage bound.
+    reference_price: Option<u64>,
+}
+
+/// Outcome of a purchase as priced by `SimulatePurchase`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SimulatedPurchase {
+    /// Base units the buyer would receive.
+    pub base_out: u64,
+    /// Quote units the buyer would pay in total, taker fee included.
+    pub quote_in: u64,
+    /// Part of `quote_in` that would go to the fee treasury.
+    pub taker_fee: u64,
+    /// Whether the fill would complete the listing.
+    pub would_complete: bool,
+    /// The seller's note to buyers, as stored on the listing.
+    pub note: [u8; NOTE_LEN],
+}
+
+/// Parameters for creating a listing, shared by `InitializeListing` and `DryRunInitialize`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct InitializeListingArgs {
+    /// External identifier supplied by the client (e.g. auto increment, timestamp).
+    pub listing_id: u64,
+    /// Price per base token in quote token units.
+    pub price_per_token: u64,
+    /// Total amount of base tokens available for sale.
+    pub quantity: u64,
+    /// Whether the listing can be partially filled.
+    pub allow_partial: bool,
+    /// Fee payment method (0 = NativeSol, 1 = X402).
+    pub fee_payment_method: u8,
+    /// x402 payment proof payload (base64-encoded, optional).
+    pub x402_payload: Option<String>,
+    /// Issuer whose attestation buyers must hold to purchase (none = open to all).
+    pub required_attestation_issuer: Option<Pubkey>,
+    /// Hold a NativeSol fee in escrow so the unearned share is refunded on cancel.
+    pub escrow_fee: bool,
+    /// Number of tranche vaults the inventory is split across (1 = single vault).
+    pub vault_count: u8,
+    /// Seconds between tranche unlocks; tranche `i` unlocks `i * tranche_interval`
+    /// after the deposit. Ignored for single-vault listings.
+    pub tranche_interval: i64,
+    /// Agent allowed to cancel the listing alongside the seller (none = seller only).
+    pub cancel_authority: Option<Pubkey>,
+    /// Smallest fill as basis points of the total quantity (0 = no minimum).
+    pub min_fill_bps: u16,
+    /// Largest fill as basis points of the total quantity (0 = no maximum).
+    pub max_fill_bps: u16,
+    /// Price feed that sets the per-token price at purchase time (none = static price).
+    pub oracle: Option<Pubkey>,
+    /// Signed spread applied to the oracle price, in basis points.
+    pub spread_bps: i32,
+    /// Smallest quote amount a single fill may pay (0 = no minimum).
+    pub min_fill_quote: u64,
+    /// Unix timestamp closing an English auction for the whole quantity
+    /// (0 = fixed-price listing).
+    pub auction_end: i64,
+    /// Oracle price at or above which purchases may fill (0 = no trigger).
+    /// Requires an oracle.
+    pub trigger_price: u64,
+    /// Quote proceeds after which the listing completes, even with inventory
+    /// left (0 = no cap).
+    pub max_quote_raise: u64,
+    /// Price grid `price_per_token` must sit on (0 = any price).
+    pub tick_size: u64,
+    /// Quote units paid per `quote_per_base_den` base units, in raw amounts
+    /// (0 = priced by `price_per_token`). Requires `price_per_token` = 0.
+    pub quote_per_base_num: u64,
+    /// Base units `quote_per_base_num` buys (0 = priced by `price_per_token`).
+    pub quote_per_base_den: u64,
+    /// Fee charged to the buyer on each purchase, in basis points of the quote
+    /// amount and paid to the fee treasury (0 = none). Requires a config.
+    pub taker_fee_bps: u16,
+    /// `ListingCategory` byte marketplaces filter on.
+    pub category: u8,
+    /// UTF-8 message to buyers, zero-padded (all zeros = none).
+    pub note: [u8; NOTE_LEN],
+    /// Unix timestamp after which the listing can no longer be funded and
+    /// anyone may close it with `ExpireListing` (0 = no deadline).
+    pub deposit_deadline: i64,
+    /// Complete the listing after this many purchases, even with inventory
+    /// left for the seller to reclaim by cancelling (0 = never).
+    pub auto_complete_after_fills: u16,
+    /// Largest move of the oracle price away from a buyer's reference price,
+    /// in basis points. Requires an oracle.
+    pub max_slippage_bps: u16,
+    /// Unix timestamp before which accrued proceeds cannot be claimed
+    /// (0 = no cliff).
+    pub proceeds_unlock_ts: i64,
+    /// Oldest oracle price, in seconds, a purchase may use
+    /// (0 = `MAX_ORACLE_STALENESS`). Requires an oracle.
+    pub max_oracle_staleness_secs: u32,
+}
+
+/// A fixed-price, single-vault listing with no optional features set.
+impl Default for InitializeListingArgs {
+    fn default() -> Self {
+        Self {
+            listing_id: 0,
+            price_per_token: 0,
+            quantity: 0,
+            allow_partial: false,
+            fee_payment_method: FeePaymentMethod::NativeSol.as_u8(),
+            x402_payload: None,
+            required_attestation_issuer: None,
+            escrow_fee: false,
+            vault_count: 1,
+            tranche_interval: 0,
+            cancel_authority: None,
+            min_fill_bps: 0,
+            max_fill_bps: 0,
+            oracle: None,
+            spread_bps: 0,
+            min_fill_quote: 0,
+            auction_end: 0,
+            trigger_price: 0,
+            max_quote_raise: 0,
+            tick_size: 0,
+            quote_per_base_num: 0,
+            quote_per_base_den: 0,
+            taker_fee_bps: 0,
+            category: ListingCategory::Uncategorized.as_u8(),
+            note: [0; NOTE_LEN],
+            deposit_deadline: 0,
+            auto_complete_after_fills: 0,
+            max_slippage_bps: 0,
+            proceeds_unlock_ts: 0,
+            max_oracle_staleness_secs: 0,
+        }
+    }
 }
 
 /// Fee payment method for listing creation.
@@ -94,10 +908,36 @@ pub enum FeePaymentMethod {
     NativeSol = 0,
     /// Pay fee via x402 payment protocol.
     X402 = 1,
+    /// Pay fee in the SPL token mint designated by the config.
+    SplToken = 2,
 }
 
 impl FeePaymentMethod {
-    fn as_u8(self) -> u8 {
+    /// Raw byte stored on the listing.
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Marketplace category a seller files a listing under.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive, BorshSerialize, BorshDeserialize)]
+pub enum ListingCategory {
+    /// No category given (default).
+    Uncategorized = 0,
+    /// Governance or DAO tokens.
+    Governance = 1,
+    /// Meme tokens.
+    Meme = 2,
+    /// Stablecoins.
+    Stablecoin = 3,
+    /// Utility tokens.
+    Utility = 4,
+}
+
+impl ListingCategory {
+    /// Raw byte stored on the listing.
+    pub fn as_u8(self) -> u8 {
         self as u8
     }
 }
@@ -114,6 +954,9 @@ pub enum ListingStatus {
     Completed = 2,
     /// Listing was cancelled by the seller.
     Cancelled = 3,
+    /// Vault outflow exceeded the recorded fills; purchases are blocked until
+    /// the admin reviews it.
+    Frozen = 4,
 }
 
 impl ListingStatus {
@@ -153,500 +996,5569 @@ pub struct Listing {
     pub fee_payment_method: u8,
     /// Amount paid as listing fee (1% of trade value).
     pub fee_amount_paid: u64,
-    /// SHA256 hash of x402 payment proof (if X402 method used).
+    /// Keccak-256 hash of x402 payment proof (if X402 method used).
     pub x402_payload_hash: [u8; 32],
+    /// Treasury the listing fee was routed to (default pubkey if fees were not enabled).
+    pub fee_treasury: Pubkey,
+    /// Issuer program buyers need an attestation from (default pubkey if not required).
+    pub required_attestation_issuer: Pubkey,
+    /// Number of tranche vaults holding the inventory.
+    pub vault_count: u8,
+    /// Seconds between tranche unlocks.
+    pub tranche_interval: i64,
+    /// Unix timestamp of the deposit; tranche unlocks are measured from here.
+    pub activated_at: i64,
+    /// Agent that may cancel in addition to the seller (default pubkey if none).
+    pub cancel_authority: Pubkey,
+    /// Smallest fill as basis points of `quantity` (0 = no minimum).
+    pub min_fill_bps: u16,
+    /// Largest fill as basis points of `quantity` (0 = no maximum).
+    pub max_fill_bps: u16,
+    /// Price feed purchases are priced from (default pubkey for a static price).
+    pub oracle: Pubkey,
+    /// Signed spread applied to the oracle price, in basis points.
+    pub spread_bps: i32,
+    /// Number of `RegisterInterest` calls, a soft popularity signal.
+    pub interest_count: u64,
+    /// Smallest quote amount a single fill may pay (0 = no minimum).
+    pub min_fill_quote: u64,
+    /// Only buyer allowed to purchase, for private deals (default pubkey = anyone).
+    pub allowed_buyer: Pubkey,
+    /// Unix timestamp after which no more bids are taken (0 = not an auction).
+    pub auction_end: i64,
+    /// Quote locked in the bid vault: the highest auction bid, or a swap counterparty's deposit.
+    pub high_bid: u64,
+    /// Bidder or swap counterparty holding `high_bid` (default pubkey = nothing locked).
+    pub high_bidder: Pubkey,
+    /// Mint `fee_amount_paid` is denominated in for `SplToken` fees (default pubkey otherwise).
+    pub fee_mint: Pubkey,
+    /// Oracle price below which purchases revert with `TriggerNotMet` (0 = no trigger).
+    pub trigger_price: u64,
+    /// Quote proceeds at which purchases complete the listing (0 = no cap).
+    pub max_quote_raise: u64,
+    /// Quote paid to the seller so far through `Purchase` and its variants.
+    pub quote_received: u64,
+    /// Price grid `price_per_token` sits on (0 = no grid).
+    pub tick_size: u64,
+    /// `canonical_listing_id` of the seller, `listing_id` and creation time,
+    /// unique across sellers for indexers.
+    pub canonical_id: [u8; 32],
+    /// Raw quote units paid per `quote_per_base_den` raw base units (0 = not ratio priced).
+    pub quote_per_base_num: u64,
+    /// Raw base units `quote_per_base_num` buys (0 = not ratio priced).
+    pub quote_per_base_den: u64,
+    /// Buyer-paid fee per purchase, in basis points of the quote amount, sent to `fee_treasury`.
+    pub taker_fee_bps: u16,
+    /// `ListingCategory` byte for marketplace filters.
+    pub category: u8,
+    /// Seller's UTF-8 message to buyers, zero-padded.
+    pub note: [u8; NOTE_LEN],
+    /// Merkle root of the buyers allowed to purchase (all zeros = anyone).
+    pub allowlist_root: [u8; 32],
+    /// Unix timestamp after which an unfunded listing can be expired (0 = none).
+    pub deposit_deadline: i64,
+    /// Quote paid into the quote vault and not yet claimed by the seller.
+    pub proceeds_accrued: u64,
+    /// Purchases after which the listing completes (0 = only when sold out).
+    pub auto_complete_after_fills: u16,
+    /// Purchases filled so far.
+    pub fill_count: u16,
+    /// Tolerance for `PurchaseWithReferencePrice`, in basis points of the reference.
+    pub max_slippage_bps: u16,
+    /// Claims registered in the current round and not yet settled or closed.
+    pub claim_count: u16,
+    /// Sum of `desired` over the current round's claims.
+    pub claims_desired: u64,
+    /// When the current round stops taking claims and settlement opens (0 = no round).
+    pub claims_close_at: i64,
+    /// `remaining` when settlement of the current round began, shared out pro rata.
+    pub claims_supply: u64,
+    /// Lamports owed for cancelling the listing while active and unfilled, taken
+    /// from the config at creation (0 = none).
+    pub cancellation_fee: u64,
+    /// Unix timestamp before which `ClaimProceeds` fails (0 = no cliff).
+    pub proceeds_unlock_ts: i64,
+    /// Oldest oracle price, in seconds, a purchase may use (0 = `MAX_ORACLE_STALENESS`).
+    pub max_oracle_staleness_secs: u32,
 }
 
+/// Flag bit set when partial fills are allowed.
+const FLAG_ALLOW_PARTIAL: u8 = 0b0000_0001;
+/// Flag bit set when purchases are priced by a `PriceSchedule`.
+const FLAG_PRICE_SCHEDULE: u8 = 0b0000_0010;
+/// Flag bit set when the listing fee is held in a fee escrow PDA.
+const FLAG_FEE_ESCROW: u8 = 0b0000_0100;
+/// Flag bit set when the listing sells a basket of additional legs.
+const FLAG_BASKET: u8 = 0b0000_1000;
+/// Flag bit set when the listing holds a slot in its seller's active listing count.
+const FLAG_COUNTED: u8 = 0b0001_0000;
+/// Flag bit set when status transitions are recorded in a `StatusHistory` PDA.
+const FLAG_STATUS_HISTORY: u8 = 0b0010_0000;
+/// Flag bit set when the listing is a two-sided swap settled by `SettleSwap`.
+const FLAG_SWAP: u8 = 0b0100_0000;
+/// Flag bit set when purchase proceeds accrue in a quote vault until `ClaimProceeds`.
+const FLAG_PROCEEDS_VAULT: u8 = 0b1000_0000;
+
 impl Listing {
     /// Number of bytes required to store the listing.
-    pub const LEN: usize = 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 1 + 1 + 1 + 1 + 1 + 8 + 32;
+    pub const LEN: usize = 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 1 + 1 + 1 + 1 + 1 + 8 + 32 + 32 + 32 + 1 + 8 + 8 + 32
+        + 2 + 2 + 32 + 4 + 8 + 8 + 32 + 8 + 8 + 32 + 32 + 8 + 8 + 8 + 8 + 32 + 8 + 8 + 2 + 1 + NOTE_LEN + 32 + 8 + 8
+        + 2 + 2 + 2 + 2 + 8 + 8 + 8 + 8 + 8 + 4;
 
     /// Whether partial fills are allowed.
     pub fn allow_partial(&self) -> bool {
-        self.flags & 0b0000_0001 == 1
+        self.flags & FLAG_ALLOW_PARTIAL != 0
     }
 
-    /// Convenience for remaining base tokens still available.
-    pub fn remaining(&self) -> u64 {
-        self.quantity.saturating_sub(self.filled)
+    /// Whether purchases are priced by a step schedule.
+    pub fn has_price_schedule(&self) -> bool {
+        self.flags & FLAG_PRICE_SCHEDULE != 0
     }
 
-    /// Current status as enum.
-    pub fn status(&self) -> ListingStatus {
-        ListingStatus::from_u8(self.status).unwrap_or(ListingStatus::Cancelled)
+    /// Whether the listing fee is held in escrow until the listing settles.
+    pub fn has_fee_escrow(&self) -> bool {
+        self.flags & FLAG_FEE_ESCROW != 0
     }
 
-    /// Update status.
-    pub fn set_status(&mut self, status: ListingStatus) {
-        self.status = status.as_u8();
+    /// Whether the listing also sells the legs of a `Basket`.
+    pub fn has_basket(&self) -> bool {
+        self.flags & FLAG_BASKET != 0
     }
-}
 
-/// Escrow program specific errors.
-#[derive(Debug, Error)]
-pub enum EscrowError {
-    /// Supplied instruction data could not be parsed.
-    #[error("Invalid instruction data")]
-    InvalidInstructionData,
-    /// Account data length was unexpected.
-    #[error("Account length mismatch")]
-    AccountLengthMismatch,
-    /// Listing already initialised.
-    #[error("Listing already initialised")]
-    AlreadyInitialized,
-    /// Caller does not match expected authority.
-    #[error("Incorrect authority provided")]
-    IncorrectAuthority,
-    /// Listing not ready for this operation.
-    #[error("Invalid listing status for action")]
-    InvalidListingStatus,
-    /// Math overflow or invalid quantity.
-    #[error("Amount overflow or invalid quantity")]
-    AmountOverflow,
-    /// Provided accounts do not match expected mints.
-    #[error("Token mint mismatch")]
-    MintMismatch,
-    /// Not enough tokens remain to satisfy the purchase.
-    #[error("Insufficient remaining quantity")]
-    InsufficientQuantity,
-    /// Partial fills are disabled.
-    #[error("Partial fills disabled")]
-    PartialFillDisabled,
-    /// x402 payment proof missing or invalid.
-    #[error("x402 payment proof missing or invalid")]
-    InvalidX402Proof,
-    /// x402 payment amount mismatch.
-    #[error("x402 payment amount mismatch")]
-    X402AmountMismatch,
-}
+    /// Whether cancelling now owes the listing's cancellation fee: one was set
+    /// when it was created, and it is active and has never been filled.
+    pub fn owes_cancellation_fee(&self) -> bool {
+        self.cancellation_fee > 0 && self.status() == ListingStatus::Active && self.filled == 0
+    }
 
-impl From<EscrowError> for ProgramError {
-    fn from(value: EscrowError) -> Self {
-        ProgramError::Custom(value as u32)
+    /// Whether purchase proceeds accrue in the quote vault for `ClaimProceeds`.
+    pub fn accrues_proceeds(&self) -> bool {
+        self.flags & FLAG_PROCEEDS_VAULT != 0
     }
-}
 
-fn deserialize_listing<'a>(
-    program_id: &Pubkey,
-    listing_info: &'a AccountInfo,
-) -> Result<Listing, ProgramError> {
-    if listing_info.owner != program_id {
-        return Err(ProgramError::IncorrectProgramId);
+    /// Whether the listing counts toward its seller's active listing cap.
+    pub fn counts_toward_limit(&self) -> bool {
+        self.flags & FLAG_COUNTED != 0
     }
-    if listing_info.data_len() < Listing::LEN {
-        return Err(EscrowError::AccountLengthMismatch.into());
+
+    /// Whether purchases have raised the listing's full `max_quote_raise`.
+    pub fn raise_cap_reached(&self) -> bool {
+        self.max_quote_raise > 0 && self.quote_received >= self.max_quote_raise
     }
-    Listing::try_from_slice(&listing_info.data.borrow())
-        .map_err(|_| EscrowError::InvalidInstructionData.into())
-}
 
-fn serialize_listing(listing_info: &AccountInfo, listing: &Listing) -> ProgramResult {
-    if listing_info.data_len() < Listing::LEN {
-        return Err(EscrowError::AccountLengthMismatch.into());
+    /// Whether the listing has taken its `auto_complete_after_fills` purchases.
+    pub fn fill_limit_reached(&self) -> bool {
+        self.auto_complete_after_fills > 0 && self.fill_count >= self.auto_complete_after_fills
     }
-    listing
-        .serialize(&mut &mut listing_info.data.borrow_mut()[..])
-        .map_err(|_| EscrowError::InvalidInstructionData.into())
-}
 
-fn assert_token_account_owner(account: &TokenAccount, owner: &Pubkey) -> ProgramResult {
-    if &account.owner != owner {
-        return Err(EscrowError::IncorrectAuthority.into());
+    /// Whether fills are priced by the raw `quote_per_base_num / quote_per_base_den` ratio.
+    pub fn uses_ratio_price(&self) -> bool {
+        self.quote_per_base_den > 0
     }
-    Ok(())
-}
 
-fn assert_token_account_mint(account: &TokenAccount, mint: &Pubkey) -> ProgramResult {
-    if &account.mint != mint {
-        return Err(EscrowError::MintMismatch.into());
+    /// Whether status transitions are recorded in a `StatusHistory` PDA.
+    pub fn has_status_history(&self) -> bool {
+        self.flags & FLAG_STATUS_HISTORY != 0
     }
-    Ok(())
-}
 
-/// Verify x402 payment proof and return the hash for storage.
-/// This is a stub implementation that accepts any non-empty payload.
-/// TODO: Replace with oracle integration or on-chain proof verification.
-fn verify_x402_payment(payload: &str, _expected_amount: u64) -> Result<[u8; 32], ProgramError> {
-    if payload.is_empty() {
-        return Err(EscrowError::InvalidX402Proof.into());
+    /// Whether the listing is a two-sided swap with its `allowed_buyer`.
+    pub fn is_swap(&self) -> bool {
+        self.flags & FLAG_SWAP != 0
     }
 
-    // Compute SHA256 hash of payload using Solana's native hash function
-    use solana_program::keccak;
-    let hash_result = keccak::hash(payload.as_bytes());
-    
-    Ok(hash_result.to_bytes())
-}
+    /// Base tokens deposited into tranche `index`; the last tranche takes the remainder.
+    pub fn tranche_quantity(&self, index: u8) -> u64 {
+        let count = u64::from(self.vault_count.max(1));
+        let per_tranche = self.quantity / count;
+        if u64::from(index) + 1 == count {
+            self.quantity - per_tranche * (count - 1)
+        } else {
+            per_tranche
+        }
+    }
 
-fn initialize_listing(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    listing_id: u64,
-    price_per_token: u64,
-    quantity: u64,
-    allow_partial: bool,
-    fee_payment_method: u8,
-    x402_payload: Option<String>,
-) -> ProgramResult {
-    if quantity == 0 || price_per_token == 0 {
-        return Err(EscrowError::AmountOverflow.into());
+    /// Number of tranches buyers may draw from at `now` (always at least one).
+    pub fn unlocked_tranches(&self, now: i64) -> u8 {
+        let count = self.vault_count.max(1);
+        if self.tranche_interval <= 0 {
+            return count;
+        }
+        let elapsed = now.saturating_sub(self.activated_at).max(0);
+        let unlocked = (elapsed / self.tranche_interval).saturating_add(1);
+        u8::try_from(unlocked).unwrap_or(u8::MAX).min(count)
     }
 
-    let account_info_iter = &mut accounts.iter();
-    let seller_info = next_account_info(account_info_iter)?;
-    let listing_info = next_account_info(account_info_iter)?;
-    let vault_authority_info = next_account_info(account_info_iter)?;
-    let vault_token_account_info = next_account_info(account_info_iter)?;
-    let base_mint_info = next_account_info(account_info_iter)?;
-    let quote_mint_info = next_account_info(account_info_iter)?;
-    let system_program_info = next_account_info(account_info_iter)?;
+    /// Whether the listing's deposit deadline has passed at `now`.
+    pub fn deposit_deadline_passed(&self, now: i64) -> bool {
+        self.deposit_deadline != 0 && now > self.deposit_deadline
+    }
 
-    if !seller_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
+    /// Oldest oracle price, in seconds, a purchase may use.
+    pub fn max_oracle_staleness(&self) -> i64 {
+        match self.max_oracle_staleness_secs {
+            0 => MAX_ORACLE_STALENESS,
+            secs => i64::from(secs),
+        }
     }
 
-    if listing_info.owner != program_id {
-        return Err(ProgramError::IncorrectProgramId);
+    /// Whether accrued proceeds are still behind the listing's vesting cliff at `now`.
+    pub fn proceeds_locked(&self, now: i64) -> bool {
+        now < self.proceeds_unlock_ts
     }
-    if listing_info.data.borrow().iter().any(|b| *b != 0) {
-        return Err(EscrowError::AlreadyInitialized.into());
+
+    /// Whether `key` may cancel the listing: the seller or its designated agent.
+    pub fn can_cancel(&self, key: &Pubkey) -> bool {
+        key == &self.seller
+            || (self.cancel_authority != Pubkey::default() && key == &self.cancel_authority)
     }
 
-    if system_program_info.key != &system_program::ID {
-        return Err(ProgramError::IncorrectProgramId);
+    /// Whether purchases are priced from an oracle instead of `price_per_token`.
+    pub fn uses_oracle(&self) -> bool {
+        self.oracle != Pubkey::default()
     }
 
-    let listing_id_bytes = listing_id.to_le_bytes();
-    let seeds: [&[u8]; 3] = [b"vault", seller_info.key.as_ref(), listing_id_bytes.as_ref()];
-    let (expected_vault_authority, bump) = Pubkey::find_program_address(&seeds, program_id);
-    if vault_authority_info.key != &expected_vault_authority {
-        return Err(EscrowError::IncorrectAuthority.into());
+    /// Whether the listing is sold by English auction rather than at a fixed price.
+    pub fn is_auction(&self) -> bool {
+        self.auction_end != 0
     }
 
-    let expected_vault_ata =
-        get_associated_token_address(vault_authority_info.key, base_mint_info.key);
-    if vault_token_account_info.key != &expected_vault_ata {
-        return Err(EscrowError::MintMismatch.into());
+    /// Whether `buyer` may purchase from this listing without an allowlist proof.
+    pub fn buyer_allowed(&self, buyer: &Pubkey) -> bool {
+        self.buyer_allowed_with_proof(buyer, &[])
     }
 
-    let base_mint = Mint::unpack(&base_mint_info.data.borrow())?;
+    /// Whether `buyer` may purchase from this listing, proving allowlist
+    /// membership with `proof` when the listing has an allowlist.
+    pub fn buyer_allowed_with_proof(&self, buyer: &Pubkey, proof: &[[u8; 32]]) -> bool {
+        (self.allowed_buyer == Pubkey::default() || &self.allowed_buyer == buyer)
+            && (self.allowlist_root == [0; 32] || verify_allowlist_proof(&self.allowlist_root, buyer, proof))
+    }
 
-    // Calculate 1% listing fee from total trade value
-    let trade_value = u128::from(price_per_token)
-        .checked_mul(u128::from(quantity))
-        .ok_or(EscrowError::AmountOverflow)?;
-    let fee_amount = trade_value
-        .checked_div(100)
-        .ok_or(EscrowError::AmountOverflow)?;
-    let fee_amount_u64 = u64::try_from(fee_amount).map_err(|_| EscrowError::AmountOverflow)?;
+    /// Whether buyers must present an attestation to purchase.
+    pub fn requires_attestation(&self) -> bool {
+        self.required_attestation_issuer != Pubkey::default()
+    }
 
-    // Process fee payment based on method
-    let x402_payload_hash = match fee_payment_method {
-        1 => {
-            // X402 payment method
-            let payload = x402_payload.ok_or(EscrowError::InvalidX402Proof)?;
-            verify_x402_payment(&payload, fee_amount_u64)?
-        }
-        0 => {
-            // NativeSol payment method (default, backward compatible)
-            // No SOL fee transfer implemented yet, maintain compatibility
-            [0u8; 32]
-        }
-        _ => {
-            // Invalid fee payment method
-            return Err(EscrowError::InvalidInstructionData.into());
-        }
+    /// Convenience for remaining base tokens still available.
+    pub fn remaining(&self) -> u64 {
+        self.quantity.saturating_sub(self.filled)
+    }
+
+    /// Current status as enum.
+    pub fn status(&self) -> ListingStatus {
+        ListingStatus::from_u8(self.status).unwrap_or(ListingStatus::Cancelled)
+    }
+
+    /// Update status.
+    pub fn set_status(&mut self, status: ListingStatus) {
+        self.status = status.as_u8();
+    }
+}
+
+/// Borsh layout of `Listing` as `(field, type)` pairs in serialization order.
+/// Types are Borsh primitives, `pubkey` (32 bytes) or `[u8; 32]`.
+pub const LISTING_FIELDS: &[(&str, &str)] = &[
+    ("seller", "pubkey"),
+    ("base_mint", "pubkey"),
+    ("quote_mint", "pubkey"),
+    ("vault_authority", "pubkey"),
+    ("price_per_token", "u64"),
+    ("quantity", "u64"),
+    ("filled", "u64"),
+    ("listing_id", "u64"),
+    ("flags", "u8"),
+    ("vault_bump", "u8"),
+    ("status", "u8"),
+    ("base_decimals", "u8"),
+    ("fee_payment_method", "u8"),
+    ("fee_amount_paid", "u64"),
+    ("x402_payload_hash", "[u8; 32]"),
+    ("fee_treasury", "pubkey"),
+    ("required_attestation_issuer", "pubkey"),
+    ("vault_count", "u8"),
+    ("tranche_interval", "i64"),
+    ("activated_at", "i64"),
+    ("cancel_authority", "pubkey"),
+    ("min_fill_bps", "u16"),
+    ("max_fill_bps", "u16"),
+    ("oracle", "pubkey"),
+    ("spread_bps", "i32"),
+    ("interest_count", "u64"),
+    ("min_fill_quote", "u64"),
+    ("allowed_buyer", "pubkey"),
+    ("auction_end", "i64"),
+    ("high_bid", "u64"),
+    ("high_bidder", "pubkey"),
+    ("fee_mint", "pubkey"),
+    ("trigger_price", "u64"),
+    ("max_quote_raise", "u64"),
+    ("quote_received", "u64"),
+    ("tick_size", "u64"),
+    ("canonical_id", "[u8; 32]"),
+    ("quote_per_base_num", "u64"),
+    ("quote_per_base_den", "u64"),
+    ("taker_fee_bps", "u16"),
+    ("category", "u8"),
+    ("note", "[u8; 64]"),
+    ("allowlist_root", "[u8; 32]"),
+    ("deposit_deadline", "i64"),
+    ("proceeds_accrued", "u64"),
+    ("auto_complete_after_fills", "u16"),
+    ("fill_count", "u16"),
+    ("max_slippage_bps", "u16"),
+    ("claim_count", "u16"),
+    ("claims_desired", "u64"),
+    ("claims_close_at", "i64"),
+    ("claims_supply", "u64"),
+    ("cancellation_fee", "u64"),
+    ("proceeds_unlock_ts", "i64"),
+    ("max_oracle_staleness_secs", "u32"),
+];
+
+/// JSON description of the `Listing` layout for client codegen, shaped as
+/// `{"name":"Listing","size":..,"fields":[{"name":"seller","type":"pubkey"},..]}`
+/// with `size` equal to `Listing::LEN`.
+pub fn listing_schema() -> String {
+    let fields = LISTING_FIELDS
+        .iter()
+        .map(|(name, ty)| format!(r#"{{"name":"{name}","type":"{ty}"}}"#))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(r#"{{"name":"Listing","size":{},"fields":[{fields}]}}"#, Listing::LEN)
+}
+
+fn escrow_ix(program_id: &Pubkey, instruction: &EscrowInstruction, accounts: Vec<AccountMeta>) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().expect("instruction data serializes into a Vec"),
+    }
+}
+
+/// Build `InitializeListing` with its fixed accounts, deriving the vault
+/// authority, vault ATA and config PDA. Once the config is initialized, append
+/// the fee accounts described in the README.
+pub fn initialize_listing_ix(
+    program_id: &Pubkey,
+    seller: &Pubkey,
+    listing: &Pubkey,
+    base_mint: &Pubkey,
+    quote_mint: &Pubkey,
+    args: InitializeListingArgs,
+) -> Instruction {
+    let (vault_authority, _) = find_tranche_vault_authority(program_id, seller, args.listing_id, 0);
+    let accounts = vec![
+        AccountMeta::new(*seller, true),
+        AccountMeta::new(*listing, false),
+        AccountMeta::new_readonly(vault_authority, false),
+        AccountMeta::new_readonly(get_associated_token_address(&vault_authority, base_mint), false),
+        AccountMeta::new_readonly(*base_mint, false),
+        AccountMeta::new_readonly(*quote_mint, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ];
+    escrow_ix(program_id, &EscrowInstruction::InitializeListing(args), accounts)
+}
+
+/// Build `DepositTokens` from the seller's base ATA into the listing's primary vault.
+pub fn deposit_tokens_ix(program_id: &Pubkey, listing_key: &Pubkey, listing: &Listing) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(listing.seller, true),
+        AccountMeta::new(*listing_key, false),
+        AccountMeta::new(get_associated_token_address(&listing.seller, &listing.base_mint), false),
+        AccountMeta::new_readonly(listing.vault_authority, false),
+        AccountMeta::new(get_associated_token_address(&listing.vault_authority, &listing.base_mint), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    escrow_ix(program_id, &EscrowInstruction::DepositTokens, accounts)
+}
+
+/// Build `Purchase` of `quantity` from the primary vault, paying from and
+/// receiving into the buyer's ATAs, with the seller paid into their quote ATA.
+pub fn purchase_ix(
+    program_id: &Pubkey,
+    listing_key: &Pubkey,
+    listing: &Listing,
+    buyer: &Pubkey,
+    quantity: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*buyer, true),
+        AccountMeta::new(*listing_key, false),
+        AccountMeta::new(get_associated_token_address(&listing.seller, &listing.quote_mint), false),
+        AccountMeta::new(get_associated_token_address(buyer, &listing.quote_mint), false),
+        AccountMeta::new(get_associated_token_address(buyer, &listing.base_mint), false),
+        AccountMeta::new_readonly(listing.vault_authority, false),
+        AccountMeta::new(get_associated_token_address(&listing.vault_authority, &listing.base_mint), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(find_fills_ledger_address(program_id, listing_key).0, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+    ];
+    escrow_ix(program_id, &EscrowInstruction::Purchase { quantity }, accounts)
+}
+
+/// Build `CancelListing` for the seller, returning the primary vault to their base ATA.
+pub fn cancel_listing_ix(program_id: &Pubkey, listing_key: &Pubkey, listing: &Listing) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(listing.seller, true),
+        AccountMeta::new(*listing_key, false),
+        AccountMeta::new_readonly(listing.vault_authority, false),
+        AccountMeta::new(get_associated_token_address(&listing.vault_authority, &listing.base_mint), false),
+        AccountMeta::new(get_associated_token_address(&listing.seller, &listing.base_mint), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    escrow_ix(program_id, &EscrowInstruction::CancelListing, accounts)
+}
+
+/// Oracle price published by a feed, read from the start of the oracle account.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct OraclePrice {
+    /// Quote units per whole base token, like `Listing::price_per_token`.
+    pub price: u64,
+    /// Unix timestamp the price was published at.
+    pub publish_time: i64,
+}
+
+/// Maximum age, in seconds, of an oracle price used for a purchase when the
+/// listing sets no `max_oracle_staleness_secs`.
+pub const MAX_ORACLE_STALENESS: i64 = 60;
+
+/// Largest spread, in either direction, an oracle listing may apply.
+pub const MAX_ORACLE_SPREAD_BPS: i32 = 5_000;
+
+/// Size in bytes of a listing's seller note.
+pub const NOTE_LEN: usize = 64;
+
+/// Longest x402 payload, in bytes, `InitializeListing` accepts.
+pub const MAX_X402_PAYLOAD_LEN: usize = 512;
+
+/// Lamports a `RegisterInterest` caller pays the seller.
+pub const INTEREST_FEE_LAMPORTS: u64 = 10_000;
+
+/// Largest tip a buyer may pay a relayer through `PurchaseWithRelayerTip`.
+pub const MAX_RELAYER_TIP_LAMPORTS: u64 = 10_000_000;
+
+/// Seconds a buyer must wait between `CommitPurchase` and `RevealPurchase`.
+pub const MIN_REVEAL_DELAY: i64 = 2;
+
+/// Maximum number of tranche vaults per listing.
+pub const MAX_VAULTS: u8 = 8;
+
+/// Derive the vault authority for tranche `index`. Tranche 0 is the listing's
+/// primary vault and keeps the original `["vault", seller, listing_id]` seeds.
+pub fn find_tranche_vault_authority(
+    program_id: &Pubkey,
+    seller: &Pubkey,
+    listing_id: u64,
+    index: u8,
+) -> (Pubkey, u8) {
+    let listing_id_bytes = listing_id.to_le_bytes();
+    if index == 0 {
+        Pubkey::find_program_address(&[b"vault", seller.as_ref(), &listing_id_bytes], program_id)
+    } else {
+        Pubkey::find_program_address(
+            &[b"vault", seller.as_ref(), &listing_id_bytes, &[index]],
+            program_id,
+        )
+    }
+}
+
+/// Maximum number of steps in a price schedule.
+pub const MAX_PRICE_STEPS: usize = 16;
+
+/// Step-price schedule stored in the `["price_steps", listing]` PDA.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct PriceSchedule {
+    /// Listing this schedule prices.
+    pub listing: Pubkey,
+    /// `(cumulative threshold, price)` pairs with strictly increasing thresholds.
+    pub steps: Vec<(u64, u64)>,
+}
+
+impl PriceSchedule {
+    /// Number of bytes required to store a schedule with `step_count` steps.
+    pub fn len_for(step_count: usize) -> usize {
+        32 + 4 + step_count * 16
+    }
+
+    /// Sum of `units * price` for the units `[filled, filled + quantity)`,
+    /// each unit priced by the step its cumulative position falls into.
+    pub fn notional(&self, filled: u64, quantity: u64) -> Result<u128, ProgramError> {
+        let end = filled.checked_add(quantity).ok_or(EscrowError::AmountOverflow)?;
+        let mut notional = 0u128;
+        let mut step_start = 0u64;
+        for &(threshold, price) in &self.steps {
+            let from = step_start.max(filled);
+            let to = threshold.min(end);
+            if to > from {
+                let units_price = u128::from(to - from)
+                    .checked_mul(u128::from(price))
+                    .ok_or(EscrowError::AmountOverflow)?;
+                notional = notional
+                    .checked_add(units_price)
+                    .ok_or(EscrowError::AmountOverflow)?;
+            }
+            if threshold >= end {
+                return Ok(notional);
+            }
+            step_start = threshold;
+        }
+        Err(EscrowError::InvalidPriceSchedule.into())
+    }
+}
+
+/// Derive the price schedule PDA for a listing.
+pub fn find_price_schedule_address(program_id: &Pubkey, listing: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"price_steps", listing.as_ref()], program_id)
+}
+
+/// Maximum number of legs a single batch instruction (e.g. a basket) may move.
+/// Each leg costs a token transfer CPI, so this keeps such instructions well
+/// inside the compute budget instead of failing on exhaustion.
+pub const MAX_BATCH_LEGS: usize = 4;
+
+/// One extra base mint sold as part of a basket.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BasketLeg {
+    /// Mint of this leg's tokens.
+    pub base_mint: Pubkey,
+    /// Base units of this leg delivered with every basket purchase.
+    pub quantity: u64,
+}
+
+/// Basket leg definitions stored in the `["basket", listing]` PDA.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct Basket {
+    /// Listing these legs belong to.
+    pub listing: Pubkey,
+    /// Legs beyond the listing's own `base_mint`.
+    pub legs: Vec<BasketLeg>,
+}
+
+impl Basket {
+    /// Number of bytes required to store a basket with `leg_count` legs.
+    pub fn len_for(leg_count: usize) -> usize {
+        32 + 4 + leg_count * 40
+    }
+}
+
+/// Allowlist Merkle leaf for `buyer`: `keccak(0x00 || buyer)`.
+pub fn allowlist_leaf(buyer: &Pubkey) -> [u8; 32] {
+    keccak::hashv(&[&[0], buyer.as_ref()]).to_bytes()
+}
+
+/// Allowlist Merkle parent of two nodes: `keccak(0x01 || lower || higher)`.
+/// Sorting the pair means proofs carry no left/right flags.
+pub fn allowlist_node(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (lower, higher) = if a <= b { (a, b) } else { (b, a) };
+    keccak::hashv(&[&[1], lower, higher]).to_bytes()
+}
+
+/// Whether `proof` leads from `buyer`'s leaf to `root`.
+pub fn verify_allowlist_proof(root: &[u8; 32], buyer: &Pubkey, proof: &[[u8; 32]]) -> bool {
+    let computed = proof.iter().fold(allowlist_leaf(buyer), |node, sibling| allowlist_node(&node, sibling));
+    &computed == root
+}
+
+/// Derive the basket PDA for a listing.
+pub fn find_basket_address(program_id: &Pubkey, listing: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"basket", listing.as_ref()], program_id)
+}
+
+/// A buyer's registered intent, stored in the `["claim", listing, buyer]` PDA.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct Claim {
+    /// Listing the claim is against.
+    pub listing: Pubkey,
+    /// Buyer who registered it.
+    pub buyer: Pubkey,
+    /// Base units the buyer wants.
+    pub desired: u64,
+    /// `claims_close_at` of the round the claim was registered in.
+    pub round_close_at: i64,
+}
+
+impl Claim {
+    /// Number of bytes required to store a claim.
+    pub const LEN: usize = 32 + 32 + 8 + 8;
+}
+
+/// Derive a buyer's claim PDA for a listing.
+pub fn find_claim_address(program_id: &Pubkey, listing: &Pubkey, buyer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"claim", listing.as_ref(), buyer.as_ref()], program_id)
+}
+
+/// A buyer's cumulative installment progress, stored in the
+/// `["installments", listing, buyer]` PDA.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default)]
+pub struct InstallmentPlan {
+    /// Listing the installments pay for.
+    pub listing: Pubkey,
+    /// Buyer paying them.
+    pub buyer: Pubkey,
+    /// Total quote units paid so far.
+    pub quote_paid: u64,
+    /// Total base units delivered so far.
+    pub base_delivered: u64,
+}
+
+impl InstallmentPlan {
+    /// Number of bytes required to store an installment plan.
+    pub const LEN: usize = 32 + 32 + 8 + 8;
+}
+
+/// Derive a buyer's installment PDA for a listing.
+pub fn find_installment_address(program_id: &Pubkey, listing: &Pubkey, buyer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"installments", listing.as_ref(), buyer.as_ref()], program_id)
+}
+
+/// A buyer's standing order for `PurchaseWithDelegate`, stored in the
+/// `["delegation", listing, buyer]` PDA, which is also the SPL delegate of the
+/// buyer's quote account.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct Delegation {
+    /// Listing the delegate may buy from.
+    pub listing: Pubkey,
+    /// Buyer who pays and receives the base.
+    pub buyer: Pubkey,
+    /// Key allowed to submit the purchases.
+    pub delegate: Pubkey,
+    /// Base units the delegate may still buy.
+    pub quantity: u64,
+    /// Highest per-token price, in the units of `price_per_token`.
+    pub max_price: u64,
+}
+
+impl Delegation {
+    /// Number of bytes required to store a delegation.
+    pub const LEN: usize = 32 + 32 + 32 + 8 + 8;
+}
+
+/// Derive a buyer's delegation PDA for a listing.
+pub fn find_delegation_address(program_id: &Pubkey, listing: &Pubkey, buyer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"delegation", listing.as_ref(), buyer.as_ref()], program_id)
+}
+
+/// Seconds a claim round takes claims after its first one, and again the
+/// seconds the seller then has to settle it.
+pub const CLAIM_WINDOW_SECS: i64 = 3_600;
+
+/// A claim's share of `supply` when the round wants `total_desired` in all:
+/// in full when undersubscribed, otherwise `desired * supply / total_desired`
+/// rounded down.
+pub fn claim_allocation(desired: u64, total_desired: u64, supply: u64) -> u64 {
+    if total_desired <= supply {
+        return desired;
+    }
+    (u128::from(desired) * u128::from(supply) / u128::from(total_desired)) as u64
+}
+
+/// Derive the attestation PDA an issuer program creates for a buyer.
+pub fn find_attestation_address(issuer: &Pubkey, buyer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"attestation", buyer.as_ref()], issuer)
+}
+
+/// Derive the fee escrow PDA holding a listing's unsettled fee.
+pub fn find_fee_escrow_address(program_id: &Pubkey, listing: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"fee_escrow", listing.as_ref()], program_id)
+}
+
+/// Maximum number of fills recorded in a listing's fills ledger.
+pub const MAX_FILL_RECORDS: usize = 128;
+
+/// A single purchase recorded in the fills ledger.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FillRecord {
+    /// Buyer wallet that made the purchase.
+    pub buyer: Pubkey,
+    /// Base tokens purchased.
+    pub quantity: u64,
+    /// Quote tokens paid to the seller.
+    pub quote_amount: u64,
+    /// Unix timestamp of the purchase.
+    pub ts: i64,
+}
+
+/// Append-only record of a listing's fills stored in the `["fills", listing]` PDA.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct FillsLedger {
+    /// Listing whose fills are recorded.
+    pub listing: Pubkey,
+    /// Fills in fill order.
+    pub records: Vec<FillRecord>,
+}
+
+impl FillsLedger {
+    /// Number of bytes required to store a ledger with `record_count` records.
+    pub fn len_for(record_count: usize) -> usize {
+        32 + 4 + record_count * (32 + 8 + 8 + 8)
+    }
+}
+
+/// Derive the fills ledger PDA for a listing.
+pub fn find_fills_ledger_address(program_id: &Pubkey, listing: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"fills", listing.as_ref()], program_id)
+}
+
+/// Buyer-owned record of one purchase, stored in the
+/// `["receipt", listing, buyer, fill_index]` PDA.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Receipt {
+    /// Buyer wallet that made the purchase.
+    pub buyer: Pubkey,
+    /// Seller of the listing.
+    pub seller: Pubkey,
+    /// Mint of the tokens bought.
+    pub base_mint: Pubkey,
+    /// Mint the purchase was paid in.
+    pub quote_mint: Pubkey,
+    /// Base tokens purchased.
+    pub quantity: u64,
+    /// Quote tokens paid to the seller.
+    pub quote_amount: u64,
+    /// Unix timestamp of the purchase.
+    pub ts: i64,
+}
+
+impl Receipt {
+    /// Number of bytes required to store the receipt.
+    pub const LEN: usize = 32 + 32 + 32 + 32 + 8 + 8 + 8;
+}
+
+/// Derive the receipt PDA for the fill at `fill_index` in a listing's fills ledger.
+pub fn find_receipt_address(program_id: &Pubkey, listing: &Pubkey, buyer: &Pubkey, fill_index: u32) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"receipt", listing.as_ref(), buyer.as_ref(), &fill_index.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Maximum number of entries in a status history. A listing passes through at
+/// most four statuses (awaiting deposit, active, frozen, cancelled) unless it is
+/// unfrozen; past that the first entry is kept and the oldest later one dropped.
+pub const MAX_STATUS_ENTRIES: usize = 4;
+
+/// A status a listing entered, and when.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StatusEntry {
+    /// Status entered.
+    pub status: ListingStatus,
+    /// Unix timestamp of the transition.
+    pub ts: i64,
+}
+
+/// Timeline of a listing's statuses stored in the `["status_history", listing]` PDA.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct StatusHistory {
+    /// Listing whose transitions are recorded.
+    pub listing: Pubkey,
+    /// The first status recorded, then the latest ones in the order entered.
+    pub entries: Vec<StatusEntry>,
+}
+
+impl StatusHistory {
+    /// Number of bytes allocated for a status history, sized for `MAX_STATUS_ENTRIES`.
+    pub const LEN: usize = 32 + 4 + MAX_STATUS_ENTRIES * (1 + 8);
+}
+
+/// A buyer's pending commit-reveal purchase, stored in the
+/// `["commitment", listing, buyer]` PDA.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct PurchaseCommitment {
+    /// Listing the purchase is against.
+    pub listing: Pubkey,
+    /// Buyer who committed.
+    pub buyer: Pubkey,
+    /// `purchase_commitment_hash` of the hidden fill.
+    pub commitment: [u8; 32],
+    /// Unix timestamp of the commit.
+    pub committed_at: i64,
+}
+
+impl PurchaseCommitment {
+    /// Number of bytes required to store a commitment.
+    pub const LEN: usize = 32 + 32 + 32 + 8;
+}
+
+/// Derive a buyer's purchase commitment PDA for a listing.
+pub fn find_purchase_commitment_address(program_id: &Pubkey, listing: &Pubkey, buyer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"commitment", listing.as_ref(), buyer.as_ref()], program_id)
+}
+
+/// Keccak hash a buyer commits to: `quantity` and `nonce` (little endian), then the buyer key.
+pub fn purchase_commitment_hash(buyer: &Pubkey, quantity: u64, nonce: u64) -> [u8; 32] {
+    keccak::hashv(&[&quantity.to_le_bytes(), &nonce.to_le_bytes(), buyer.as_ref()]).to_bytes()
+}
+
+/// Derive the status history PDA for a listing.
+pub fn find_status_history_address(program_id: &Pubkey, listing: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"status_history", listing.as_ref()], program_id)
+}
+
+/// Global program configuration stored in the `["config"]` PDA.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct Config {
+    /// Authority allowed to change program-wide settings.
+    pub admin: Pubkey,
+    /// Treasury receiving fees for quote mints without a dedicated route.
+    pub default_treasury: Pubkey,
+    /// Most listings a seller may have open at once; 0 means unlimited.
+    pub max_active_listings: u32,
+    /// Mint charged by `FeePaymentMethod::SplToken` (default pubkey = not offered).
+    pub fee_mint: Pubkey,
+    /// Successor proposed by the admin, pending acceptance (default pubkey = none).
+    pub pending_admin: Pubkey,
+    /// Largest base `quantity` a new listing may have; 0 means unlimited.
+    pub max_listing_quantity: u64,
+    /// Share of each listing fee burned rather than paid to the treasury, in basis points.
+    pub burn_bps: u16,
+    /// Lamports a seller pays the treasury to cancel an active listing with no fills; 0 means none.
+    pub cancellation_fee: u64,
+    /// Smallest base `quantity` a new fungible listing may have; 0 means no minimum.
+    pub min_listing_quantity: u64,
+    /// Flat listing fee in `fee_mint` units charged by `FeePaymentMethod::SplToken`; 0 = not offered.
+    pub spl_fee_amount: u64,
+    /// Flat listing fee in lamports charged by `FeePaymentMethod::NativeSol`; 0 = free.
+    pub native_fee_lamports: u64,
+}
+
+impl Config {
+    /// Number of bytes required to store the config.
+    pub const LEN: usize = 32 + 32 + 4 + 32 + 32 + 8 + 2 + 8 + 8 + 8 + 8;
+}
+
+/// Fee treasury for one quote mint, stored in the `["treasury", quote_mint]` PDA.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct TreasuryRoute {
+    /// Quote mint this route applies to.
+    pub quote_mint: Pubkey,
+    /// Wallet receiving fees for listings quoted in `quote_mint`.
+    pub treasury: Pubkey,
+}
+
+impl TreasuryRoute {
+    /// Number of bytes required to store the route.
+    pub const LEN: usize = 32 + 32;
+}
+
+/// Derive the global config PDA.
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config"], program_id)
+}
+
+/// Derive the treasury route PDA for a quote mint.
+pub fn find_treasury_address(program_id: &Pubkey, quote_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"treasury", quote_mint.as_ref()], program_id)
+}
+
+/// Marks a seller as exempt from listing fees, stored in the `["fee_exempt", seller]` PDA.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct FeeExemption {
+    /// Seller whose listings are created without a fee.
+    pub seller: Pubkey,
+}
+
+impl FeeExemption {
+    /// Number of bytes required to store the exemption.
+    pub const LEN: usize = 32;
+}
+
+/// Derive the fee exemption PDA for a seller.
+pub fn find_fee_exempt_address(program_id: &Pubkey, seller: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"fee_exempt", seller.as_ref()], program_id)
+}
+
+/// Per-seller count of open listings, stored in the `["seller_stats", seller]` PDA.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default)]
+pub struct SellerStats {
+    /// Seller the counter belongs to.
+    pub seller: Pubkey,
+    /// Listings counted against the cap that are not yet completed or cancelled.
+    pub active_listings: u32,
+}
+
+impl SellerStats {
+    /// Number of bytes required to store the stats.
+    pub const LEN: usize = 32 + 4;
+}
+
+/// Derive the seller stats PDA for a seller.
+pub fn find_seller_stats_address(program_id: &Pubkey, seller: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"seller_stats", seller.as_ref()], program_id)
+}
+
+/// Protocol-wide counters, stored in the `["global_stats"]` PDA.
+///
+/// Amounts are raw units summed across mints; they saturate instead of overflowing.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GlobalStats {
+    /// Listings initialized while the PDA was passed.
+    pub listings_created: u64,
+    /// Quote paid to sellers by purchases.
+    pub quote_volume: u64,
+    /// Listing fees charged at creation plus taker fees paid on purchases.
+    pub fees_collected: u64,
+}
+
+impl GlobalStats {
+    /// Number of bytes required to store the stats.
+    pub const LEN: usize = 8 + 8 + 8;
+}
+
+/// Derive the global stats PDA.
+pub fn find_global_stats_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"global_stats"], program_id)
+}
+
+/// Escrow program specific errors.
+#[derive(Debug, Error)]
+pub enum EscrowError {
+    /// Supplied instruction data could not be parsed.
+    #[error("Invalid instruction data")]
+    InvalidInstructionData,
+    /// Account data length was unexpected.
+    #[error("Account length mismatch")]
+    AccountLengthMismatch,
+    /// Listing already initialised.
+    #[error("Listing already initialised")]
+    AlreadyInitialized,
+    /// Caller does not match expected authority.
+    #[error("Incorrect authority provided")]
+    IncorrectAuthority,
+    /// Listing not ready for this operation.
+    #[error("Invalid listing status for action")]
+    InvalidListingStatus,
+    /// Math overflow or invalid quantity.
+    #[error("Amount overflow or invalid quantity")]
+    AmountOverflow,
+    /// Provided accounts do not match expected mints.
+    #[error("Token mint mismatch")]
+    MintMismatch,
+    /// Not enough tokens remain to satisfy the purchase.
+    #[error("Insufficient remaining quantity")]
+    InsufficientQuantity,
+    /// Partial fills are disabled.
+    #[error("Partial fills disabled")]
+    PartialFillDisabled,
+    /// x402 payment proof missing or invalid.
+    #[error("x402 payment proof missing or invalid")]
+    InvalidX402Proof,
+    /// x402 payment amount mismatch.
+    #[error("x402 payment amount mismatch")]
+    X402AmountMismatch,
+    /// Price schedule is empty, too long, unordered, or does not cover the listing.
+    #[error("Invalid price schedule")]
+    InvalidPriceSchedule,
+    /// The same account was supplied for more than one role.
+    #[error("Duplicate account supplied")]
+    DuplicateAccount,
+    /// Basis-point value is zero or exceeds 10_000.
+    #[error("Invalid basis points")]
+    InvalidBasisPoints,
+    /// Config or treasury route account is missing, malformed, or at the wrong address.
+    #[error("Invalid program config")]
+    InvalidConfig,
+    /// Buyer did not present a valid attestation from the listing's required issuer.
+    #[error("Buyer attestation missing or invalid")]
+    AttestationMissing,
+    /// Fills ledger account is at the wrong address or malformed.
+    #[error("Invalid fills ledger")]
+    InvalidFillsLedger,
+    /// Fills ledger already holds `MAX_FILL_RECORDS` records.
+    #[error("Fills ledger is full")]
+    FillsLedgerFull,
+    /// Fee escrow account is at the wrong address or not held by the program.
+    #[error("Invalid fee escrow")]
+    InvalidFeeEscrow,
+    /// Purchase drew from a tranche vault that has not unlocked yet.
+    #[error("Tranche is still locked")]
+    TrancheLocked,
+    /// Buying the full quantity would need more than `u64::MAX` quote units.
+    #[error("Quote amount overflows at full fill")]
+    QuoteOverflowAtFullFill,
+    /// Purchase is smaller than the listing's minimum fill.
+    #[error("Fill below listing minimum")]
+    FillBelowMinimum,
+    /// Purchase is larger than the listing's maximum fill.
+    #[error("Fill above listing maximum")]
+    FillAboveMaximum,
+    /// Oracle price is older than the listing's staleness limit or the feed is malformed.
+    #[error("Stale or invalid oracle price")]
+    StaleOracle,
+    /// Basket legs are malformed, or the basket PDA does not match the listing.
+    #[error("Invalid basket")]
+    InvalidBasket,
+    /// Batch instruction carries more than `MAX_BATCH_LEGS` legs.
+    #[error("Batch too large")]
+    BatchTooLarge,
+    /// Purchase pays less quote than the listing's `min_fill_quote`.
+    #[error("Fill below minimum quote amount")]
+    BelowMinimumQuoteFill,
+    /// Claim PDA does not match the listing and buyer, or is malformed.
+    #[error("Invalid claim")]
+    InvalidClaim,
+    /// Listing is reserved for a different buyer.
+    #[error("Buyer not allowed")]
+    BuyerNotAllowed,
+    /// Installment PDA does not match the listing and buyer, or is malformed.
+    #[error("Invalid installment plan")]
+    InvalidInstallmentPlan,
+    /// Seller already has the maximum number of open listings.
+    #[error("Too many active listings")]
+    TooManyActiveListings,
+    /// Seller stats PDA does not match the seller, or is malformed.
+    #[error("Invalid seller stats")]
+    InvalidSellerStats,
+    /// Auction parameters are invalid, or the instruction does not apply to
+    /// this listing's sale mode.
+    #[error("Invalid auction")]
+    InvalidAuction,
+    /// Bid is below the reserve or does not beat the current high bid.
+    #[error("Bid too low")]
+    BidTooLow,
+    /// Bidding on the auction has already closed.
+    #[error("Auction closed")]
+    AuctionClosed,
+    /// The auction is still taking bids.
+    #[error("Auction still open")]
+    AuctionStillOpen,
+    /// Vault token account has a close authority set.
+    #[error("Vault close authority set")]
+    VaultCloseAuthoritySet,
+    /// Oracle price is below the listing's trigger price.
+    #[error("Trigger price not met")]
+    TriggerNotMet,
+    /// Relayer tip exceeds `MAX_RELAYER_TIP_LAMPORTS`.
+    #[error("Relayer tip too large")]
+    RelayerTipTooLarge,
+    /// Status history account is at the wrong address or not held by the program.
+    #[error("Invalid status history")]
+    InvalidStatusHistory,
+    /// Fill would take the listing's quote proceeds past `max_quote_raise`.
+    #[error("Raise cap exceeded")]
+    RaiseCapExceeded,
+    /// Purchase exceeds the buyer's delegation: its quantity, max price or approved quote.
+    #[error("Insufficient delegation")]
+    InsufficientDelegation,
+    /// Base mint has no supply and no mint authority, so it can never be deposited.
+    #[error("Degenerate base mint")]
+    DegenerateMint,
+    /// `price_per_token` is not a multiple of the listing's `tick_size`.
+    #[error("Price not on tick")]
+    PriceNotOnTick,
+    /// Vault token account is frozen or has a delegate.
+    #[error("Vault account unsafe")]
+    VaultAccountUnsafe,
+    /// Commitment PDA is missing, at the wrong address, or does not match the reveal.
+    #[error("Invalid purchase commitment")]
+    InvalidCommitment,
+    /// `RevealPurchase` came less than `MIN_REVEAL_DELAY` seconds after the commit.
+    #[error("Reveal too early")]
+    RevealTooEarly,
+    /// Listing `quantity` exceeds the config's `max_listing_quantity`.
+    #[error("Quantity too large")]
+    QuantityTooLarge,
+    /// Vaults still hold less than the listing's unfilled quantity.
+    #[error("Reconciliation failed")]
+    ReconciliationFailed,
+    /// The listing has not been funded yet.
+    #[error("Listing not funded")]
+    ListingNotFunded,
+    /// Every token in the listing has already been sold.
+    #[error("Listing already completed")]
+    ListingAlreadyCompleted,
+    /// The listing was cancelled.
+    #[error("Listing cancelled")]
+    ListingCancelled,
+    /// The listing is frozen until the admin reconciles its vaults.
+    #[error("Listing frozen")]
+    ListingFrozen,
+    /// Listing cannot be a swap, is not one, or the swap is not in the required state.
+    #[error("Invalid swap")]
+    InvalidSwap,
+    /// Listing category is not a known `ListingCategory`.
+    #[error("Invalid listing category")]
+    InvalidCategory,
+    /// x402 payload is longer than `MAX_X402_PAYLOAD_LEN`.
+    #[error("x402 payload too large")]
+    X402PayloadTooLarge,
+    /// Listing note is not UTF-8 followed by zero padding.
+    #[error("Invalid listing note")]
+    InvalidNote,
+    /// A basket leg could not be delivered in full; the log names the leg index.
+    #[error("Basket leg unsatisfiable")]
+    BasketLegUnsatisfiable,
+    /// The listing's deposit deadline has passed.
+    #[error("Deposit deadline passed")]
+    DepositDeadlinePassed,
+    /// The listing has no deposit deadline, or it has not passed yet.
+    #[error("Deposit deadline not reached")]
+    DepositDeadlineNotReached,
+    /// The global stats account is not the program's global stats PDA.
+    #[error("Invalid global stats account")]
+    InvalidGlobalStats,
+    /// The receipt account is not the purchase's receipt PDA.
+    #[error("Invalid receipt account")]
+    InvalidReceipt,
+    /// Fungible listing `quantity` is below the config's `min_listing_quantity`.
+    #[error("Quantity below minimum")]
+    QuantityBelowMinimum,
+    /// The oracle price moved further from the buyer's reference price than `max_slippage_bps`.
+    #[error("Slippage exceeded")]
+    SlippageExceeded,
+    /// The claim round is still taking claims, so it cannot be settled yet.
+    #[error("Claim window still open")]
+    ClaimWindowOpen,
+    /// The claim round no longer takes claims, or its settlement window has lapsed.
+    #[error("Claim window closed")]
+    ClaimWindowClosed,
+    /// Delegation account is at the wrong address or not held by the program.
+    #[error("Invalid delegation")]
+    InvalidDelegation,
+    /// The buyer's quote account already approves a delegate this instruction would replace.
+    #[error("Quote account delegate in use")]
+    QuoteDelegateInUse,
+    /// Accrued proceeds cannot be claimed before the listing's `proceeds_unlock_ts`.
+    #[error("Proceeds are locked")]
+    ProceedsLocked,
+}
+
+impl From<EscrowError> for ProgramError {
+    fn from(value: EscrowError) -> Self {
+        ProgramError::Custom(value as u32)
+    }
+}
+
+fn deserialize_listing(
+    program_id: &Pubkey,
+    listing_info: &AccountInfo,
+) -> Result<Listing, ProgramError> {
+    if listing_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if listing_info.data_len() < Listing::LEN {
+        return Err(EscrowError::AccountLengthMismatch.into());
+    }
+    let data = listing_info.data.borrow();
+    let mut cursor: &[u8] = &data;
+    let listing = Listing::deserialize(&mut cursor).map_err(|_| EscrowError::InvalidInstructionData)?;
+    // Trailing bytes would be ignored here but could be misread by off-chain decoders.
+    if !cursor.is_empty() {
+        return Err(EscrowError::AccountLengthMismatch.into());
+    }
+    Ok(listing)
+}
+
+fn serialize_listing(listing_info: &AccountInfo, listing: &Listing) -> ProgramResult {
+    if listing_info.data_len() < Listing::LEN {
+        return Err(EscrowError::AccountLengthMismatch.into());
+    }
+    listing
+        .serialize(&mut &mut listing_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+/// Drain all lamports from a program-owned account (e.g. a claim) into
+/// `destination_info` and zero its data so the account is garbage collected at
+/// the end of the transaction.
+fn close_program_account(account_info: &AccountInfo, destination_info: &AccountInfo) -> ProgramResult {
+    drain_lamports(account_info, destination_info)?;
+    account_info.data.borrow_mut().fill(0);
+    Ok(())
+}
+
+/// Move all of `account_info`'s lamports to `destination_info`; the runtime
+/// reclaims the emptied account at the end of the transaction.
+fn drain_lamports(account_info: &AccountInfo, destination_info: &AccountInfo) -> ProgramResult {
+    let refund = account_info.lamports();
+    **destination_info.try_borrow_mut_lamports()? = destination_info
+        .lamports()
+        .checked_add(refund)
+        .ok_or(EscrowError::AmountOverflow)?;
+    **account_info.try_borrow_mut_lamports()? = 0;
+    Ok(())
+}
+
+/// Mark a never-funded listing `Cancelled` and refund its rent to the seller.
+/// The status stays in the account data, so a listing revived within the same
+/// transaction is still cancelled rather than blank.
+fn close_unfunded_listing(
+    listing_info: &AccountInfo,
+    listing: &mut Listing,
+    seller_info: &AccountInfo,
+) -> ProgramResult {
+    listing.set_status(ListingStatus::Cancelled);
+    serialize_listing(listing_info, listing)?;
+    drain_lamports(listing_info, seller_info)
+}
+
+/// Convert `units * price` into quote units, scaling down by the base mint decimals.
+fn quote_amount_from_notional(notional: u128, base_decimals: u8) -> Result<u64, ProgramError> {
+    let decimals_factor = 10u128
+        .checked_pow(u32::from(base_decimals))
+        .ok_or(EscrowError::AmountOverflow)?;
+    let quote_amount = notional
+        .checked_div(decimals_factor.max(1))
+        .ok_or(EscrowError::AmountOverflow)?;
+    if quote_amount == 0 {
+        return Err(EscrowError::AmountOverflow.into());
+    }
+    u64::try_from(quote_amount).map_err(|_| EscrowError::AmountOverflow.into())
+}
+
+fn load_price_schedule(
+    program_id: &Pubkey,
+    listing_key: &Pubkey,
+    price_schedule_info: &AccountInfo,
+) -> Result<PriceSchedule, ProgramError> {
+    let (expected_schedule, _) = find_price_schedule_address(program_id, listing_key);
+    if price_schedule_info.key != &expected_schedule {
+        return Err(EscrowError::InvalidPriceSchedule.into());
+    }
+    if price_schedule_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    PriceSchedule::deserialize(&mut &price_schedule_info.data.borrow()[..])
+        .map_err(|_| EscrowError::InvalidPriceSchedule.into())
+}
+
+/// Unpack a basket leg's vault, which like the base vault must be the vault
+/// authority's own ATA for the leg mint and neither frozen nor delegated.
+fn load_leg_vault(
+    leg: &BasketLeg,
+    vault_authority: &Pubkey,
+    leg_vault_info: &AccountInfo,
+) -> Result<TokenAccount, ProgramError> {
+    if leg_vault_info.key != &get_associated_token_address(vault_authority, &leg.base_mint) {
+        return Err(EscrowError::MintMismatch.into());
+    }
+    let leg_vault_account = TokenAccount::unpack(&leg_vault_info.data.borrow())?;
+    assert_token_account_owner(&leg_vault_account, vault_authority)?;
+    assert_token_account_mint(&leg_vault_account, &leg.base_mint)?;
+    assert_vault_account_safe(&leg_vault_account)?;
+    Ok(leg_vault_account)
+}
+
+/// Check a basket leg can be delivered in full: the vault holds the leg and
+/// neither side is frozen or belongs to the wrong owner or mint.
+fn check_basket_leg(
+    leg: &BasketLeg,
+    vault_authority: &Pubkey,
+    buyer: &Pubkey,
+    leg_vault_info: &AccountInfo,
+    buyer_leg_account_info: &AccountInfo,
+) -> ProgramResult {
+    let leg_vault_account = load_leg_vault(leg, vault_authority, leg_vault_info)?;
+    let buyer_leg_account = TokenAccount::unpack(&buyer_leg_account_info.data.borrow())?;
+    assert_token_account_owner(&buyer_leg_account, buyer)?;
+    assert_token_account_mint(&buyer_leg_account, &leg.base_mint)?;
+    if buyer_leg_account.is_frozen() {
+        return Err(EscrowError::VaultAccountUnsafe.into());
+    }
+    if leg_vault_account.amount < leg.quantity {
+        return Err(ProgramError::InsufficientFunds);
+    }
+    Ok(())
+}
+
+fn load_basket(program_id: &Pubkey, listing_key: &Pubkey, basket_info: &AccountInfo) -> Result<Basket, ProgramError> {
+    let (expected_basket, _) = find_basket_address(program_id, listing_key);
+    if basket_info.key != &expected_basket {
+        return Err(EscrowError::InvalidBasket.into());
+    }
+    if basket_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Basket::deserialize(&mut &basket_info.data.borrow()[..]).map_err(|_| EscrowError::InvalidBasket.into())
+}
+
+/// Re-derive the vault authority from the listing's seeds so a tampered
+/// `vault_authority` fails clearly instead of as a signer error in CPI.
+///
+/// Returns the canonical bump to sign with. The stored `vault_bump` is not
+/// trusted: if it was corrupted, the vault can still be emptied.
+fn vault_authority_bump(program_id: &Pubkey, listing: &Listing) -> Result<u8, ProgramError> {
+    let listing_id_bytes = listing.listing_id.to_le_bytes();
+    let (derived, bump) = Pubkey::find_program_address(
+        &[b"vault", listing.seller.as_ref(), listing_id_bytes.as_ref()],
+        program_id,
+    );
+    if derived != listing.vault_authority {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    Ok(bump)
+}
+
+/// A tranche vault authority and the bump needed to sign for it.
+struct TrancheVault {
+    index: u8,
+    bump: u8,
+}
+
+/// Check `vault_authority` is the listing's authority for tranche `index`.
+fn tranche_vault(
+    program_id: &Pubkey,
+    listing: &Listing,
+    vault_authority: &Pubkey,
+    index: u8,
+) -> Result<TrancheVault, ProgramError> {
+    let bump = if index == 0 {
+        if vault_authority != &listing.vault_authority {
+            return Err(EscrowError::IncorrectAuthority.into());
+        }
+        vault_authority_bump(program_id, listing)?
+    } else {
+        let (expected, bump) =
+            find_tranche_vault_authority(program_id, &listing.seller, listing.listing_id, index);
+        if vault_authority != &expected {
+            return Err(EscrowError::IncorrectAuthority.into());
+        }
+        bump
+    };
+    Ok(TrancheVault { index, bump })
+}
+
+/// Find which of the listing's tranches `vault_authority` belongs to.
+fn resolve_tranche(
+    program_id: &Pubkey,
+    listing: &Listing,
+    vault_authority: &Pubkey,
+) -> Result<TrancheVault, ProgramError> {
+    if vault_authority == &listing.vault_authority {
+        return tranche_vault(program_id, listing, vault_authority, 0);
+    }
+    (1..listing.vault_count)
+        .find_map(|index| tranche_vault(program_id, listing, vault_authority, index).ok())
+        .ok_or_else(|| EscrowError::IncorrectAuthority.into())
+}
+
+/// Move `amount` base tokens out of a tranche vault, signing with its PDA seeds.
+fn transfer_from_vault<'a>(
+    listing: &Listing,
+    tranche: &TrancheVault,
+    token_program_info: &AccountInfo<'a>,
+    vault_token_account_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    vault_authority_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    let transfer_ix = spl_token::instruction::transfer(
+        token_program_info.key,
+        vault_token_account_info.key,
+        destination_info.key,
+        vault_authority_info.key,
+        &[],
+        amount,
+    )?;
+    invoke_signed_by_vault(
+        listing,
+        tranche,
+        &transfer_ix,
+        &[
+            vault_token_account_info.clone(),
+            destination_info.clone(),
+            vault_authority_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
+
+/// Reject any token program but SPL Token before it is handed a CPI: another
+/// program could report a transfer that never happened.
+fn assert_token_program(token_program_info: &AccountInfo) -> ProgramResult {
+    if token_program_info.key != &spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+/// Invoke `instruction` with the tranche vault authority's PDA signature.
+fn invoke_signed_by_vault(
+    listing: &Listing,
+    tranche: &TrancheVault,
+    instruction: &Instruction,
+    account_infos: &[AccountInfo],
+) -> ProgramResult {
+    // The vault authority's signature may only ever reach SPL Token, or the
+    // System Program for lamport sweeps; any other program could spend with it.
+    if instruction.program_id != spl_token::id() && instruction.program_id != system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let listing_id_bytes = listing.listing_id.to_le_bytes();
+    let index_seed = [tranche.index];
+    let bump_seed = [tranche.bump];
+    let mut signer_seeds: Vec<&[u8]> = vec![b"vault", listing.seller.as_ref(), listing_id_bytes.as_ref()];
+    if tranche.index > 0 {
+        signer_seeds.push(&index_seed);
+    }
+    signer_seeds.push(&bump_seed);
+
+    invoke_signed(instruction, account_infos, &[&signer_seeds])
+}
+
+/// Oracle price adjusted by the listing's spread, clamped at zero. Raw prices
+/// below the listing's `trigger_price` fail with `TriggerNotMet`.
+fn oracle_price_per_token(listing: &Listing, oracle_info: &AccountInfo, now: i64) -> Result<u64, ProgramError> {
+    if oracle_info.key != &listing.oracle {
+        return Err(EscrowError::StaleOracle.into());
+    }
+    let oracle = OraclePrice::deserialize(&mut &oracle_info.data.borrow()[..])
+        .map_err(|_| EscrowError::StaleOracle)?;
+    if now.saturating_sub(oracle.publish_time) > listing.max_oracle_staleness() {
+        return Err(EscrowError::StaleOracle.into());
+    }
+    if oracle.price < listing.trigger_price {
+        return Err(EscrowError::TriggerNotMet.into());
+    }
+
+    let denominator = i128::from(BPS_DENOMINATOR);
+    let adjusted = i128::from(oracle.price) * (denominator + i128::from(listing.spread_bps)) / denominator;
+    u64::try_from(adjusted.max(0)).map_err(|_| EscrowError::AmountOverflow.into())
+}
+
+/// Reject a price more than `max_slippage_bps` of `reference_price` away from it, in either direction.
+fn assert_within_slippage(price: u64, reference_price: u64, max_slippage_bps: u16) -> ProgramResult {
+    let deviation = u128::from(price.abs_diff(reference_price)) * u128::from(BPS_DENOMINATOR);
+    if deviation > u128::from(reference_price) * u128::from(max_slippage_bps) {
+        return Err(EscrowError::SlippageExceeded.into());
+    }
+    Ok(())
+}
+
+/// Check `quantity` against the listing's fill bounds, expressed in basis points
+/// of the total quantity. Taking everything that remains is always allowed, so
+/// a tail smaller than the minimum can still be bought.
+fn assert_fill_size(listing: &Listing, quantity: u64) -> ProgramResult {
+    let bound = |bps: u16| {
+        u128::from(listing.quantity) * u128::from(bps) / u128::from(BPS_DENOMINATOR)
+    };
+    if listing.min_fill_bps > 0
+        && u128::from(quantity) < bound(listing.min_fill_bps)
+        && quantity < listing.remaining()
+    {
+        return Err(EscrowError::FillBelowMinimum.into());
+    }
+    if listing.max_fill_bps > 0 && u128::from(quantity) > bound(listing.max_fill_bps) {
+        return Err(EscrowError::FillAboveMaximum.into());
+    }
+    Ok(())
+}
+
+/// Reject account sets where the same key is supplied for more than one role.
+fn assert_distinct_accounts(accounts: &[&AccountInfo]) -> ProgramResult {
+    for (index, account) in accounts.iter().enumerate() {
+        if accounts[index + 1..].iter().any(|other| other.key == account.key) {
+            return Err(EscrowError::DuplicateAccount.into());
+        }
+    }
+    Ok(())
+}
+
+/// Create a program-owned PDA account of `space` bytes funded by `payer_info`.
+fn create_pda_account<'a>(
+    program_id: &Pubkey,
+    payer_info: &AccountInfo<'a>,
+    new_account_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    space: usize,
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    let create_ix = system_instruction::create_account(
+        payer_info.key,
+        new_account_info.key,
+        Rent::get()?.minimum_balance(space),
+        space as u64,
+        program_id,
+    );
+    invoke_signed(
+        &create_ix,
+        &[
+            payer_info.clone(),
+            new_account_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[signer_seeds],
+    )
+}
+
+/// Load the config PDA, returning `None` while it has not been initialized.
+fn load_config(program_id: &Pubkey, config_info: &AccountInfo) -> Result<Option<Config>, ProgramError> {
+    let (expected_config, _) = find_config_address(program_id);
+    if config_info.key != &expected_config {
+        return Err(EscrowError::InvalidConfig.into());
+    }
+    if config_info.data_is_empty() {
+        return Ok(None);
+    }
+    if config_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Config::try_from_slice(&config_info.data.borrow())
+        .map(Some)
+        .map_err(|_| EscrowError::InvalidConfig.into())
+}
+
+/// Refuse to approve `delegate` over a token account that already approves a
+/// different delegate, since SPL Token keeps only one per account.
+fn assert_delegate_replaceable(account: &TokenAccount, delegate: &Pubkey) -> ProgramResult {
+    match account.delegate {
+        COption::Some(current) if &current != delegate && account.delegated_amount > 0 => {
+            Err(EscrowError::QuoteDelegateInUse.into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check the buyer holds a live attestation PDA owned by `issuer`.
+fn assert_attestation(issuer: &Pubkey, buyer: &Pubkey, attestation_info: &AccountInfo) -> ProgramResult {
+    let (expected, _) = find_attestation_address(issuer, buyer);
+    if attestation_info.key != &expected
+        || attestation_info.owner != issuer
+        || attestation_info.data_is_empty()
+    {
+        return Err(EscrowError::AttestationMissing.into());
+    }
+    Ok(())
+}
+
+/// Load an initialized config and check `admin_info` is its signing admin.
+fn load_config_as_admin(
+    program_id: &Pubkey,
+    config_info: &AccountInfo,
+    admin_info: &AccountInfo,
+) -> Result<Config, ProgramError> {
+    if !admin_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let config = load_config(program_id, config_info)?.ok_or(EscrowError::InvalidConfig)?;
+    if &config.admin != admin_info.key {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    Ok(config)
+}
+
+/// Take the next account only if it is `key`, leaving the iterator untouched otherwise.
+fn next_account_if_key<'a, 'b>(
+    account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    key: &Pubkey,
+) -> Option<&'a AccountInfo<'b>> {
+    if account_info_iter.as_slice().first().is_some_and(|info| info.key == key) {
+        account_info_iter.next()
+    } else {
+        None
+    }
+}
+
+/// Unwrap an optional system program account once an instruction actually needs it.
+fn require_system_program<'a, 'b>(
+    system_program_info: Option<&'a AccountInfo<'b>>,
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    system_program_info.ok_or(ProgramError::NotEnoughAccountKeys)
+}
+
+/// Portion of a listing fee burned under the config's `burn_bps`, rounded down.
+fn burn_share(fee_amount: u64, burn_bps: u16) -> Result<u64, ProgramError> {
+    let burn = u128::from(fee_amount) * u128::from(burn_bps) / u128::from(BPS_DENOMINATOR);
+    u64::try_from(burn).map_err(|_| EscrowError::AmountOverflow.into())
+}
+
+/// Whether `seller` holds a fee exemption; an empty PDA means no exemption.
+fn is_fee_exempt(
+    program_id: &Pubkey,
+    seller: &Pubkey,
+    fee_exempt_info: &AccountInfo,
+) -> Result<bool, ProgramError> {
+    let (expected_exemption, _) = find_fee_exempt_address(program_id, seller);
+    if fee_exempt_info.key != &expected_exemption {
+        return Err(EscrowError::InvalidConfig.into());
+    }
+    Ok(!fee_exempt_info.data_is_empty() && fee_exempt_info.owner == program_id)
+}
+
+/// Load the seller stats PDA, defaulting to zero open listings while it is empty.
+fn load_seller_stats(
+    program_id: &Pubkey,
+    seller: &Pubkey,
+    seller_stats_info: &AccountInfo,
+) -> Result<SellerStats, ProgramError> {
+    let (expected_stats, _) = find_seller_stats_address(program_id, seller);
+    if seller_stats_info.key != &expected_stats {
+        return Err(EscrowError::InvalidSellerStats.into());
+    }
+    if seller_stats_info.data_is_empty() {
+        return Ok(SellerStats {
+            seller: *seller,
+            active_listings: 0,
+        });
+    }
+    if seller_stats_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    SellerStats::try_from_slice(&seller_stats_info.data.borrow())
+        .map_err(|_| EscrowError::InvalidSellerStats.into())
+}
+
+fn store_seller_stats(seller_stats_info: &AccountInfo, stats: &SellerStats) -> ProgramResult {
+    stats
+        .serialize(&mut &mut seller_stats_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidSellerStats.into())
+}
+
+/// Load the global stats PDA, defaulting to zero counters while it is empty.
+fn load_global_stats(program_id: &Pubkey, global_stats_info: &AccountInfo) -> Result<GlobalStats, ProgramError> {
+    let (expected_stats, _) = find_global_stats_address(program_id);
+    if global_stats_info.key != &expected_stats {
+        return Err(EscrowError::InvalidGlobalStats.into());
+    }
+    if global_stats_info.data_is_empty() {
+        return Ok(GlobalStats::default());
+    }
+    if global_stats_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    GlobalStats::try_from_slice(&global_stats_info.data.borrow()).map_err(|_| EscrowError::InvalidGlobalStats.into())
+}
+
+/// Apply `update` to the global stats PDA, creating it (payer pays) on first use.
+fn record_global_stats<'a>(
+    program_id: &Pubkey,
+    payer_info: &AccountInfo<'a>,
+    global_stats_info: &AccountInfo<'a>,
+    system_program_info: Option<&AccountInfo<'a>>,
+    update: impl FnOnce(&mut GlobalStats),
+) -> ProgramResult {
+    let mut stats = load_global_stats(program_id, global_stats_info)?;
+    if global_stats_info.data_is_empty() {
+        let (_, bump) = find_global_stats_address(program_id);
+        create_pda_account(
+            program_id,
+            payer_info,
+            global_stats_info,
+            require_system_program(system_program_info)?,
+            GlobalStats::LEN,
+            &[b"global_stats", &[bump]],
+        )?;
+    }
+    update(&mut stats);
+    stats
+        .serialize(&mut &mut global_stats_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidGlobalStats.into())
+}
+
+/// Take one of the seller's `max_active_listings` slots, creating the stats PDA
+/// (seller pays) on first use.
+fn reserve_listing_slot<'a>(
+    program_id: &Pubkey,
+    seller_info: &AccountInfo<'a>,
+    seller_stats_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    max_active_listings: u32,
+) -> ProgramResult {
+    let mut stats = load_seller_stats(program_id, seller_info.key, seller_stats_info)?;
+    if stats.active_listings >= max_active_listings {
+        return Err(EscrowError::TooManyActiveListings.into());
+    }
+    if seller_stats_info.data_is_empty() {
+        let (_, bump) = find_seller_stats_address(program_id, seller_info.key);
+        create_pda_account(
+            program_id,
+            seller_info,
+            seller_stats_info,
+            system_program_info,
+            SellerStats::LEN,
+            &[b"seller_stats", seller_info.key.as_ref(), &[bump]],
+        )?;
+    }
+    stats.active_listings += 1;
+    store_seller_stats(seller_stats_info, &stats)
+}
+
+/// Free the slot a counted listing holds once it is completed or cancelled.
+fn release_listing_slot(
+    program_id: &Pubkey,
+    listing: &Listing,
+    seller_stats_info: Option<&AccountInfo>,
+) -> ProgramResult {
+    let Some(seller_stats_info) = seller_stats_info else {
+        return Ok(());
+    };
+    let mut stats = load_seller_stats(program_id, &listing.seller, seller_stats_info)?;
+    stats.active_listings = stats.active_listings.saturating_sub(1);
+    store_seller_stats(seller_stats_info, &stats)
+}
+
+/// The seller stats PDA trailing a counted listing's accounts, if it has one.
+fn next_seller_stats<'a, 'b>(
+    listing: &Listing,
+    account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+) -> Result<Option<&'a AccountInfo<'b>>, ProgramError> {
+    if listing.counts_toward_limit() {
+        next_account_info(account_info_iter).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// The status history PDA trailing a listing's accounts, if it keeps one.
+fn next_status_history<'a, 'b>(
+    listing: &Listing,
+    account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+) -> Result<Option<&'a AccountInfo<'b>>, ProgramError> {
+    if listing.has_status_history() {
+        next_account_info(account_info_iter).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Append the status the listing just entered to its history, if it keeps one.
+fn record_status(
+    program_id: &Pubkey,
+    listing_key: &Pubkey,
+    listing: &Listing,
+    status_history_info: Option<&AccountInfo>,
+) -> ProgramResult {
+    let Some(status_history_info) = status_history_info else {
+        return Ok(());
+    };
+    let (expected_history, _) = find_status_history_address(program_id, listing_key);
+    if status_history_info.key != &expected_history || status_history_info.owner != program_id {
+        return Err(EscrowError::InvalidStatusHistory.into());
+    }
+    let mut history = StatusHistory::deserialize(&mut &status_history_info.data.borrow()[..])
+        .map_err(|_| EscrowError::InvalidStatusHistory)?;
+    // Freeze/unfreeze cycles can outgrow the account; keep the entry the
+    // history started with and the latest ones.
+    if history.entries.len() >= MAX_STATUS_ENTRIES {
+        history.entries.remove(1);
+    }
+    history.entries.push(StatusEntry {
+        status: listing.status(),
+        ts: Clock::get()?.unix_timestamp,
+    });
+    history
+        .serialize(&mut &mut status_history_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidStatusHistory.into())
+}
+
+/// Treasury for `quote_mint`: its dedicated route if one exists, else the default.
+fn resolve_treasury(
+    program_id: &Pubkey,
+    config: &Config,
+    quote_mint: &Pubkey,
+    treasury_route_info: &AccountInfo,
+) -> Result<Pubkey, ProgramError> {
+    let (expected_route, _) = find_treasury_address(program_id, quote_mint);
+    if treasury_route_info.key != &expected_route {
+        return Err(EscrowError::InvalidConfig.into());
+    }
+    if treasury_route_info.data_is_empty() {
+        return Ok(config.default_treasury);
+    }
+    if treasury_route_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let route = TreasuryRoute::try_from_slice(&treasury_route_info.data.borrow())
+        .map_err(|_| EscrowError::InvalidConfig)?;
+    Ok(route.treasury)
+}
+
+fn assert_token_account_owner(account: &TokenAccount, owner: &Pubkey) -> ProgramResult {
+    if &account.owner != owner {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    Ok(())
+}
+
+fn assert_token_account_mint(account: &TokenAccount, mint: &Pubkey) -> ProgramResult {
+    if &account.mint != mint {
+        return Err(EscrowError::MintMismatch.into());
+    }
+    Ok(())
+}
+
+/// A single indivisible token, exempt from `min_listing_quantity`.
+fn is_single_nft(mint: &Mint) -> bool {
+    mint.decimals == 0 && mint.supply == 1
+}
+
+/// A frozen vault or one with a delegate could trap or leak deposited base.
+fn assert_vault_account_safe(account: &TokenAccount) -> ProgramResult {
+    if account.state != AccountState::Initialized || account.delegate.is_some() {
+        return Err(EscrowError::VaultAccountUnsafe.into());
+    }
+    Ok(())
+}
+
+/// Verify x402 payment proof and return the hash for storage.
+/// This is a stub implementation that accepts any non-empty payload.
+/// TODO: Replace with oracle integration or on-chain proof verification.
+fn verify_x402_payment(payload: &str, _expected_amount: u64) -> Result<[u8; 32], ProgramError> {
+    if payload.is_empty() {
+        return Err(EscrowError::InvalidX402Proof.into());
+    }
+    if payload.len() > MAX_X402_PAYLOAD_LEN {
+        return Err(EscrowError::X402PayloadTooLarge.into());
+    }
+
+    // Keccak-256 through Solana's native hash syscall.
+    let hash_result = keccak::hash(payload.as_bytes());
+    Ok(hash_result.to_bytes())
+}
+
+/// The text of a zero-padded listing note; bytes after the first zero must all be zero.
+fn note_text(note: &[u8; NOTE_LEN]) -> Result<&str, ProgramError> {
+    let len = note.iter().position(|b| *b == 0).unwrap_or(NOTE_LEN);
+    if note[len..].iter().any(|b| *b != 0) {
+        return Err(EscrowError::InvalidNote.into());
+    }
+    core::str::from_utf8(&note[..len]).map_err(|_| EscrowError::InvalidNote.into())
+}
+
+/// Values derived while validating a listing's initialization parameters.
+struct ListingInitPlan {
+    price_per_token: u64,
+    vault_bump: u8,
+    base_decimals: u8,
+    fee_amount: u64,
+    x402_payload_hash: [u8; 32],
+}
+
+/// The config and the accounts after it that set a new listing's fee.
+struct ListingFeePolicy<'a, 'b> {
+    config: Config,
+    treasury: Pubkey,
+    treasury_info: &'a AccountInfo<'b>,
+    fee_exempt: bool,
+    /// Seller stats PDA, passed when the config caps active listings.
+    seller_stats_info: Option<&'a AccountInfo<'b>>,
+}
+
+/// Read the fee policy from the config PDA, if passed and initialized, then the
+/// treasury route, treasury wallet, fee exemption PDA and, under a listing cap,
+/// the seller stats PDA.
+fn next_listing_fee_policy<'a, 'b>(
+    program_id: &Pubkey,
+    seller: &Pubkey,
+    quote_mint: &Pubkey,
+    config_info: Option<&AccountInfo>,
+    account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+) -> Result<Option<ListingFeePolicy<'a, 'b>>, ProgramError> {
+    let config = match config_info {
+        Some(config_info) => load_config(program_id, config_info)?,
+        None => None,
+    };
+    let Some(config) = config else {
+        return Ok(None);
+    };
+    let treasury_route_info = next_account_info(account_info_iter)?;
+    let treasury_info = next_account_info(account_info_iter)?;
+    let fee_exempt_info = next_account_info(account_info_iter)?;
+    let treasury = resolve_treasury(program_id, &config, quote_mint, treasury_route_info)?;
+    if treasury_info.key != &treasury {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    let fee_exempt = is_fee_exempt(program_id, seller, fee_exempt_info)?;
+    let seller_stats_info = if config.max_active_listings > 0 {
+        Some(next_account_info(account_info_iter)?)
+    } else {
+        None
+    };
+    Ok(Some(ListingFeePolicy {
+        config,
+        treasury,
+        treasury_info,
+        fee_exempt,
+        seller_stats_info,
+    }))
+}
+
+/// Validate initialization parameters, the vault/mint accounts and the config
+/// limits, computing everything the listing needs without writing any state.
+#[allow(clippy::too_many_arguments)]
+fn plan_listing_init(
+    program_id: &Pubkey,
+    seller: &Pubkey,
+    vault_authority_info: &AccountInfo,
+    vault_token_account_info: &AccountInfo,
+    base_mint_info: &AccountInfo,
+    quote_mint_info: &AccountInfo,
+    fee_policy: Option<&ListingFeePolicy>,
+    args: &InitializeListingArgs,
+) -> Result<ListingInitPlan, ProgramError> {
+    let ratio_priced = args.quote_per_base_den > 0;
+    if args.quantity == 0 {
+        return Err(EscrowError::AmountOverflow.into());
+    }
+    // Exactly one pricing mode: `price_per_token` or the raw quote/base ratio.
+    if ratio_priced {
+        if args.price_per_token != 0 || args.quote_per_base_num == 0 {
+            return Err(EscrowError::InvalidInstructionData.into());
+        }
+        if args.oracle.is_some() || args.auction_end != 0 {
+            return Err(EscrowError::InvalidInstructionData.into());
+        }
+    } else if args.quote_per_base_num != 0 {
+        return Err(EscrowError::InvalidInstructionData.into());
+    } else if args.price_per_token == 0 {
+        return Err(EscrowError::AmountOverflow.into());
+    }
+    if ListingCategory::from_u8(args.category).is_none() {
+        return Err(EscrowError::InvalidCategory.into());
+    }
+    note_text(&args.note)?;
+    let bps_in_range = |bps: u16| u64::from(bps) <= BPS_DENOMINATOR;
+    if !bps_in_range(args.min_fill_bps)
+        || !bps_in_range(args.max_fill_bps)
+        || !bps_in_range(args.taker_fee_bps)
+        || !bps_in_range(args.max_slippage_bps)
+        || (args.max_fill_bps > 0 && args.min_fill_bps > args.max_fill_bps)
+    {
+        return Err(EscrowError::InvalidBasisPoints.into());
+    }
+    if args.spread_bps.abs() > MAX_ORACLE_SPREAD_BPS {
+        return Err(EscrowError::InvalidBasisPoints.into());
+    }
+    if args.vault_count == 0 || args.vault_count > MAX_VAULTS {
+        return Err(EscrowError::InvalidInstructionData.into());
+    }
+    if args.vault_count > 1
+        && (args.tranche_interval <= 0 || args.quantity < u64::from(args.vault_count))
+    {
+        return Err(EscrowError::InvalidInstructionData.into());
+    }
+    if args.deposit_deadline != 0 && args.deposit_deadline <= Clock::get()?.unix_timestamp {
+        return Err(EscrowError::InvalidInstructionData.into());
+    }
+    // Auctions sell the whole quantity from a single vault at the winning bid.
+    if args.auction_end != 0
+        && (args.auction_end <= Clock::get()?.unix_timestamp
+            || args.allow_partial
+            || args.vault_count > 1
+            || args.oracle.is_some()
+            || args.max_quote_raise > 0
+            || args.taker_fee_bps > 0)
+    {
+        return Err(EscrowError::InvalidAuction.into());
+    }
+    if (args.trigger_price > 0 || args.max_slippage_bps > 0 || args.max_oracle_staleness_secs > 0)
+        && args.oracle.is_none()
+    {
+        return Err(EscrowError::InvalidInstructionData.into());
+    }
+    if args.tick_size > 0 && !args.price_per_token.is_multiple_of(args.tick_size) {
+        return Err(EscrowError::PriceNotOnTick.into());
+    }
+
+    let listing_id_bytes = args.listing_id.to_le_bytes();
+    let seeds: [&[u8]; 3] = [b"vault", seller.as_ref(), listing_id_bytes.as_ref()];
+    let (expected_vault_authority, bump) = Pubkey::find_program_address(&seeds, program_id);
+    if vault_authority_info.key != &expected_vault_authority {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+
+    let expected_vault_ata =
+        get_associated_token_address(vault_authority_info.key, base_mint_info.key);
+    if vault_token_account_info.key != &expected_vault_ata {
+        return Err(EscrowError::MintMismatch.into());
+    }
+
+    let base_mint = Mint::unpack(&base_mint_info.data.borrow())?;
+    // Nobody could ever mint the tokens a deposit needs.
+    if base_mint.supply == 0 && base_mint.mint_authority.is_none() {
+        return Err(EscrowError::DegenerateMint.into());
+    }
+    // A wallet passed as the quote mint would leave the listing unpurchasable.
+    if quote_mint_info.owner != &spl_token::id() || Mint::unpack(&quote_mint_info.data.borrow()).is_err() {
+        return Err(EscrowError::MintMismatch.into());
+    }
+
+    let decimals_factor = 10u128
+        .checked_pow(u32::from(base_mint.decimals))
+        .ok_or(EscrowError::AmountOverflow)?;
+    // Ratio-priced listings store the equivalent per-whole-token price for
+    // display and fees; fills still use the exact ratio.
+    let price_per_token = if ratio_priced {
+        let price = u128::from(args.quote_per_base_num)
+            .checked_mul(decimals_factor)
+            .ok_or(EscrowError::AmountOverflow)?
+            / u128::from(args.quote_per_base_den);
+        u64::try_from(price).map_err(|_| EscrowError::AmountOverflow)?
+    } else {
+        args.price_per_token
+    };
+    let trade_value = u128::from(price_per_token)
+        .checked_mul(u128::from(args.quantity))
+        .ok_or(EscrowError::AmountOverflow)?;
+
+    // Reject listings whose full fill could never be paid for in a u64 quote amount.
+    let full_fill_quote = if ratio_priced {
+        u128::from(args.quantity) * u128::from(args.quote_per_base_num) / u128::from(args.quote_per_base_den)
+    } else {
+        trade_value / decimals_factor
+    };
+    if full_fill_quote > u128::from(u64::MAX) {
+        return Err(EscrowError::QuoteOverflowAtFullFill.into());
+    }
+
+    // Calculate 1% listing fee from total trade value
+    let fee_amount = trade_value
+        .checked_div(100)
+        .ok_or(EscrowError::AmountOverflow)?;
+    let fee_amount_u64 = u64::try_from(fee_amount).map_err(|_| EscrowError::AmountOverflow)?;
+
+    // Process fee payment based on method; unknown method bytes are rejected.
+    let fee_payment_method =
+        FeePaymentMethod::from_u8(args.fee_payment_method).ok_or(EscrowError::InvalidInstructionData)?;
+    let x402_payload_hash = match fee_payment_method {
+        FeePaymentMethod::X402 => {
+            let payload = args.x402_payload.as_deref().ok_or(EscrowError::InvalidX402Proof)?;
+            verify_x402_payment(payload, fee_amount_u64)?
+        }
+        // The transfer to the treasury happens in `initialize_listing`.
+        FeePaymentMethod::NativeSol | FeePaymentMethod::SplToken => [0u8; 32],
+    };
+    // Only lamport fees can be held in escrow.
+    if args.escrow_fee && fee_payment_method != FeePaymentMethod::NativeSol {
+        return Err(EscrowError::InvalidInstructionData.into());
+    }
+
+    // Without a config the fee is recorded but never charged, and taker fees
+    // have no treasury to go to.
+    let Some(policy) = fee_policy else {
+        if args.taker_fee_bps > 0 {
+            return Err(EscrowError::InvalidConfig.into());
+        }
+        return Ok(ListingInitPlan {
+            price_per_token,
+            vault_bump: bump,
+            base_decimals: base_mint.decimals,
+            fee_amount: fee_amount_u64,
+            x402_payload_hash,
+        });
+    };
+    let config = &policy.config;
+    if config.max_listing_quantity > 0 && args.quantity > config.max_listing_quantity {
+        return Err(EscrowError::QuantityTooLarge.into());
+    }
+    if args.quantity < config.min_listing_quantity && !is_single_nft(&base_mint) {
+        return Err(EscrowError::QuantityBelowMinimum.into());
+    }
+    if let Some(seller_stats_info) = policy.seller_stats_info {
+        let stats = load_seller_stats(program_id, seller, seller_stats_info)?;
+        if stats.active_listings >= config.max_active_listings {
+            return Err(EscrowError::TooManyActiveListings.into());
+        }
+    }
+    if fee_payment_method == FeePaymentMethod::SplToken
+        && (config.fee_mint == Pubkey::default() || config.spl_fee_amount == 0)
+    {
+        return Err(EscrowError::InvalidConfig.into());
+    }
+    if args.taker_fee_bps > 0 && policy.treasury == Pubkey::default() {
+        return Err(EscrowError::InvalidConfig.into());
+    }
+    // The quote-derived fee means nothing in lamports or another mint, so the
+    // config sets those flat.
+    let fee_amount = match fee_payment_method {
+        _ if policy.fee_exempt => 0,
+        FeePaymentMethod::X402 => fee_amount_u64,
+        FeePaymentMethod::NativeSol => config.native_fee_lamports,
+        FeePaymentMethod::SplToken => config.spl_fee_amount,
+    };
+
+    Ok(ListingInitPlan {
+        price_per_token,
+        vault_bump: bump,
+        base_decimals: base_mint.decimals,
+        fee_amount,
+        x402_payload_hash,
+    })
+}
+
+fn initialize_listing(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    args: InitializeListingArgs,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let seller_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let vault_authority_info = next_account_info(account_info_iter)?;
+    let vault_token_account_info = next_account_info(account_info_iter)?;
+    let base_mint_info = next_account_info(account_info_iter)?;
+    let quote_mint_info = next_account_info(account_info_iter)?;
+    // The system program is only needed to move SOL or create accounts, so callers may leave it out.
+    let system_program_info = next_account_if_key(account_info_iter, &system_program::ID);
+    // Without the config PDA no fee is charged, as when the config is not yet initialized.
+    let (config_key, _) = find_config_address(program_id);
+    let config_info = next_account_if_key(account_info_iter, &config_key);
+
+    if !seller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if listing_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    // An oversized account could carry trailing bytes that alias other state.
+    if listing_info.data_len() != Listing::LEN {
+        return Err(EscrowError::AccountLengthMismatch.into());
+    }
+    if listing_info.data.borrow().iter().any(|b| *b != 0) {
+        return Err(EscrowError::AlreadyInitialized.into());
+    }
+
+    // Fees are only collected once the operator has initialized the config.
+    let fee_policy =
+        next_listing_fee_policy(program_id, seller_info.key, quote_mint_info.key, config_info, account_info_iter)?;
+    let plan = plan_listing_init(
+        program_id,
+        seller_info.key,
+        vault_authority_info,
+        vault_token_account_info,
+        base_mint_info,
+        quote_mint_info,
+        fee_policy.as_ref(),
+        &args,
+    )?;
+
+    // A vault left over from an aborted attempt is reused, but only if the vault authority owns it.
+    if vault_token_account_info.data_len() > 0 {
+        let vault_token_account = TokenAccount::unpack(&vault_token_account_info.data.borrow())?;
+        assert_token_account_owner(&vault_token_account, vault_authority_info.key)?;
+        assert_token_account_mint(&vault_token_account, base_mint_info.key)?;
+    }
+
+    let mut flags = if args.allow_partial { FLAG_ALLOW_PARTIAL } else { 0 };
+    let fee_amount = plan.fee_amount;
+    let mut fee_mint = Pubkey::default();
+    let mut cancellation_fee = 0;
+
+    let fee_treasury = match fee_policy {
+        None => Pubkey::default(),
+        Some(ListingFeePolicy {
+            config,
+            treasury,
+            treasury_info,
+            seller_stats_info,
+            ..
+        }) => {
+            cancellation_fee = config.cancellation_fee;
+            if let Some(seller_stats_info) = seller_stats_info {
+                reserve_listing_slot(
+                    program_id,
+                    seller_info,
+                    seller_stats_info,
+                    require_system_program(system_program_info)?,
+                    config.max_active_listings,
+                )?;
+                flags |= FLAG_COUNTED;
+            }
+            if args.fee_payment_method == FeePaymentMethod::SplToken.as_u8() {
+                fee_mint = config.fee_mint;
+                if fee_amount > 0 {
+                    let seller_fee_account_info = next_account_info(account_info_iter)?;
+                    let treasury_fee_account_info = next_account_info(account_info_iter)?;
+                    let token_program_info = next_account_info(account_info_iter)?;
+                    assert_token_program(token_program_info)?;
+                    let seller_fee_account = TokenAccount::unpack(&seller_fee_account_info.data.borrow())?;
+                    assert_token_account_owner(&seller_fee_account, seller_info.key)?;
+                    assert_token_account_mint(&seller_fee_account, &fee_mint)?;
+                    let treasury_fee_account = TokenAccount::unpack(&treasury_fee_account_info.data.borrow())?;
+                    assert_token_account_owner(&treasury_fee_account, &treasury)?;
+                    assert_token_account_mint(&treasury_fee_account, &fee_mint)?;
+                    let burn_amount = burn_share(fee_amount, config.burn_bps)?;
+                    invoke(
+                        &spl_token::instruction::transfer(
+                            token_program_info.key,
+                            seller_fee_account_info.key,
+                            treasury_fee_account_info.key,
+                            seller_info.key,
+                            &[],
+                            fee_amount - burn_amount,
+                        )?,
+                        &[
+                            seller_fee_account_info.clone(),
+                            treasury_fee_account_info.clone(),
+                            seller_info.clone(),
+                            token_program_info.clone(),
+                        ],
+                    )?;
+                    if burn_amount > 0 {
+                        // The classic token program lets any holder burn, so the mint only has to be genuine.
+                        let fee_mint_info = next_account_info(account_info_iter)?;
+                        if fee_mint_info.key != &fee_mint {
+                            return Err(EscrowError::MintMismatch.into());
+                        }
+                        if fee_mint_info.owner != &spl_token::id() {
+                            return Err(ProgramError::IncorrectProgramId);
+                        }
+                        invoke(
+                            &spl_token::instruction::burn(
+                                token_program_info.key,
+                                seller_fee_account_info.key,
+                                fee_mint_info.key,
+                                seller_info.key,
+                                &[],
+                                burn_amount,
+                            )?,
+                            &[
+                                seller_fee_account_info.clone(),
+                                fee_mint_info.clone(),
+                                seller_info.clone(),
+                                token_program_info.clone(),
+                            ],
+                        )?;
+                    }
+                }
+            }
+            if args.fee_payment_method == FeePaymentMethod::NativeSol.as_u8() && fee_amount > 0 {
+                let system_program_info = require_system_program(system_program_info)?;
+                let fee_destination_info = if args.escrow_fee {
+                    let fee_escrow_info = next_account_info(account_info_iter)?;
+                    let (expected_escrow, bump) = find_fee_escrow_address(program_id, listing_info.key);
+                    if fee_escrow_info.key != &expected_escrow {
+                        return Err(EscrowError::InvalidFeeEscrow.into());
+                    }
+                    create_pda_account(
+                        program_id,
+                        seller_info,
+                        fee_escrow_info,
+                        system_program_info,
+                        0,
+                        &[b"fee_escrow", listing_info.key.as_ref(), &[bump]],
+                    )?;
+                    flags |= FLAG_FEE_ESCROW;
+                    fee_escrow_info
+                } else {
+                    treasury_info
+                };
+                // Escrowed fees may still be refunded, so only fees paid straight to the treasury are burned.
+                let burn_amount = if args.escrow_fee { 0 } else { burn_share(fee_amount, config.burn_bps)? };
+                invoke(
+                    &system_instruction::transfer(seller_info.key, fee_destination_info.key, fee_amount - burn_amount),
+                    &[
+                        seller_info.clone(),
+                        fee_destination_info.clone(),
+                        system_program_info.clone(),
+                    ],
+                )?;
+                if burn_amount > 0 {
+                    let incinerator_info = next_account_info(account_info_iter)?;
+                    if incinerator_info.key != &incinerator::ID {
+                        return Err(EscrowError::IncorrectAuthority.into());
+                    }
+                    invoke(
+                        &system_instruction::transfer(seller_info.key, incinerator_info.key, burn_amount),
+                        &[
+                            seller_info.clone(),
+                            incinerator_info.clone(),
+                            system_program_info.clone(),
+                        ],
+                    )?;
+                }
+            }
+            treasury
+        }
+    };
+
+    // Trailing token and associated token programs ask us to create the vault ATA if it is missing.
+    let (global_stats_address, _) = find_global_stats_address(program_id);
+    let mut trailing_info = account_info_iter.next();
+    if let Some(token_program_info) = trailing_info.filter(|info| info.key != &global_stats_address) {
+        trailing_info = None;
+        let associated_token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = require_system_program(system_program_info)?;
+        invoke(
+            &create_associated_token_account_idempotent(
+                seller_info.key,
+                vault_authority_info.key,
+                base_mint_info.key,
+                token_program_info.key,
+            ),
+            &[
+                seller_info.clone(),
+                vault_token_account_info.clone(),
+                vault_authority_info.clone(),
+                base_mint_info.clone(),
+                system_program_info.clone(),
+                token_program_info.clone(),
+                associated_token_program_info.clone(),
+            ],
+        )?;
+    }
+    if let Some(global_stats_info) = trailing_info.or_else(|| account_info_iter.next()) {
+        record_global_stats(program_id, seller_info, global_stats_info, system_program_info, |stats| {
+            stats.listings_created = stats.listings_created.saturating_add(1);
+            // Without a config no fee is charged.
+            if fee_treasury != Pubkey::default() {
+                stats.fees_collected = stats.fees_collected.saturating_add(fee_amount);
+            }
+        })?;
+    }
+
+    let listing = Listing {
+        seller: *seller_info.key,
+        base_mint: *base_mint_info.key,
+        quote_mint: *quote_mint_info.key,
+        vault_authority: *vault_authority_info.key,
+        price_per_token: plan.price_per_token,
+        quantity: args.quantity,
+        filled: 0,
+        listing_id: args.listing_id,
+        flags,
+        vault_bump: plan.vault_bump,
+        status: ListingStatus::AwaitingDeposit.as_u8(),
+        base_decimals: plan.base_decimals,
+        fee_payment_method: args.fee_payment_method,
+        fee_amount_paid: fee_amount,
+        x402_payload_hash: plan.x402_payload_hash,
+        fee_treasury,
+        required_attestation_issuer: args.required_attestation_issuer.unwrap_or_default(),
+        vault_count: args.vault_count,
+        tranche_interval: args.tranche_interval,
+        activated_at: 0,
+        cancel_authority: args.cancel_authority.unwrap_or_default(),
+        min_fill_bps: args.min_fill_bps,
+        max_fill_bps: args.max_fill_bps,
+        oracle: args.oracle.unwrap_or_default(),
+        spread_bps: args.spread_bps,
+        interest_count: 0,
+        min_fill_quote: args.min_fill_quote,
+        allowed_buyer: Pubkey::default(),
+        auction_end: args.auction_end,
+        high_bid: 0,
+        high_bidder: Pubkey::default(),
+        fee_mint,
+        trigger_price: args.trigger_price,
+        max_quote_raise: args.max_quote_raise,
+        quote_received: 0,
+        tick_size: args.tick_size,
+        canonical_id: canonical_listing_id(seller_info.key, args.listing_id, Clock::get()?.unix_timestamp),
+        quote_per_base_num: args.quote_per_base_num,
+        quote_per_base_den: args.quote_per_base_den,
+        taker_fee_bps: args.taker_fee_bps,
+        category: args.category,
+        note: args.note,
+        allowlist_root: [0; 32],
+        deposit_deadline: args.deposit_deadline,
+        proceeds_accrued: 0,
+        auto_complete_after_fills: args.auto_complete_after_fills,
+        fill_count: 0,
+        max_slippage_bps: args.max_slippage_bps,
+        claim_count: 0,
+        claims_desired: 0,
+        claims_close_at: 0,
+        claims_supply: 0,
+        cancellation_fee,
+        proceeds_unlock_ts: args.proceeds_unlock_ts,
+        max_oracle_staleness_secs: args.max_oracle_staleness_secs,
+    };
+
+    serialize_listing(listing_info, &listing)?;
+    msg!(
+        "ListingCreated listing={} listing_id={} canonical_id={} category={} note={:?}",
+        listing_info.key,
+        args.listing_id,
+        keccak::Hash::new_from_array(listing.canonical_id),
+        listing.category,
+        note_text(&listing.note)?
+    );
+    Ok(())
+}
+
+fn dry_run_initialize(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    args: InitializeListingArgs,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let seller_info = next_account_info(account_info_iter)?;
+    let vault_authority_info = next_account_info(account_info_iter)?;
+    let vault_token_account_info = next_account_info(account_info_iter)?;
+    let base_mint_info = next_account_info(account_info_iter)?;
+    let quote_mint_info = next_account_info(account_info_iter)?;
+    let (config_key, _) = find_config_address(program_id);
+    let config_info = next_account_if_key(account_info_iter, &config_key);
+
+    let fee_policy =
+        next_listing_fee_policy(program_id, seller_info.key, quote_mint_info.key, config_info, account_info_iter)?;
+    let plan = plan_listing_init(
+        program_id,
+        seller_info.key,
+        vault_authority_info,
+        vault_token_account_info,
+        base_mint_info,
+        quote_mint_info,
+        fee_policy.as_ref(),
+        &args,
+    )?;
+
+    set_return_data(&plan.fee_amount.to_le_bytes());
+    Ok(())
+}
+
+fn deposit_tokens(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let seller_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let seller_token_account_info = next_account_info(account_info_iter)?;
+    let vault_authority_info = next_account_info(account_info_iter)?;
+    let vault_token_account_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    if !seller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    assert_token_program(token_program_info)?;
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    let status_history_info = next_status_history(&listing, account_info_iter)?;
+
+    if listing.status() != ListingStatus::AwaitingDeposit {
+        return Err(EscrowError::InvalidListingStatus.into());
+    }
+    if seller_info.key != &listing.seller {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    if listing.deposit_deadline_passed(Clock::get()?.unix_timestamp) {
+        return Err(EscrowError::DepositDeadlinePassed.into());
+    }
+
+    let seller_token_account = TokenAccount::unpack(&seller_token_account_info.data.borrow())?;
+    assert_token_account_owner(&seller_token_account, seller_info.key)?;
+    assert_token_account_mint(&seller_token_account, &listing.base_mint)?;
+
+    // Purchases only read from the canonical ATA, so tokens deposited anywhere else would be stranded.
+    if vault_token_account_info.key != &get_associated_token_address(vault_authority_info.key, &listing.base_mint) {
+        return Err(EscrowError::MintMismatch.into());
+    }
+    let vault_token_account = TokenAccount::unpack(&vault_token_account_info.data.borrow())?;
+    assert_token_account_owner(&vault_token_account, vault_authority_info.key)?;
+    assert_token_account_mint(&vault_token_account, &listing.base_mint)?;
+    assert_vault_account_safe(&vault_token_account)?;
+
+    if vault_authority_info.key != &listing.vault_authority {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+
+    let amount = listing.quantity;
+    if seller_token_account.amount < amount {
+        return Err(ProgramError::InsufficientFunds);
+    }
+
+    // Tranche listings pass an authority/vault pair for every tranche after the first.
+    for index in 0..listing.vault_count.max(1) {
+        let tranche_vault_info = if index == 0 {
+            vault_token_account_info
+        } else {
+            let tranche_authority_info = next_account_info(account_info_iter)?;
+            let tranche_vault_info = next_account_info(account_info_iter)?;
+            let (expected_authority, _) =
+                find_tranche_vault_authority(program_id, &listing.seller, listing.listing_id, index);
+            if tranche_authority_info.key != &expected_authority {
+                return Err(EscrowError::IncorrectAuthority.into());
+            }
+            if tranche_vault_info.key != &get_associated_token_address(&expected_authority, &listing.base_mint) {
+                return Err(EscrowError::MintMismatch.into());
+            }
+            let tranche_vault_account = TokenAccount::unpack(&tranche_vault_info.data.borrow())?;
+            assert_token_account_owner(&tranche_vault_account, tranche_authority_info.key)?;
+            assert_token_account_mint(&tranche_vault_account, &listing.base_mint)?;
+            assert_vault_account_safe(&tranche_vault_account)?;
+            tranche_vault_info
+        };
+
+        let ix = spl_token::instruction::transfer(
+            token_program_info.key,
+            seller_token_account_info.key,
+            tranche_vault_info.key,
+            seller_info.key,
+            &[],
+            listing.tranche_quantity(index),
+        )?;
+
+        invoke(
+            &ix,
+            &[
+                seller_token_account_info.clone(),
+                tranche_vault_info.clone(),
+                seller_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
+    }
+
+    // Basket listings then pass the basket PDA and a (seller account, vault) pair per leg.
+    if listing.has_basket() {
+        let basket_info = next_account_info(account_info_iter)?;
+        let basket = load_basket(program_id, listing_info.key, basket_info)?;
+        for leg in &basket.legs {
+            let seller_leg_account_info = next_account_info(account_info_iter)?;
+            let leg_vault_info = next_account_info(account_info_iter)?;
+            load_leg_vault(leg, &listing.vault_authority, leg_vault_info)?;
+
+            let ix = spl_token::instruction::transfer(
+                token_program_info.key,
+                seller_leg_account_info.key,
+                leg_vault_info.key,
+                seller_info.key,
+                &[],
+                leg.quantity,
+            )?;
+            invoke(
+                &ix,
+                &[
+                    seller_leg_account_info.clone(),
+                    leg_vault_info.clone(),
+                    seller_info.clone(),
+                    token_program_info.clone(),
+                ],
+            )?;
+        }
+    }
+
+    listing.activated_at = Clock::get()?.unix_timestamp;
+    listing.set_status(ListingStatus::Active);
+    record_status(program_id, listing_info.key, &listing, status_history_info)?;
+    serialize_listing(listing_info, &listing)
+}
+
+/// Reject purchases from listings that are not active fixed-price sales. Each
+/// non-active status gets its own error so clients can explain why.
+fn assert_purchasable(listing: &Listing) -> ProgramResult {
+    match listing.status() {
+        ListingStatus::Active => {}
+        ListingStatus::AwaitingDeposit => return Err(EscrowError::ListingNotFunded.into()),
+        ListingStatus::Completed => return Err(EscrowError::ListingAlreadyCompleted.into()),
+        ListingStatus::Cancelled => return Err(EscrowError::ListingCancelled.into()),
+        ListingStatus::Frozen => return Err(EscrowError::ListingFrozen.into()),
+    }
+    if listing.is_auction() {
+        return Err(EscrowError::InvalidAuction.into());
+    }
+    if listing.is_swap() {
+        return Err(EscrowError::InvalidSwap.into());
+    }
+    Ok(())
+}
+
+/// Resolve `size` against the remaining inventory and check the fill limits.
+fn resolve_fill_quantity(listing: &Listing, size: PurchaseSize) -> Result<u64, ProgramError> {
+    let remaining = listing.remaining();
+    let quantity = size.resolve(remaining)?;
+    if quantity > remaining {
+        return Err(EscrowError::InsufficientQuantity.into());
+    }
+    if quantity < remaining && !listing.allow_partial() {
+        return Err(EscrowError::PartialFillDisabled.into());
+    }
+    assert_fill_size(listing, quantity)?;
+    Ok(quantity)
+}
+
+/// Quote side of a fill, as priced by `quote_fill`.
+struct FillQuote {
+    /// Paid to the seller.
+    quote_amount: u64,
+    /// Paid to the fee treasury on top of `quote_amount`.
+    taker_fee: u64,
+    /// Everything the buyer pays.
+    buyer_total: u64,
+    /// The listing's `quote_received` after the fill.
+    quote_received: u64,
+}
+
+/// Price `quantity` base units from the schedule, the oracle or the static
+/// price, then apply the quote-denominated limits and the taker fee. A buyer's
+/// `reference_price` bounds the oracle price by the listing's `max_slippage_bps`.
+fn quote_fill(
+    listing: &Listing,
+    quantity: u64,
+    price_schedule: Option<&PriceSchedule>,
+    oracle_info: Option<&AccountInfo>,
+    now: i64,
+    reference_price: Option<u64>,
+) -> Result<FillQuote, ProgramError> {
+    let quote_amount = match (price_schedule, oracle_info) {
+        (Some(schedule), _) => {
+            quote_amount_from_notional(schedule.notional(listing.filled, quantity)?, listing.base_decimals)?
+        }
+        (None, Some(oracle_info)) => {
+            let price_per_token = oracle_price_per_token(listing, oracle_info, now)?;
+            if let Some(reference_price) = reference_price {
+                assert_within_slippage(price_per_token, reference_price, listing.max_slippage_bps)?;
+            }
+            let notional = u128::from(quantity)
+                .checked_mul(u128::from(price_per_token))
+                .ok_or(EscrowError::AmountOverflow)?;
+            quote_amount_from_notional(notional, listing.base_decimals)?
+        }
+        (None, None) => static_quote_amount(listing, quantity)?,
+    };
+    // As with the base minimum, sweeping the remainder is always allowed.
+    if quote_amount < listing.min_fill_quote && quantity < listing.remaining() {
+        return Err(EscrowError::BelowMinimumQuoteFill.into());
+    }
+    let quote_received = listing
+        .quote_received
+        .checked_add(quote_amount)
+        .ok_or(EscrowError::AmountOverflow)?;
+    if listing.max_quote_raise > 0 && quote_received > listing.max_quote_raise {
+        return Err(EscrowError::RaiseCapExceeded.into());
+    }
+    let taker_fee = u128::from(quote_amount) * u128::from(listing.taker_fee_bps) / u128::from(BPS_DENOMINATOR);
+    let taker_fee = u64::try_from(taker_fee).map_err(|_| EscrowError::AmountOverflow)?;
+    let buyer_total = quote_amount
+        .checked_add(taker_fee)
+        .ok_or(EscrowError::AmountOverflow)?;
+    Ok(FillQuote {
+        quote_amount,
+        taker_fee,
+        buyer_total,
+        quote_received,
+    })
+}
+
+fn purchase_tokens(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    size: PurchaseSize,
+    options: PurchaseOptions,
+) -> ProgramResult {
+    let PurchaseOptions {
+        create_seller_quote,
+        relayer_tip,
+        delegated,
+        with_receipt,
+        allowlist_proof,
+        reference_price,
+    } = options;
+    if let PurchaseSize::Exact(0) = size {
+        return Err(EscrowError::AmountOverflow.into());
+    }
+    if relayer_tip.is_some_and(|tip| tip > MAX_RELAYER_TIP_LAMPORTS) {
+        return Err(EscrowError::RelayerTipTooLarge.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let buyer_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let seller_quote_account_info = next_account_info(account_info_iter)?;
+    let buyer_quote_account_info = next_account_info(account_info_iter)?;
+    let buyer_base_account_info = next_account_info(account_info_iter)?;
+    let vault_authority_info = next_account_info(account_info_iter)?;
+    let vault_token_account_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let fills_ledger_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let receipt_info = if with_receipt {
+        Some(next_account_info(account_info_iter)?)
+    } else {
+        None
+    };
+    let seller_quote_creation = if create_seller_quote {
+        Some((
+            next_account_info(account_info_iter)?,
+            next_account_info(account_info_iter)?,
+            next_account_info(account_info_iter)?,
+        ))
+    } else {
+        None
+    };
+    let relayer_info = match relayer_tip {
+        Some(_) => Some(next_account_info(account_info_iter)?),
+        None => None,
+    };
+    // A delegated purchase is authorized by the buyer's delegation; its
+    // delegate submits it and pays for any account growth instead.
+    let (payer_info, delegation_info) = if delegated {
+        (next_account_info(account_info_iter)?, Some(next_account_info(account_info_iter)?))
+    } else {
+        (buyer_info, None)
+    };
+
+    // Cheap signer, key and listing-field checks all run before any token
+    // account is unpacked or address derived, so rejected purchases fail early.
+    if !payer_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if token_program_info.key != &spl_token::id()
+        || system_program_info.key != &system_program::ID
+    {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    assert_distinct_accounts(&[
+        buyer_info,
+        listing_info,
+        seller_quote_account_info,
+        buyer_quote_account_info,
+        buyer_base_account_info,
+        vault_authority_info,
+        vault_token_account_info,
+    ])?;
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    let seller_stats_info = next_seller_stats(&listing, account_info_iter)?;
+    let status_history_info = next_status_history(&listing, account_info_iter)?;
+    let treasury_quote_info = if listing.taker_fee_bps > 0 {
+        Some(next_account_info(account_info_iter)?)
+    } else {
+        None
+    };
+    assert_purchasable(&listing)?;
+    if !listing.buyer_allowed_with_proof(buyer_info.key, allowlist_proof) {
+        return Err(EscrowError::BuyerNotAllowed.into());
+    }
+
+    let report_quantity = matches!(size, PurchaseSize::UpTo(_));
+    let quantity = resolve_fill_quantity(&listing, size)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let tranche = resolve_tranche(program_id, &listing, vault_authority_info.key)?;
+    if tranche.index >= listing.unlocked_tranches(now) {
+        return Err(EscrowError::TrancheLocked.into());
+    }
+
+    let price_schedule = if listing.has_price_schedule() {
+        let price_schedule_info = next_account_info(account_info_iter)?;
+        Some(load_price_schedule(program_id, listing_info.key, price_schedule_info)?)
+    } else {
+        None
+    };
+
+    if listing.requires_attestation() {
+        let attestation_info = next_account_info(account_info_iter)?;
+        assert_attestation(&listing.required_attestation_issuer, buyer_info.key, attestation_info)?;
+    }
+
+    let oracle_info = if price_schedule.is_none() && listing.uses_oracle() {
+        Some(next_account_info(account_info_iter)?)
+    } else {
+        None
+    };
+    let FillQuote {
+        quote_amount,
+        taker_fee,
+        buyer_total,
+        quote_received,
+    } = quote_fill(&listing, quantity, price_schedule.as_ref(), oracle_info, now, reference_price)?;
+    let delegation = match delegation_info {
+        Some(delegation_info) => {
+            let (mut delegation, bump) =
+                load_delegation(program_id, listing_info.key, buyer_info.key, delegation_info)?;
+            if payer_info.key != &delegation.delegate && payer_info.key != buyer_info.key {
+                return Err(EscrowError::IncorrectAuthority.into());
+            }
+            let max_quote = quote_amount_from_notional(
+                u128::from(quantity) * u128::from(delegation.max_price),
+                listing.base_decimals,
+            )?;
+            if quantity > delegation.quantity || quote_amount > max_quote {
+                return Err(EscrowError::InsufficientDelegation.into());
+            }
+            delegation.quantity -= quantity;
+            Some((delegation_info, delegation, bump))
+        }
+        None => None,
+    };
+
+    let basket = if listing.has_basket() {
+        let basket_info = next_account_info(account_info_iter)?;
+        Some(load_basket(program_id, listing_info.key, basket_info)?)
+    } else {
+        None
+    };
+
+    if let Some((seller_info, quote_mint_info, associated_token_program_info)) = seller_quote_creation {
+        if seller_info.key != &listing.seller || quote_mint_info.key != &listing.quote_mint {
+            return Err(EscrowError::IncorrectAuthority.into());
+        }
+        if seller_quote_account_info.key != &get_associated_token_address(&listing.seller, &listing.quote_mint) {
+            return Err(EscrowError::MintMismatch.into());
+        }
+        if seller_quote_account_info.data_is_empty() {
+            invoke(
+                &create_associated_token_account_idempotent(
+                    buyer_info.key,
+                    seller_info.key,
+                    quote_mint_info.key,
+                    token_program_info.key,
+                ),
+                &[
+                    buyer_info.clone(),
+                    seller_quote_account_info.clone(),
+                    seller_info.clone(),
+                    quote_mint_info.clone(),
+                    system_program_info.clone(),
+                    token_program_info.clone(),
+                    associated_token_program_info.clone(),
+                ],
+            )?;
+        }
+    }
+
+    // Validate token accounts
+    let seller_quote_account = TokenAccount::unpack(&seller_quote_account_info.data.borrow())?;
+    if listing.accrues_proceeds() {
+        // Proceeds go to the quote vault in the seller quote account's place.
+        if seller_quote_account_info.key != &get_associated_token_address(&listing.vault_authority, &listing.quote_mint)
+        {
+            return Err(EscrowError::MintMismatch.into());
+        }
+        assert_token_account_owner(&seller_quote_account, &listing.vault_authority)?;
+    } else {
+        assert_token_account_owner(&seller_quote_account, &listing.seller)?;
+    }
+    assert_token_account_mint(&seller_quote_account, &listing.quote_mint)?;
+
+    let buyer_quote_account = TokenAccount::unpack(&buyer_quote_account_info.data.borrow())?;
+    assert_token_account_owner(&buyer_quote_account, buyer_info.key)?;
+    assert_token_account_mint(&buyer_quote_account, &listing.quote_mint)?;
+    // Defense in depth: both legs of the quote transfer must share a mint even if listing state were corrupt.
+    if buyer_quote_account.mint != seller_quote_account.mint {
+        return Err(EscrowError::MintMismatch.into());
+    }
+    if buyer_quote_account.amount < buyer_total {
+        return Err(ProgramError::InsufficientFunds);
+    }
+    if delegation_info.is_some_and(|delegation_info| {
+        buyer_quote_account.delegate != COption::Some(*delegation_info.key)
+            || buyer_quote_account.delegated_amount < buyer_total
+    }) {
+        return Err(EscrowError::InsufficientDelegation.into());
+    }
+    if let Some(treasury_quote_info) = treasury_quote_info {
+        let treasury_quote_account = TokenAccount::unpack(&treasury_quote_info.data.borrow())?;
+        assert_token_account_owner(&treasury_quote_account, &listing.fee_treasury)?;
+        assert_token_account_mint(&treasury_quote_account, &listing.quote_mint)?;
+    }
+
+    let buyer_base_account = TokenAccount::unpack(&buyer_base_account_info.data.borrow())?;
+    assert_token_account_owner(&buyer_base_account, buyer_info.key)?;
+    assert_token_account_mint(&buyer_base_account, &listing.base_mint)?;
+
+    // Only the tranche authority's own ATA is the vault; a look-alike account
+    // with the same owner and mint is rejected.
+    let expected_vault = get_associated_token_address(vault_authority_info.key, &listing.base_mint);
+    if vault_token_account_info.key != &expected_vault {
+        return Err(EscrowError::MintMismatch.into());
+    }
+    let vault_token_account = TokenAccount::unpack(&vault_token_account_info.data.borrow())?;
+    assert_token_account_owner(&vault_token_account, vault_authority_info.key)?;
+    assert_token_account_mint(&vault_token_account, &listing.base_mint)?;
+    if vault_token_account.close_authority.is_some() {
+        return Err(EscrowError::VaultCloseAuthoritySet.into());
+    }
+    if vault_token_account.amount < quantity {
+        return Err(ProgramError::InsufficientFunds);
+    }
+
+    // Every basket leg is checked before any tokens move, so a leg that cannot
+    // be delivered is reported by index instead of as a mid-CPI failure.
+    let mut basket_legs = Vec::new();
+    if let Some(basket) = &basket {
+        for (index, leg) in basket.legs.iter().enumerate() {
+            let leg_vault_info = next_account_info(account_info_iter)?;
+            let buyer_leg_account_info = next_account_info(account_info_iter)?;
+            if let Err(err) =
+                check_basket_leg(leg, vault_authority_info.key, buyer_info.key, leg_vault_info, buyer_leg_account_info)
+            {
+                msg!("basket leg {} unsatisfiable: {}", index, err);
+                return Err(EscrowError::BasketLegUnsatisfiable.into());
+            }
+            basket_legs.push((leg.quantity, leg_vault_info, buyer_leg_account_info));
+        }
+    }
+    let global_stats_info = account_info_iter.next();
+
+    // The quote leg runs before the base leg. If either CPI fails the whole
+    // transaction is rolled back, so a buyer never pays without receiving base;
+    // the markers below only tell from the logs which leg failed.
+    msg!("quote transfer");
+    let mut quote_legs = vec![(seller_quote_account_info, quote_amount)];
+    if let (Some(treasury_quote_info), 1..) = (treasury_quote_info, taker_fee) {
+        quote_legs.push((treasury_quote_info, taker_fee));
+    }
+    for (destination_info, amount) in quote_legs {
+        if let Some((delegation_info, _, bump)) = &delegation {
+            let transfer_quote_ix = spl_token::instruction::transfer(
+                token_program_info.key,
+                buyer_quote_account_info.key,
+                destination_info.key,
+                delegation_info.key,
+                &[],
+                amount,
+            )?;
+            invoke_signed(
+                &transfer_quote_ix,
+                &[
+                    buyer_quote_account_info.clone(),
+                    destination_info.clone(),
+                    (*delegation_info).clone(),
+                    token_program_info.clone(),
+                ],
+                &[&[b"delegation", listing_info.key.as_ref(), buyer_info.key.as_ref(), &[*bump]]],
+            )?;
+        } else {
+            let transfer_quote_ix = spl_token::instruction::transfer(
+                token_program_info.key,
+                buyer_quote_account_info.key,
+                destination_info.key,
+                buyer_info.key,
+                &[],
+                amount,
+            )?;
+            invoke(
+                &transfer_quote_ix,
+                &[
+                    buyer_quote_account_info.clone(),
+                    destination_info.clone(),
+                    buyer_info.clone(),
+                    token_program_info.clone(),
+                ],
+            )?;
+        }
+    }
+
+    msg!("base transfer");
+    transfer_from_vault(
+        &listing,
+        &tranche,
+        token_program_info,
+        vault_token_account_info,
+        buyer_base_account_info,
+        vault_authority_info,
+        quantity,
+    )?;
+
+    // Basket legs are never split: each (leg vault, buyer account) pair receives the full leg.
+    for (leg_quantity, leg_vault_info, buyer_leg_account_info) in basket_legs {
+        transfer_from_vault(
+            &listing,
+            &tranche,
+            token_program_info,
+            leg_vault_info,
+            buyer_leg_account_info,
+            vault_authority_info,
+            leg_quantity,
+        )?;
+    }
+
+    listing.filled = listing
+        .filled
+        .checked_add(quantity)
+        .ok_or(EscrowError::AmountOverflow)?;
+    listing.quote_received = quote_received;
+    if listing.accrues_proceeds() {
+        listing.proceeds_accrued = listing
+            .proceeds_accrued
+            .checked_add(quote_amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+    }
+
+    listing.fill_count = listing.fill_count.saturating_add(1);
+
+    if listing.filled >= listing.quantity || listing.raise_cap_reached() || listing.fill_limit_reached() {
+        listing.set_status(ListingStatus::Completed);
+        release_listing_slot(program_id, &listing, seller_stats_info)?;
+        record_status(program_id, listing_info.key, &listing, status_history_info)?;
+    }
+
+    let fill_index = append_fill_record(
+        program_id,
+        listing_info.key,
+        payer_info,
+        fills_ledger_info,
+        system_program_info,
+        FillRecord {
+            buyer: *buyer_info.key,
+            quantity,
+            quote_amount,
+            ts: now,
+        },
+    )?;
+    if let Some(receipt_info) = receipt_info {
+        let (expected_receipt, bump) = find_receipt_address(program_id, listing_info.key, buyer_info.key, fill_index);
+        if receipt_info.key != &expected_receipt {
+            return Err(EscrowError::InvalidReceipt.into());
+        }
+        create_pda_account(
+            program_id,
+            buyer_info,
+            receipt_info,
+            system_program_info,
+            Receipt::LEN,
+            &[
+                b"receipt",
+                listing_info.key.as_ref(),
+                buyer_info.key.as_ref(),
+                &fill_index.to_le_bytes(),
+                &[bump],
+            ],
+        )?;
+        Receipt {
+            buyer: *buyer_info.key,
+            seller: listing.seller,
+            base_mint: listing.base_mint,
+            quote_mint: listing.quote_mint,
+            quantity,
+            quote_amount,
+            ts: now,
+        }
+        .serialize(&mut &mut receipt_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidReceipt)?;
+    }
+    if let Some(global_stats_info) = global_stats_info {
+        record_global_stats(program_id, payer_info, global_stats_info, Some(system_program_info), |stats| {
+            stats.quote_volume = stats.quote_volume.saturating_add(quote_amount);
+            stats.fees_collected = stats.fees_collected.saturating_add(taker_fee);
+        })?;
+    }
+
+    serialize_listing(listing_info, &listing)?;
+    if let Some((delegation_info, delegation, _)) = delegation {
+        delegation
+            .serialize(&mut &mut delegation_info.data.borrow_mut()[..])
+            .map_err(|_| EscrowError::InvalidDelegation)?;
+    }
+    if let (Some(relayer_info), Some(tip @ 1..)) = (relayer_info, relayer_tip) {
+        invoke(
+            &system_instruction::transfer(buyer_info.key, relayer_info.key, tip),
+            &[
+                buyer_info.clone(),
+                relayer_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+    }
+    if report_quantity {
+        set_return_data(&quantity.to_le_bytes());
+    }
+    Ok(())
+}
+
+/// Append `record` to the listing's fills ledger, creating the PDA on the first
+/// fill and growing it (the payer covers the extra rent) on later ones. A full
+/// ledger rejects the fill rather than drop a record.
+/// Returns the fill's index in the ledger.
+fn append_fill_record<'a>(
+    program_id: &Pubkey,
+    listing_key: &Pubkey,
+    payer_info: &AccountInfo<'a>,
+    fills_ledger_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    record: FillRecord,
+) -> Result<u32, ProgramError> {
+    let (expected_ledger, bump) = find_fills_ledger_address(program_id, listing_key);
+    if fills_ledger_info.key != &expected_ledger {
+        return Err(EscrowError::InvalidFillsLedger.into());
+    }
+    if system_program_info.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut ledger = if fills_ledger_info.data_is_empty() {
+        create_pda_account(
+            program_id,
+            payer_info,
+            fills_ledger_info,
+            system_program_info,
+            FillsLedger::len_for(1),
+            &[b"fills", listing_key.as_ref(), &[bump]],
+        )?;
+        FillsLedger {
+            listing: *listing_key,
+            records: Vec::new(),
+        }
+    } else {
+        if fills_ledger_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        FillsLedger::deserialize(&mut &fills_ledger_info.data.borrow()[..])
+            .map_err(|_| EscrowError::InvalidFillsLedger)?
+    };
+
+    if ledger.records.len() >= MAX_FILL_RECORDS {
+        return Err(EscrowError::FillsLedgerFull.into());
+    }
+    let fill_index = ledger.records.len() as u32;
+    ledger.records.push(record);
+
+    let new_len = FillsLedger::len_for(ledger.records.len());
+    if new_len > fills_ledger_info.data_len() {
+        let shortfall = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(fills_ledger_info.lamports());
+        if shortfall > 0 {
+            invoke(
+                &system_instruction::transfer(payer_info.key, fills_ledger_info.key, shortfall),
+                &[
+                    payer_info.clone(),
+                    fills_ledger_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        fills_ledger_info.realloc(new_len, false)?;
+    }
+
+    ledger
+        .serialize(&mut &mut fills_ledger_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidFillsLedger)?;
+    Ok(fill_index)
+}
+
+fn cancel_listing(program_id: &Pubkey, accounts: &[AccountInfo], force: bool) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let vault_authority_info = next_account_info(account_info_iter)?;
+    let vault_token_account_info = next_account_info(account_info_iter)?;
+    let seller_token_account_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    if !authority_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    assert_token_program(token_program_info)?;
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+
+    let seller_info = if force {
+        let seller_info = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+        load_config_as_admin(program_id, config_info, authority_info)?;
+        if seller_info.key != &listing.seller {
+            return Err(EscrowError::IncorrectAuthority.into());
+        }
+        msg!("ForceCancelled listing={} admin={}", listing_info.key, authority_info.key);
+        seller_info
+    } else if !listing.can_cancel(authority_info.key) {
+        return Err(EscrowError::IncorrectAuthority.into());
+    } else if authority_info.key == &listing.seller {
+        authority_info
+    } else {
+        let seller_info = next_account_info(account_info_iter)?;
+        if seller_info.key != &listing.seller {
+            return Err(EscrowError::IncorrectAuthority.into());
+        }
+        seller_info
+    };
+    // A raise that hit its cap, or a listing that took its `auto_complete_after_fills`
+    // purchases, completed with inventory left for the seller to reclaim; its
+    // slot is already free and `ReleaseFeeEscrow` settles its fee.
+    let reclaiming = listing.status() == ListingStatus::Completed;
+    // The seller pays to pull an unfilled listing, so a cancel authority needs
+    // the seller's co-signature to do it.
+    let charge_fee = !force && listing.owes_cancellation_fee();
+    if charge_fee && !seller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let seller_stats_info = if reclaiming {
+        None
+    } else {
+        next_seller_stats(&listing, account_info_iter)?
+    };
+    let status_history_info = next_status_history(&listing, account_info_iter)?;
+
+    match listing.status() {
+        ListingStatus::AwaitingDeposit | ListingStatus::Active => {}
+        // Only the admin may unwind a listing frozen by reconciliation.
+        ListingStatus::Frozen if force => {}
+        ListingStatus::Completed
+            if (listing.raise_cap_reached() || listing.fill_limit_reached()) && listing.remaining() > 0 => {}
+        _ => return Err(EscrowError::InvalidListingStatus.into()),
+    }
+    // A locked swap deposit is refunded by `CancelSwap`, not stranded.
+    if listing.is_swap() && listing.high_bid > 0 {
+        return Err(EscrowError::InvalidSwap.into());
+    }
+    // A locked high bid must be settled by `FinalizeAuction`, not stranded.
+    if listing.high_bid > 0 {
+        return Err(EscrowError::InvalidAuction.into());
+    }
+    release_listing_slot(program_id, &listing, seller_stats_info)?;
+
+    if listing.has_fee_escrow() && !reclaiming {
+        let fee_escrow_info = next_account_info(account_info_iter)?;
+        let treasury_info = next_account_info(account_info_iter)?;
+        settle_fee_escrow(program_id, listing_info.key, &listing, fee_escrow_info, seller_info, treasury_info)?;
+    }
+
+    if listing.status() == ListingStatus::AwaitingDeposit {
+        // Nothing was ever deposited, so cancel it and refund its rent.
+        listing.set_status(ListingStatus::Cancelled);
+        record_status(program_id, listing_info.key, &listing, status_history_info)?;
+        return close_unfunded_listing(listing_info, &mut listing, seller_info);
+    }
+
+    let remaining = listing.remaining();
+    if remaining > 0 {
+        let seller_base_account = TokenAccount::unpack(&seller_token_account_info.data.borrow())?;
+        assert_token_account_owner(&seller_base_account, seller_info.key)?;
+        assert_token_account_mint(&seller_base_account, &listing.base_mint)?;
+
+        // A single vault returns exactly the unsold quantity; tranche listings
+        // pass the remaining tranche authority/vault pairs and are emptied.
+        for index in 0..listing.vault_count.max(1) {
+            let (authority_info, vault_info) = if index == 0 {
+                (vault_authority_info, vault_token_account_info)
+            } else {
+                (
+                    next_account_info(account_info_iter)?,
+                    next_account_info(account_info_iter)?,
+                )
+            };
+            let tranche = tranche_vault(program_id, &listing, authority_info.key, index)?;
+            // A cancel agent must not pick which account the tranche is returned from.
+            if vault_info.key != &get_associated_token_address(authority_info.key, &listing.base_mint) {
+                return Err(EscrowError::MintMismatch.into());
+            }
+
+            let vault_token_account = TokenAccount::unpack(&vault_info.data.borrow())?;
+            assert_token_account_owner(&vault_token_account, authority_info.key)?;
+            assert_token_account_mint(&vault_token_account, &listing.base_mint)?;
+
+            // A frozen vault holds less than `remaining`; return what is left.
+            let amount = if listing.vault_count > 1 || listing.status() == ListingStatus::Frozen {
+                vault_token_account.amount
+            } else {
+                remaining
+            };
+            if amount > 0 {
+                transfer_from_vault(
+                    &listing,
+                    &tranche,
+                    token_program_info,
+                    vault_info,
+                    seller_token_account_info,
+                    authority_info,
+                    amount,
+                )?;
+            }
+        }
+
+        // Unsold basket legs go back through (leg vault, seller account) pairs.
+        if listing.has_basket() {
+            let basket_info = next_account_info(account_info_iter)?;
+            let basket = load_basket(program_id, listing_info.key, basket_info)?;
+            let tranche = tranche_vault(program_id, &listing, vault_authority_info.key, 0)?;
+            for leg in &basket.legs {
+                let leg_vault_info = next_account_info(account_info_iter)?;
+                let seller_leg_account_info = next_account_info(account_info_iter)?;
+                let leg_vault_account = load_leg_vault(leg, vault_authority_info.key, leg_vault_info)?;
+                let seller_leg_account = TokenAccount::unpack(&seller_leg_account_info.data.borrow())?;
+                assert_token_account_owner(&seller_leg_account, seller_info.key)?;
+                assert_token_account_mint(&seller_leg_account, &leg.base_mint)?;
+
+                if leg_vault_account.amount > 0 {
+                    transfer_from_vault(
+                        &listing,
+                        &tranche,
+                        token_program_info,
+                        leg_vault_info,
+                        seller_leg_account_info,
+                        vault_authority_info,
+                        leg_vault_account.amount,
+                    )?;
+                }
+            }
+        }
+    }
+
+    // Unclaimed proceeds go back with the unsold inventory, unless still vesting;
+    // those wait in the quote vault for `ClaimProceeds` after the cliff.
+    if listing.accrues_proceeds() {
+        let quote_vault_info = next_account_info(account_info_iter)?;
+        let seller_quote_account_info = next_account_info(account_info_iter)?;
+        if !listing.proceeds_locked(Clock::get()?.unix_timestamp) {
+            pay_out_proceeds(
+                program_id,
+                &mut listing,
+                vault_authority_info,
+                quote_vault_info,
+                seller_quote_account_info,
+                token_program_info,
+            )?;
+        }
+    }
+
+    if charge_fee {
+        let treasury_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        charge_cancellation_fee(&listing, seller_info, treasury_info, system_program_info)?;
+    }
+
+    listing.set_status(ListingStatus::Cancelled);
+    record_status(program_id, listing_info.key, &listing, status_history_info)?;
+    serialize_listing(listing_info, &listing)
+}
+
+/// Pay the listing's `cancellation_fee` from the seller to the listing's treasury.
+fn charge_cancellation_fee<'a>(
+    listing: &Listing,
+    payer_info: &AccountInfo<'a>,
+    treasury_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+) -> ProgramResult {
+    if treasury_info.key != &listing.fee_treasury {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    if system_program_info.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    invoke(
+        &system_instruction::transfer(payer_info.key, treasury_info.key, listing.cancellation_fee),
+        &[payer_info.clone(), treasury_info.clone(), system_program_info.clone()],
+    )
+}
+
+fn expire_listing(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let caller_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let seller_info = next_account_info(account_info_iter)?;
+
+    if !caller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    if seller_info.key != &listing.seller {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    if listing.status() != ListingStatus::AwaitingDeposit {
+        return Err(EscrowError::InvalidListingStatus.into());
+    }
+    if !listing.deposit_deadline_passed(Clock::get()?.unix_timestamp) {
+        return Err(EscrowError::DepositDeadlineNotReached.into());
+    }
+    // A locked swap deposit is refunded by `CancelSwap`, not stranded.
+    if listing.is_swap() && listing.high_bid > 0 {
+        return Err(EscrowError::InvalidSwap.into());
+    }
+
+    let seller_stats_info = next_seller_stats(&listing, account_info_iter)?;
+    let status_history_info = next_status_history(&listing, account_info_iter)?;
+    release_listing_slot(program_id, &listing, seller_stats_info)?;
+    if listing.has_fee_escrow() {
+        let fee_escrow_info = next_account_info(account_info_iter)?;
+        let treasury_info = next_account_info(account_info_iter)?;
+        settle_fee_escrow(program_id, listing_info.key, &listing, fee_escrow_info, seller_info, treasury_info)?;
+    }
+
+    msg!("ListingExpired listing={}", listing_info.key);
+    listing.set_status(ListingStatus::Cancelled);
+    record_status(program_id, listing_info.key, &listing, status_history_info)?;
+    close_unfunded_listing(listing_info, &mut listing, seller_info)
+}
+
+fn batch_cancel(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let seller_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    if !seller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    assert_token_program(token_program_info)?;
+
+    let (seller_stats_key, _) = find_seller_stats_address(program_id, seller_info.key);
+    let mut listing_keys: Vec<&Pubkey> = Vec::new();
+    while !account_info_iter.as_slice().is_empty() {
+        if listing_keys.len() == MAX_BATCH_LEGS {
+            return Err(EscrowError::BatchTooLarge.into());
+        }
+        let listing_info = next_account_info(account_info_iter)?;
+        let vault_authority_info = next_account_info(account_info_iter)?;
+        let vault_token_account_info = next_account_info(account_info_iter)?;
+        let seller_token_account_info = next_account_info(account_info_iter)?;
+        if listing_keys.contains(&listing_info.key) {
+            return Err(EscrowError::DuplicateAccount.into());
+        }
+        listing_keys.push(listing_info.key);
+        // A listing closed earlier (e.g. cancelled while awaiting deposit) is
+        // skipped, along with its seller stats PDA if the caller still passes it.
+        if listing_info.data_is_empty() {
+            if account_info_iter.as_slice().first().is_some_and(|info| info.key == &seller_stats_key) {
+                next_account_info(account_info_iter)?;
+            }
+            continue;
+        }
+        let mut listing = deserialize_listing(program_id, listing_info)?;
+        let seller_stats_info = next_seller_stats(&listing, account_info_iter)?;
+        if seller_info.key != &listing.seller {
+            return Err(EscrowError::IncorrectAuthority.into());
+        }
+        match listing.status() {
+            ListingStatus::Completed | ListingStatus::Cancelled => continue,
+            ListingStatus::AwaitingDeposit | ListingStatus::Active => {}
+            _ => return Err(EscrowError::InvalidListingStatus.into()),
+        }
+        // Listings needing per-listing trailing accounts are cancelled one at a time.
+        if listing.has_fee_escrow()
+            || listing.owes_cancellation_fee()
+            || listing.has_status_history()
+            || listing.has_basket()
+            || listing.accrues_proceeds()
+            || listing.vault_count > 1
+            || listing.high_bid > 0
+        {
+            return Err(EscrowError::InvalidListingStatus.into());
+        }
+        release_listing_slot(program_id, &listing, seller_stats_info)?;
+
+        if listing.status() == ListingStatus::AwaitingDeposit {
+            close_unfunded_listing(listing_info, &mut listing, seller_info)?;
+            continue;
+        }
+
+        let remaining = listing.remaining();
+        if remaining > 0 {
+            let seller_base_account = TokenAccount::unpack(&seller_token_account_info.data.borrow())?;
+            assert_token_account_owner(&seller_base_account, seller_info.key)?;
+            assert_token_account_mint(&seller_base_account, &listing.base_mint)?;
+            let tranche = tranche_vault(program_id, &listing, vault_authority_info.key, 0)?;
+            // As in `CancelListing`, only the vault purchases read from may be emptied.
+            if vault_token_account_info.key != &get_associated_token_address(vault_authority_info.key, &listing.base_mint)
+            {
+                return Err(EscrowError::MintMismatch.into());
+            }
+            let vault_token_account = TokenAccount::unpack(&vault_token_account_info.data.borrow())?;
+            assert_token_account_owner(&vault_token_account, vault_authority_info.key)?;
+            assert_token_account_mint(&vault_token_account, &listing.base_mint)?;
+            transfer_from_vault(
+                &listing,
+                &tranche,
+                token_program_info,
+                vault_token_account_info,
+                seller_token_account_info,
+                vault_authority_info,
+                remaining,
+            )?;
+        }
+
+        listing.set_status(ListingStatus::Cancelled);
+        serialize_listing(listing_info, &listing)?;
+    }
+    Ok(())
+}
+
+/// Split the escrowed fee by fill progress: the filled share is earned by the
+/// treasury, the unfilled share and the escrow rent go back to the seller.
+fn settle_fee_escrow(
+    program_id: &Pubkey,
+    listing_key: &Pubkey,
+    listing: &Listing,
+    fee_escrow_info: &AccountInfo,
+    seller_info: &AccountInfo,
+    treasury_info: &AccountInfo,
+) -> ProgramResult {
+    let (expected_escrow, _) = find_fee_escrow_address(program_id, listing_key);
+    if fee_escrow_info.key != &expected_escrow || fee_escrow_info.owner != program_id {
+        return Err(EscrowError::InvalidFeeEscrow.into());
+    }
+    if treasury_info.key != &listing.fee_treasury {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+
+    let refund = u128::from(listing.fee_amount_paid)
+        .checked_mul(u128::from(listing.remaining()))
+        .and_then(|value| value.checked_div(u128::from(listing.quantity)))
+        .ok_or(EscrowError::AmountOverflow)?;
+    let refund = u64::try_from(refund).map_err(|_| EscrowError::AmountOverflow)?;
+    let earned = listing
+        .fee_amount_paid
+        .checked_sub(refund)
+        .ok_or(EscrowError::AmountOverflow)?;
+
+    let escrow_lamports = fee_escrow_info.lamports();
+    let seller_share = escrow_lamports
+        .checked_sub(earned)
+        .ok_or(EscrowError::AmountOverflow)?;
+    **treasury_info.try_borrow_mut_lamports()? = treasury_info
+        .lamports()
+        .checked_add(earned)
+        .ok_or(EscrowError::AmountOverflow)?;
+    **seller_info.try_borrow_mut_lamports()? = seller_info
+        .lamports()
+        .checked_add(seller_share)
+        .ok_or(EscrowError::AmountOverflow)?;
+    **fee_escrow_info.try_borrow_mut_lamports()? = 0;
+    Ok(())
+}
+
+fn read_listing(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let listing_info = next_account_info(account_info_iter)?;
+
+    let listing = deserialize_listing(program_id, listing_info)?;
+    let data = listing
+        .try_to_vec()
+        .map_err(|_| EscrowError::InvalidInstructionData)?;
+    if data.len() > MAX_RETURN_DATA {
+        return Err(EscrowError::AccountLengthMismatch.into());
+    }
+
+    set_return_data(&data);
+    Ok(())
+}
+
+fn read_global_stats(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let global_stats_info = next_account_info(account_info_iter)?;
+
+    let stats = load_global_stats(program_id, global_stats_info)?;
+    let data = stats
+        .try_to_vec()
+        .map_err(|_| EscrowError::InvalidInstructionData)?;
+    set_return_data(&data);
+    Ok(())
+}
+
+fn get_time_remaining(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let listing_info = next_account_info(account_info_iter)?;
+
+    let listing = deserialize_listing(program_id, listing_info)?;
+    let remaining = if listing.is_auction() {
+        let now = Clock::get()?.unix_timestamp;
+        listing.auction_end.saturating_sub(now).max(0) as u64
+    } else {
+        0
+    };
+
+    set_return_data(&remaining.to_le_bytes());
+    Ok(())
+}
+
+fn set_price_schedule(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    price_steps: Vec<(u64, u64)>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let seller_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let price_schedule_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !seller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if system_program_info.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    if &listing.seller != seller_info.key {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    if listing.status() != ListingStatus::AwaitingDeposit || listing.has_price_schedule() {
+        return Err(EscrowError::InvalidListingStatus.into());
+    }
+    if listing.is_auction() {
+        return Err(EscrowError::InvalidAuction.into());
+    }
+    if listing.uses_ratio_price() {
+        return Err(EscrowError::InvalidPriceSchedule.into());
+    }
+
+    if price_steps.is_empty() || price_steps.len() > MAX_PRICE_STEPS {
+        return Err(EscrowError::InvalidPriceSchedule.into());
+    }
+    let mut previous_threshold = 0u64;
+    for &(threshold, price) in &price_steps {
+        if threshold <= previous_threshold || price == 0 {
+            return Err(EscrowError::InvalidPriceSchedule.into());
+        }
+        previous_threshold = threshold;
+    }
+    if previous_threshold < listing.quantity {
+        return Err(EscrowError::InvalidPriceSchedule.into());
+    }
+
+    let (expected_schedule, bump) = find_price_schedule_address(program_id, listing_info.key);
+    if price_schedule_info.key != &expected_schedule {
+        return Err(EscrowError::InvalidPriceSchedule.into());
+    }
+
+    create_pda_account(
+        program_id,
+        seller_info,
+        price_schedule_info,
+        system_program_info,
+        PriceSchedule::len_for(price_steps.len()),
+        &[b"price_steps", listing_info.key.as_ref(), &[bump]],
+    )?;
+
+    let schedule = PriceSchedule {
+        listing: *listing_info.key,
+        steps: price_steps,
+    };
+    schedule
+        .serialize(&mut &mut price_schedule_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData)?;
+
+    listing.flags |= FLAG_PRICE_SCHEDULE;
+    serialize_listing(listing_info, &listing)
+}
+
+fn initialize_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    default_treasury: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !admin_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if system_program_info.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let (expected_config, bump) = find_config_address(program_id);
+    if config_info.key != &expected_config {
+        return Err(EscrowError::InvalidConfig.into());
+    }
+    if !config_info.data_is_empty() {
+        return Err(EscrowError::AlreadyInitialized.into());
+    }
+
+    create_pda_account(
+        program_id,
+        admin_info,
+        config_info,
+        system_program_info,
+        Config::LEN,
+        &[b"config", &[bump]],
+    )?;
+
+    let config = Config {
+        admin: *admin_info.key,
+        default_treasury,
+        max_active_listings: 0,
+        fee_mint: Pubkey::default(),
+        pending_admin: Pubkey::default(),
+        max_listing_quantity: 0,
+        burn_bps: 0,
+        cancellation_fee: 0,
+        min_listing_quantity: 0,
+        spl_fee_amount: 0,
+        native_fee_lamports: 0,
+    };
+    config
+        .serialize(&mut &mut config_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+fn set_treasury(program_id: &Pubkey, accounts: &[AccountInfo], treasury: Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let treasury_route_info = next_account_info(account_info_iter)?;
+    let quote_mint_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    load_config_as_admin(program_id, config_info, admin_info)?;
+    if system_program_info.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let (expected_route, bump) = find_treasury_address(program_id, quote_mint_info.key);
+    if treasury_route_info.key != &expected_route {
+        return Err(EscrowError::InvalidConfig.into());
+    }
+
+    if treasury_route_info.data_is_empty() {
+        Mint::unpack(&quote_mint_info.data.borrow())?;
+        create_pda_account(
+            program_id,
+            admin_info,
+            treasury_route_info,
+            system_program_info,
+            TreasuryRoute::LEN,
+            &[b"treasury", quote_mint_info.key.as_ref(), &[bump]],
+        )?;
+    } else if treasury_route_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let route = TreasuryRoute {
+        quote_mint: *quote_mint_info.key,
+        treasury,
+    };
+    route
+        .serialize(&mut &mut treasury_route_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+fn release_fee_escrow(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let listing_info = next_account_info(account_info_iter)?;
+    let fee_escrow_info = next_account_info(account_info_iter)?;
+    let seller_info = next_account_info(account_info_iter)?;
+    let treasury_info = next_account_info(account_info_iter)?;
+
+    let listing = deserialize_listing(program_id, listing_info)?;
+    if !listing.has_fee_escrow() {
+        return Err(EscrowError::InvalidFeeEscrow.into());
+    }
+    if listing.status() != ListingStatus::Completed {
+        return Err(EscrowError::InvalidListingStatus.into());
+    }
+    if seller_info.key != &listing.seller {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+
+    settle_fee_escrow(program_id, listing_info.key, &listing, fee_escrow_info, seller_info, treasury_info)
+}
+
+fn sweep_vault_lamports(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let seller_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let vault_authority_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !seller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if system_program_info.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let listing = deserialize_listing(program_id, listing_info)?;
+    if seller_info.key != &listing.seller {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    match listing.status() {
+        ListingStatus::Completed | ListingStatus::Cancelled => {}
+        _ => return Err(EscrowError::InvalidListingStatus.into()),
+    }
+    let tranche = resolve_tranche(program_id, &listing, vault_authority_info.key)?;
+
+    // The authority is a data-less, system-owned PDA: it can be fully drained
+    // without a rent reserve and still signs through its seeds afterwards.
+    let lamports = vault_authority_info.lamports();
+    if lamports == 0 {
+        return Ok(());
+    }
+    invoke_signed_by_vault(
+        &listing,
+        &tranche,
+        &system_instruction::transfer(vault_authority_info.key, seller_info.key, lamports),
+        &[
+            vault_authority_info.clone(),
+            seller_info.clone(),
+            system_program_info.clone(),
+        ],
+    )
+}
+
+fn add_fee_exempt(program_id: &Pubkey, accounts: &[AccountInfo], seller: Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let fee_exempt_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    load_config_as_admin(program_id, config_info, admin_info)?;
+    if system_program_info.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let (expected_exemption, bump) = find_fee_exempt_address(program_id, &seller);
+    if fee_exempt_info.key != &expected_exemption {
+        return Err(EscrowError::InvalidConfig.into());
+    }
+    if !fee_exempt_info.data_is_empty() {
+        return Err(EscrowError::AlreadyInitialized.into());
+    }
+
+    create_pda_account(
+        program_id,
+        admin_info,
+        fee_exempt_info,
+        system_program_info,
+        FeeExemption::LEN,
+        &[b"fee_exempt", seller.as_ref(), &[bump]],
+    )?;
+    FeeExemption { seller }
+        .serialize(&mut &mut fee_exempt_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+fn remove_fee_exempt(program_id: &Pubkey, accounts: &[AccountInfo], seller: Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let fee_exempt_info = next_account_info(account_info_iter)?;
+
+    load_config_as_admin(program_id, config_info, admin_info)?;
+
+    let (expected_exemption, _) = find_fee_exempt_address(program_id, &seller);
+    if fee_exempt_info.key != &expected_exemption || fee_exempt_info.owner != program_id {
+        return Err(EscrowError::InvalidConfig.into());
+    }
+
+    let rent = fee_exempt_info.lamports();
+    **admin_info.try_borrow_mut_lamports()? = admin_info
+        .lamports()
+        .checked_add(rent)
+        .ok_or(EscrowError::AmountOverflow)?;
+    **fee_exempt_info.try_borrow_mut_lamports()? = 0;
+    fee_exempt_info.data.borrow_mut().fill(0);
+    Ok(())
+}
+
+fn register_interest(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let caller_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let seller_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !caller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if system_program_info.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    if seller_info.key != &listing.seller {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    match listing.status() {
+        ListingStatus::AwaitingDeposit | ListingStatus::Active => {}
+        _ => return Err(EscrowError::InvalidListingStatus.into()),
+    }
+
+    invoke(
+        &system_instruction::transfer(caller_info.key, seller_info.key, INTEREST_FEE_LAMPORTS),
+        &[
+            caller_info.clone(),
+            seller_info.clone(),
+            system_program_info.clone(),
+        ],
+    )?;
+
+    // Saturate rather than fail: the counter is only a hint.
+    listing.interest_count = listing.interest_count.saturating_add(1);
+    serialize_listing(listing_info, &listing)
+}
+
+fn set_basket_legs(program_id: &Pubkey, accounts: &[AccountInfo], legs: Vec<BasketLeg>) -> ProgramResult {
+    if legs.len() > MAX_BATCH_LEGS {
+        return Err(EscrowError::BatchTooLarge.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let seller_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let basket_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !seller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if system_program_info.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    if &listing.seller != seller_info.key {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    if listing.status() != ListingStatus::AwaitingDeposit || listing.has_basket() {
+        return Err(EscrowError::InvalidListingStatus.into());
+    }
+    // A basket is only ever bought whole, from a single vault authority.
+    if listing.is_auction() {
+        return Err(EscrowError::InvalidAuction.into());
+    }
+    if listing.allow_partial() || listing.vault_count > 1 {
+        return Err(EscrowError::InvalidBasket.into());
+    }
+
+    if legs.is_empty() {
+        return Err(EscrowError::InvalidBasket.into());
+    }
+    for (index, leg) in legs.iter().enumerate() {
+        let duplicate = leg.base_mint == listing.base_mint
+            || legs[..index].iter().any(|other| other.base_mint == leg.base_mint);
+        if leg.quantity == 0 || duplicate {
+            return Err(EscrowError::InvalidBasket.into());
+        }
+    }
+
+    let (expected_basket, bump) = find_basket_address(program_id, listing_info.key);
+    if basket_info.key != &expected_basket {
+        return Err(EscrowError::InvalidBasket.into());
+    }
+
+    create_pda_account(
+        program_id,
+        seller_info,
+        basket_info,
+        system_program_info,
+        Basket::len_for(legs.len()),
+        &[b"basket", listing_info.key.as_ref(), &[bump]],
+    )?;
+
+    let basket = Basket {
+        listing: *listing_info.key,
+        legs,
+    };
+    basket
+        .serialize(&mut &mut basket_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData)?;
+
+    listing.flags |= FLAG_BASKET;
+    serialize_listing(listing_info, &listing)
+}
+
+fn enable_proceeds_vault(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let seller_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let vault_authority_info = next_account_info(account_info_iter)?;
+    let quote_vault_info = next_account_info(account_info_iter)?;
+    let quote_mint_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let associated_token_program_info = next_account_info(account_info_iter)?;
+
+    if !seller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if system_program_info.key != &system_program::ID || token_program_info.key != &spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    if &listing.seller != seller_info.key {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    match listing.status() {
+        ListingStatus::AwaitingDeposit | ListingStatus::Active if !listing.accrues_proceeds() => {}
+        _ => return Err(EscrowError::InvalidListingStatus.into()),
+    }
+    if vault_authority_info.key != &listing.vault_authority || quote_mint_info.key != &listing.quote_mint {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    if quote_vault_info.key != &get_associated_token_address(vault_authority_info.key, quote_mint_info.key) {
+        return Err(EscrowError::MintMismatch.into());
+    }
+
+    invoke(
+        &create_associated_token_account_idempotent(
+            seller_info.key,
+            vault_authority_info.key,
+            quote_mint_info.key,
+            token_program_info.key,
+        ),
+        &[
+            seller_info.clone(),
+            quote_vault_info.clone(),
+            vault_authority_info.clone(),
+            quote_mint_info.clone(),
+            system_program_info.clone(),
+            token_program_info.clone(),
+            associated_token_program_info.clone(),
+        ],
+    )?;
+
+    listing.flags |= FLAG_PROCEEDS_VAULT;
+    serialize_listing(listing_info, &listing)
+}
+
+fn claim_proceeds(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let seller_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let vault_authority_info = next_account_info(account_info_iter)?;
+    let quote_vault_info = next_account_info(account_info_iter)?;
+    let seller_quote_account_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    if !seller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    assert_token_program(token_program_info)?;
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    if &listing.seller != seller_info.key {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    if !listing.accrues_proceeds() {
+        return Err(EscrowError::InvalidListingStatus.into());
+    }
+    if listing.proceeds_locked(Clock::get()?.unix_timestamp) {
+        return Err(EscrowError::ProceedsLocked.into());
+    }
+    // Claiming with nothing accrued is a no-op, so sellers can sweep on a schedule.
+    let amount = pay_out_proceeds(
+        program_id,
+        &mut listing,
+        vault_authority_info,
+        quote_vault_info,
+        seller_quote_account_info,
+        token_program_info,
+    )?;
+    if amount == 0 {
+        return Ok(());
+    }
+    msg!("ProceedsClaimed listing={} amount={}", listing_info.key, amount);
+    serialize_listing(listing_info, &listing)
+}
+
+/// Move the listing's `proceeds_accrued` from its quote vault to the seller's
+/// quote account, returning the amount paid.
+fn pay_out_proceeds<'a>(
+    program_id: &Pubkey,
+    listing: &mut Listing,
+    vault_authority_info: &AccountInfo<'a>,
+    quote_vault_info: &AccountInfo<'a>,
+    seller_quote_account_info: &AccountInfo<'a>,
+    token_program_info: &AccountInfo<'a>,
+) -> Result<u64, ProgramError> {
+    let tranche = tranche_vault(program_id, listing, vault_authority_info.key, 0)?;
+    if quote_vault_info.key != &get_associated_token_address(vault_authority_info.key, &listing.quote_mint) {
+        return Err(EscrowError::MintMismatch.into());
+    }
+    let seller_quote_account = TokenAccount::unpack(&seller_quote_account_info.data.borrow())?;
+    assert_token_account_owner(&seller_quote_account, &listing.seller)?;
+    assert_token_account_mint(&seller_quote_account, &listing.quote_mint)?;
+
+    let amount = listing.proceeds_accrued;
+    if amount > 0 {
+        transfer_from_vault(
+            listing,
+            &tranche,
+            token_program_info,
+            quote_vault_info,
+            seller_quote_account_info,
+            vault_authority_info,
+            amount,
+        )?;
+        listing.proceeds_accrued = 0;
+    }
+    Ok(amount)
+}
+
+fn enable_status_history(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let seller_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let status_history_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !seller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if system_program_info.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    if &listing.seller != seller_info.key {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    match listing.status() {
+        ListingStatus::AwaitingDeposit | ListingStatus::Active if !listing.has_status_history() => {}
+        _ => return Err(EscrowError::InvalidListingStatus.into()),
+    }
+
+    let (expected_history, bump) = find_status_history_address(program_id, listing_info.key);
+    if status_history_info.key != &expected_history {
+        return Err(EscrowError::InvalidStatusHistory.into());
+    }
+    create_pda_account(
+        program_id,
+        seller_info,
+        status_history_info,
+        system_program_info,
+        StatusHistory::LEN,
+        &[b"status_history", listing_info.key.as_ref(), &[bump]],
+    )?;
+    StatusHistory {
+        listing: *listing_info.key,
+        entries: Vec::new(),
+    }
+    .serialize(&mut &mut status_history_info.data.borrow_mut()[..])
+    .map_err(|_| EscrowError::InvalidStatusHistory)?;
+
+    listing.flags |= FLAG_STATUS_HISTORY;
+    record_status(program_id, listing_info.key, &listing, Some(status_history_info))?;
+    serialize_listing(listing_info, &listing)
+}
+
+/// Quote cost of `quantity` base units at the listing's static price.
+fn static_quote_amount(listing: &Listing, quantity: u64) -> Result<u64, ProgramError> {
+    if listing.uses_ratio_price() {
+        let quote_amount = u128::from(quantity) * u128::from(listing.quote_per_base_num)
+            / u128::from(listing.quote_per_base_den);
+        if quote_amount == 0 {
+            return Err(EscrowError::AmountOverflow.into());
+        }
+        return u64::try_from(quote_amount).map_err(|_| EscrowError::AmountOverflow.into());
+    }
+    let notional = u128::from(quantity)
+        .checked_mul(u128::from(listing.price_per_token))
+        .ok_or(EscrowError::AmountOverflow)?;
+    quote_amount_from_notional(notional, listing.base_decimals)
+}
+
+/// Claims and installments settle at `price_per_token` from the primary vault
+/// without the purchase-time checks, so they are only offered on active listings
+/// without schedules, oracles, baskets, tranches, attestation requirements,
+/// auctions, swaps, raise caps, ratio pricing or taker fees.
+fn assert_simple_active(listing: &Listing) -> ProgramResult {
+    if listing.status() != ListingStatus::Active
+        || listing.uses_ratio_price()
+        || listing.has_price_schedule()
+        || listing.uses_oracle()
+        || listing.has_basket()
+        || listing.vault_count > 1
+        || listing.requires_attestation()
+        || listing.is_auction()
+        || listing.is_swap()
+        || listing.max_quote_raise > 0
+        || listing.taker_fee_bps > 0
+    {
+        return Err(EscrowError::InvalidListingStatus.into());
+    }
+    Ok(())
+}
+
+fn register_claim(program_id: &Pubkey, accounts: &[AccountInfo], desired: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let buyer_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let vault_authority_info = next_account_info(account_info_iter)?;
+    let claim_info = next_account_info(account_info_iter)?;
+    let buyer_quote_account_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !buyer_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if system_program_info.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    assert_token_program(token_program_info)?;
+    if desired == 0 {
+        return Err(EscrowError::AmountOverflow.into());
+    }
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    assert_simple_active(&listing)?;
+    let now = Clock::get()?.unix_timestamp;
+    // A round whose settlement window lapsed is abandoned; its claims can only be closed.
+    if listing.claim_count > 0 && now >= listing.claims_close_at.saturating_add(CLAIM_WINDOW_SECS) {
+        listing.claim_count = 0;
+        end_claim_round(&mut listing);
+    }
+    if listing.claim_count == 0 {
+        listing.claims_close_at = now.checked_add(CLAIM_WINDOW_SECS).ok_or(EscrowError::AmountOverflow)?;
+    } else if now >= listing.claims_close_at {
+        return Err(EscrowError::ClaimWindowClosed.into());
+    }
+    listing.claim_count = listing.claim_count.checked_add(1).ok_or(EscrowError::AmountOverflow)?;
+    listing.claims_desired = listing
+        .claims_desired
+        .checked_add(desired)
+        .ok_or(EscrowError::AmountOverflow)?;
+    if !listing.buyer_allowed(buyer_info.key) {
+        return Err(EscrowError::BuyerNotAllowed.into());
+    }
+    if vault_authority_info.key != &listing.vault_authority {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+
+    let buyer_quote_account = TokenAccount::unpack(&buyer_quote_account_info.data.borrow())?;
+    assert_token_account_owner(&buyer_quote_account, buyer_info.key)?;
+    assert_token_account_mint(&buyer_quote_account, &listing.quote_mint)?;
+    // A token account has a single delegate; approving ours would silently
+    // void another claim's or delegation's approval.
+    assert_delegate_replaceable(&buyer_quote_account, vault_authority_info.key)?;
+
+    let (expected_claim, bump) = find_claim_address(program_id, listing_info.key, buyer_info.key);
+    if claim_info.key != &expected_claim {
+        return Err(EscrowError::InvalidClaim.into());
+    }
+    if !claim_info.data_is_empty() {
+        return Err(EscrowError::AlreadyInitialized.into());
+    }
+    create_pda_account(
+        program_id,
+        buyer_info,
+        claim_info,
+        system_program_info,
+        Claim::LEN,
+        &[b"claim", listing_info.key.as_ref(), buyer_info.key.as_ref(), &[bump]],
+    )?;
+    Claim {
+        listing: *listing_info.key,
+        buyer: *buyer_info.key,
+        desired,
+        round_close_at: listing.claims_close_at,
+    }
+    .serialize(&mut &mut claim_info.data.borrow_mut()[..])
+    .map_err(|_| EscrowError::InvalidClaim)?;
+    serialize_listing(listing_info, &listing)?;
+
+    // The vault authority pulls the quote at settlement, so approve it for the
+    // most the claim could cost.
+    let approve_ix = spl_token::instruction::approve(
+        token_program_info.key,
+        buyer_quote_account_info.key,
+        vault_authority_info.key,
+        buyer_info.key,
+        &[],
+        static_quote_amount(&listing, desired)?,
+    )?;
+    invoke(
+        &approve_ix,
+        &[
+            buyer_quote_account_info.clone(),
+            vault_authority_info.clone(),
+            buyer_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
+
+fn settle_claims(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let seller_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let vault_authority_info = next_account_info(account_info_iter)?;
+    let vault_token_account_info = next_account_info(account_info_iter)?;
+    let seller_quote_account_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let fills_ledger_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !seller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    assert_token_program(token_program_info)?;
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    if seller_info.key != &listing.seller {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    assert_simple_active(&listing)?;
+    let seller_stats_info = next_seller_stats(&listing, account_info_iter)?;
+    let status_history_info = next_status_history(&listing, account_info_iter)?;
+    let tranche = tranche_vault(program_id, &listing, vault_authority_info.key, 0)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    if listing.claim_count == 0 || now < listing.claims_close_at {
+        return Err(EscrowError::ClaimWindowOpen.into());
+    }
+    if now >= listing.claims_close_at.saturating_add(CLAIM_WINDOW_SECS) {
+        return Err(EscrowError::ClaimWindowClosed.into());
+    }
+    // Every batch of the round shares out what was left when settlement began.
+    if listing.claims_supply == 0 {
+        listing.claims_supply = listing.remaining();
+    }
+
+    let seller_quote_account = TokenAccount::unpack(&seller_quote_account_info.data.borrow())?;
+    assert_token_account_owner(&seller_quote_account, &listing.seller)?;
+    assert_token_account_mint(&seller_quote_account, &listing.quote_mint)?;
+
+    let remaining_accounts = account_info_iter.as_slice();
+    if !remaining_accounts.len().is_multiple_of(4) {
+        return Err(EscrowError::InvalidClaim.into());
+    }
+    let claim_accounts = remaining_accounts.chunks(4).collect::<Vec<_>>();
+    if claim_accounts.len() > MAX_BATCH_LEGS {
+        return Err(EscrowError::BatchTooLarge.into());
+    }
+
+    for accounts in &claim_accounts {
+        let [claim_info, buyer_info, buyer_quote_account_info, buyer_base_account_info] = accounts else {
+            return Err(EscrowError::InvalidClaim.into());
+        };
+        // A claim passed twice is already closed by its first settlement.
+        let (expected_claim, _) = find_claim_address(program_id, listing_info.key, buyer_info.key);
+        if claim_info.key != &expected_claim || claim_info.owner != program_id || claim_info.data_is_empty() {
+            return Err(EscrowError::InvalidClaim.into());
+        }
+        let claim = Claim::deserialize(&mut &claim_info.data.borrow()[..]).map_err(|_| EscrowError::InvalidClaim)?;
+        if claim.round_close_at != listing.claims_close_at {
+            return Err(EscrowError::InvalidClaim.into());
+        }
+
+        // The last claim of the round also takes the rounding dust.
+        let share = if listing.claim_count == 1 {
+            claim.desired
+        } else {
+            claim_allocation(claim.desired, listing.claims_desired, listing.claims_supply)
+        };
+        let mut quantity = share.min(listing.remaining());
+        listing.claim_count -= 1;
+
+        let mut quote_amount = 0;
+        if quantity > 0 {
+            let buyer_quote_account = TokenAccount::unpack(&buyer_quote_account_info.data.borrow())?;
+            assert_token_account_owner(&buyer_quote_account, buyer_info.key)?;
+            assert_token_account_mint(&buyer_quote_account, &listing.quote_mint)?;
+            quote_amount = static_quote_amount(&listing, quantity)?;
+            // The buyer may have revoked, replaced or spent the approval since
+            // registering; skip the claim rather than fail the whole batch.
+            if buyer_quote_account.delegate != COption::Some(*vault_authority_info.key)
+                || buyer_quote_account.delegated_amount < quote_amount
+                || buyer_quote_account.amount < quote_amount
+            {
+                msg!("ClaimSkipped listing={} buyer={}", listing_info.key, buyer_info.key);
+                quantity = 0;
+            }
+        }
+
+        if quantity > 0 {
+            let buyer_base_account = TokenAccount::unpack(&buyer_base_account_info.data.borrow())?;
+            assert_token_account_owner(&buyer_base_account, buyer_info.key)?;
+            assert_token_account_mint(&buyer_base_account, &listing.base_mint)?;
+
+            let transfer_quote_ix = spl_token::instruction::transfer(
+                token_program_info.key,
+                buyer_quote_account_info.key,
+                seller_quote_account_info.key,
+                vault_authority_info.key,
+                &[],
+                quote_amount,
+            )?;
+            invoke_signed_by_vault(
+                &listing,
+                &tranche,
+                &transfer_quote_ix,
+                &[
+                    buyer_quote_account_info.clone(),
+                    seller_quote_account_info.clone(),
+                    vault_authority_info.clone(),
+                    token_program_info.clone(),
+                ],
+            )?;
+            transfer_from_vault(
+                &listing,
+                &tranche,
+                token_program_info,
+                vault_token_account_info,
+                buyer_base_account_info,
+                vault_authority_info,
+                quantity,
+            )?;
+
+            listing.filled = listing
+                .filled
+                .checked_add(quantity)
+                .ok_or(EscrowError::AmountOverflow)?;
+            append_fill_record(
+                program_id,
+                listing_info.key,
+                seller_info,
+                fills_ledger_info,
+                system_program_info,
+                FillRecord {
+                    buyer: *buyer_info.key,
+                    quantity,
+                    quote_amount,
+                    ts: now,
+                },
+            )?;
+        }
+
+        // Settled claims are closed either way, returning their rent to the buyer.
+        close_program_account(claim_info, buyer_info)?;
+    }
+
+    if listing.claim_count == 0 {
+        end_claim_round(&mut listing);
+    }
+    if listing.filled >= listing.quantity {
+        listing.set_status(ListingStatus::Completed);
+        release_listing_slot(program_id, &listing, seller_stats_info)?;
+        record_status(program_id, listing_info.key, &listing, status_history_info)?;
+    }
+    serialize_listing(listing_info, &listing)
+}
+
+/// Reset the claim round once its last claim is settled or closed.
+fn end_claim_round(listing: &mut Listing) {
+    listing.claims_desired = 0;
+    listing.claims_close_at = 0;
+    listing.claims_supply = 0;
+}
+
+fn close_claim(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let buyer_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let claim_info = next_account_info(account_info_iter)?;
+    let buyer_quote_account_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    if !buyer_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    assert_token_program(token_program_info)?;
+
+    let (expected_claim, _) = find_claim_address(program_id, listing_info.key, buyer_info.key);
+    if claim_info.key != &expected_claim || claim_info.owner != program_id || claim_info.data_is_empty() {
+        return Err(EscrowError::InvalidClaim.into());
+    }
+    let claim = Claim::deserialize(&mut &claim_info.data.borrow()[..]).map_err(|_| EscrowError::InvalidClaim)?;
+
+    // Only a claim of the listing's current round still counts toward it; a
+    // closed listing or an abandoned round has nothing to update.
+    let mut vault_authority = None;
+    if !listing_info.data_is_empty() {
+        let mut listing = deserialize_listing(program_id, listing_info)?;
+        vault_authority = Some(find_tranche_vault_authority(program_id, &listing.seller, listing.listing_id, 0).0);
+        if listing.claim_count > 0 && claim.round_close_at == listing.claims_close_at {
+            let now = Clock::get()?.unix_timestamp;
+            let settling = now >= listing.claims_close_at
+                && now < listing.claims_close_at.saturating_add(CLAIM_WINDOW_SECS);
+            if settling && listing.status() == ListingStatus::Active {
+                return Err(EscrowError::ClaimWindowClosed.into());
+            }
+            listing.claim_count -= 1;
+            listing.claims_desired = listing.claims_desired.saturating_sub(claim.desired);
+            if listing.claim_count == 0 {
+                end_claim_round(&mut listing);
+            }
+            serialize_listing(listing_info, &listing)?;
+        }
+    }
+
+    // Leave a delegate the buyer approved since registering in place.
+    let buyer_quote_account = TokenAccount::unpack(&buyer_quote_account_info.data.borrow())?;
+    if vault_authority.is_some() && buyer_quote_account.delegate == COption::from(vault_authority) {
+        let revoke_ix = spl_token::instruction::revoke(
+            token_program_info.key,
+            buyer_quote_account_info.key,
+            buyer_info.key,
+            &[],
+        )?;
+        invoke(
+            &revoke_ix,
+            &[buyer_quote_account_info.clone(), buyer_info.clone(), token_program_info.clone()],
+        )?;
+    }
+    close_program_account(claim_info, buyer_info)
+}
+
+/// Load a buyer's delegation PDA for a listing, with its bump.
+fn load_delegation(
+    program_id: &Pubkey,
+    listing_key: &Pubkey,
+    buyer_key: &Pubkey,
+    delegation_info: &AccountInfo,
+) -> Result<(Delegation, u8), ProgramError> {
+    let (expected_delegation, bump) = find_delegation_address(program_id, listing_key, buyer_key);
+    if delegation_info.key != &expected_delegation || delegation_info.owner != program_id {
+        return Err(EscrowError::InvalidDelegation.into());
+    }
+    let delegation = Delegation::deserialize(&mut &delegation_info.data.borrow()[..])
+        .map_err(|_| EscrowError::InvalidDelegation)?;
+    Ok((delegation, bump))
+}
+
+fn approve_delegate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    delegate: Pubkey,
+    quantity: u64,
+    max_price: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let buyer_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let delegation_info = next_account_info(account_info_iter)?;
+    let buyer_quote_account_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !buyer_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    assert_token_program(token_program_info)?;
+    if system_program_info.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let listing = deserialize_listing(program_id, listing_info)?;
+    let buyer_quote_account = TokenAccount::unpack(&buyer_quote_account_info.data.borrow())?;
+    assert_token_account_owner(&buyer_quote_account, buyer_info.key)?;
+    assert_token_account_mint(&buyer_quote_account, &listing.quote_mint)?;
+    let (expected_delegation, bump) = find_delegation_address(program_id, listing_info.key, buyer_info.key);
+    if delegation_info.key != &expected_delegation {
+        return Err(EscrowError::InvalidDelegation.into());
+    }
+
+    if quantity == 0 {
+        // Only revoke our own approval; another delegate stays in place.
+        if buyer_quote_account.delegate == COption::Some(expected_delegation) {
+            invoke(
+                &spl_token::instruction::revoke(token_program_info.key, buyer_quote_account_info.key, buyer_info.key, &[])?,
+                &[buyer_quote_account_info.clone(), buyer_info.clone(), token_program_info.clone()],
+            )?;
+        }
+        if !delegation_info.data_is_empty() {
+            close_program_account(delegation_info, buyer_info)?;
+        }
+        return Ok(());
+    }
+
+    // The approval covers the whole order at `max_price`, taker fee included.
+    let max_quote = quote_amount_from_notional(u128::from(quantity) * u128::from(max_price), listing.base_decimals)?;
+    let max_fee = u128::from(max_quote) * u128::from(listing.taker_fee_bps) / u128::from(BPS_DENOMINATOR);
+    let approved = u64::try_from(u128::from(max_quote) + max_fee).map_err(|_| EscrowError::AmountOverflow)?;
+    assert_delegate_replaceable(&buyer_quote_account, &expected_delegation)?;
+
+    if delegation_info.data_is_empty() {
+        create_pda_account(
+            program_id,
+            buyer_info,
+            delegation_info,
+            system_program_info,
+            Delegation::LEN,
+            &[b"delegation", listing_info.key.as_ref(), buyer_info.key.as_ref(), &[bump]],
+        )?;
+    } else if delegation_info.owner != program_id {
+        return Err(EscrowError::InvalidDelegation.into());
+    }
+    Delegation {
+        listing: *listing_info.key,
+        buyer: *buyer_info.key,
+        delegate,
+        quantity,
+        max_price,
+    }
+    .serialize(&mut &mut delegation_info.data.borrow_mut()[..])
+    .map_err(|_| EscrowError::InvalidDelegation)?;
+
+    invoke(
+        &spl_token::instruction::approve(
+            token_program_info.key,
+            buyer_quote_account_info.key,
+            delegation_info.key,
+            buyer_info.key,
+            &[],
+            approved,
+        )?,
+        &[
+            buyer_quote_account_info.clone(),
+            delegation_info.clone(),
+            buyer_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
+
+fn set_allowed_buyer(program_id: &Pubkey, accounts: &[AccountInfo], buyer: Option<Pubkey>) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let seller_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+
+    if !seller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    if seller_info.key != &listing.seller {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    match listing.status() {
+        ListingStatus::AwaitingDeposit | ListingStatus::Active => {}
+        _ => return Err(EscrowError::InvalidListingStatus.into()),
+    }
+    // A swap's counterparty is fixed once `EnableSwap` names it.
+    if listing.is_swap() {
+        return Err(EscrowError::InvalidSwap.into());
+    }
+
+    listing.allowed_buyer = buyer.unwrap_or_default();
+    serialize_listing(listing_info, &listing)
+}
+
+fn set_allowlist_root(program_id: &Pubkey, accounts: &[AccountInfo], root: Option<[u8; 32]>) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let seller_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+
+    if !seller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    if seller_info.key != &listing.seller {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    match listing.status() {
+        ListingStatus::AwaitingDeposit | ListingStatus::Active => {}
+        _ => return Err(EscrowError::InvalidListingStatus.into()),
+    }
+    if listing.is_swap() {
+        return Err(EscrowError::InvalidSwap.into());
+    }
+
+    listing.allowlist_root = root.unwrap_or_default();
+    serialize_listing(listing_info, &listing)
+}
+
+fn pay_installment(program_id: &Pubkey, accounts: &[AccountInfo], quote_amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let buyer_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let seller_quote_account_info = next_account_info(account_info_iter)?;
+    let buyer_quote_account_info = next_account_info(account_info_iter)?;
+    let buyer_base_account_info = next_account_info(account_info_iter)?;
+    let vault_authority_info = next_account_info(account_info_iter)?;
+    let vault_token_account_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let installment_info = next_account_info(account_info_iter)?;
+    let fills_ledger_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !buyer_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    // Checked before any CPI: a fake token program would skip the payment.
+    if token_program_info.key != &spl_token::id() || system_program_info.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if quote_amount == 0 {
+        return Err(EscrowError::AmountOverflow.into());
+    }
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    let seller_stats_info = next_seller_stats(&listing, account_info_iter)?;
+    let status_history_info = next_status_history(&listing, account_info_iter)?;
+    assert_simple_active(&listing)?;
+    if !listing.allow_partial() {
+        return Err(EscrowError::PartialFillDisabled.into());
+    }
+    if !listing.buyer_allowed(buyer_info.key) {
+        return Err(EscrowError::BuyerNotAllowed.into());
+    }
+    let tranche = tranche_vault(program_id, &listing, vault_authority_info.key, 0)?;
+
+    let seller_quote_account = TokenAccount::unpack(&seller_quote_account_info.data.borrow())?;
+    assert_token_account_owner(&seller_quote_account, &listing.seller)?;
+    assert_token_account_mint(&seller_quote_account, &listing.quote_mint)?;
+    let buyer_quote_account = TokenAccount::unpack(&buyer_quote_account_info.data.borrow())?;
+    assert_token_account_owner(&buyer_quote_account, buyer_info.key)?;
+    assert_token_account_mint(&buyer_quote_account, &listing.quote_mint)?;
+    let buyer_base_account = TokenAccount::unpack(&buyer_base_account_info.data.borrow())?;
+    assert_token_account_owner(&buyer_base_account, buyer_info.key)?;
+    assert_token_account_mint(&buyer_base_account, &listing.base_mint)?;
+    let vault_token_account = TokenAccount::unpack(&vault_token_account_info.data.borrow())?;
+    assert_token_account_owner(&vault_token_account, vault_authority_info.key)?;
+    assert_token_account_mint(&vault_token_account, &listing.base_mint)?;
+
+    let (expected_plan, bump) = find_installment_address(program_id, listing_info.key, buyer_info.key);
+    if installment_info.key != &expected_plan {
+        return Err(EscrowError::InvalidInstallmentPlan.into());
+    }
+    let mut plan = if installment_info.data_is_empty() {
+        create_pda_account(
+            program_id,
+            buyer_info,
+            installment_info,
+            system_program_info,
+            InstallmentPlan::LEN,
+            &[b"installments", listing_info.key.as_ref(), buyer_info.key.as_ref(), &[bump]],
+        )?;
+        InstallmentPlan {
+            listing: *listing_info.key,
+            buyer: *buyer_info.key,
+            ..InstallmentPlan::default()
+        }
+    } else {
+        if installment_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        InstallmentPlan::deserialize(&mut &installment_info.data.borrow()[..])
+            .map_err(|_| EscrowError::InvalidInstallmentPlan)?
     };
 
-    let flags = if allow_partial { 1 } else { 0 };
+    // Entitlement is computed from the cumulative payment so rounding never
+    // accumulates against the buyer across installments.
+    let decimals_factor = 10u128
+        .checked_pow(u32::from(listing.base_decimals))
+        .ok_or(EscrowError::AmountOverflow)?;
+    let mut quote_paid = plan
+        .quote_paid
+        .checked_add(quote_amount)
+        .ok_or(EscrowError::AmountOverflow)?;
+    let entitled = u128::from(quote_paid) * decimals_factor / u128::from(listing.price_per_token);
+    let entitled = u64::try_from(entitled).unwrap_or(u64::MAX);
+    let mut quantity = entitled.saturating_sub(plan.base_delivered);
+    let mut charge = quote_amount;
+
+    let remaining = listing.remaining();
+    if quantity >= remaining {
+        // Final installment: deliver the remainder and charge only what it costs.
+        quantity = remaining;
+        let delivered = plan
+            .base_delivered
+            .checked_add(remaining)
+            .ok_or(EscrowError::AmountOverflow)?;
+        quote_paid = static_quote_amount(&listing, delivered)?.max(plan.quote_paid);
+        charge = quote_paid - plan.quote_paid;
+    }
+    if buyer_quote_account.amount < charge {
+        return Err(ProgramError::InsufficientFunds);
+    }
+
+    if charge > 0 {
+        let transfer_quote_ix = spl_token::instruction::transfer(
+            token_program_info.key,
+            buyer_quote_account_info.key,
+            seller_quote_account_info.key,
+            buyer_info.key,
+            &[],
+            charge,
+        )?;
+        invoke(
+            &transfer_quote_ix,
+            &[
+                buyer_quote_account_info.clone(),
+                seller_quote_account_info.clone(),
+                buyer_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
+    }
+
+    if quantity > 0 {
+        transfer_from_vault(
+            &listing,
+            &tranche,
+            token_program_info,
+            vault_token_account_info,
+            buyer_base_account_info,
+            vault_authority_info,
+            quantity,
+        )?;
+        listing.filled = listing
+            .filled
+            .checked_add(quantity)
+            .ok_or(EscrowError::AmountOverflow)?;
+        if listing.filled >= listing.quantity {
+            listing.set_status(ListingStatus::Completed);
+            release_listing_slot(program_id, &listing, seller_stats_info)?;
+            record_status(program_id, listing_info.key, &listing, status_history_info)?;
+        }
+        append_fill_record(
+            program_id,
+            listing_info.key,
+            buyer_info,
+            fills_ledger_info,
+            system_program_info,
+            FillRecord {
+                buyer: *buyer_info.key,
+                quantity,
+                quote_amount: charge,
+                ts: Clock::get()?.unix_timestamp,
+            },
+        )?;
+    }
+
+    plan.quote_paid = quote_paid;
+    plan.base_delivered = plan
+        .base_delivered
+        .checked_add(quantity)
+        .ok_or(EscrowError::AmountOverflow)?;
+    plan.serialize(&mut &mut installment_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstallmentPlan)?;
+    serialize_listing(listing_info, &listing)
+}
+
+fn set_max_active_listings(program_id: &Pubkey, accounts: &[AccountInfo], max_active_listings: u32) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    let mut config = load_config_as_admin(program_id, config_info, admin_info)?;
+    config.max_active_listings = max_active_listings;
+    config
+        .serialize(&mut &mut config_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+fn set_max_listing_quantity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_listing_quantity: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    let mut config = load_config_as_admin(program_id, config_info, admin_info)?;
+    config.max_listing_quantity = max_listing_quantity;
+    config
+        .serialize(&mut &mut config_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+fn set_burn_bps(program_id: &Pubkey, accounts: &[AccountInfo], burn_bps: u16) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    if u64::from(burn_bps) > BPS_DENOMINATOR {
+        return Err(EscrowError::InvalidBasisPoints.into());
+    }
+    let mut config = load_config_as_admin(program_id, config_info, admin_info)?;
+    config.burn_bps = burn_bps;
+    config
+        .serialize(&mut &mut config_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+fn set_min_listing_quantity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    min_listing_quantity: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    let mut config = load_config_as_admin(program_id, config_info, admin_info)?;
+    config.min_listing_quantity = min_listing_quantity;
+    config
+        .serialize(&mut &mut config_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+fn set_spl_fee_amount(program_id: &Pubkey, accounts: &[AccountInfo], spl_fee_amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    let mut config = load_config_as_admin(program_id, config_info, admin_info)?;
+    config.spl_fee_amount = spl_fee_amount;
+    config
+        .serialize(&mut &mut config_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+fn set_native_fee_lamports(program_id: &Pubkey, accounts: &[AccountInfo], native_fee_lamports: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    let mut config = load_config_as_admin(program_id, config_info, admin_info)?;
+    config.native_fee_lamports = native_fee_lamports;
+    config
+        .serialize(&mut &mut config_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+fn set_cancellation_fee(program_id: &Pubkey, accounts: &[AccountInfo], cancellation_fee: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    let mut config = load_config_as_admin(program_id, config_info, admin_info)?;
+    config.cancellation_fee = cancellation_fee;
+    config
+        .serialize(&mut &mut config_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+fn propose_admin(program_id: &Pubkey, accounts: &[AccountInfo], new_admin: Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    let mut config = load_config_as_admin(program_id, config_info, admin_info)?;
+    config.pending_admin = new_admin;
+    config
+        .serialize(&mut &mut config_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+fn accept_admin(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let new_admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    if !new_admin_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let mut config = load_config(program_id, config_info)?.ok_or(EscrowError::InvalidConfig)?;
+    if config.pending_admin == Pubkey::default() || &config.pending_admin != new_admin_info.key {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    config.admin = config.pending_admin;
+    config.pending_admin = Pubkey::default();
+    config
+        .serialize(&mut &mut config_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+/// Check `bid_vault_info` is a quote token account held by the listing's vault authority.
+fn assert_bid_vault(listing: &Listing, bid_vault_info: &AccountInfo) -> ProgramResult {
+    let bid_vault = TokenAccount::unpack(&bid_vault_info.data.borrow())?;
+    assert_token_account_owner(&bid_vault, &listing.vault_authority)?;
+    assert_token_account_mint(&bid_vault, &listing.quote_mint)
+}
+
+fn place_bid(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let bidder_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let bidder_quote_account_info = next_account_info(account_info_iter)?;
+    let bid_vault_info = next_account_info(account_info_iter)?;
+    let vault_authority_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    if !bidder_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    // A fake token program would record a bid that was never escrowed.
+    assert_token_program(token_program_info)?;
+    assert_distinct_accounts(&[bidder_quote_account_info, bid_vault_info])?;
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    if listing.status() != ListingStatus::Active {
+        return Err(EscrowError::InvalidListingStatus.into());
+    }
+    if !listing.is_auction() {
+        return Err(EscrowError::InvalidAuction.into());
+    }
+    if !listing.buyer_allowed(bidder_info.key) {
+        return Err(EscrowError::BuyerNotAllowed.into());
+    }
+    if Clock::get()?.unix_timestamp >= listing.auction_end {
+        return Err(EscrowError::AuctionClosed.into());
+    }
+    // The first bid must meet the reserve of `price_per_token` for the whole quantity.
+    if amount <= listing.high_bid || amount < static_quote_amount(&listing, listing.quantity)? {
+        return Err(EscrowError::BidTooLow.into());
+    }
+    let tranche = tranche_vault(program_id, &listing, vault_authority_info.key, 0)?;
+    assert_bid_vault(&listing, bid_vault_info)?;
+
+    let bidder_quote_account = TokenAccount::unpack(&bidder_quote_account_info.data.borrow())?;
+    assert_token_account_owner(&bidder_quote_account, bidder_info.key)?;
+    assert_token_account_mint(&bidder_quote_account, &listing.quote_mint)?;
+
+    let transfer_ix = spl_token::instruction::transfer(
+        token_program_info.key,
+        bidder_quote_account_info.key,
+        bid_vault_info.key,
+        bidder_info.key,
+        &[],
+        amount,
+    )?;
+    invoke(
+        &transfer_ix,
+        &[
+            bidder_quote_account_info.clone(),
+            bid_vault_info.clone(),
+            bidder_info.clone(),
+            token_program_info.clone(),
+        ],
+    )?;
+
+    // The outbid bidder gets their locked bid back in full.
+    if listing.high_bid > 0 {
+        let previous_quote_account_info = next_account_info(account_info_iter)?;
+        let previous_quote_account = TokenAccount::unpack(&previous_quote_account_info.data.borrow())?;
+        assert_token_account_owner(&previous_quote_account, &listing.high_bidder)?;
+        assert_token_account_mint(&previous_quote_account, &listing.quote_mint)?;
+        transfer_from_vault(
+            &listing,
+            &tranche,
+            token_program_info,
+            bid_vault_info,
+            previous_quote_account_info,
+            vault_authority_info,
+            listing.high_bid,
+        )?;
+    }
+
+    listing.high_bid = amount;
+    listing.high_bidder = *bidder_info.key;
+    serialize_listing(listing_info, &listing)
+}
+
+fn finalize_auction(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let caller_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let vault_authority_info = next_account_info(account_info_iter)?;
+    let vault_token_account_info = next_account_info(account_info_iter)?;
+    let bid_vault_info = next_account_info(account_info_iter)?;
+    let seller_quote_account_info = next_account_info(account_info_iter)?;
+    let winner_base_account_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let fills_ledger_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !caller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    // Anyone may finalize, so the programs the vault signs for must be the real ones.
+    assert_token_program(token_program_info)?;
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    let seller_stats_info = next_seller_stats(&listing, account_info_iter)?;
+    let status_history_info = next_status_history(&listing, account_info_iter)?;
+    if listing.status() != ListingStatus::Active {
+        return Err(EscrowError::InvalidListingStatus.into());
+    }
+    // Without a bid there is nothing to settle; the seller cancels instead.
+    if !listing.is_auction() || listing.high_bid == 0 {
+        return Err(EscrowError::InvalidAuction.into());
+    }
+    let now = Clock::get()?.unix_timestamp;
+    if now < listing.auction_end {
+        return Err(EscrowError::AuctionStillOpen.into());
+    }
+    let tranche = tranche_vault(program_id, &listing, vault_authority_info.key, 0)?;
+    assert_bid_vault(&listing, bid_vault_info)?;
+
+    let vault_token_account = TokenAccount::unpack(&vault_token_account_info.data.borrow())?;
+    assert_token_account_owner(&vault_token_account, vault_authority_info.key)?;
+    assert_token_account_mint(&vault_token_account, &listing.base_mint)?;
+    let seller_quote_account = TokenAccount::unpack(&seller_quote_account_info.data.borrow())?;
+    assert_token_account_owner(&seller_quote_account, &listing.seller)?;
+    assert_token_account_mint(&seller_quote_account, &listing.quote_mint)?;
+    let winner_base_account = TokenAccount::unpack(&winner_base_account_info.data.borrow())?;
+    assert_token_account_owner(&winner_base_account, &listing.high_bidder)?;
+    assert_token_account_mint(&winner_base_account, &listing.base_mint)?;
+
+    let quantity = listing.remaining();
+    transfer_from_vault(
+        &listing,
+        &tranche,
+        token_program_info,
+        bid_vault_info,
+        seller_quote_account_info,
+        vault_authority_info,
+        listing.high_bid,
+    )?;
+    transfer_from_vault(
+        &listing,
+        &tranche,
+        token_program_info,
+        vault_token_account_info,
+        winner_base_account_info,
+        vault_authority_info,
+        quantity,
+    )?;
+
+    listing.filled = listing.quantity;
+    listing.set_status(ListingStatus::Completed);
+    release_listing_slot(program_id, &listing, seller_stats_info)?;
+    record_status(program_id, listing_info.key, &listing, status_history_info)?;
+    append_fill_record(
+        program_id,
+        listing_info.key,
+        caller_info,
+        fills_ledger_info,
+        system_program_info,
+        FillRecord {
+            buyer: listing.high_bidder,
+            quantity,
+            quote_amount: listing.high_bid,
+            ts: now,
+        },
+    )?;
+    serialize_listing(listing_info, &listing)
+}
+
+fn set_fee_mint(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let fee_mint_info = next_account_info(account_info_iter)?;
+
+    let mut config = load_config_as_admin(program_id, config_info, admin_info)?;
+    if fee_mint_info.owner != &spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Mint::unpack(&fee_mint_info.data.borrow())?;
+    config.fee_mint = *fee_mint_info.key;
+    config
+        .serialize(&mut &mut config_info.data.borrow_mut()[..])
+        .map_err(|_| EscrowError::InvalidInstructionData.into())
+}
+
+fn reconcile_listing(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let listing_info = next_account_info(account_info_iter)?;
+
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    let status_history_info = next_status_history(&listing, account_info_iter)?;
+    if listing.status() != ListingStatus::Active {
+        return Err(EscrowError::InvalidListingStatus.into());
+    }
+
+    let vault_balance = total_vault_balance(program_id, &listing, account_info_iter)?;
+
+    // Surplus tokens sent to a vault are harmless; only a shortfall means base
+    // left without a recorded fill.
+    if vault_balance < listing.remaining() {
+        msg!(
+            "reconcile: vault holds {} but {} remain unfilled",
+            vault_balance,
+            listing.remaining()
+        );
+        listing.set_status(ListingStatus::Frozen);
+        record_status(program_id, listing_info.key, &listing, status_history_info)?;
+        serialize_listing(listing_info, &listing)?;
+    }
+    Ok(())
+}
+
+fn commit_purchase(program_id: &Pubkey, accounts: &[AccountInfo], commitment: [u8; 32]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let buyer_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let commitment_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !buyer_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if system_program_info.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
 
-    let listing = Listing {
-        seller: *seller_info.key,
-        base_mint: *base_mint_info.key,
-        quote_mint: *quote_mint_info.key,
-        vault_authority: *vault_authority_info.key,
-        price_per_token,
-        quantity,
-        filled: 0,
-        listing_id,
-        flags,
-        vault_bump: bump,
-        status: ListingStatus::AwaitingDeposit.as_u8(),
-        base_decimals: base_mint.decimals,
-        fee_payment_method,
-        fee_amount_paid: fee_amount_u64,
-        x402_payload_hash,
-    };
+    let listing = deserialize_listing(program_id, listing_info)?;
+    if listing.status() != ListingStatus::Active {
+        return Err(EscrowError::InvalidListingStatus.into());
+    }
+    if !listing.buyer_allowed(buyer_info.key) {
+        return Err(EscrowError::BuyerNotAllowed.into());
+    }
+
+    let (expected_commitment, bump) = find_purchase_commitment_address(program_id, listing_info.key, buyer_info.key);
+    if commitment_info.key != &expected_commitment {
+        return Err(EscrowError::InvalidCommitment.into());
+    }
+    if !commitment_info.data_is_empty() {
+        return Err(EscrowError::AlreadyInitialized.into());
+    }
+    create_pda_account(
+        program_id,
+        buyer_info,
+        commitment_info,
+        system_program_info,
+        PurchaseCommitment::LEN,
+        &[b"commitment", listing_info.key.as_ref(), buyer_info.key.as_ref(), &[bump]],
+    )?;
+    PurchaseCommitment {
+        listing: *listing_info.key,
+        buyer: *buyer_info.key,
+        commitment,
+        committed_at: Clock::get()?.unix_timestamp,
+    }
+    .serialize(&mut &mut commitment_info.data.borrow_mut()[..])
+    .map_err(|_| EscrowError::InvalidCommitment.into())
+}
+
+fn reveal_purchase(program_id: &Pubkey, accounts: &[AccountInfo], quantity: u64, nonce: u64) -> ProgramResult {
+    let (commitment_info, purchase_accounts) = accounts.split_first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let account_info_iter = &mut purchase_accounts.iter();
+    let buyer_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+
+    if !buyer_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let (expected_commitment, _) = find_purchase_commitment_address(program_id, listing_info.key, buyer_info.key);
+    if commitment_info.key != &expected_commitment || commitment_info.owner != program_id {
+        return Err(EscrowError::InvalidCommitment.into());
+    }
+    let pending = PurchaseCommitment::deserialize(&mut &commitment_info.data.borrow()[..])
+        .map_err(|_| EscrowError::InvalidCommitment)?;
+    if pending.commitment != purchase_commitment_hash(buyer_info.key, quantity, nonce) {
+        return Err(EscrowError::InvalidCommitment.into());
+    }
+    if Clock::get()?.unix_timestamp < pending.committed_at.saturating_add(MIN_REVEAL_DELAY) {
+        return Err(EscrowError::RevealTooEarly.into());
+    }
+
+    purchase_tokens(program_id, purchase_accounts, PurchaseSize::Exact(quantity), PurchaseOptions::default())?;
+    close_program_account(commitment_info, buyer_info)
+}
+
+/// Sum the balances of the listing's tranche vault ATAs, passed in tranche order.
+fn total_vault_balance(
+    program_id: &Pubkey,
+    listing: &Listing,
+    account_info_iter: &mut std::slice::Iter<AccountInfo>,
+) -> Result<u64, ProgramError> {
+    let mut vault_balance = 0u64;
+    for index in 0..listing.vault_count.max(1) {
+        let vault_info = next_account_info(account_info_iter)?;
+        let (vault_authority, _) =
+            find_tranche_vault_authority(program_id, &listing.seller, listing.listing_id, index);
+        if vault_info.key != &get_associated_token_address(&vault_authority, &listing.base_mint) {
+            return Err(EscrowError::MintMismatch.into());
+        }
+        let vault = TokenAccount::unpack(&vault_info.data.borrow())?;
+        vault_balance = vault_balance
+            .checked_add(vault.amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+    }
+    Ok(vault_balance)
+}
+
+fn unfreeze_listing(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+
+    load_config_as_admin(program_id, config_info, admin_info)?;
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    let status_history_info = next_status_history(&listing, account_info_iter)?;
+    if listing.status() != ListingStatus::Frozen {
+        return Err(EscrowError::InvalidListingStatus.into());
+    }
+
+    let vault_balance = total_vault_balance(program_id, &listing, account_info_iter)?;
+    if vault_balance < listing.remaining() {
+        return Err(EscrowError::ReconciliationFailed.into());
+    }
 
+    listing.set_status(ListingStatus::Active);
+    record_status(program_id, listing_info.key, &listing, status_history_info)?;
     serialize_listing(listing_info, &listing)
 }
 
-fn deposit_tokens(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+fn enable_swap(program_id: &Pubkey, accounts: &[AccountInfo], counterparty: Pubkey) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let seller_info = next_account_info(account_info_iter)?;
     let listing_info = next_account_info(account_info_iter)?;
-    let seller_token_account_info = next_account_info(account_info_iter)?;
-    let vault_authority_info = next_account_info(account_info_iter)?;
-    let vault_token_account_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
 
     if !seller_info.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
 
     let mut listing = deserialize_listing(program_id, listing_info)?;
-
+    if seller_info.key != &listing.seller {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
     if listing.status() != ListingStatus::AwaitingDeposit {
         return Err(EscrowError::InvalidListingStatus.into());
     }
-    if seller_info.key != &listing.seller {
-        return Err(EscrowError::IncorrectAuthority.into());
+    // Settlement moves the whole quantity from the primary vault at the static
+    // price, with nothing owed to a treasury beyond what init already took.
+    if counterparty == Pubkey::default()
+        || counterparty == listing.seller
+        || listing.is_swap()
+        || listing.allow_partial()
+        || listing.has_price_schedule()
+        || listing.uses_oracle()
+        || listing.has_basket()
+        || listing.has_fee_escrow()
+        || listing.vault_count > 1
+        || listing.requires_attestation()
+        || listing.is_auction()
+        || listing.taker_fee_bps > 0
+    {
+        return Err(EscrowError::InvalidSwap.into());
     }
 
-    let seller_token_account = TokenAccount::unpack(&seller_token_account_info.data.borrow())?;
-    assert_token_account_owner(&seller_token_account, seller_info.key)?;
-    assert_token_account_mint(&seller_token_account, &listing.base_mint)?;
+    listing.allowed_buyer = counterparty;
+    listing.flags |= FLAG_SWAP;
+    serialize_listing(listing_info, &listing)
+}
 
-    let vault_token_account = TokenAccount::unpack(&vault_token_account_info.data.borrow())?;
-    assert_token_account_owner(&vault_token_account, vault_authority_info.key)?;
-    assert_token_account_mint(&vault_token_account, &listing.base_mint)?;
+fn deposit_swap_quote(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let counterparty_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+    let counterparty_quote_account_info = next_account_info(account_info_iter)?;
+    let quote_vault_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
 
-    if vault_authority_info.key != &listing.vault_authority {
-        return Err(EscrowError::IncorrectAuthority.into());
+    if !counterparty_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
     }
+    // A fake token program would record a deposit that never happened.
+    assert_token_program(token_program_info)?;
+    assert_distinct_accounts(&[counterparty_quote_account_info, quote_vault_info])?;
 
-    let amount = listing.quantity;
-    if seller_token_account.amount < amount {
-        return Err(ProgramError::InsufficientFunds);
+    let mut listing = deserialize_listing(program_id, listing_info)?;
+    match listing.status() {
+        ListingStatus::AwaitingDeposit | ListingStatus::Active => {}
+        _ => return Err(EscrowError::InvalidListingStatus.into()),
     }
+    if !listing.is_swap() || listing.high_bid > 0 {
+        return Err(EscrowError::InvalidSwap.into());
+    }
+    if counterparty_info.key != &listing.allowed_buyer {
+        return Err(EscrowError::BuyerNotAllowed.into());
+    }
+    assert_bid_vault(&listing, quote_vault_info)?;
 
-    let ix = spl_token::instruction::transfer(
+    let counterparty_quote_account = TokenAccount::unpack(&counterparty_quote_account_info.data.borrow())?;
+    assert_token_account_owner(&counterparty_quote_account, counterparty_info.key)?;
+    assert_token_account_mint(&counterparty_quote_account, &listing.quote_mint)?;
+
+    let quote_amount = static_quote_amount(&listing, listing.quantity)?;
+    let transfer_ix = spl_token::instruction::transfer(
         token_program_info.key,
-        seller_token_account_info.key,
-        vault_token_account_info.key,
-        seller_info.key,
+        counterparty_quote_account_info.key,
+        quote_vault_info.key,
+        counterparty_info.key,
         &[],
-        amount,
+        quote_amount,
     )?;
-
     invoke(
-        &ix,
+        &transfer_ix,
         &[
-            seller_token_account_info.clone(),
-            vault_token_account_info.clone(),
-            seller_info.clone(),
+            counterparty_quote_account_info.clone(),
+            quote_vault_info.clone(),
+            counterparty_info.clone(),
             token_program_info.clone(),
         ],
     )?;
 
-    listing.set_status(ListingStatus::Active);
+    listing.high_bid = quote_amount;
+    listing.high_bidder = *counterparty_info.key;
     serialize_listing(listing_info, &listing)
 }
 
-fn purchase_tokens(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    quantity: u64,
-) -> ProgramResult {
-    if quantity == 0 {
-        return Err(EscrowError::AmountOverflow.into());
-    }
-
+fn settle_swap(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    let buyer_info = next_account_info(account_info_iter)?;
+    let caller_info = next_account_info(account_info_iter)?;
     let listing_info = next_account_info(account_info_iter)?;
-    let seller_quote_account_info = next_account_info(account_info_iter)?;
-    let buyer_quote_account_info = next_account_info(account_info_iter)?;
-    let buyer_base_account_info = next_account_info(account_info_iter)?;
     let vault_authority_info = next_account_info(account_info_iter)?;
     let vault_token_account_info = next_account_info(account_info_iter)?;
+    let quote_vault_info = next_account_info(account_info_iter)?;
+    let seller_quote_account_info = next_account_info(account_info_iter)?;
+    let counterparty_base_account_info = next_account_info(account_info_iter)?;
     let token_program_info = next_account_info(account_info_iter)?;
+    let fills_ledger_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
 
-    if !buyer_info.is_signer {
+    if !caller_info.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
 
+    assert_token_program(token_program_info)?;
+
     let mut listing = deserialize_listing(program_id, listing_info)?;
+    let seller_stats_info = next_seller_stats(&listing, account_info_iter)?;
+    let status_history_info = next_status_history(&listing, account_info_iter)?;
     if listing.status() != ListingStatus::Active {
         return Err(EscrowError::InvalidListingStatus.into());
     }
-
-    if vault_authority_info.key != &listing.vault_authority {
-        return Err(EscrowError::IncorrectAuthority.into());
-    }
-
-    let remaining = listing.remaining();
-    if quantity > remaining {
-        return Err(EscrowError::InsufficientQuantity.into());
-    }
-    if quantity < remaining && !listing.allow_partial() {
-        return Err(EscrowError::PartialFillDisabled.into());
-    }
-
-    let decimals_factor = 10u128
-        .checked_pow(u32::from(listing.base_decimals))
-        .ok_or(EscrowError::AmountOverflow)?;
-    let quote_amount_u128 = u128::from(quantity)
-        .checked_mul(u128::from(listing.price_per_token))
-        .ok_or(EscrowError::AmountOverflow)?;
-    let quote_amount_u128 = quote_amount_u128
-        .checked_div(decimals_factor.max(1))
-        .ok_or(EscrowError::AmountOverflow)?;
-    if quote_amount_u128 == 0 {
-        return Err(EscrowError::AmountOverflow.into());
+    // Both sides must be in escrow before either is released.
+    if !listing.is_swap() || listing.high_bid == 0 {
+        return Err(EscrowError::InvalidSwap.into());
     }
-    let quote_amount = u64::try_from(quote_amount_u128).map_err(|_| EscrowError::AmountOverflow)?;
-
-    // Validate token accounts
-    let seller_quote_account = TokenAccount::unpack(&seller_quote_account_info.data.borrow())?;
-    assert_token_account_owner(&seller_quote_account, &listing.seller)?;
-    assert_token_account_mint(&seller_quote_account, &listing.quote_mint)?;
-
-    let buyer_quote_account = TokenAccount::unpack(&buyer_quote_account_info.data.borrow())?;
-    assert_token_account_owner(&buyer_quote_account, buyer_info.key)?;
-    assert_token_account_mint(&buyer_quote_account, &listing.quote_mint)?;
-    if buyer_quote_account.amount < quote_amount {
-        return Err(ProgramError::InsufficientFunds);
+    // Only the two parties to the swap may settle it.
+    if caller_info.key != &listing.seller && caller_info.key != &listing.high_bidder {
+        return Err(EscrowError::IncorrectAuthority.into());
     }
-
-    let buyer_base_account = TokenAccount::unpack(&buyer_base_account_info.data.borrow())?;
-    assert_token_account_owner(&buyer_base_account, buyer_info.key)?;
-    assert_token_account_mint(&buyer_base_account, &listing.base_mint)?;
+    let tranche = tranche_vault(program_id, &listing, vault_authority_info.key, 0)?;
+    assert_bid_vault(&listing, quote_vault_info)?;
 
     let vault_token_account = TokenAccount::unpack(&vault_token_account_info.data.borrow())?;
     assert_token_account_owner(&vault_token_account, vault_authority_info.key)?;
     assert_token_account_mint(&vault_token_account, &listing.base_mint)?;
-    if vault_token_account.amount < quantity {
-        return Err(ProgramError::InsufficientFunds);
-    }
+    let seller_quote_account = TokenAccount::unpack(&seller_quote_account_info.data.borrow())?;
+    assert_token_account_owner(&seller_quote_account, &listing.seller)?;
+    assert_token_account_mint(&seller_quote_account, &listing.quote_mint)?;
+    let counterparty_base_account = TokenAccount::unpack(&counterparty_base_account_info.data.borrow())?;
+    assert_token_account_owner(&counterparty_base_account, &listing.high_bidder)?;
+    assert_token_account_mint(&counterparty_base_account, &listing.base_mint)?;
 
-    // Transfer quote tokens from buyer to seller
-    let transfer_quote_ix = spl_token::instruction::transfer(
-        token_program_info.key,
-        buyer_quote_account_info.key,
-        seller_quote_account_info.key,
-        buyer_info.key,
-        &[],
-        quote_amount,
-    )?;
-    invoke(
-        &transfer_quote_ix,
-        &[
-            buyer_quote_account_info.clone(),
-            seller_quote_account_info.clone(),
-            buyer_info.clone(),
-            token_program_info.clone(),
-        ],
+    let quantity = listing.remaining();
+    transfer_from_vault(
+        &listing,
+        &tranche,
+        token_program_info,
+        quote_vault_info,
+        seller_quote_account_info,
+        vault_authority_info,
+        listing.high_bid,
     )?;
-
-    // Transfer base tokens from vault to buyer
-    let transfer_base_ix = spl_token::instruction::transfer(
-        token_program_info.key,
-        vault_token_account_info.key,
-        buyer_base_account_info.key,
-        vault_authority_info.key,
-        &[],
+    transfer_from_vault(
+        &listing,
+        &tranche,
+        token_program_info,
+        vault_token_account_info,
+        counterparty_base_account_info,
+        vault_authority_info,
         quantity,
     )?;
-    let listing_id_bytes = listing.listing_id.to_le_bytes();
-    let bump_seed = [listing.vault_bump];
-    let signer_seeds: &[&[u8]] = &[
-        b"vault",
-        listing.seller.as_ref(),
-        listing_id_bytes.as_ref(),
-        &bump_seed,
-    ];
 
-    invoke_signed(
-        &transfer_base_ix,
-        &[
-            vault_token_account_info.clone(),
-            buyer_base_account_info.clone(),
-            vault_authority_info.clone(),
-            token_program_info.clone(),
-        ],
-        &[signer_seeds],
+    listing.filled = listing.quantity;
+    listing.quote_received = listing.high_bid;
+    listing.set_status(ListingStatus::Completed);
+    release_listing_slot(program_id, &listing, seller_stats_info)?;
+    record_status(program_id, listing_info.key, &listing, status_history_info)?;
+    append_fill_record(
+        program_id,
+        listing_info.key,
+        caller_info,
+        fills_ledger_info,
+        system_program_info,
+        FillRecord {
+            buyer: listing.high_bidder,
+            quantity,
+            quote_amount: listing.high_bid,
+            ts: Clock::get()?.unix_timestamp,
+        },
     )?;
-
-    listing.filled = listing
-        .filled
-        .checked_add(quantity)
-        .ok_or(EscrowError::AmountOverflow)?;
-
-    if listing.filled >= listing.quantity {
-        listing.set_status(ListingStatus::Completed);
-    }
-
     serialize_listing(listing_info, &listing)
 }
 
-fn cancel_listing(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+fn cancel_swap(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    let seller_info = next_account_info(account_info_iter)?;
+    let caller_info = next_account_info(account_info_iter)?;
     let listing_info = next_account_info(account_info_iter)?;
+    let seller_info = next_account_info(account_info_iter)?;
     let vault_authority_info = next_account_info(account_info_iter)?;
     let vault_token_account_info = next_account_info(account_info_iter)?;
-    let seller_token_account_info = next_account_info(account_info_iter)?;
+    let seller_base_account_info = next_account_info(account_info_iter)?;
+    let quote_vault_info = next_account_info(account_info_iter)?;
+    let counterparty_quote_account_info = next_account_info(account_info_iter)?;
     let token_program_info = next_account_info(account_info_iter)?;
 
-    if !seller_info.is_signer {
+    if !caller_info.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
+    assert_token_program(token_program_info)?;
 
     let mut listing = deserialize_listing(program_id, listing_info)?;
-
-    if &listing.seller != seller_info.key {
+    let seller_stats_info = next_seller_stats(&listing, account_info_iter)?;
+    let status_history_info = next_status_history(&listing, account_info_iter)?;
+    if !listing.is_swap() {
+        return Err(EscrowError::InvalidSwap.into());
+    }
+    if caller_info.key != &listing.seller && caller_info.key != &listing.allowed_buyer {
+        return Err(EscrowError::IncorrectAuthority.into());
+    }
+    if seller_info.key != &listing.seller {
         return Err(EscrowError::IncorrectAuthority.into());
     }
-
     match listing.status() {
-        ListingStatus::AwaitingDeposit => {
-            listing.set_status(ListingStatus::Cancelled);
-            return serialize_listing(listing_info, &listing);
-        }
-        ListingStatus::Active => {}
+        ListingStatus::AwaitingDeposit | ListingStatus::Active => {}
         _ => return Err(EscrowError::InvalidListingStatus.into()),
     }
+    let tranche = tranche_vault(program_id, &listing, vault_authority_info.key, 0)?;
 
-    let remaining = listing.remaining();
-    if remaining > 0 {
-        let vault_token_account = TokenAccount::unpack(&vault_token_account_info.data.borrow())?;
-        assert_token_account_owner(&vault_token_account, vault_authority_info.key)?;
-        assert_token_account_mint(&vault_token_account, &listing.base_mint)?;
-
-        let seller_base_account = TokenAccount::unpack(&seller_token_account_info.data.borrow())?;
-        assert_token_account_owner(&seller_base_account, seller_info.key)?;
-        assert_token_account_mint(&seller_base_account, &listing.base_mint)?;
-
-        let transfer_ix = spl_token::instruction::transfer(
-            token_program_info.key,
-            vault_token_account_info.key,
-            seller_token_account_info.key,
-            vault_authority_info.key,
-            &[],
-            remaining,
-        )?;
-        let listing_id_bytes = listing.listing_id.to_le_bytes();
-        let bump_seed = [listing.vault_bump];
-        let signer_seeds: &[&[u8]] = &[
-            b"vault",
-            listing.seller.as_ref(),
-            listing_id_bytes.as_ref(),
-            &bump_seed,
-        ];
-
-        invoke_signed(
-            &transfer_ix,
-            &[
-                vault_token_account_info.clone(),
-                seller_token_account_info.clone(),
-                vault_authority_info.clone(),
-                token_program_info.clone(),
-            ],
-            &[signer_seeds],
+    if listing.high_bid > 0 {
+        assert_bid_vault(&listing, quote_vault_info)?;
+        let counterparty_quote_account = TokenAccount::unpack(&counterparty_quote_account_info.data.borrow())?;
+        assert_token_account_owner(&counterparty_quote_account, &listing.high_bidder)?;
+        assert_token_account_mint(&counterparty_quote_account, &listing.quote_mint)?;
+        transfer_from_vault(
+            &listing,
+            &tranche,
+            token_program_info,
+            quote_vault_info,
+            counterparty_quote_account_info,
+            vault_authority_info,
+            listing.high_bid,
         )?;
+        listing.high_bid = 0;
     }
+    release_listing_slot(program_id, &listing, seller_stats_info)?;
+
+    if listing.status() == ListingStatus::AwaitingDeposit {
+        // The seller never deposited, so cancel it and refund its rent.
+        listing.set_status(ListingStatus::Cancelled);
+        record_status(program_id, listing_info.key, &listing, status_history_info)?;
+        return close_unfunded_listing(listing_info, &mut listing, seller_info);
+    }
+
+    let vault_token_account = TokenAccount::unpack(&vault_token_account_info.data.borrow())?;
+    assert_token_account_owner(&vault_token_account, vault_authority_info.key)?;
+    assert_token_account_mint(&vault_token_account, &listing.base_mint)?;
+    let seller_base_account = TokenAccount::unpack(&seller_base_account_info.data.borrow())?;
+    assert_token_account_owner(&seller_base_account, &listing.seller)?;
+    assert_token_account_mint(&seller_base_account, &listing.base_mint)?;
+    transfer_from_vault(
+        &listing,
+        &tranche,
+        token_program_info,
+        vault_token_account_info,
+        seller_base_account_info,
+        vault_authority_info,
+        listing.remaining(),
+    )?;
 
     listing.set_status(ListingStatus::Cancelled);
+    record_status(program_id, listing_info.key, &listing, status_history_info)?;
     serialize_listing(listing_info, &listing)
 }
+
+fn simulate_purchase(program_id: &Pubkey, accounts: &[AccountInfo], quantity: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let listing_info = next_account_info(account_info_iter)?;
+
+    let listing = deserialize_listing(program_id, listing_info)?;
+    assert_purchasable(&listing)?;
+    if quantity == 0 {
+        return Err(EscrowError::AmountOverflow.into());
+    }
+    let quantity = resolve_fill_quantity(&listing, PurchaseSize::Exact(quantity))?;
+
+    let price_schedule = if listing.has_price_schedule() {
+        let price_schedule_info = next_account_info(account_info_iter)?;
+        Some(load_price_schedule(program_id, listing_info.key, price_schedule_info)?)
+    } else {
+        None
+    };
+    let oracle_info = if price_schedule.is_none() && listing.uses_oracle() {
+        Some(next_account_info(account_info_iter)?)
+    } else {
+        None
+    };
+    let fill = quote_fill(
+        &listing,
+        quantity,
+        price_schedule.as_ref(),
+        oracle_info,
+        Clock::get()?.unix_timestamp,
+        None,
+    )?;
+
+    let filled = listing.filled.saturating_add(quantity);
+    let simulated = SimulatedPurchase {
+        base_out: quantity,
+        quote_in: fill.buyer_total,
+        taker_fee: fill.taker_fee,
+        would_complete: filled >= listing.quantity
+            || (listing.max_quote_raise > 0 && fill.quote_received >= listing.max_quote_raise)
+            || (listing.auto_complete_after_fills > 0
+                && listing.fill_count.saturating_add(1) >= listing.auto_complete_after_fills),
+        note: listing.note,
+    };
+    let data = simulated
+        .try_to_vec()
+        .map_err(|_| EscrowError::InvalidInstructionData)?;
+    set_return_data(&data);
+    Ok(())
+}
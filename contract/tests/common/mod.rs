@@ -0,0 +1,762 @@
+//! Shared fixtures for escrow program integration tests.
+#![allow(dead_code)]
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    clock::Clock,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+};
+use solana_program_test::{BanksClientError, ProgramTest, ProgramTestContext, processor};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_associated_token_account::get_associated_token_address;
+
+use escrow_program::{
+    EscrowInstruction, InitializeListingArgs, Listing, find_config_address, find_fee_escrow_address,
+    find_fee_exempt_address, find_fills_ledger_address, find_seller_stats_address, find_treasury_address,
+};
+
+/// Running program-test bank plus the id the escrow program was loaded under.
+pub struct TestEnv {
+    pub ctx: ProgramTestContext,
+    pub program_id: Pubkey,
+}
+
+/// Build a `ProgramTest` with the escrow processor registered under a fresh id.
+pub fn escrow_program_test() -> (ProgramTest, Pubkey) {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new("escrow_program", program_id, processor!(escrow_program::process_instruction));
+    (program_test, program_id)
+}
+
+/// Start a fresh bank with the escrow program loaded.
+pub async fn start() -> TestEnv {
+    let (program_test, program_id) = escrow_program_test();
+    start_with(program_test, program_id).await
+}
+
+/// Start a bank from a customised `ProgramTest` (e.g. with extra programs added).
+pub async fn start_with(program_test: ProgramTest, program_id: Pubkey) -> TestEnv {
+    TestEnv { ctx: program_test.start_with_context().await, program_id }
+}
+
+/// Extract the custom program error code from a failed transaction.
+pub fn custom_error_code(err: BanksClientError) -> Option<u32> {
+    match err.unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => Some(code),
+        _ => None,
+    }
+}
+
+/// Extract the instruction error from a failed transaction.
+pub fn instruction_error(err: BanksClientError) -> Option<InstructionError> {
+    match err.unwrap() {
+        TransactionError::InstructionError(_, error) => Some(error),
+        _ => None,
+    }
+}
+
+impl TestEnv {
+    /// Sign and submit `instructions` with the context payer plus `signers`.
+    pub async fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), BanksClientError> {
+        let blockhash = self.ctx.banks_client.get_latest_blockhash().await.unwrap();
+        let mut all_signers: Vec<&Keypair> = vec![&self.ctx.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction =
+            Transaction::new_signed_with_payer(instructions, Some(&self.ctx.payer.pubkey()), &all_signers, blockhash);
+        self.ctx.banks_client.process_transaction(transaction).await
+    }
+
+    /// Like `process`, but return the transaction's log messages on success.
+    pub async fn process_with_logs(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<Vec<String>, BanksClientError> {
+        let blockhash = self.ctx.banks_client.get_latest_blockhash().await.unwrap();
+        let mut all_signers: Vec<&Keypair> = vec![&self.ctx.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction =
+            Transaction::new_signed_with_payer(instructions, Some(&self.ctx.payer.pubkey()), &all_signers, blockhash);
+        let outcome = self.ctx.banks_client.process_transaction_with_metadata(transaction).await?;
+        outcome.result?;
+        Ok(outcome.metadata.map(|metadata| metadata.log_messages).unwrap_or_default())
+    }
+
+    /// Simulate `instructions` and return the transaction's program return data.
+    pub async fn simulate_return_data(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Option<Vec<u8>> {
+        let blockhash = self.ctx.banks_client.get_latest_blockhash().await.unwrap();
+        let mut all_signers: Vec<&Keypair> = vec![&self.ctx.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction =
+            Transaction::new_signed_with_payer(instructions, Some(&self.ctx.payer.pubkey()), &all_signers, blockhash);
+        let simulation = self.ctx.banks_client.simulate_transaction(transaction).await.unwrap();
+        simulation.result.unwrap().unwrap();
+        simulation.simulation_details.and_then(|details| details.return_data).map(|return_data| return_data.data)
+    }
+
+    /// Simulate `instructions` and return the compute units they consume.
+    ///
+    /// Under the native `processor!` shim only CPIs and syscalls are metered,
+    /// not the handler's own instructions; an SBF build meters everything.
+    pub async fn units_consumed(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> u64 {
+        let blockhash = self.ctx.banks_client.get_latest_blockhash().await.unwrap();
+        let mut all_signers: Vec<&Keypair> = vec![&self.ctx.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction =
+            Transaction::new_signed_with_payer(instructions, Some(&self.ctx.payer.pubkey()), &all_signers, blockhash);
+        let simulation = self.ctx.banks_client.simulate_transaction(transaction).await.unwrap();
+        simulation.result.unwrap().unwrap();
+        simulation.simulation_details.unwrap().units_consumed
+    }
+
+    /// Wait for a fresh blockhash so an identical transaction can be resubmitted.
+    pub async fn refresh_blockhash(&mut self) {
+        self.ctx.last_blockhash = self.ctx.banks_client.get_latest_blockhash().await.unwrap();
+        self.ctx.get_new_latest_blockhash().await.unwrap();
+    }
+
+    /// Move the bank clock forward by `seconds`, on a fresh blockhash so repeated
+    /// transactions are not deduplicated.
+    pub async fn advance_clock(&mut self, seconds: i64) {
+        self.refresh_blockhash().await;
+        let mut clock: Clock = self.ctx.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += seconds;
+        self.ctx.set_sysvar(&clock);
+    }
+
+    /// Create and fund a new system-owned wallet.
+    pub async fn funded_keypair(&mut self, lamports: u64) -> Keypair {
+        let keypair = Keypair::new();
+        let ix = system_instruction::transfer(&self.ctx.payer.pubkey(), &keypair.pubkey(), lamports);
+        self.process(&[ix], &[]).await.unwrap();
+        keypair
+    }
+
+    /// Create a mint whose mint authority is the context payer.
+    pub async fn create_mint(&mut self, decimals: u8) -> Pubkey {
+        let mint = Keypair::new();
+        let rent = self.ctx.banks_client.get_rent().await.unwrap();
+        let payer = self.ctx.payer.pubkey();
+        let ixs = [
+            system_instruction::create_account(
+                &payer,
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(&spl_token::id(), &mint.pubkey(), &payer, None, decimals).unwrap(),
+        ];
+        self.process(&ixs, &[&mint]).await.unwrap();
+        mint.pubkey()
+    }
+
+    /// Create the associated token account of `owner` for `mint`.
+    pub async fn create_ata(&mut self, owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        let ix = spl_associated_token_account::instruction::create_associated_token_account(
+            &self.ctx.payer.pubkey(),
+            owner,
+            mint,
+            &spl_token::id(),
+        );
+        self.process(&[ix], &[]).await.unwrap();
+        get_associated_token_address(owner, mint)
+    }
+
+    /// Mint `amount` tokens of `mint` into `destination`.
+    pub async fn mint_to(&mut self, mint: &Pubkey, destination: &Pubkey, amount: u64) {
+        let payer = self.ctx.payer.pubkey();
+        let ix = spl_token::instruction::mint_to(&spl_token::id(), mint, destination, &payer, &[], amount).unwrap();
+        self.process(&[ix], &[]).await.unwrap();
+    }
+
+    /// Current token balance of `account`.
+    pub async fn token_balance(&mut self, account: &Pubkey) -> u64 {
+        let account = self.ctx.banks_client.get_account(*account).await.unwrap().unwrap();
+        spl_token::state::Account::unpack(&account.data).unwrap().amount
+    }
+
+    /// Current lamport balance of `address` (zero when the account does not exist).
+    pub async fn lamports(&mut self, address: &Pubkey) -> u64 {
+        self.ctx.banks_client.get_balance(*address).await.unwrap()
+    }
+
+    /// Fetch and decode a listing account.
+    pub async fn listing(&mut self, address: &Pubkey) -> Listing {
+        let account = self.ctx.banks_client.get_account(*address).await.unwrap().unwrap();
+        Listing::try_from_slice(&account.data[..Listing::LEN]).unwrap()
+    }
+
+    /// Overwrite the stored listing state, e.g. to simulate corrupted data.
+    pub async fn set_listing(&mut self, address: &Pubkey, listing: &Listing) {
+        let mut account = self.ctx.banks_client.get_account(*address).await.unwrap().unwrap();
+        let data = listing.try_to_vec().unwrap();
+        account.data[..data.len()].copy_from_slice(&data);
+        self.ctx.set_account(address, &account.into());
+    }
+
+    /// Initialize the program config with a fresh admin, returning the admin keypair.
+    pub async fn initialize_config(&mut self, default_treasury: Pubkey) -> Keypair {
+        let admin = self.funded_keypair(1_000_000_000).await;
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(admin.pubkey(), true),
+                AccountMeta::new(find_config_address(&self.program_id).0, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: EscrowInstruction::InitializeConfig { default_treasury }.try_to_vec().unwrap(),
+        };
+        self.process(&[ix], &[&admin]).await.unwrap();
+        admin
+    }
+
+    /// Route fees for `quote_mint` to `treasury` as the config admin.
+    pub async fn set_treasury(
+        &mut self,
+        admin: &Keypair,
+        quote_mint: &Pubkey,
+        treasury: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(admin.pubkey(), true),
+                AccountMeta::new_readonly(find_config_address(&self.program_id).0, false),
+                AccountMeta::new(find_treasury_address(&self.program_id, quote_mint).0, false),
+                AccountMeta::new_readonly(*quote_mint, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: EscrowInstruction::SetTreasury { treasury }.try_to_vec().unwrap(),
+        };
+        self.process(&[ix], &[admin]).await
+    }
+
+    /// Exempt `seller` from listing fees as the config admin.
+    pub async fn add_fee_exempt(&mut self, admin: &Keypair, seller: Pubkey) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(admin.pubkey(), true),
+                AccountMeta::new_readonly(find_config_address(&self.program_id).0, false),
+                AccountMeta::new(find_fee_exempt_address(&self.program_id, &seller).0, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: EscrowInstruction::AddFeeExempt { seller }.try_to_vec().unwrap(),
+        };
+        self.process(&[ix], &[admin]).await
+    }
+
+    /// Cap each seller's open listings as the config admin.
+    pub async fn set_max_active_listings(
+        &mut self,
+        admin: &Keypair,
+        max_active_listings: u32,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(admin.pubkey(), true),
+                AccountMeta::new(find_config_address(&self.program_id).0, false),
+            ],
+            data: EscrowInstruction::SetMaxActiveListings { max_active_listings }.try_to_vec().unwrap(),
+        };
+        self.process(&[ix], &[admin]).await
+    }
+
+    /// Cap the base quantity of new listings as the config admin.
+    pub async fn set_max_listing_quantity(
+        &mut self,
+        admin: &Keypair,
+        max_listing_quantity: u64,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(admin.pubkey(), true),
+                AccountMeta::new(find_config_address(&self.program_id).0, false),
+            ],
+            data: EscrowInstruction::SetMaxListingQuantity { max_listing_quantity }.try_to_vec().unwrap(),
+        };
+        self.process(&[ix], &[admin]).await
+    }
+
+    /// Set the share of listing fees burned as the config admin.
+    pub async fn set_burn_bps(&mut self, admin: &Keypair, burn_bps: u16) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(admin.pubkey(), true),
+                AccountMeta::new(find_config_address(&self.program_id).0, false),
+            ],
+            data: EscrowInstruction::SetBurnBps { burn_bps }.try_to_vec().unwrap(),
+        };
+        self.process(&[ix], &[admin]).await
+    }
+
+    /// Set the lamports charged for cancelling an unfilled active listing as the config admin.
+    pub async fn set_cancellation_fee(
+        &mut self,
+        admin: &Keypair,
+        cancellation_fee: u64,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(admin.pubkey(), true),
+                AccountMeta::new(find_config_address(&self.program_id).0, false),
+            ],
+            data: EscrowInstruction::SetCancellationFee { cancellation_fee }.try_to_vec().unwrap(),
+        };
+        self.process(&[ix], &[admin]).await
+    }
+
+    /// Set the smallest fungible listing quantity as the config admin.
+    pub async fn set_min_listing_quantity(
+        &mut self,
+        admin: &Keypair,
+        min_listing_quantity: u64,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(admin.pubkey(), true),
+                AccountMeta::new(find_config_address(&self.program_id).0, false),
+            ],
+            data: EscrowInstruction::SetMinListingQuantity { min_listing_quantity }.try_to_vec().unwrap(),
+        };
+        self.process(&[ix], &[admin]).await
+    }
+
+    /// Set the flat `SplToken` listing fee as the config admin.
+    pub async fn set_spl_fee_amount(&mut self, admin: &Keypair, spl_fee_amount: u64) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(admin.pubkey(), true),
+                AccountMeta::new(find_config_address(&self.program_id).0, false),
+            ],
+            data: EscrowInstruction::SetSplFeeAmount { spl_fee_amount }.try_to_vec().unwrap(),
+        };
+        self.process(&[ix], &[admin]).await
+    }
+
+    /// Set the flat NativeSol listing fee in lamports as the config admin.
+    pub async fn set_native_fee_lamports(
+        &mut self,
+        admin: &Keypair,
+        native_fee_lamports: u64,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(admin.pubkey(), true),
+                AccountMeta::new(find_config_address(&self.program_id).0, false),
+            ],
+            data: EscrowInstruction::SetNativeFeeLamports { native_fee_lamports }.try_to_vec().unwrap(),
+        };
+        self.process(&[ix], &[admin]).await
+    }
+
+    /// Designate the mint charged for `SplToken` fees as the config admin.
+    pub async fn set_fee_mint(&mut self, admin: &Keypair, fee_mint: &Pubkey) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(admin.pubkey(), true),
+                AccountMeta::new(find_config_address(&self.program_id).0, false),
+                AccountMeta::new_readonly(*fee_mint, false),
+            ],
+            data: EscrowInstruction::SetFeeMint.try_to_vec().unwrap(),
+        };
+        self.process(&[ix], &[admin]).await
+    }
+
+    /// Allocate a zeroed, program-owned account sized for a listing.
+    pub async fn create_listing_account(&mut self, funder: &Keypair) -> Keypair {
+        self.create_listing_account_with_len(funder, Listing::LEN).await
+    }
+
+    /// Allocate a program-owned listing account of an arbitrary size.
+    pub async fn create_listing_account_with_len(&mut self, funder: &Keypair, len: usize) -> Keypair {
+        let listing = Keypair::new();
+        let rent = self.ctx.banks_client.get_rent().await.unwrap();
+        let ix = system_instruction::create_account(
+            &funder.pubkey(),
+            &listing.pubkey(),
+            rent.minimum_balance(len),
+            len as u64,
+            &self.program_id,
+        );
+        self.process(&[ix], &[funder, &listing]).await.unwrap();
+        listing
+    }
+
+    /// Create base and quote mints, a funded seller, and an initialized listing.
+    pub async fn setup_listing(&mut self, args: ListingArgs) -> ListingFixture {
+        let seller = self.funded_keypair(1_000_000_000).await;
+        self.setup_listing_for(seller, args).await
+    }
+
+    /// Like `setup_listing`, but for an existing funded seller.
+    pub async fn setup_listing_for(&mut self, seller: Keypair, args: ListingArgs) -> ListingFixture {
+        let base_mint = self.create_mint(args.base_decimals).await;
+        let quote_mint = match args.quote_mint {
+            Some(quote_mint) => quote_mint,
+            None => self.create_mint(args.quote_decimals).await,
+        };
+        let seller_base = self.create_ata(&seller.pubkey(), &base_mint).await;
+        let seller_quote = self.create_ata(&seller.pubkey(), &quote_mint).await;
+        self.mint_to(&base_mint, &seller_base, args.quantity).await;
+
+        let listing = self.create_listing_account(&seller).await;
+        let (vault_authority, _) = vault_authority_address(&self.program_id, &seller.pubkey(), args.listing_id);
+        let vault = get_associated_token_address(&vault_authority, &base_mint);
+
+        let fixture = ListingFixture {
+            seller,
+            listing: listing.pubkey(),
+            listing_id: args.listing_id,
+            vault_authority,
+            vault,
+            base_mint,
+            quote_mint,
+            seller_base,
+            seller_quote,
+        };
+
+        let ix = initialize_ix(&self.program_id, &fixture, &args);
+        self.process(&[ix], &[&fixture.seller]).await.unwrap();
+        fixture
+    }
+
+    /// Create the vault ATA and deposit the full listing quantity.
+    pub async fn deposit(&mut self, fixture: &ListingFixture) -> Result<(), BanksClientError> {
+        let vault_authority = fixture.vault_authority;
+        let base_mint = fixture.base_mint;
+        if self.ctx.banks_client.get_account(fixture.vault).await.unwrap().is_none() {
+            self.create_ata(&vault_authority, &base_mint).await;
+        }
+        let ix = deposit_ix(&self.program_id, fixture);
+        self.process(&[ix], &[&fixture.seller]).await
+    }
+
+    /// Initialize and fund a listing in one go, returning it in the `Active` state.
+    pub async fn setup_active_listing(&mut self, args: ListingArgs) -> ListingFixture {
+        let fixture = self.setup_listing(args).await;
+        self.deposit(&fixture).await.unwrap();
+        fixture
+    }
+
+    /// Create a buyer holding `quote_amount` quote tokens and an empty base account.
+    pub async fn setup_buyer(&mut self, fixture: &ListingFixture, quote_amount: u64) -> Buyer {
+        let keypair = self.funded_keypair(1_000_000_000).await;
+        let quote_account = self.create_ata(&keypair.pubkey(), &fixture.quote_mint).await;
+        let base_account = self.create_ata(&keypair.pubkey(), &fixture.base_mint).await;
+        self.mint_to(&fixture.quote_mint, &quote_account, quote_amount).await;
+        Buyer { keypair, quote_account, base_account }
+    }
+
+    /// Purchase `quantity` base tokens from the listing.
+    pub async fn purchase(
+        &mut self,
+        fixture: &ListingFixture,
+        buyer: &Buyer,
+        quantity: u64,
+    ) -> Result<(), BanksClientError> {
+        let ix = purchase_ix(&self.program_id, fixture, buyer, quantity);
+        self.process(&[ix], &[&buyer.keypair]).await
+    }
+
+    /// Cancel the listing as its seller.
+    pub async fn cancel(&mut self, fixture: &ListingFixture) -> Result<(), BanksClientError> {
+        let ix = cancel_ix(&self.program_id, fixture);
+        self.process(&[ix], &[&fixture.seller]).await
+    }
+}
+
+/// Parameters for creating a test listing.
+#[derive(Clone, Debug)]
+pub struct ListingArgs {
+    pub listing_id: u64,
+    pub price_per_token: u64,
+    pub quantity: u64,
+    pub allow_partial: bool,
+    pub fee_payment_method: u8,
+    pub x402_payload: Option<String>,
+    pub base_decimals: u8,
+    pub quote_decimals: u8,
+    /// Reuse an existing quote mint instead of creating a fresh one.
+    pub quote_mint: Option<Pubkey>,
+    /// Treasury wallet to pass once the program config has been initialized.
+    pub treasury: Option<Pubkey>,
+    /// Pass the seller stats PDA, required once the config caps active listings.
+    pub seller_stats: bool,
+    /// Seller and treasury token accounts paying an `SplToken` fee.
+    pub fee_token_accounts: Option<(Pubkey, Pubkey)>,
+    /// Fee mint or incinerator receiving the burned share of the fee.
+    pub burn_account: Option<Pubkey>,
+    /// Pass the token programs so initialization creates the vault ATA itself.
+    pub create_vault: bool,
+    pub required_attestation_issuer: Option<Pubkey>,
+    pub escrow_fee: bool,
+    pub vault_count: u8,
+    pub tranche_interval: i64,
+    pub cancel_authority: Option<Pubkey>,
+    pub min_fill_bps: u16,
+    pub max_fill_bps: u16,
+    pub oracle: Option<Pubkey>,
+    pub spread_bps: i32,
+    pub min_fill_quote: u64,
+    pub auction_end: i64,
+    pub trigger_price: u64,
+    pub max_quote_raise: u64,
+    pub tick_size: u64,
+    pub quote_per_base_num: u64,
+    pub quote_per_base_den: u64,
+    pub taker_fee_bps: u16,
+    pub category: u8,
+    pub note: [u8; 64],
+    pub deposit_deadline: i64,
+    pub auto_complete_after_fills: u16,
+    pub max_slippage_bps: u16,
+    pub proceeds_unlock_ts: i64,
+    pub max_oracle_staleness_secs: u32,
+}
+
+impl Default for ListingArgs {
+    fn default() -> Self {
+        Self {
+            listing_id: 1,
+            price_per_token: 1_000_000,
+            quantity: 100_000_000,
+            allow_partial: true,
+            fee_payment_method: 0,
+            x402_payload: None,
+            base_decimals: 6,
+            quote_decimals: 6,
+            quote_mint: None,
+            treasury: None,
+            seller_stats: false,
+            fee_token_accounts: None,
+            burn_account: None,
+            create_vault: false,
+            required_attestation_issuer: None,
+            escrow_fee: false,
+            vault_count: 1,
+            tranche_interval: 0,
+            cancel_authority: None,
+            min_fill_bps: 0,
+            max_fill_bps: 0,
+            oracle: None,
+            spread_bps: 0,
+            min_fill_quote: 0,
+            auction_end: 0,
+            trigger_price: 0,
+            max_quote_raise: 0,
+            tick_size: 0,
+            quote_per_base_num: 0,
+            quote_per_base_den: 0,
+            taker_fee_bps: 0,
+            category: 0,
+            note: [0; 64],
+            deposit_deadline: 0,
+            auto_complete_after_fills: 0,
+            max_slippage_bps: 0,
+            proceeds_unlock_ts: 0,
+            max_oracle_staleness_secs: 0,
+        }
+    }
+}
+
+/// Accounts making up a single listing.
+pub struct ListingFixture {
+    pub seller: Keypair,
+    pub listing: Pubkey,
+    pub listing_id: u64,
+    pub vault_authority: Pubkey,
+    pub vault: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub seller_base: Pubkey,
+    pub seller_quote: Pubkey,
+}
+
+/// Buyer wallet plus its token accounts.
+pub struct Buyer {
+    pub keypair: Keypair,
+    pub quote_account: Pubkey,
+    pub base_account: Pubkey,
+}
+
+/// Derive the vault authority PDA for a seller/listing id pair.
+pub fn vault_authority_address(program_id: &Pubkey, seller: &Pubkey, listing_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"vault", seller.as_ref(), &listing_id.to_le_bytes()], program_id)
+}
+
+pub fn initialize_ix(program_id: &Pubkey, fixture: &ListingFixture, args: &ListingArgs) -> Instruction {
+    let data = EscrowInstruction::InitializeListing(InitializeListingArgs {
+        listing_id: args.listing_id,
+        price_per_token: args.price_per_token,
+        quantity: args.quantity,
+        allow_partial: args.allow_partial,
+        fee_payment_method: args.fee_payment_method,
+        x402_payload: args.x402_payload.clone(),
+        required_attestation_issuer: args.required_attestation_issuer,
+        escrow_fee: args.escrow_fee,
+        vault_count: args.vault_count,
+        tranche_interval: args.tranche_interval,
+        cancel_authority: args.cancel_authority,
+        min_fill_bps: args.min_fill_bps,
+        max_fill_bps: args.max_fill_bps,
+        oracle: args.oracle,
+        spread_bps: args.spread_bps,
+        min_fill_quote: args.min_fill_quote,
+        auction_end: args.auction_end,
+        trigger_price: args.trigger_price,
+        max_quote_raise: args.max_quote_raise,
+        tick_size: args.tick_size,
+        quote_per_base_num: args.quote_per_base_num,
+        quote_per_base_den: args.quote_per_base_den,
+        taker_fee_bps: args.taker_fee_bps,
+        category: args.category,
+        note: args.note,
+        deposit_deadline: args.deposit_deadline,
+        auto_complete_after_fills: args.auto_complete_after_fills,
+        max_slippage_bps: args.max_slippage_bps,
+        proceeds_unlock_ts: args.proceeds_unlock_ts,
+        max_oracle_staleness_secs: args.max_oracle_staleness_secs,
+    });
+    let mut ix = Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(fixture.seller.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new_readonly(fixture.vault_authority, false),
+            AccountMeta::new_readonly(fixture.vault, false),
+            AccountMeta::new_readonly(fixture.base_mint, false),
+            AccountMeta::new_readonly(fixture.quote_mint, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        ],
+        data: data.try_to_vec().unwrap(),
+    };
+    if let Some(treasury) = args.treasury {
+        ix.accounts.push(AccountMeta::new_readonly(find_treasury_address(program_id, &fixture.quote_mint).0, false));
+        ix.accounts.push(AccountMeta::new(treasury, false));
+        ix.accounts
+            .push(AccountMeta::new_readonly(find_fee_exempt_address(program_id, &fixture.seller.pubkey()).0, false));
+        if args.seller_stats {
+            ix.accounts
+                .push(AccountMeta::new(find_seller_stats_address(program_id, &fixture.seller.pubkey()).0, false));
+        }
+        if let Some((seller_fee_account, treasury_fee_account)) = args.fee_token_accounts {
+            ix.accounts.push(AccountMeta::new(seller_fee_account, false));
+            ix.accounts.push(AccountMeta::new(treasury_fee_account, false));
+            ix.accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+        }
+        if args.escrow_fee {
+            ix.accounts.push(AccountMeta::new(find_fee_escrow_address(program_id, &fixture.listing).0, false));
+        }
+        if let Some(burn_account) = args.burn_account {
+            ix.accounts.push(AccountMeta::new(burn_account, false));
+        }
+    }
+    if args.create_vault {
+        ix.accounts[3] = AccountMeta::new(fixture.vault, false);
+        ix.accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+        ix.accounts.push(AccountMeta::new_readonly(spl_associated_token_account::id(), false));
+    }
+    ix
+}
+
+pub fn deposit_ix(program_id: &Pubkey, fixture: &ListingFixture) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(fixture.seller.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new(fixture.seller_base, false),
+            AccountMeta::new_readonly(fixture.vault_authority, false),
+            AccountMeta::new(fixture.vault, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: EscrowInstruction::DepositTokens.try_to_vec().unwrap(),
+    }
+}
+
+pub fn purchase_ix(program_id: &Pubkey, fixture: &ListingFixture, buyer: &Buyer, quantity: u64) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(buyer.keypair.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new(fixture.seller_quote, false),
+            AccountMeta::new(buyer.quote_account, false),
+            AccountMeta::new(buyer.base_account, false),
+            AccountMeta::new_readonly(fixture.vault_authority, false),
+            AccountMeta::new(fixture.vault, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(find_fills_ledger_address(program_id, &fixture.listing).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::Purchase { quantity }.try_to_vec().unwrap(),
+    }
+}
+
+pub fn cancel_ix(program_id: &Pubkey, fixture: &ListingFixture) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(fixture.seller.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new_readonly(fixture.vault_authority, false),
+            AccountMeta::new(fixture.vault, false),
+            AccountMeta::new(fixture.seller_base, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: EscrowInstruction::CancelListing.try_to_vec().unwrap(),
+    }
+}
+
+/// Register an initialized mint account with the bank before it starts.
+pub fn add_mint_account(program_test: &mut ProgramTest, decimals: u8) -> Pubkey {
+    let mint = Pubkey::new_unique();
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    spl_token::state::Mint::pack(
+        spl_token::state::Mint {
+            mint_authority: solana_program::program_option::COption::Some(Pubkey::new_unique()),
+            supply: 0,
+            decimals,
+            is_initialized: true,
+            freeze_authority: solana_program::program_option::COption::None,
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        mint,
+        solana_sdk::account::Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: spl_token::id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    mint
+}
@@ -0,0 +1,125 @@
+//! Tests for private-deal listings reserved for a single buyer.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{custom_error_code, Buyer, ListingArgs, ListingFixture, TestEnv};
+use escrow_program::{allowlist_leaf, allowlist_node, EscrowError, EscrowInstruction};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+fn set_allowed_buyer_ix(
+    program_id: &Pubkey,
+    fixture: &ListingFixture,
+    signer: &Pubkey,
+    buyer: Option<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*signer, true),
+            AccountMeta::new(fixture.listing, false),
+        ],
+        data: EscrowInstruction::SetAllowedBuyer { buyer }.try_to_vec().unwrap(),
+    }
+}
+
+/// After the seller switches the allowed buyer, only the new one can purchase.
+#[tokio::test]
+async fn test_change_allowed_buyer() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let old_buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let new_buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let seller = fixture.seller.pubkey();
+
+    let ix = set_allowed_buyer_ix(&env.program_id, &fixture, &seller, Some(old_buyer.keypair.pubkey()));
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    let ix = set_allowed_buyer_ix(&env.program_id, &fixture, &seller, Some(new_buyer.keypair.pubkey()));
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    assert_eq!(env.listing(&fixture.listing).await.allowed_buyer, new_buyer.keypair.pubkey());
+
+    let err = env.purchase(&fixture, &old_buyer, 10_000_000).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::BuyerNotAllowed as u32));
+
+    env.purchase(&fixture, &new_buyer, 10_000_000).await.unwrap();
+    assert_eq!(env.token_balance(&new_buyer.base_account).await, 10_000_000);
+
+    // Clearing the restriction opens the listing again.
+    let ix = set_allowed_buyer_ix(&env.program_id, &fixture, &seller, None);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    env.purchase(&fixture, &old_buyer, 10_000_000).await.unwrap();
+    assert_eq!(env.token_balance(&old_buyer.base_account).await, 10_000_000);
+}
+
+/// Only the seller can change the allowed buyer.
+#[tokio::test]
+async fn test_set_allowed_buyer_requires_seller() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let intruder = Keypair::new();
+
+    let ix = set_allowed_buyer_ix(&env.program_id, &fixture, &intruder.pubkey(), Some(intruder.pubkey()));
+    let err = env.process(&[ix], &[&intruder]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::IncorrectAuthority as u32));
+}
+
+/// Set a four-buyer allowlist root and return the first buyer's proof.
+async fn set_allowlist(env: &mut TestEnv, fixture: &ListingFixture, members: [&Pubkey; 4]) -> Vec<[u8; 32]> {
+    let leaves = members.map(allowlist_leaf);
+    let right = allowlist_node(&leaves[2], &leaves[3]);
+    let root = allowlist_node(&allowlist_node(&leaves[0], &leaves[1]), &right);
+    let ix = Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(fixture.seller.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+        ],
+        data: EscrowInstruction::SetAllowlistRoot { root: Some(root) }.try_to_vec().unwrap(),
+    };
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    vec![leaves[1], right]
+}
+
+fn purchase_with_proof_ix(env: &TestEnv, fixture: &ListingFixture, buyer: &Buyer, proof: Vec<[u8; 32]>) -> Instruction {
+    let mut ix = common::purchase_ix(&env.program_id, fixture, buyer, 10_000_000);
+    ix.data = EscrowInstruction::PurchaseWithProof { quantity: 10_000_000, proof }.try_to_vec().unwrap();
+    ix
+}
+
+/// A buyer in the allowlist purchases by proving membership against the root.
+#[tokio::test]
+async fn test_allowlist_proof_admits_member() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let member = env.setup_buyer(&fixture, 100_000_000).await;
+    let others = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    let proof =
+        set_allowlist(&mut env, &fixture, [&member.keypair.pubkey(), &others[0], &others[1], &others[2]]).await;
+
+    // Plain purchases carry no proof.
+    let err = env.purchase(&fixture, &member, 10_000_000).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::BuyerNotAllowed as u32));
+
+    let ix = purchase_with_proof_ix(&env, &fixture, &member, proof);
+    env.process(&[ix], &[&member.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&member.base_account).await, 10_000_000);
+}
+
+/// A proof that does not lead from the buyer's leaf to the root is rejected.
+#[tokio::test]
+async fn test_allowlist_rejects_invalid_proof() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let outsider = env.setup_buyer(&fixture, 100_000_000).await;
+    let members = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    let proof = set_allowlist(&mut env, &fixture, [&members[0], &members[1], &members[2], &members[3]]).await;
+
+    let ix = purchase_with_proof_ix(&env, &fixture, &outsider, proof);
+    let err = env.process(&[ix], &[&outsider.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::BuyerNotAllowed as u32));
+    assert_eq!(env.token_balance(&outsider.base_account).await, 0);
+}
@@ -0,0 +1,63 @@
+//! Tests for listings that require a buyer attestation.
+
+mod common;
+
+use common::{custom_error_code, ListingArgs};
+use escrow_program::{find_attestation_address, EscrowError};
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey};
+use solana_sdk::{account::Account, signature::Signer};
+
+/// A buyer holding the issuer's attestation PDA can purchase.
+#[tokio::test]
+async fn test_purchase_with_valid_attestation() {
+    let mut env = common::start().await;
+    let issuer = Pubkey::new_unique();
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            required_attestation_issuer: Some(issuer),
+            ..ListingArgs::default()
+        })
+        .await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    let (attestation, _) = find_attestation_address(&issuer, &buyer.keypair.pubkey());
+    env.ctx.set_account(
+        &attestation,
+        &Account {
+            lamports: 1_000_000,
+            data: vec![1],
+            owner: issuer,
+            executable: false,
+            rent_epoch: 0,
+        }
+        .into(),
+    );
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 10_000_000);
+    ix.accounts.push(AccountMeta::new_readonly(attestation, false));
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&buyer.base_account).await, 10_000_000);
+}
+
+/// Without an attestation from the required issuer the purchase is rejected.
+#[tokio::test]
+async fn test_purchase_without_attestation_rejected() {
+    let mut env = common::start().await;
+    let issuer = Pubkey::new_unique();
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            required_attestation_issuer: Some(issuer),
+            ..ListingArgs::default()
+        })
+        .await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    // The PDA address is right but nothing was ever issued there.
+    let (attestation, _) = find_attestation_address(&issuer, &buyer.keypair.pubkey());
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 10_000_000);
+    ix.accounts.push(AccountMeta::new_readonly(attestation, false));
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+
+    assert_eq!(custom_error_code(err), Some(EscrowError::AttestationMissing as u32));
+    assert_eq!(env.token_balance(&buyer.base_account).await, 0);
+}
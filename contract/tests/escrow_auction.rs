@@ -0,0 +1,199 @@
+//! Tests for English auction listings.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{custom_error_code, Buyer, ListingArgs, ListingFixture, TestEnv};
+use escrow_program::{find_fills_ledger_address, EscrowError, EscrowInstruction, ListingStatus};
+use solana_program::{
+    clock::Clock,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::signature::Signer;
+
+fn place_bid_ix(
+    env: &TestEnv,
+    fixture: &ListingFixture,
+    bid_vault: &Pubkey,
+    bidder: &Buyer,
+    amount: u64,
+    previous: Option<&Buyer>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(bidder.keypair.pubkey(), true),
+        AccountMeta::new(fixture.listing, false),
+        AccountMeta::new(bidder.quote_account, false),
+        AccountMeta::new(*bid_vault, false),
+        AccountMeta::new_readonly(fixture.vault_authority, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if let Some(previous) = previous {
+        accounts.push(AccountMeta::new(previous.quote_account, false));
+    }
+    Instruction {
+        program_id: env.program_id,
+        accounts,
+        data: EscrowInstruction::PlaceBid { amount }.try_to_vec().unwrap(),
+    }
+}
+
+fn finalize_ix(env: &TestEnv, fixture: &ListingFixture, bid_vault: &Pubkey, winner: &Buyer) -> Instruction {
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new(winner.keypair.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new_readonly(fixture.vault_authority, false),
+            AccountMeta::new(fixture.vault, false),
+            AccountMeta::new(*bid_vault, false),
+            AccountMeta::new(fixture.seller_quote, false),
+            AccountMeta::new(winner.base_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(find_fills_ledger_address(&env.program_id, &fixture.listing).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::FinalizeAuction.try_to_vec().unwrap(),
+    }
+}
+
+/// An auction for 100 tokens with a reserve of 100 quote, closing in 100 seconds.
+async fn setup_auction(env: &mut TestEnv) -> (ListingFixture, Pubkey) {
+    let clock: Clock = env.ctx.banks_client.get_sysvar().await.unwrap();
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            allow_partial: false,
+            auction_end: clock.unix_timestamp + 100,
+            ..ListingArgs::default()
+        })
+        .await;
+    let bid_vault = env.create_ata(&fixture.vault_authority, &fixture.quote_mint).await;
+    (fixture, bid_vault)
+}
+
+/// Competing bids must escalate; the outbid bidder is refunded in full.
+#[tokio::test]
+async fn test_outbid_bidder_is_refunded() {
+    let mut env = common::start().await;
+    let (fixture, bid_vault) = setup_auction(&mut env).await;
+    let alice = env.setup_buyer(&fixture, 200_000_000).await;
+    let bob = env.setup_buyer(&fixture, 200_000_000).await;
+
+    let ix = place_bid_ix(&env, &fixture, &bid_vault, &alice, 99_999_999, None);
+    let err = env.process(&[ix], &[&alice.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::BidTooLow as u32));
+
+    let ix = place_bid_ix(&env, &fixture, &bid_vault, &alice, 100_000_000, None);
+    env.process(&[ix], &[&alice.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&bid_vault).await, 100_000_000);
+
+    let ix = place_bid_ix(&env, &fixture, &bid_vault, &bob, 120_000_000, Some(&alice));
+    env.process(&[ix], &[&bob.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&alice.quote_account).await, 200_000_000);
+    assert_eq!(env.token_balance(&bob.quote_account).await, 80_000_000);
+    assert_eq!(env.token_balance(&bid_vault).await, 120_000_000);
+
+    let ix = place_bid_ix(&env, &fixture, &bid_vault, &alice, 110_000_000, Some(&bob));
+    let err = env.process(&[ix], &[&alice.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::BidTooLow as u32));
+
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!(listing.high_bid, 120_000_000);
+    assert_eq!(listing.high_bidder, bob.keypair.pubkey());
+}
+
+/// After the auction closes, finalizing pays the seller the high bid and
+/// delivers the whole quantity to the winner.
+#[tokio::test]
+async fn test_finalize_auction_delivers_to_winner() {
+    let mut env = common::start().await;
+    let (fixture, bid_vault) = setup_auction(&mut env).await;
+    let alice = env.setup_buyer(&fixture, 200_000_000).await;
+    let bob = env.setup_buyer(&fixture, 200_000_000).await;
+
+    let ix = place_bid_ix(&env, &fixture, &bid_vault, &alice, 100_000_000, None);
+    env.process(&[ix], &[&alice.keypair]).await.unwrap();
+    let ix = place_bid_ix(&env, &fixture, &bid_vault, &bob, 150_000_000, Some(&alice));
+    env.process(&[ix], &[&bob.keypair]).await.unwrap();
+
+    let ix = finalize_ix(&env, &fixture, &bid_vault, &bob);
+    let err = env.process(&[ix], &[&bob.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::AuctionStillOpen as u32));
+
+    env.advance_clock(100).await;
+    let ix = place_bid_ix(&env, &fixture, &bid_vault, &alice, 200_000_000, Some(&bob));
+    let err = env.process(&[ix], &[&alice.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::AuctionClosed as u32));
+
+    let ix = finalize_ix(&env, &fixture, &bid_vault, &bob);
+    env.process(&[ix], &[&bob.keypair]).await.unwrap();
+
+    assert_eq!(env.token_balance(&bob.base_account).await, 100_000_000);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 150_000_000);
+    assert_eq!(env.token_balance(&alice.quote_account).await, 200_000_000);
+    assert_eq!(env.token_balance(&bid_vault).await, 0);
+    assert_eq!(env.token_balance(&fixture.vault).await, 0);
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!(listing.status(), ListingStatus::Completed);
+    assert_eq!(listing.filled, 100_000_000);
+}
+
+/// A finalizer passing a fake token program gets no vault signature: both the
+/// base vault and the escrowed high bid stay put.
+#[tokio::test]
+async fn test_finalize_auction_rejects_fake_token_program() {
+    let mut env = common::start().await;
+    let (fixture, bid_vault) = setup_auction(&mut env).await;
+    let alice = env.setup_buyer(&fixture, 200_000_000).await;
+    let ix = place_bid_ix(&env, &fixture, &bid_vault, &alice, 100_000_000, None);
+    env.process(&[ix], &[&alice.keypair]).await.unwrap();
+    env.advance_clock(100).await;
+
+    let attacker = env.setup_buyer(&fixture, 0).await;
+    let mut ix = finalize_ix(&env, &fixture, &bid_vault, &attacker);
+    ix.accounts[7] = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+    let err = env.process(&[ix], &[&attacker.keypair]).await.unwrap_err();
+
+    assert_eq!(common::instruction_error(err), Some(InstructionError::IncorrectProgramId));
+    assert_eq!(env.token_balance(&bid_vault).await, 100_000_000);
+    assert_eq!(env.token_balance(&fixture.vault).await, 100_000_000);
+}
+
+/// Fixed-price purchases are not accepted on an auction listing.
+#[tokio::test]
+async fn test_purchase_rejected_on_auction() {
+    let mut env = common::start().await;
+    let (fixture, _) = setup_auction(&mut env).await;
+    let buyer = env.setup_buyer(&fixture, 200_000_000).await;
+
+    let err = env.purchase(&fixture, &buyer, 100_000_000).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidAuction as u32));
+}
+
+fn time_remaining_ix(env: &TestEnv, fixture: &ListingFixture) -> Instruction {
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![AccountMeta::new_readonly(fixture.listing, false)],
+        data: EscrowInstruction::GetTimeRemaining.try_to_vec().unwrap(),
+    }
+}
+
+/// The seconds left until `auction_end` are returned; fixed-price listings report zero.
+#[tokio::test]
+async fn test_get_time_remaining() {
+    let mut env = common::start().await;
+    let (fixture, _) = setup_auction(&mut env).await;
+    let auction_end = env.listing(&fixture.listing).await.auction_end;
+
+    let clock: Clock = env.ctx.banks_client.get_sysvar().await.unwrap();
+    let ix = time_remaining_ix(&env, &fixture);
+    let data = env.simulate_return_data(&[ix], &[]).await.unwrap();
+    let remaining = u64::from_le_bytes(data.try_into().unwrap());
+    assert_eq!(remaining, (auction_end - clock.unix_timestamp) as u64);
+
+    let fixed = env.setup_active_listing(ListingArgs::default()).await;
+    let ix = time_remaining_ix(&env, &fixed);
+    let data = env.simulate_return_data(&[ix], &[]).await.unwrap();
+    assert_eq!(u64::from_le_bytes(data.try_into().unwrap()), 0);
+}
@@ -0,0 +1,221 @@
+//! Tests for basket listings selling several base mints together.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{custom_error_code, ListingArgs, ListingFixture, TestEnv};
+use escrow_program::{
+    find_basket_address, BasketLeg, EscrowError, EscrowInstruction, ListingStatus, MAX_BATCH_LEGS,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::signature::Signer;
+use spl_token::state::Account as TokenAccount;
+
+fn set_basket_legs_ix(env: &TestEnv, fixture: &ListingFixture, legs: Vec<BasketLeg>) -> Instruction {
+    let (basket, _) = find_basket_address(&env.program_id, &fixture.listing);
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new(fixture.seller.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new(basket, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::SetBasketLegs { legs }.try_to_vec().unwrap(),
+    }
+}
+
+/// A funded two-token basket: the listing's base plus a 40-token second leg,
+/// returned with its basket PDA, second mint and second-leg vault.
+async fn setup_funded_basket(env: &mut TestEnv, args: &ListingArgs) -> (ListingFixture, Pubkey, Pubkey, Pubkey) {
+    let fixture = env.setup_listing(args.clone()).await;
+    let (basket, _) = find_basket_address(&env.program_id, &fixture.listing);
+
+    let second_mint = env.create_mint(6).await;
+    let seller_second = env.create_ata(&fixture.seller.pubkey(), &second_mint).await;
+    env.mint_to(&second_mint, &seller_second, 40_000_000).await;
+    let second_vault = env.create_ata(&fixture.vault_authority, &second_mint).await;
+
+    let legs = vec![BasketLeg {
+        base_mint: second_mint,
+        quantity: 40_000_000,
+    }];
+    let ix = set_basket_legs_ix(env, &fixture, legs);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    env.create_ata(&fixture.vault_authority, &fixture.base_mint).await;
+    let mut ix = common::deposit_ix(&env.program_id, &fixture);
+    ix.accounts.push(AccountMeta::new_readonly(basket, false));
+    ix.accounts.push(AccountMeta::new(seller_second, false));
+    ix.accounts.push(AccountMeta::new(second_vault, false));
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    (fixture, basket, second_mint, second_vault)
+}
+
+/// A two-token basket is funded leg by leg and delivered whole for one quote payment.
+#[tokio::test]
+async fn test_basket_purchase_delivers_every_leg() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        allow_partial: false,
+        ..ListingArgs::default()
+    };
+    let (fixture, basket, second_mint, second_vault) = setup_funded_basket(&mut env, &args).await;
+    assert_eq!(env.token_balance(&second_vault).await, 40_000_000);
+
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let buyer_second = env.create_ata(&buyer.keypair.pubkey(), &second_mint).await;
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, args.quantity);
+    ix.accounts.push(AccountMeta::new_readonly(basket, false));
+    ix.accounts.push(AccountMeta::new(second_vault, false));
+    ix.accounts.push(AccountMeta::new(buyer_second, false));
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+
+    assert_eq!(env.token_balance(&buyer.base_account).await, args.quantity);
+    assert_eq!(env.token_balance(&buyer_second).await, 40_000_000);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 100_000_000);
+    assert_eq!(env.token_balance(&second_vault).await, 0);
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!(listing.status(), ListingStatus::Completed);
+}
+
+/// A leg whose vault no longer holds its full quantity fails the pre-flight
+/// check before the buyer pays anything.
+#[tokio::test]
+async fn test_basket_purchase_rejects_underfunded_leg() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        allow_partial: false,
+        ..ListingArgs::default()
+    };
+    let (fixture, basket, second_mint, second_vault) = setup_funded_basket(&mut env, &args).await;
+
+    let mut account = env.ctx.banks_client.get_account(second_vault).await.unwrap().unwrap();
+    let mut vault = TokenAccount::unpack(&account.data).unwrap();
+    vault.amount = 39_999_999;
+    TokenAccount::pack(vault, &mut account.data).unwrap();
+    env.ctx.set_account(&second_vault, &account.into());
+
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let buyer_second = env.create_ata(&buyer.keypair.pubkey(), &second_mint).await;
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, args.quantity);
+    ix.accounts.push(AccountMeta::new_readonly(basket, false));
+    ix.accounts.push(AccountMeta::new(second_vault, false));
+    ix.accounts.push(AccountMeta::new(buyer_second, false));
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+
+    assert_eq!(custom_error_code(err), Some(EscrowError::BasketLegUnsatisfiable as u32));
+    assert_eq!(env.token_balance(&buyer.quote_account).await, 100_000_000);
+    assert_eq!(env.token_balance(&buyer.base_account).await, 0);
+}
+
+/// A look-alike leg vault owned by the vault authority, but not at its ATA
+/// address, cannot be funded.
+#[tokio::test]
+async fn test_basket_deposit_rejects_look_alike_leg_vault() {
+    let mut env = common::start().await;
+    let fixture = env
+        .setup_listing(ListingArgs {
+            allow_partial: false,
+            ..ListingArgs::default()
+        })
+        .await;
+    let (basket, _) = find_basket_address(&env.program_id, &fixture.listing);
+    let second_mint = env.create_mint(6).await;
+    let seller_second = env.create_ata(&fixture.seller.pubkey(), &second_mint).await;
+    env.mint_to(&second_mint, &seller_second, 40_000_000).await;
+    let second_vault = env.create_ata(&fixture.vault_authority, &second_mint).await;
+    let legs = vec![BasketLeg {
+        base_mint: second_mint,
+        quantity: 40_000_000,
+    }];
+    let ix = set_basket_legs_ix(&env, &fixture, legs);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    let account = env.ctx.banks_client.get_account(second_vault).await.unwrap().unwrap();
+    let look_alike = Pubkey::new_unique();
+    env.ctx.set_account(&look_alike, &account.into());
+
+    env.create_ata(&fixture.vault_authority, &fixture.base_mint).await;
+    let mut ix = common::deposit_ix(&env.program_id, &fixture);
+    ix.accounts.push(AccountMeta::new_readonly(basket, false));
+    ix.accounts.push(AccountMeta::new(seller_second, false));
+    ix.accounts.push(AccountMeta::new(look_alike, false));
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+
+    assert_eq!(custom_error_code(err), Some(EscrowError::MintMismatch as u32));
+    assert_eq!(env.token_balance(&seller_second).await, 40_000_000);
+}
+
+/// A leg vault with a delegate fails the pre-flight check like the base vault.
+#[tokio::test]
+async fn test_basket_purchase_rejects_delegated_leg_vault() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        allow_partial: false,
+        ..ListingArgs::default()
+    };
+    let (fixture, basket, second_mint, second_vault) = setup_funded_basket(&mut env, &args).await;
+
+    let mut account = env.ctx.banks_client.get_account(second_vault).await.unwrap().unwrap();
+    let mut vault = TokenAccount::unpack(&account.data).unwrap();
+    vault.delegate = COption::Some(Pubkey::new_unique());
+    vault.delegated_amount = 40_000_000;
+    TokenAccount::pack(vault, &mut account.data).unwrap();
+    env.ctx.set_account(&second_vault, &account.into());
+
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let buyer_second = env.create_ata(&buyer.keypair.pubkey(), &second_mint).await;
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, args.quantity);
+    ix.accounts.push(AccountMeta::new_readonly(basket, false));
+    ix.accounts.push(AccountMeta::new(second_vault, false));
+    ix.accounts.push(AccountMeta::new(buyer_second, false));
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+
+    assert_eq!(custom_error_code(err), Some(EscrowError::BasketLegUnsatisfiable as u32));
+    assert_eq!(env.token_balance(&buyer.quote_account).await, 100_000_000);
+}
+
+/// Baskets cannot be attached to listings that allow partial fills.
+#[tokio::test]
+async fn test_basket_rejects_partial_fill_listing() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+    let second_mint = env.create_mint(6).await;
+
+    let legs = vec![BasketLeg {
+        base_mint: second_mint,
+        quantity: 1_000_000,
+    }];
+    let ix = set_basket_legs_ix(&env, &fixture, legs);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidBasket as u32));
+}
+
+/// More legs than `MAX_BATCH_LEGS` are rejected up front with `BatchTooLarge`.
+#[tokio::test]
+async fn test_basket_rejects_too_many_legs() {
+    let mut env = common::start().await;
+    let fixture = env
+        .setup_listing(ListingArgs {
+            allow_partial: false,
+            ..ListingArgs::default()
+        })
+        .await;
+
+    let legs = (0..=MAX_BATCH_LEGS)
+        .map(|_| BasketLeg {
+            base_mint: Pubkey::new_unique(),
+            quantity: 1_000_000,
+        })
+        .collect();
+    let ix = set_basket_legs_ix(&env, &fixture, legs);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::BatchTooLarge as u32));
+}
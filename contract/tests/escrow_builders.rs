@@ -0,0 +1,47 @@
+//! Tests for the public instruction builders.
+
+mod common;
+
+use common::ListingArgs;
+use escrow_program::{
+    cancel_listing_ix, deposit_tokens_ix, initialize_listing_ix, purchase_ix, EscrowInstruction,
+};
+use solana_sdk::signature::Signer;
+
+/// Each builder matches the instruction the tests assemble by hand.
+#[tokio::test]
+async fn test_builders_match_hand_built_instructions() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let fixture = env.setup_active_listing(args.clone()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let listing = env.listing(&fixture.listing).await;
+    let program_id = env.program_id;
+
+    let hand_built = common::initialize_ix(&program_id, &fixture, &args);
+    let EscrowInstruction::InitializeListing(init_args) = EscrowInstruction::unpack(&hand_built.data).unwrap() else {
+        panic!("not an InitializeListing");
+    };
+    let built = initialize_listing_ix(
+        &program_id,
+        &fixture.seller.pubkey(),
+        &fixture.listing,
+        &fixture.base_mint,
+        &fixture.quote_mint,
+        init_args,
+    );
+    assert_eq!(built, hand_built);
+
+    assert_eq!(
+        deposit_tokens_ix(&program_id, &fixture.listing, &listing),
+        common::deposit_ix(&program_id, &fixture)
+    );
+    assert_eq!(
+        purchase_ix(&program_id, &fixture.listing, &listing, &buyer.keypair.pubkey(), 10_000_000),
+        common::purchase_ix(&program_id, &fixture, &buyer, 10_000_000)
+    );
+    assert_eq!(
+        cancel_listing_ix(&program_id, &fixture.listing, &listing),
+        common::cancel_ix(&program_id, &fixture)
+    );
+}
@@ -0,0 +1,315 @@
+//! Tests for listing cancellation.
+
+mod common;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use common::{custom_error_code, ListingArgs, ListingFixture};
+use escrow_program::{
+    find_config_address, find_seller_stats_address, EscrowError, EscrowInstruction, ListingStatus, SellerStats,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+fn force_cancel_ix(program_id: &Pubkey, fixture: &ListingFixture, admin: &Pubkey) -> Instruction {
+    let mut ix = common::cancel_ix(program_id, fixture);
+    ix.accounts[0] = AccountMeta::new_readonly(*admin, true);
+    ix.accounts.push(AccountMeta::new(fixture.seller.pubkey(), false));
+    ix.accounts.push(AccountMeta::new_readonly(find_config_address(program_id).0, false));
+    ix.data = EscrowInstruction::ForceCancel.try_to_vec().unwrap();
+    ix
+}
+
+/// Cancelling a listing that was never funded refunds its rent to the seller, closing it.
+#[tokio::test]
+async fn test_cancel_unfunded_listing_refunds_rent() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+
+    let seller = fixture.seller.pubkey();
+    let listing_rent = env.lamports(&fixture.listing).await;
+    assert!(listing_rent > 0);
+    let seller_before = env.lamports(&seller).await;
+
+    env.cancel(&fixture).await.unwrap();
+
+    assert_eq!(env.lamports(&seller).await, seller_before + listing_rent);
+    assert!(env
+        .ctx
+        .banks_client
+        .get_account(fixture.listing)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+/// Cancelling a funded listing returns the unsold inventory to the seller.
+#[tokio::test]
+async fn test_cancel_active_listing_returns_inventory() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let fixture = env.setup_active_listing(args.clone()).await;
+    assert_eq!(env.token_balance(&fixture.vault).await, args.quantity);
+
+    env.cancel(&fixture).await.unwrap();
+
+    assert_eq!(env.token_balance(&fixture.vault).await, 0);
+    assert_eq!(env.token_balance(&fixture.seller_base).await, args.quantity);
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!(listing.status(), ListingStatus::Cancelled);
+}
+
+/// Cancelling after a partial fill returns the unsold base to the seller; the
+/// quote proceeds from the fill are already in the seller's account.
+#[tokio::test]
+async fn test_cancel_after_partial_fill_returns_base_and_keeps_proceeds() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let fixture = env.setup_active_listing(args.clone()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    env.purchase(&fixture, &buyer, 30_000_000).await.unwrap();
+    env.cancel(&fixture).await.unwrap();
+
+    assert_eq!(env.token_balance(&fixture.seller_base).await, args.quantity - 30_000_000);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 30_000_000);
+    assert_eq!(env.token_balance(&fixture.vault).await, 0);
+}
+
+/// The seller can still cancel a listing that names an escrow agent.
+#[tokio::test]
+async fn test_seller_cancels_listing_with_agent() {
+    let mut env = common::start().await;
+    let agent = Keypair::new();
+    let args = ListingArgs {
+        cancel_authority: Some(agent.pubkey()),
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_active_listing(args.clone()).await;
+
+    env.cancel(&fixture).await.unwrap();
+
+    assert_eq!(env.token_balance(&fixture.seller_base).await, args.quantity);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Cancelled);
+}
+
+/// The designated agent can cancel; unsold inventory still goes back to the seller.
+#[tokio::test]
+async fn test_cancel_authority_cancels_listing() {
+    let mut env = common::start().await;
+    let agent = env.funded_keypair(1_000_000_000).await;
+    let args = ListingArgs {
+        cancel_authority: Some(agent.pubkey()),
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_active_listing(args.clone()).await;
+
+    let mut ix = common::cancel_ix(&env.program_id, &fixture);
+    ix.accounts[0] = AccountMeta::new(agent.pubkey(), true);
+    ix.accounts.push(AccountMeta::new(fixture.seller.pubkey(), false));
+    env.process(&[ix], &[&agent]).await.unwrap();
+
+    assert_eq!(env.token_balance(&fixture.vault).await, 0);
+    assert_eq!(env.token_balance(&fixture.seller_base).await, args.quantity);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Cancelled);
+}
+
+/// A cancel authority cannot swap in a look-alike vault that is not the vault authority's ATA.
+#[tokio::test]
+async fn test_cancel_authority_rejects_look_alike_vault() {
+    let mut env = common::start().await;
+    let agent = env.funded_keypair(1_000_000_000).await;
+    let args = ListingArgs {
+        cancel_authority: Some(agent.pubkey()),
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_active_listing(args.clone()).await;
+
+    let account = env.ctx.banks_client.get_account(fixture.vault).await.unwrap().unwrap();
+    let look_alike = Pubkey::new_unique();
+    env.ctx.set_account(&look_alike, &account.into());
+
+    let mut ix = common::cancel_ix(&env.program_id, &fixture);
+    ix.accounts[0] = AccountMeta::new(agent.pubkey(), true);
+    ix.accounts[3] = AccountMeta::new(look_alike, false);
+    ix.accounts.push(AccountMeta::new(fixture.seller.pubkey(), false));
+    let err = env.process(&[ix], &[&agent]).await.unwrap_err();
+
+    assert_eq!(custom_error_code(err), Some(EscrowError::MintMismatch as u32));
+    assert_eq!(env.token_balance(&fixture.vault).await, args.quantity);
+}
+
+/// A signer that is neither the seller nor the cancel authority is rejected.
+#[tokio::test]
+async fn test_cancel_rejects_unrelated_signer() {
+    let mut env = common::start().await;
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            cancel_authority: Some(Keypair::new().pubkey()),
+            ..ListingArgs::default()
+        })
+        .await;
+    let stranger = env.funded_keypair(1_000_000_000).await;
+
+    let mut ix = common::cancel_ix(&env.program_id, &fixture);
+    ix.accounts[0] = AccountMeta::new(stranger.pubkey(), true);
+    ix.accounts.push(AccountMeta::new(fixture.seller.pubkey(), false));
+    let err = env.process(&[ix], &[&stranger]).await.unwrap_err();
+
+    assert_eq!(custom_error_code(err), Some(EscrowError::IncorrectAuthority as u32));
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Active);
+}
+
+/// A corrupted stored `vault_bump` does not strand the inventory: the program
+/// signs with the re-derived canonical bump.
+#[tokio::test]
+async fn test_cancel_with_corrupted_vault_bump() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let fixture = env.setup_active_listing(args.clone()).await;
+
+    let mut listing = env.listing(&fixture.listing).await;
+    listing.vault_bump = listing.vault_bump.wrapping_sub(1);
+    env.set_listing(&fixture.listing, &listing).await;
+
+    env.cancel(&fixture).await.unwrap();
+
+    assert_eq!(env.token_balance(&fixture.vault).await, 0);
+    assert_eq!(env.token_balance(&fixture.seller_base).await, args.quantity);
+}
+
+/// The config admin can take down an active listing; the seller gets the inventory back.
+#[tokio::test]
+async fn test_admin_force_cancels_active_listing() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let fixture = env.setup_active_listing(args.clone()).await;
+    let admin = env.initialize_config(Pubkey::new_unique()).await;
+
+    let ix = force_cancel_ix(&env.program_id, &fixture, &admin.pubkey());
+    env.process(&[ix], &[&admin]).await.unwrap();
+
+    assert_eq!(env.token_balance(&fixture.vault).await, 0);
+    assert_eq!(env.token_balance(&fixture.seller_base).await, args.quantity);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Cancelled);
+}
+
+/// Only the config admin may force-cancel.
+#[tokio::test]
+async fn test_force_cancel_rejects_non_admin() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    env.initialize_config(Pubkey::new_unique()).await;
+    let stranger = env.funded_keypair(1_000_000_000).await;
+
+    let ix = force_cancel_ix(&env.program_id, &fixture, &stranger.pubkey());
+    let err = env.process(&[ix], &[&stranger]).await.unwrap_err();
+
+    assert_eq!(custom_error_code(err), Some(EscrowError::IncorrectAuthority as u32));
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Active);
+}
+
+fn batch_cancel_ix(program_id: &Pubkey, seller: &Pubkey, fixtures: &[&ListingFixture], counted: bool) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*seller, true), AccountMeta::new_readonly(spl_token::id(), false)];
+    for fixture in fixtures {
+        accounts.push(AccountMeta::new(fixture.listing, false));
+        accounts.push(AccountMeta::new_readonly(fixture.vault_authority, false));
+        accounts.push(AccountMeta::new(fixture.vault, false));
+        accounts.push(AccountMeta::new(fixture.seller_base, false));
+        if counted {
+            accounts.push(AccountMeta::new(find_seller_stats_address(program_id, seller).0, false));
+        }
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: EscrowInstruction::BatchCancel.try_to_vec().unwrap(),
+    }
+}
+
+/// `BatchCancel` cancels several of a seller's active listings at once, and a
+/// later batch skips the ones already cancelled.
+#[tokio::test]
+async fn test_batch_cancel_two_active_listings() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let first = env.setup_active_listing(args.clone()).await;
+    let second_args = ListingArgs { listing_id: 2, ..args.clone() };
+    let second = env.setup_listing_for(first.seller.insecure_clone(), second_args).await;
+    env.deposit(&second).await.unwrap();
+    let buyer = env.setup_buyer(&second, 100_000_000).await;
+    env.purchase(&second, &buyer, 30_000_000).await.unwrap();
+
+    let seller = first.seller.pubkey();
+    let ix = batch_cancel_ix(&env.program_id, &seller, &[&first, &second], false);
+    env.process(&[ix], &[&first.seller]).await.unwrap();
+
+    assert_eq!(env.token_balance(&first.seller_base).await, args.quantity);
+    assert_eq!(env.token_balance(&second.seller_base).await, args.quantity - 30_000_000);
+    assert_eq!(env.token_balance(&first.vault).await, 0);
+    assert_eq!(env.token_balance(&second.vault).await, 0);
+    assert_eq!(env.listing(&first.listing).await.status(), ListingStatus::Cancelled);
+    assert_eq!(env.listing(&second.listing).await.status(), ListingStatus::Cancelled);
+
+    let ix = batch_cancel_ix(&env.program_id, &seller, &[&second], false);
+    env.process(&[ix], &[&first.seller]).await.unwrap();
+    assert_eq!(env.listing(&second.listing).await.status(), ListingStatus::Cancelled);
+}
+
+/// `BatchCancel` refuses a look-alike vault that is not the vault authority's ATA.
+#[tokio::test]
+async fn test_batch_cancel_rejects_look_alike_vault() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let fixture = env.setup_active_listing(args.clone()).await;
+
+    let account = env.ctx.banks_client.get_account(fixture.vault).await.unwrap().unwrap();
+    let look_alike = Pubkey::new_unique();
+    env.ctx.set_account(&look_alike, &account.into());
+
+    let mut ix = batch_cancel_ix(&env.program_id, &fixture.seller.pubkey(), &[&fixture], false);
+    ix.accounts[4] = AccountMeta::new(look_alike, false);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+
+    assert_eq!(custom_error_code(err), Some(EscrowError::MintMismatch as u32));
+    assert_eq!(env.token_balance(&fixture.vault).await, args.quantity);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Active);
+}
+
+/// Counted listings each take the seller stats PDA after their accounts, and
+/// the batch frees every slot; a retry skips the closed and cancelled ones.
+#[tokio::test]
+async fn test_batch_cancel_frees_counted_slots() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    env.set_max_active_listings(&admin, 2).await.unwrap();
+
+    let seller = env.funded_keypair(1_000_000_000).await;
+    let args = ListingArgs {
+        price_per_token: 10_000,
+        quantity: 100_000,
+        treasury: Some(treasury),
+        seller_stats: true,
+        ..ListingArgs::default()
+    };
+    let first = env.setup_listing_for(seller.insecure_clone(), args.clone()).await;
+    env.deposit(&first).await.unwrap();
+    let (stats, _) = find_seller_stats_address(&env.program_id, &seller.pubkey());
+    let second = env.setup_listing_for(seller.insecure_clone(), ListingArgs { listing_id: 2, ..args.clone() }).await;
+
+    let ix = batch_cancel_ix(&env.program_id, &seller.pubkey(), &[&first, &second], true);
+    env.process(&[ix], &[&seller]).await.unwrap();
+
+    let account = env.ctx.banks_client.get_account(stats).await.unwrap().unwrap();
+    assert_eq!(SellerStats::try_from_slice(&account.data).unwrap().active_listings, 0);
+    assert_eq!(env.token_balance(&first.seller_base).await, args.quantity);
+    assert_eq!(env.listing(&first.listing).await.status(), ListingStatus::Cancelled);
+    assert!(env.ctx.banks_client.get_account(second.listing).await.unwrap().is_none());
+
+    env.refresh_blockhash().await;
+    let ix = batch_cancel_ix(&env.program_id, &seller.pubkey(), &[&first, &second], true);
+    env.process(&[ix], &[&seller]).await.unwrap();
+}
@@ -0,0 +1,237 @@
+//! Tests for the claim queue and pro-rata settlement.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{custom_error_code, Buyer, ListingArgs, ListingFixture, TestEnv};
+use escrow_program::{
+    find_claim_address, find_fills_ledger_address, EscrowError, EscrowInstruction, ListingStatus, CLAIM_WINDOW_SECS,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_option::COption,
+    program_pack::Pack,
+    system_program,
+};
+use solana_sdk::signature::Signer;
+use spl_token::state::Account as TokenAccount;
+
+fn register_claim_ix(env: &TestEnv, fixture: &ListingFixture, buyer: &Buyer, desired: u64) -> Instruction {
+    let buyer_key = buyer.keypair.pubkey();
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new(buyer_key, true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new_readonly(fixture.vault_authority, false),
+            AccountMeta::new(find_claim_address(&env.program_id, &fixture.listing, &buyer_key).0, false),
+            AccountMeta::new(buyer.quote_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::RegisterClaim { desired }.try_to_vec().unwrap(),
+    }
+}
+
+fn close_claim_ix(env: &TestEnv, fixture: &ListingFixture, buyer: &Buyer) -> Instruction {
+    let buyer_key = buyer.keypair.pubkey();
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new(buyer_key, true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new(find_claim_address(&env.program_id, &fixture.listing, &buyer_key).0, false),
+            AccountMeta::new(buyer.quote_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: EscrowInstruction::CloseClaim.try_to_vec().unwrap(),
+    }
+}
+
+fn settle_claims_ix(env: &TestEnv, fixture: &ListingFixture, buyers: &[&Buyer]) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(fixture.seller.pubkey(), true),
+        AccountMeta::new(fixture.listing, false),
+        AccountMeta::new_readonly(fixture.vault_authority, false),
+        AccountMeta::new(fixture.vault, false),
+        AccountMeta::new(fixture.seller_quote, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(find_fills_ledger_address(&env.program_id, &fixture.listing).0, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+    ];
+    for buyer in buyers {
+        let buyer_key = buyer.keypair.pubkey();
+        accounts.push(AccountMeta::new(
+            find_claim_address(&env.program_id, &fixture.listing, &buyer_key).0,
+            false,
+        ));
+        accounts.push(AccountMeta::new(buyer_key, false));
+        accounts.push(AccountMeta::new(buyer.quote_account, false));
+        accounts.push(AccountMeta::new(buyer.base_account, false));
+    }
+    Instruction {
+        program_id: env.program_id,
+        accounts,
+        data: EscrowInstruction::SettleClaims.try_to_vec().unwrap(),
+    }
+}
+
+/// Oversubscribed claims split the remaining quantity pro rata, the rounding
+/// unit going to the last claim settled, and each buyer pays for what they receive.
+#[tokio::test]
+async fn test_settle_oversubscribed_claims_pro_rata() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let first = env.setup_buyer(&fixture, 100_000_000).await;
+    let second = env.setup_buyer(&fixture, 100_000_000).await;
+
+    // 150 tokens wanted against 100 available.
+    let ix = register_claim_ix(&env, &fixture, &first, 50_000_000);
+    env.process(&[ix], &[&first.keypair]).await.unwrap();
+    let ix = register_claim_ix(&env, &fixture, &second, 100_000_000);
+    env.process(&[ix], &[&second.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&first.quote_account).await, 100_000_000);
+
+    env.advance_clock(CLAIM_WINDOW_SECS).await;
+    let ix = settle_claims_ix(&env, &fixture, &[&first, &second]);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    assert_eq!(env.token_balance(&first.base_account).await, 33_333_333);
+    assert_eq!(env.token_balance(&second.base_account).await, 66_666_667);
+    assert_eq!(env.token_balance(&first.quote_account).await, 100_000_000 - 33_333_333);
+    assert_eq!(env.token_balance(&second.quote_account).await, 100_000_000 - 66_666_667);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 100_000_000);
+
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!(listing.status(), ListingStatus::Completed);
+    assert_eq!(listing.claim_count, 0);
+    let (first_claim, _) = find_claim_address(&env.program_id, &fixture.listing, &first.keypair.pubkey());
+    assert!(env.ctx.banks_client.get_account(first_claim).await.unwrap().is_none());
+}
+
+/// Settlement waits for registration to close, and a claim settled on its own
+/// still gets its share of the whole round rather than of the claims passed.
+#[tokio::test]
+async fn test_claims_settle_in_window_against_round_total() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let first = env.setup_buyer(&fixture, 100_000_000).await;
+    let second = env.setup_buyer(&fixture, 100_000_000).await;
+    let late = env.setup_buyer(&fixture, 100_000_000).await;
+
+    let ix = register_claim_ix(&env, &fixture, &first, 60_000_000);
+    env.process(&[ix], &[&first.keypair]).await.unwrap();
+    let ix = register_claim_ix(&env, &fixture, &second, 140_000_000);
+    env.process(&[ix], &[&second.keypair]).await.unwrap();
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!((listing.claim_count, listing.claims_desired), (2, 200_000_000));
+
+    let ix = settle_claims_ix(&env, &fixture, &[&first]);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::ClaimWindowOpen as u32));
+
+    env.advance_clock(CLAIM_WINDOW_SECS).await;
+    let ix = register_claim_ix(&env, &fixture, &late, 10_000_000);
+    let err = env.process(&[ix], &[&late.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::ClaimWindowClosed as u32));
+
+    // 60 of 200 wanted against 100 available: 30, not all 60.
+    let ix = settle_claims_ix(&env, &fixture, &[&first]);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    assert_eq!(env.token_balance(&first.base_account).await, 30_000_000);
+    let ix = settle_claims_ix(&env, &fixture, &[&second]);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    assert_eq!(env.token_balance(&second.base_account).await, 70_000_000);
+
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!(listing.status(), ListingStatus::Completed);
+    assert_eq!((listing.claim_count, listing.claims_desired, listing.claims_supply), (0, 0, 0));
+}
+
+/// Claims on a cancelled listing can be closed by their buyer, which revokes
+/// the vault authority's delegation and returns the claim rent.
+#[tokio::test]
+async fn test_close_claim_on_cancelled_listing() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let ix = register_claim_ix(&env, &fixture, &buyer, 40_000_000);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    env.cancel(&fixture).await.unwrap();
+
+    let buyer_lamports = env.lamports(&buyer.keypair.pubkey()).await;
+    let (claim, _) = find_claim_address(&env.program_id, &fixture.listing, &buyer.keypair.pubkey());
+    let claim_rent = env.lamports(&claim).await;
+    let ix = close_claim_ix(&env, &fixture, &buyer);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+
+    assert!(env.ctx.banks_client.get_account(claim).await.unwrap().is_none());
+    let quote_account = env.ctx.banks_client.get_account(buyer.quote_account).await.unwrap().unwrap();
+    let quote_account = TokenAccount::unpack(&quote_account.data).unwrap();
+    assert!(quote_account.delegate.is_none());
+    assert_eq!(quote_account.delegated_amount, 0);
+    assert_eq!(env.token_balance(&buyer.quote_account).await, 100_000_000);
+    assert_eq!(env.lamports(&buyer.keypair.pubkey()).await, buyer_lamports + claim_rent);
+}
+
+/// A buyer's quote account holds one delegate, so a claim on a second listing
+/// in the same quote mint is refused instead of voiding the first approval.
+#[tokio::test]
+async fn test_register_claim_rejects_quote_account_with_other_delegate() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let other = env
+        .setup_active_listing(ListingArgs {
+            listing_id: 2,
+            quote_mint: Some(fixture.quote_mint),
+            ..ListingArgs::default()
+        })
+        .await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let ix = register_claim_ix(&env, &fixture, &buyer, 40_000_000);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+
+    let other_buyer = Buyer {
+        keypair: buyer.keypair.insecure_clone(),
+        quote_account: buyer.quote_account,
+        base_account: env.create_ata(&buyer.keypair.pubkey(), &other.base_mint).await,
+    };
+    let ix = register_claim_ix(&env, &other, &other_buyer, 10_000_000);
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::QuoteDelegateInUse as u32));
+
+    let quote_account = env.ctx.banks_client.get_account(buyer.quote_account).await.unwrap().unwrap();
+    let quote_account = TokenAccount::unpack(&quote_account.data).unwrap();
+    assert_eq!(quote_account.delegate, COption::Some(fixture.vault_authority));
+    assert_eq!(quote_account.delegated_amount, 40_000_000);
+}
+
+/// A claim whose approval was revoked after registering is closed unfilled,
+/// and the rest of the batch still settles.
+#[tokio::test]
+async fn test_settle_skips_claim_without_approval() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let first = env.setup_buyer(&fixture, 100_000_000).await;
+    let second = env.setup_buyer(&fixture, 100_000_000).await;
+    let ix = register_claim_ix(&env, &fixture, &first, 40_000_000);
+    env.process(&[ix], &[&first.keypair]).await.unwrap();
+    let ix = register_claim_ix(&env, &fixture, &second, 40_000_000);
+    env.process(&[ix], &[&second.keypair]).await.unwrap();
+
+    let revoke_ix =
+        spl_token::instruction::revoke(&spl_token::id(), &first.quote_account, &first.keypair.pubkey(), &[]).unwrap();
+    env.process(&[revoke_ix], &[&first.keypair]).await.unwrap();
+
+    env.advance_clock(CLAIM_WINDOW_SECS).await;
+    let ix = settle_claims_ix(&env, &fixture, &[&first, &second]);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    assert_eq!(env.token_balance(&first.base_account).await, 0);
+    assert_eq!(env.token_balance(&first.quote_account).await, 100_000_000);
+    assert_eq!(env.token_balance(&second.base_account).await, 40_000_000);
+    let (first_claim, _) = find_claim_address(&env.program_id, &fixture.listing, &first.keypair.pubkey());
+    assert!(env.ctx.banks_client.get_account(first_claim).await.unwrap().is_none());
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!((listing.filled, listing.claim_count), (40_000_000, 0));
+}
@@ -0,0 +1,79 @@
+//! Tests for commit-reveal purchases.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{custom_error_code, Buyer, ListingArgs, ListingFixture, TestEnv};
+use escrow_program::{
+    find_purchase_commitment_address, purchase_commitment_hash, EscrowError, EscrowInstruction, MIN_REVEAL_DELAY,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    system_program,
+};
+use solana_sdk::signature::Signer;
+
+fn commit_ix(env: &TestEnv, fixture: &ListingFixture, buyer: &Buyer, commitment: [u8; 32]) -> Instruction {
+    let buyer_key = buyer.keypair.pubkey();
+    let (pda, _) = find_purchase_commitment_address(&env.program_id, &fixture.listing, &buyer_key);
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new(buyer_key, true),
+            AccountMeta::new_readonly(fixture.listing, false),
+            AccountMeta::new(pda, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::CommitPurchase { commitment }.try_to_vec().unwrap(),
+    }
+}
+
+fn reveal_ix(env: &TestEnv, fixture: &ListingFixture, buyer: &Buyer, quantity: u64, nonce: u64) -> Instruction {
+    let (pda, _) = find_purchase_commitment_address(&env.program_id, &fixture.listing, &buyer.keypair.pubkey());
+    let mut ix = common::purchase_ix(&env.program_id, fixture, buyer, quantity);
+    ix.accounts.insert(0, AccountMeta::new(pda, false));
+    ix.data = EscrowInstruction::RevealPurchase { quantity, nonce }.try_to_vec().unwrap();
+    ix
+}
+
+/// A reveal matching the commitment executes the purchase after the delay and closes the PDA.
+#[tokio::test]
+async fn test_commit_then_reveal_purchases() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let commitment = purchase_commitment_hash(&buyer.keypair.pubkey(), 10_000_000, 42);
+
+    let ix = commit_ix(&env, &fixture, &buyer, commitment);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+
+    let ix = reveal_ix(&env, &fixture, &buyer, 10_000_000, 42);
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::RevealTooEarly as u32));
+
+    env.advance_clock(MIN_REVEAL_DELAY).await;
+    let ix = reveal_ix(&env, &fixture, &buyer, 10_000_000, 42);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&buyer.base_account).await, 10_000_000);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 10_000_000);
+    let (pda, _) = find_purchase_commitment_address(&env.program_id, &fixture.listing, &buyer.keypair.pubkey());
+    assert!(env.ctx.banks_client.get_account(pda).await.unwrap().is_none());
+}
+
+/// Revealing a different quantity than was committed is rejected.
+#[tokio::test]
+async fn test_reveal_rejects_mismatched_quantity() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let commitment = purchase_commitment_hash(&buyer.keypair.pubkey(), 10_000_000, 42);
+
+    let ix = commit_ix(&env, &fixture, &buyer, commitment);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    env.advance_clock(MIN_REVEAL_DELAY).await;
+
+    let ix = reveal_ix(&env, &fixture, &buyer, 20_000_000, 42);
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidCommitment as u32));
+    assert_eq!(env.token_balance(&buyer.base_account).await, 0);
+}
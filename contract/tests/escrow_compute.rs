@@ -0,0 +1,54 @@
+//! Compute budget checks for the hot-path handlers.
+//!
+//! The native test processor meters only the CPIs and syscalls a handler
+//! makes, not its own logic, so these are not on-chain costs. The budgets are
+//! usage measured under that runner plus headroom: a new CPI, log line or
+//! syscall on these paths fails here, while a slower handler body does not.
+
+mod common;
+
+use common::{ListingArgs, ListingFixture};
+use solana_sdk::signature::Signer;
+use spl_associated_token_account::get_associated_token_address;
+
+/// Ceiling for a plain `Purchase` (quote transfer, base transfer, fills ledger).
+const PURCHASE_CU_BUDGET: u64 = 12_000;
+/// Ceiling for a plain `InitializeListing` without a config.
+const INITIALIZE_CU_BUDGET: u64 = 200;
+
+/// A partial fill of a simple listing stays under `PURCHASE_CU_BUDGET`.
+#[tokio::test]
+async fn test_purchase_stays_within_compute_budget() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    let ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 10_000_000);
+    let units = env.units_consumed(&[ix], &[&buyer.keypair]).await;
+    assert!(units <= PURCHASE_CU_BUDGET, "Purchase used {units} CU, budget {PURCHASE_CU_BUDGET}");
+}
+
+/// Creating a listing without a config stays under `INITIALIZE_CU_BUDGET`.
+#[tokio::test]
+async fn test_initialize_stays_within_compute_budget() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+
+    // A second listing for the same seller and mints, simulated rather than processed.
+    let args = ListingArgs { listing_id: 2, ..ListingArgs::default() };
+    let seller = fixture.seller.insecure_clone();
+    let listing = env.create_listing_account(&seller).await;
+    let (vault_authority, _) = common::vault_authority_address(&env.program_id, &seller.pubkey(), args.listing_id);
+    let next = ListingFixture {
+        seller,
+        listing: listing.pubkey(),
+        listing_id: args.listing_id,
+        vault_authority,
+        vault: get_associated_token_address(&vault_authority, &fixture.base_mint),
+        ..fixture
+    };
+
+    let ix = common::initialize_ix(&env.program_id, &next, &args);
+    let units = env.units_consumed(&[ix], &[&next.seller]).await;
+    assert!(units <= INITIALIZE_CU_BUDGET, "InitializeListing used {units} CU, budget {INITIALIZE_CU_BUDGET}");
+}
@@ -0,0 +1,84 @@
+//! Tests for handing over the config admin role.
+
+mod common;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use common::{custom_error_code, TestEnv};
+use escrow_program::{find_config_address, Config, EscrowError, EscrowInstruction};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+fn propose_ix(env: &TestEnv, admin: &Pubkey, new_admin: Pubkey) -> Instruction {
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(find_config_address(&env.program_id).0, false),
+        ],
+        data: EscrowInstruction::ProposeAdmin { new_admin }.try_to_vec().unwrap(),
+    }
+}
+
+fn accept_ix(env: &TestEnv, new_admin: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*new_admin, true),
+            AccountMeta::new(find_config_address(&env.program_id).0, false),
+        ],
+        data: EscrowInstruction::AcceptAdmin.try_to_vec().unwrap(),
+    }
+}
+
+async fn config(env: &mut TestEnv) -> Config {
+    let address = find_config_address(&env.program_id).0;
+    let account = env.ctx.banks_client.get_account(address).await.unwrap().unwrap();
+    Config::try_from_slice(&account.data).unwrap()
+}
+
+/// The proposed admin takes over only once they accept; the old admin then loses access.
+#[tokio::test]
+async fn test_propose_then_accept_admin() {
+    let mut env = common::start().await;
+    let admin = env.initialize_config(Pubkey::new_unique()).await;
+    let successor = Keypair::new();
+
+    let ix = propose_ix(&env, &admin.pubkey(), successor.pubkey());
+    env.process(&[ix], &[&admin]).await.unwrap();
+    let pending = config(&mut env).await;
+    assert_eq!(pending.admin, admin.pubkey());
+    assert_eq!(pending.pending_admin, successor.pubkey());
+
+    let ix = accept_ix(&env, &successor.pubkey());
+    env.process(&[ix], &[&successor]).await.unwrap();
+    let accepted = config(&mut env).await;
+    assert_eq!(accepted.admin, successor.pubkey());
+    assert_eq!(accepted.pending_admin, Pubkey::default());
+
+    let err = env.set_max_active_listings(&admin, 3).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::IncorrectAuthority as u32));
+    env.set_max_active_listings(&successor, 3).await.unwrap();
+}
+
+/// Only the proposed key may accept, and only the admin may propose.
+#[tokio::test]
+async fn test_accept_by_other_key_rejected() {
+    let mut env = common::start().await;
+    let admin = env.initialize_config(Pubkey::new_unique()).await;
+    let successor = Keypair::new();
+    let intruder = Keypair::new();
+
+    let ix = propose_ix(&env, &intruder.pubkey(), intruder.pubkey());
+    let err = env.process(&[ix], &[&intruder]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::IncorrectAuthority as u32));
+
+    let ix = propose_ix(&env, &admin.pubkey(), successor.pubkey());
+    env.process(&[ix], &[&admin]).await.unwrap();
+    let ix = accept_ix(&env, &intruder.pubkey());
+    let err = env.process(&[ix], &[&intruder]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::IncorrectAuthority as u32));
+    assert_eq!(config(&mut env).await.admin, admin.pubkey());
+}
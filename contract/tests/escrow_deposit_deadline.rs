@@ -0,0 +1,78 @@
+//! Tests for deposit deadlines and expiring listings that were never funded.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{custom_error_code, ListingArgs, ListingFixture, TestEnv};
+use escrow_program::{EscrowError, EscrowInstruction, ListingStatus};
+use solana_program::{
+    clock::Clock,
+    instruction::{AccountMeta, Instruction},
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+fn expire_ix(env: &TestEnv, fixture: &ListingFixture, caller: &Keypair) -> Instruction {
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(caller.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new(fixture.seller.pubkey(), false),
+        ],
+        data: EscrowInstruction::ExpireListing.try_to_vec().unwrap(),
+    }
+}
+
+/// A listing whose deposit deadline is 100 seconds away.
+async fn setup_deadline_listing(env: &mut TestEnv) -> ListingFixture {
+    let clock: Clock = env.ctx.banks_client.get_sysvar().await.unwrap();
+    env.setup_listing(ListingArgs {
+        deposit_deadline: clock.unix_timestamp + 100,
+        ..ListingArgs::default()
+    })
+    .await
+}
+
+/// Funding before the deadline activates the listing as usual.
+#[tokio::test]
+async fn test_deposit_before_deadline() {
+    let mut env = common::start().await;
+    let fixture = setup_deadline_listing(&mut env).await;
+
+    env.advance_clock(50).await;
+    env.deposit(&fixture).await.unwrap();
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Active);
+}
+
+/// After the deadline the listing can no longer be funded.
+#[tokio::test]
+async fn test_deposit_after_deadline_rejected() {
+    let mut env = common::start().await;
+    let fixture = setup_deadline_listing(&mut env).await;
+
+    env.advance_clock(101).await;
+    let err = env.deposit(&fixture).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::DepositDeadlinePassed as u32));
+    assert_eq!(env.token_balance(&fixture.seller_base).await, ListingArgs::default().quantity);
+}
+
+/// Anyone may close an expired unfunded listing; the rent goes back to the seller.
+#[tokio::test]
+async fn test_expire_unfunded_listing_after_deadline() {
+    let mut env = common::start().await;
+    let fixture = setup_deadline_listing(&mut env).await;
+    let caller = env.funded_keypair(1_000_000_000).await;
+
+    let ix = expire_ix(&env, &fixture, &caller);
+    let err = env.process(&[ix], &[&caller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::DepositDeadlineNotReached as u32));
+
+    env.advance_clock(101).await;
+    let rent = env.lamports(&fixture.listing).await;
+    let seller_before = env.lamports(&fixture.seller.pubkey()).await;
+    let ix = expire_ix(&env, &fixture, &caller);
+    env.process(&[ix], &[&caller]).await.unwrap();
+
+    assert!(env.ctx.banks_client.get_account(fixture.listing).await.unwrap().is_none());
+    assert_eq!(env.lamports(&fixture.seller.pubkey()).await, seller_before + rent);
+}
@@ -0,0 +1,41 @@
+//! Tests for the `display_price` helper used by off-chain UIs.
+
+use escrow_program::display_price;
+
+/// 1.25 USDC (6 decimals) per token reduces to 5/4 whatever the base decimals.
+#[test]
+fn test_display_price_reduces_fraction() {
+    assert_eq!(display_price(1_250_000, 6, 6), (5, 4));
+    assert_eq!(display_price(1_250_000, 9, 6), (5, 4));
+    assert_eq!(display_price(1_250_000, 0, 6), (5, 4));
+}
+
+/// Whole-number prices come back over a denominator of one.
+#[test]
+fn test_display_price_whole_numbers() {
+    assert_eq!(display_price(2_000_000, 6, 6), (2, 1));
+    assert_eq!(display_price(7, 6, 0), (7, 1));
+    assert_eq!(display_price(3_000_000_000, 6, 9), (3, 1));
+}
+
+/// Prices below one quote unit keep every significant digit.
+#[test]
+fn test_display_price_fractional_prices() {
+    // 0.000001 USDC per token.
+    assert_eq!(display_price(1, 6, 6), (1, 1_000_000));
+    // 0.333 with 3 quote decimals.
+    assert_eq!(display_price(333, 9, 3), (333, 1_000));
+    // 0.000000125 SOL (9 decimals) per token.
+    assert_eq!(display_price(125, 2, 9), (1, 8_000_000));
+}
+
+/// Zero prices and extreme quote decimals stay well-formed.
+#[test]
+fn test_display_price_edge_cases() {
+    assert_eq!(display_price(0, 6, 6), (0, 1));
+    assert_eq!(display_price(u64::MAX, 0, 0), (u64::MAX, 1));
+    // 19 decimals is the largest power of ten a u64 denominator holds.
+    assert_eq!(display_price(1, 0, 19), (1, 10_000_000_000_000_000_000));
+    // Beyond that, the numerator loses the excess precision.
+    assert_eq!(display_price(123, 0, 20), (3, 2_500_000_000_000_000_000));
+}
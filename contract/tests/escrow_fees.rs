@@ -0,0 +1,510 @@
+//! Tests for listing fee collection and treasury routing.
+
+mod common;
+
+use common::{custom_error_code, instruction_error, ListingArgs, ListingFixture};
+use escrow_program::{find_fee_escrow_address, EscrowError, FeePaymentMethod, ListingStatus};
+use solana_program::{
+    instruction::{AccountMeta, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::Mint;
+use solana_sdk::signature::Signer;
+
+/// NativeSol fees land in the treasury configured for each listing's quote mint.
+#[tokio::test]
+async fn test_fees_route_to_per_quote_mint_treasuries() {
+    let mut env = common::start().await;
+    let default_treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(default_treasury).await;
+
+    let usdc = env.create_mint(6).await;
+    let usdt = env.create_mint(6).await;
+    let usdc_treasury = Pubkey::new_unique();
+    let usdt_treasury = Pubkey::new_unique();
+    env.set_treasury(&admin, &usdc, usdc_treasury).await.unwrap();
+    env.set_treasury(&admin, &usdt, usdt_treasury).await.unwrap();
+    env.set_native_fee_lamports(&admin, 20_000_000).await.unwrap();
+
+    let usdc_listing = env
+        .setup_listing(ListingArgs {
+            price_per_token: 10_000,
+            quantity: 200_000,
+            quote_mint: Some(usdc),
+            treasury: Some(usdc_treasury),
+            ..ListingArgs::default()
+        })
+        .await;
+    let usdt_listing = env
+        .setup_listing(ListingArgs {
+            price_per_token: 5_000,
+            quantity: 300_000,
+            quote_mint: Some(usdt),
+            treasury: Some(usdt_treasury),
+            ..ListingArgs::default()
+        })
+        .await;
+
+    assert_eq!(env.lamports(&usdc_treasury).await, 20_000_000);
+    assert_eq!(env.lamports(&usdt_treasury).await, 20_000_000);
+    assert_eq!(env.lamports(&default_treasury).await, 0);
+    assert_eq!(env.listing(&usdc_listing.listing).await.fee_treasury, usdc_treasury);
+    assert_eq!(env.listing(&usdt_listing.listing).await.fee_treasury, usdt_treasury);
+}
+
+/// The NativeSol fee is the config's flat lamport amount, not 1% of the quote
+/// value scaled by the base decimals.
+#[tokio::test]
+async fn test_native_sol_fee_is_flat_lamports() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    env.set_native_fee_lamports(&admin, 10_000_000).await.unwrap();
+    let treasury_before = env.lamports(&treasury).await;
+
+    // 100 tokens at 1 USDC each, both mints with 6 decimals.
+    let fixture = env
+        .setup_listing(ListingArgs {
+            price_per_token: 1_000_000,
+            quantity: 100_000_000,
+            base_decimals: 6,
+            quote_decimals: 6,
+            treasury: Some(treasury),
+            ..ListingArgs::default()
+        })
+        .await;
+
+    assert_eq!(env.lamports(&treasury).await - treasury_before, 10_000_000);
+    assert_eq!(env.listing(&fixture.listing).await.fee_amount_paid, 10_000_000);
+}
+
+/// A client that predates the config passes only the first seven accounts and
+/// creates its listing without a fee.
+#[tokio::test]
+async fn test_initialize_without_config_account_charges_no_fee() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    env.set_native_fee_lamports(&admin, 10_000_000).await.unwrap();
+
+    let seller = env.funded_keypair(1_000_000_000).await;
+    let base_mint = env.create_mint(6).await;
+    let quote_mint = env.create_mint(6).await;
+    let (vault_authority, _) = common::vault_authority_address(&env.program_id, &seller.pubkey(), 1);
+    let fixture = ListingFixture {
+        listing: env.create_listing_account(&seller).await.pubkey(),
+        listing_id: 1,
+        vault_authority,
+        vault: get_associated_token_address(&vault_authority, &base_mint),
+        base_mint,
+        quote_mint,
+        seller_base: get_associated_token_address(&seller.pubkey(), &base_mint),
+        seller_quote: get_associated_token_address(&seller.pubkey(), &quote_mint),
+        seller,
+    };
+    let mut ix = common::initialize_ix(&env.program_id, &fixture, &ListingArgs::default());
+    ix.accounts.truncate(7);
+    let seller_before = env.lamports(&fixture.seller.pubkey()).await;
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    assert_eq!(env.lamports(&treasury).await, 0);
+    assert_eq!(env.lamports(&fixture.seller.pubkey()).await, seller_before);
+    assert_eq!(env.listing(&fixture.listing).await.fee_treasury, Pubkey::default());
+}
+
+/// Quote mints without a dedicated route fall back to the default treasury.
+#[tokio::test]
+async fn test_fees_fall_back_to_default_treasury() {
+    let mut env = common::start().await;
+    let default_treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(default_treasury).await;
+    env.set_native_fee_lamports(&admin, 10_000_000).await.unwrap();
+
+    let fixture = env
+        .setup_listing(ListingArgs {
+            price_per_token: 10_000,
+            quantity: 100_000,
+            treasury: Some(default_treasury),
+            ..ListingArgs::default()
+        })
+        .await;
+
+    assert_eq!(env.lamports(&default_treasury).await, 10_000_000);
+    assert_eq!(env.listing(&fixture.listing).await.fee_treasury, default_treasury);
+}
+
+/// Only the config admin may route treasuries.
+#[tokio::test]
+async fn test_set_treasury_requires_admin() {
+    let mut env = common::start().await;
+    env.initialize_config(Pubkey::new_unique()).await;
+    let quote_mint = env.create_mint(6).await;
+
+    let impostor = env.funded_keypair(1_000_000_000).await;
+    let err = env
+        .set_treasury(&impostor, &quote_mint, Pubkey::new_unique())
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::IncorrectAuthority as u32));
+}
+
+/// Cancelling a partially filled listing refunds the unfilled share of an escrowed fee.
+#[tokio::test]
+async fn test_cancel_refunds_unearned_escrowed_fee() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    env.set_native_fee_lamports(&admin, 20_000_000).await.unwrap();
+
+    // The 20_000_000-lamport fee is held in escrow.
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            price_per_token: 10_000,
+            quantity: 200_000,
+            treasury: Some(treasury),
+            escrow_fee: true,
+            ..ListingArgs::default()
+        })
+        .await;
+    let (fee_escrow, _) = find_fee_escrow_address(&env.program_id, &fixture.listing);
+    assert_eq!(env.lamports(&treasury).await, 0);
+    let escrow_lamports = env.lamports(&fee_escrow).await;
+
+    let buyer = env.setup_buyer(&fixture, 1_000_000).await;
+    env.purchase(&fixture, &buyer, 50_000).await.unwrap();
+
+    let seller_before = env.lamports(&fixture.seller.pubkey()).await;
+    let mut ix = common::cancel_ix(&env.program_id, &fixture);
+    ix.accounts.push(AccountMeta::new(fee_escrow, false));
+    ix.accounts.push(AccountMeta::new(treasury, false));
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    // A quarter was filled, so the treasury keeps a quarter of the fee and the
+    // seller gets the rest back along with the escrow rent.
+    assert_eq!(env.lamports(&treasury).await, 5_000_000);
+    assert_eq!(
+        env.lamports(&fixture.seller.pubkey()).await,
+        seller_before + escrow_lamports - 5_000_000
+    );
+    assert_eq!(env.lamports(&fee_escrow).await, 0);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Cancelled);
+}
+
+/// An exempt seller lists without paying a fee while other sellers are still charged.
+#[tokio::test]
+async fn test_fee_exempt_seller_pays_no_fee() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+
+    let partner = env.funded_keypair(1_000_000_000).await;
+    env.add_fee_exempt(&admin, partner.pubkey()).await.unwrap();
+    env.set_native_fee_lamports(&admin, 10_000_000).await.unwrap();
+
+    let args = ListingArgs {
+        price_per_token: 10_000,
+        quantity: 100_000,
+        treasury: Some(treasury),
+        ..ListingArgs::default()
+    };
+    let exempt = env.setup_listing_for(partner, args.clone()).await;
+    assert_eq!(env.lamports(&treasury).await, 0);
+    assert_eq!(env.listing(&exempt.listing).await.fee_amount_paid, 0);
+
+    let normal = env.setup_listing(args).await;
+    assert_eq!(env.lamports(&treasury).await, 10_000_000);
+    assert_eq!(env.listing(&normal.listing).await.fee_amount_paid, 10_000_000);
+}
+
+/// `SplToken` fees are the config's flat amount of its fee mint, paid into the
+/// treasury's token account whatever the listing's quote value.
+#[tokio::test]
+async fn test_fee_paid_in_spl_token() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    let fee_mint = env.create_mint(6).await;
+    env.set_fee_mint(&admin, &fee_mint).await.unwrap();
+    env.set_spl_fee_amount(&admin, 4_000_000).await.unwrap();
+
+    let seller = env.funded_keypair(1_000_000_000).await;
+    let seller_fee_account = env.create_ata(&seller.pubkey(), &fee_mint).await;
+    env.mint_to(&fee_mint, &seller_fee_account, 50_000_000).await;
+    let treasury_fee_account = env.create_ata(&treasury, &fee_mint).await;
+
+    // Flat 4_000_000 fee-token units, not 1% of the 1_000_000_000 quote value.
+    let fixture = env
+        .setup_listing_for(
+            seller,
+            ListingArgs {
+                price_per_token: 10_000,
+                quantity: 100_000,
+                fee_payment_method: FeePaymentMethod::SplToken.as_u8(),
+                treasury: Some(treasury),
+                fee_token_accounts: Some((seller_fee_account, treasury_fee_account)),
+                ..ListingArgs::default()
+            },
+        )
+        .await;
+
+    assert_eq!(env.token_balance(&treasury_fee_account).await, 4_000_000);
+    assert_eq!(env.token_balance(&seller_fee_account).await, 46_000_000);
+    assert_eq!(env.lamports(&treasury).await, 0);
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!(listing.fee_amount_paid, 4_000_000);
+    assert_eq!(listing.fee_mint, fee_mint);
+}
+
+/// With `burn_bps` set, the burned share of an `SplToken` fee leaves the mint's supply.
+#[tokio::test]
+async fn test_spl_token_fee_burn_reduces_supply() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    let fee_mint = env.create_mint(6).await;
+    env.set_fee_mint(&admin, &fee_mint).await.unwrap();
+    env.set_spl_fee_amount(&admin, 10_000_000).await.unwrap();
+    env.set_burn_bps(&admin, 2_500).await.unwrap();
+
+    let seller = env.funded_keypair(1_000_000_000).await;
+    let seller_fee_account = env.create_ata(&seller.pubkey(), &fee_mint).await;
+    env.mint_to(&fee_mint, &seller_fee_account, 50_000_000).await;
+    let treasury_fee_account = env.create_ata(&treasury, &fee_mint).await;
+
+    // A quarter of the 10_000_000 fee is burned.
+    let fixture = env
+        .setup_listing_for(
+            seller,
+            ListingArgs {
+                price_per_token: 10_000,
+                quantity: 100_000,
+                fee_payment_method: FeePaymentMethod::SplToken.as_u8(),
+                treasury: Some(treasury),
+                fee_token_accounts: Some((seller_fee_account, treasury_fee_account)),
+                burn_account: Some(fee_mint),
+                ..ListingArgs::default()
+            },
+        )
+        .await;
+
+    assert_eq!(env.token_balance(&treasury_fee_account).await, 7_500_000);
+    assert_eq!(env.token_balance(&seller_fee_account).await, 40_000_000);
+    let mint_account = env.ctx.banks_client.get_account(fee_mint).await.unwrap().unwrap();
+    assert_eq!(Mint::unpack(&mint_account.data).unwrap().supply, 47_500_000);
+    assert_eq!(env.listing(&fixture.listing).await.fee_amount_paid, 10_000_000);
+}
+
+/// An `SplToken` fee needs a flat amount configured and goes through the real
+/// SPL Token program only.
+#[tokio::test]
+async fn test_spl_token_fee_requires_amount_and_token_program() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    let fee_mint = env.create_mint(6).await;
+    env.set_fee_mint(&admin, &fee_mint).await.unwrap();
+
+    let seller = env.funded_keypair(1_000_000_000).await;
+    let seller_fee_account = env.create_ata(&seller.pubkey(), &fee_mint).await;
+    env.mint_to(&fee_mint, &seller_fee_account, 50_000_000).await;
+    let treasury_fee_account = env.create_ata(&treasury, &fee_mint).await;
+    let args = ListingArgs {
+        price_per_token: 10_000,
+        quantity: 100_000,
+        fee_payment_method: FeePaymentMethod::SplToken.as_u8(),
+        treasury: Some(treasury),
+        fee_token_accounts: Some((seller_fee_account, treasury_fee_account)),
+        ..ListingArgs::default()
+    };
+    // A NativeSol listing sets up the seller's mints; the SplToken one reuses them.
+    let native_args = ListingArgs {
+        fee_payment_method: FeePaymentMethod::NativeSol.as_u8(),
+        fee_token_accounts: None,
+        ..args.clone()
+    };
+    let native = env.setup_listing_for(seller, native_args).await;
+    let (vault_authority, _) = common::vault_authority_address(&env.program_id, &native.seller.pubkey(), 2);
+    let fixture = ListingFixture {
+        listing: env.create_listing_account(&native.seller).await.pubkey(),
+        listing_id: 2,
+        vault_authority,
+        vault: get_associated_token_address(&vault_authority, &native.base_mint),
+        ..native
+    };
+    let args = ListingArgs { listing_id: 2, ..args };
+
+    let ix = common::initialize_ix(&env.program_id, &fixture, &args);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidConfig as u32));
+
+    env.set_spl_fee_amount(&admin, 4_000_000).await.unwrap();
+    let mut ix = common::initialize_ix(&env.program_id, &fixture, &args);
+    let token_program = ix.accounts.len() - 1;
+    ix.accounts[token_program] = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(instruction_error(err), Some(InstructionError::IncorrectProgramId));
+    assert_eq!(env.token_balance(&seller_fee_account).await, 50_000_000);
+}
+
+/// `burn_bps` above 10_000 is rejected.
+#[tokio::test]
+async fn test_set_burn_bps_rejects_over_full_fee() {
+    let mut env = common::start().await;
+    let admin = env.initialize_config(Pubkey::new_unique()).await;
+    let err = env.set_burn_bps(&admin, 10_001).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidBasisPoints as u32));
+}
+
+/// A taker fee is charged on top of the fill and paid to the treasury's quote account.
+#[tokio::test]
+async fn test_taker_fee_paid_by_buyer_to_treasury() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    env.initialize_config(treasury).await;
+
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            price_per_token: 10_000,
+            quantity: 100_000,
+            treasury: Some(treasury),
+            taker_fee_bps: 200,
+            ..ListingArgs::default()
+        })
+        .await;
+    let treasury_quote = env.create_ata(&treasury, &fixture.quote_mint).await;
+
+    // 50_000 base units cost 500 quote, plus a 2% taker fee of 10.
+    let buyer = env.setup_buyer(&fixture, 510).await;
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 50_000);
+    ix.accounts.push(AccountMeta::new(treasury_quote, false));
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 500);
+    assert_eq!(env.token_balance(&treasury_quote).await, 10);
+    assert_eq!(env.token_balance(&buyer.quote_account).await, 0);
+    assert_eq!(env.token_balance(&buyer.base_account).await, 50_000);
+}
+
+/// Cancelling an active listing nobody bought from pays the cancellation fee to the treasury.
+#[tokio::test]
+async fn test_cancel_unfilled_listing_charges_cancellation_fee() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    env.set_cancellation_fee(&admin, 5_000_000).await.unwrap();
+
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            price_per_token: 10_000,
+            quantity: 100_000,
+            treasury: Some(treasury),
+            ..ListingArgs::default()
+        })
+        .await;
+    assert_eq!(env.lamports(&treasury).await, 0);
+
+    // Leaving out the fee accounts does not dodge the fee.
+    let err = env.cancel(&fixture).await.unwrap_err();
+    assert_eq!(common::instruction_error(err), Some(InstructionError::NotEnoughAccountKeys));
+
+    let mut ix = common::cancel_ix(&env.program_id, &fixture);
+    ix.accounts.push(AccountMeta::new(treasury, false));
+    ix.accounts.push(AccountMeta::new_readonly(system_program::ID, false));
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    assert_eq!(env.lamports(&treasury).await, 5_000_000);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Cancelled);
+}
+
+/// A cancel authority cannot make the seller pay the cancellation fee without
+/// the seller's co-signature, and never pays it itself.
+#[tokio::test]
+async fn test_cancel_authority_needs_seller_to_pay_cancellation_fee() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    env.set_cancellation_fee(&admin, 5_000_000).await.unwrap();
+
+    let agent = env.funded_keypair(1_000_000_000).await;
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            price_per_token: 10_000,
+            quantity: 100_000,
+            treasury: Some(treasury),
+            cancel_authority: Some(agent.pubkey()),
+            ..ListingArgs::default()
+        })
+        .await;
+    let mut ix = common::cancel_ix(&env.program_id, &fixture);
+    ix.accounts[0] = AccountMeta::new(agent.pubkey(), true);
+    ix.accounts.push(AccountMeta::new(fixture.seller.pubkey(), false));
+    ix.accounts.push(AccountMeta::new(treasury, false));
+    ix.accounts.push(AccountMeta::new_readonly(system_program::ID, false));
+
+    let err = env.process(&[ix.clone()], &[&agent]).await.unwrap_err();
+    assert_eq!(common::instruction_error(err), Some(InstructionError::MissingRequiredSignature));
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Active);
+
+    ix.accounts[6] = AccountMeta::new(fixture.seller.pubkey(), true);
+    let agent_before = env.lamports(&agent.pubkey()).await;
+    let seller_before = env.lamports(&fixture.seller.pubkey()).await;
+    env.process(&[ix], &[&agent, &fixture.seller]).await.unwrap();
+
+    assert_eq!(env.lamports(&treasury).await, 5_000_000);
+    assert_eq!(env.lamports(&agent.pubkey()).await, agent_before);
+    assert_eq!(env.lamports(&fixture.seller.pubkey()).await, seller_before - 5_000_000);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Cancelled);
+}
+
+/// A listing keeps the cancellation fee in force when it was created, and owes
+/// none if that was zero.
+#[tokio::test]
+async fn test_cancellation_fee_is_fixed_at_creation() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            price_per_token: 10_000,
+            quantity: 100_000,
+            treasury: Some(treasury),
+            ..ListingArgs::default()
+        })
+        .await;
+    assert_eq!(env.listing(&fixture.listing).await.cancellation_fee, 0);
+    env.set_cancellation_fee(&admin, 5_000_000).await.unwrap();
+
+    env.cancel(&fixture).await.unwrap();
+
+    assert_eq!(env.lamports(&treasury).await, 0);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Cancelled);
+}
+
+/// A listing with at least one fill is cancelled without the cancellation fee.
+#[tokio::test]
+async fn test_cancel_filled_listing_waives_cancellation_fee() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    env.set_cancellation_fee(&admin, 5_000_000).await.unwrap();
+
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            price_per_token: 10_000,
+            quantity: 100_000,
+            treasury: Some(treasury),
+            ..ListingArgs::default()
+        })
+        .await;
+    let buyer = env.setup_buyer(&fixture, 1_000).await;
+    env.purchase(&fixture, &buyer, 10_000).await.unwrap();
+
+    env.cancel(&fixture).await.unwrap();
+
+    assert_eq!(env.lamports(&treasury).await, 0);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Cancelled);
+}
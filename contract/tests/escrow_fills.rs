@@ -0,0 +1,107 @@
+//! Tests for the per-listing fills ledger.
+
+mod common;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use common::{custom_error_code, ListingArgs};
+use escrow_program::{
+    find_fills_ledger_address, find_receipt_address, EscrowError, EscrowInstruction, FillsLedger, Receipt,
+    MAX_FILL_RECORDS,
+};
+use solana_program::instruction::{AccountMeta, InstructionError};
+use solana_sdk::signature::Signer;
+
+/// Each purchase appends a record, growing the ledger account as needed.
+#[tokio::test]
+async fn test_purchases_append_fill_records() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let fixture = env.setup_active_listing(args).await;
+    let first = env.setup_buyer(&fixture, 100_000_000).await;
+    let second = env.setup_buyer(&fixture, 100_000_000).await;
+
+    env.purchase(&fixture, &first, 10_000_000).await.unwrap();
+    env.purchase(&fixture, &second, 25_000_000).await.unwrap();
+
+    let (ledger_address, _) = find_fills_ledger_address(&env.program_id, &fixture.listing);
+    let account = env.ctx.banks_client.get_account(ledger_address).await.unwrap().unwrap();
+    assert_eq!(account.owner, env.program_id);
+    assert_eq!(account.data.len(), FillsLedger::len_for(2));
+
+    let ledger = FillsLedger::try_from_slice(&account.data).unwrap();
+    assert_eq!(ledger.listing, fixture.listing);
+    assert_eq!(ledger.records.len(), 2);
+    assert_eq!(ledger.records[0].buyer, first.keypair.pubkey());
+    assert_eq!(ledger.records[0].quantity, 10_000_000);
+    assert_eq!(ledger.records[0].quote_amount, 10_000_000);
+    assert_eq!(ledger.records[1].buyer, second.keypair.pubkey());
+    assert_eq!(ledger.records[1].quantity, 25_000_000);
+    assert_eq!(ledger.records[1].quote_amount, 25_000_000);
+    assert!(ledger.records[1].ts >= ledger.records[0].ts);
+}
+
+/// A purchase that omits the fills ledger is rejected, so no fill goes
+/// unrecorded.
+#[tokio::test]
+async fn test_purchase_requires_fills_ledger() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 10_000_000);
+    ix.accounts.truncate(8);
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+
+    assert_eq!(common::instruction_error(err), Some(InstructionError::NotEnoughAccountKeys));
+    assert_eq!(env.listing(&fixture.listing).await.filled, 0);
+    assert_eq!(env.token_balance(&buyer.quote_account).await, 100_000_000);
+}
+
+/// A full ledger rejects further fills instead of dropping a record.
+#[tokio::test]
+async fn test_full_fills_ledger_rejects_fill() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    for fill in 0..MAX_FILL_RECORDS as u64 {
+        env.purchase(&fixture, &buyer, 1_000 + fill).await.unwrap();
+    }
+    let err = env.purchase(&fixture, &buyer, 1_000).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::FillsLedgerFull as u32));
+
+    let (ledger_address, _) = find_fills_ledger_address(&env.program_id, &fixture.listing);
+    let account = env.ctx.banks_client.get_account(ledger_address).await.unwrap().unwrap();
+    let ledger = FillsLedger::try_from_slice(&account.data).unwrap();
+    assert_eq!(ledger.records.len(), MAX_FILL_RECORDS);
+    assert_eq!(ledger.records[0].quantity, 1_000);
+    assert_eq!(ledger.records[MAX_FILL_RECORDS - 1].quantity, 1_000 + MAX_FILL_RECORDS as u64 - 1);
+}
+
+/// `PurchaseWithReceipt` creates a buyer-funded receipt PDA keyed by the fill's
+/// index in the ledger.
+#[tokio::test]
+async fn test_purchase_with_receipt_creates_receipt() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    env.purchase(&fixture, &buyer, 10_000_000).await.unwrap();
+
+    // The second fill sits at index 1 of the ledger.
+    let (receipt, _) = find_receipt_address(&env.program_id, &fixture.listing, &buyer.keypair.pubkey(), 1);
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 25_000_000);
+    ix.accounts.insert(10, AccountMeta::new(receipt, false));
+    ix.data = EscrowInstruction::PurchaseWithReceipt { quantity: 25_000_000 }.try_to_vec().unwrap();
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+
+    let account = env.ctx.banks_client.get_account(receipt).await.unwrap().unwrap();
+    assert_eq!(account.owner, env.program_id);
+    let receipt = Receipt::try_from_slice(&account.data).unwrap();
+    assert_eq!(receipt.buyer, buyer.keypair.pubkey());
+    assert_eq!(receipt.seller, fixture.seller.pubkey());
+    assert_eq!(receipt.base_mint, fixture.base_mint);
+    assert_eq!(receipt.quote_mint, fixture.quote_mint);
+    assert_eq!(receipt.quantity, 25_000_000);
+    assert_eq!(receipt.quote_amount, 25_000_000);
+    assert!(receipt.ts > 0);
+}
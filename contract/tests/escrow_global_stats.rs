@@ -0,0 +1,74 @@
+//! Tests for the protocol-wide statistics PDA.
+
+mod common;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use common::{ListingArgs, ListingFixture};
+use escrow_program::{find_global_stats_address, EscrowInstruction, GlobalStats};
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_sdk::signature::Signer;
+use spl_associated_token_account::get_associated_token_address;
+
+/// A fresh listing for `fixture`'s seller and mints, initialized with the global stats PDA.
+async fn counted_listing(env: &mut common::TestEnv, fixture: &ListingFixture, args: &ListingArgs) -> ListingFixture {
+    let seller = fixture.seller.insecure_clone();
+    let listing = env.create_listing_account(&seller).await;
+    let (vault_authority, _) = common::vault_authority_address(&env.program_id, &seller.pubkey(), args.listing_id);
+    let vault = get_associated_token_address(&vault_authority, &fixture.base_mint);
+    let next = ListingFixture {
+        seller,
+        listing: listing.pubkey(),
+        listing_id: args.listing_id,
+        vault_authority,
+        vault,
+        base_mint: fixture.base_mint,
+        quote_mint: fixture.quote_mint,
+        seller_base: fixture.seller_base,
+        seller_quote: fixture.seller_quote,
+    };
+    let mut ix = common::initialize_ix(&env.program_id, &next, args);
+    ix.accounts.push(AccountMeta::new(find_global_stats_address(&env.program_id).0, false));
+    env.process(&[ix], &[&next.seller]).await.unwrap();
+    env.mint_to(&next.base_mint, &next.seller_base, args.quantity).await;
+    env.deposit(&next).await.unwrap();
+    next
+}
+
+/// Listings created and purchases made with the global stats PDA bump its
+/// counters, and `ReadGlobalStats` returns them.
+#[tokio::test]
+async fn test_global_stats_count_listings_and_volume() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    // Created without the PDA, so it is not counted.
+    let first = env.setup_active_listing(args.clone()).await;
+    let second = counted_listing(&mut env, &first, &ListingArgs { listing_id: 2, ..args.clone() }).await;
+    let third = counted_listing(&mut env, &first, &ListingArgs { listing_id: 3, ..args.clone() }).await;
+    let (global_stats, _) = find_global_stats_address(&env.program_id);
+
+    let buyer = env.setup_buyer(&second, 100_000_000).await;
+    for (fixture, quantity) in [(&second, 10_000_000), (&third, 20_000_000)] {
+        let mut ix = common::purchase_ix(&env.program_id, fixture, &buyer, quantity);
+        ix.accounts.push(AccountMeta::new(global_stats, false));
+        env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    }
+
+    let account = env.ctx.banks_client.get_account(global_stats).await.unwrap().unwrap();
+    let stats = GlobalStats::try_from_slice(&account.data).unwrap();
+    assert_eq!(
+        stats,
+        GlobalStats {
+            listings_created: 2,
+            quote_volume: 30_000_000,
+            fees_collected: 0,
+        }
+    );
+
+    let ix = Instruction {
+        program_id: env.program_id,
+        accounts: vec![AccountMeta::new_readonly(global_stats, false)],
+        data: EscrowInstruction::ReadGlobalStats.try_to_vec().unwrap(),
+    };
+    let data = env.simulate_return_data(&[ix], &[]).await.unwrap();
+    assert_eq!(GlobalStats::try_from_slice(&data).unwrap(), stats);
+}
@@ -0,0 +1,499 @@
+//! Tests for listing initialization and its validations.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{custom_error_code, initialize_ix, ListingArgs, ListingFixture};
+use escrow_program::{
+    find_config_address, find_fee_exempt_address, find_seller_stats_address, find_treasury_address, EscrowError,
+    EscrowInstruction, FeePaymentMethod, InitializeListingArgs, Listing, ListingCategory, NOTE_LEN,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_sdk::signature::Signer;
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::Account as TokenAccount;
+
+fn dry_run_ix(program_id: &Pubkey, fixture: &ListingFixture, args: &ListingArgs) -> Instruction {
+    let data = EscrowInstruction::DryRunInitialize(InitializeListingArgs {
+        listing_id: args.listing_id,
+        price_per_token: args.price_per_token,
+        quantity: args.quantity,
+        allow_partial: args.allow_partial,
+        fee_payment_method: args.fee_payment_method,
+        x402_payload: args.x402_payload.clone(),
+        required_attestation_issuer: args.required_attestation_issuer,
+        escrow_fee: args.escrow_fee,
+        vault_count: args.vault_count,
+        tranche_interval: args.tranche_interval,
+        cancel_authority: args.cancel_authority,
+        min_fill_bps: args.min_fill_bps,
+        max_fill_bps: args.max_fill_bps,
+        oracle: args.oracle,
+        spread_bps: args.spread_bps,
+        min_fill_quote: args.min_fill_quote,
+        auction_end: args.auction_end,
+        trigger_price: args.trigger_price,
+        max_quote_raise: args.max_quote_raise,
+        tick_size: args.tick_size,
+        quote_per_base_num: args.quote_per_base_num,
+        quote_per_base_den: args.quote_per_base_den,
+        taker_fee_bps: args.taker_fee_bps,
+        category: args.category,
+        note: args.note,
+        deposit_deadline: args.deposit_deadline,
+        auto_complete_after_fills: args.auto_complete_after_fills,
+        max_slippage_bps: args.max_slippage_bps,
+        proceeds_unlock_ts: args.proceeds_unlock_ts,
+        max_oracle_staleness_secs: args.max_oracle_staleness_secs,
+    });
+    let mut accounts = vec![
+        AccountMeta::new_readonly(fixture.seller.pubkey(), false),
+        AccountMeta::new_readonly(fixture.vault_authority, false),
+        AccountMeta::new_readonly(fixture.vault, false),
+        AccountMeta::new_readonly(fixture.base_mint, false),
+        AccountMeta::new_readonly(fixture.quote_mint, false),
+    ];
+    if let Some(treasury) = args.treasury {
+        let seller = fixture.seller.pubkey();
+        accounts.push(AccountMeta::new_readonly(find_config_address(program_id).0, false));
+        accounts.push(AccountMeta::new_readonly(find_treasury_address(program_id, &fixture.quote_mint).0, false));
+        accounts.push(AccountMeta::new_readonly(treasury, false));
+        accounts.push(AccountMeta::new_readonly(find_fee_exempt_address(program_id, &seller).0, false));
+        if args.seller_stats {
+            accounts.push(AccountMeta::new_readonly(find_seller_stats_address(program_id, &seller).0, false));
+        }
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: data.try_to_vec().unwrap(),
+    }
+}
+
+/// The dry-run fee matches the fee recorded by a real initialization.
+#[tokio::test]
+async fn test_dry_run_initialize_matches_real_fee() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        price_per_token: 3_000_000,
+        quantity: 40_000_000,
+        fee_payment_method: 1,
+        x402_payload: Some("x402-proof".to_string()),
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_listing(args.clone()).await;
+
+    let ix = dry_run_ix(&env.program_id, &fixture, &args);
+    let data = env.simulate_return_data(&[ix], &[]).await.unwrap();
+    let dry_run_fee = u64::from_le_bytes(data.try_into().unwrap());
+
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!(dry_run_fee, listing.fee_amount_paid);
+    assert_eq!(dry_run_fee, 1_200_000_000_000);
+}
+
+/// With a config, the dry run returns the flat NativeSol fee the real
+/// initialization charges, and zero for a fee-exempt seller.
+#[tokio::test]
+async fn test_dry_run_initialize_matches_real_fee_with_config() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    env.set_native_fee_lamports(&admin, 10_000_000).await.unwrap();
+    let args = ListingArgs {
+        price_per_token: 1_000_000,
+        quantity: 100_000_000,
+        treasury: Some(treasury),
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_listing(args.clone()).await;
+
+    let ix = dry_run_ix(&env.program_id, &fixture, &args);
+    let data = env.simulate_return_data(&[ix], &[]).await.unwrap();
+    let dry_run_fee = u64::from_le_bytes(data.try_into().unwrap());
+    assert_eq!(dry_run_fee, 10_000_000);
+    assert_eq!(dry_run_fee, env.listing(&fixture.listing).await.fee_amount_paid);
+
+    env.add_fee_exempt(&admin, fixture.seller.pubkey()).await.unwrap();
+    let ix = dry_run_ix(&env.program_id, &fixture, &args);
+    let data = env.simulate_return_data(&[ix], &[]).await.unwrap();
+    assert_eq!(u64::from_le_bytes(data.try_into().unwrap()), 0);
+}
+
+/// The dry run enforces the config's listing limits.
+#[tokio::test]
+async fn test_dry_run_initialize_rejects_quantity_above_config_max() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    let args = ListingArgs {
+        treasury: Some(treasury),
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_listing(args.clone()).await;
+    env.set_max_listing_quantity(&admin, args.quantity - 1).await.unwrap();
+
+    let ix = dry_run_ix(&env.program_id, &fixture, &args);
+    let err = env.process(&[ix], &[]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::QuantityTooLarge as u32));
+}
+
+/// The dry run surfaces the same validation errors as a real initialization.
+#[tokio::test]
+async fn test_dry_run_initialize_rejects_missing_x402_payload() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let fixture = env.setup_listing(args.clone()).await;
+
+    let invalid = ListingArgs {
+        fee_payment_method: 1,
+        x402_payload: None,
+        ..args
+    };
+    let ix = dry_run_ix(&env.program_id, &fixture, &invalid);
+    let err = env.process(&[ix], &[]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidX402Proof as u32));
+}
+
+/// Initialization rejects a listing account larger than `Listing::LEN`.
+#[tokio::test]
+async fn test_initialize_rejects_oversized_listing_account() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let fixture = env.setup_listing(args.clone()).await;
+
+    let oversized = env
+        .create_listing_account_with_len(&fixture.seller, Listing::LEN + 8)
+        .await;
+    let fixture = ListingFixture {
+        listing: oversized.pubkey(),
+        ..fixture
+    };
+    let ix = initialize_ix(&env.program_id, &fixture, &args);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::AccountLengthMismatch as u32));
+}
+
+/// A listing whose full fill would overflow the u64 quote amount is rejected up front.
+#[tokio::test]
+async fn test_initialize_rejects_quote_overflow_at_full_fill() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+
+    // u64::MAX per whole token for 1_000 whole tokens (6 decimals).
+    let overflowing = ListingArgs {
+        price_per_token: u64::MAX,
+        quantity: 1_000_000_000,
+        ..ListingArgs::default()
+    };
+    let listing = env.create_listing_account(&fixture.seller).await;
+    let fixture = ListingFixture {
+        listing: listing.pubkey(),
+        ..fixture
+    };
+    let ix = initialize_ix(&env.program_id, &fixture, &overflowing);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::QuoteOverflowAtFullFill as u32));
+}
+
+/// With zero base decimals nothing scales `price * quantity` down: the largest
+/// price that fits the full fill works, one unit more is rejected at init.
+#[tokio::test]
+async fn test_initialize_zero_decimals_checks_full_fill_quote() {
+    let mut env = common::start().await;
+    let price_per_token = u64::MAX / 1_000;
+    let args = ListingArgs {
+        base_decimals: 0,
+        price_per_token,
+        quantity: 1_000,
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_active_listing(args.clone()).await;
+    let buyer = env.setup_buyer(&fixture, price_per_token * 1_000).await;
+    env.purchase(&fixture, &buyer, 1_000).await.unwrap();
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, price_per_token * 1_000);
+
+    let overflowing = ListingArgs {
+        price_per_token: price_per_token + 1,
+        ..args
+    };
+    let listing = env.create_listing_account(&fixture.seller).await;
+    let fixture = ListingFixture {
+        listing: listing.pubkey(),
+        ..fixture
+    };
+    let ix = initialize_ix(&env.program_id, &fixture, &overflowing);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::QuoteOverflowAtFullFill as u32));
+}
+
+/// Initialization can create the vault ATA itself, leaving the listing ready for deposit.
+#[tokio::test]
+async fn test_initialize_creates_vault() {
+    let mut env = common::start().await;
+    let fixture = env
+        .setup_listing(ListingArgs {
+            create_vault: true,
+            ..ListingArgs::default()
+        })
+        .await;
+
+    let account = env.ctx.banks_client.get_account(fixture.vault).await.unwrap().unwrap();
+    let vault = TokenAccount::unpack(&account.data).unwrap();
+    assert_eq!(vault.owner, fixture.vault_authority);
+    assert_eq!(vault.mint, fixture.base_mint);
+
+    env.deposit(&fixture).await.unwrap();
+    assert_eq!(env.token_balance(&fixture.vault).await, 100_000_000);
+}
+
+/// A vault ATA left over from an earlier attempt is reused rather than failing creation.
+#[tokio::test]
+async fn test_initialize_reuses_existing_vault() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+    env.create_ata(&fixture.vault_authority, &fixture.base_mint).await;
+
+    let args = ListingArgs {
+        create_vault: true,
+        ..ListingArgs::default()
+    };
+    let listing = env.create_listing_account(&fixture.seller).await;
+    let fixture = ListingFixture {
+        listing: listing.pubkey(),
+        ..fixture
+    };
+    let ix = initialize_ix(&env.program_id, &fixture, &args);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    env.deposit(&fixture).await.unwrap();
+    assert_eq!(env.token_balance(&fixture.vault).await, 100_000_000);
+}
+
+/// An existing vault ATA whose owner was reassigned away from the vault authority is rejected.
+#[tokio::test]
+async fn test_initialize_rejects_vault_with_wrong_owner() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+    env.create_ata(&fixture.vault_authority, &fixture.base_mint).await;
+
+    let mut account = env.ctx.banks_client.get_account(fixture.vault).await.unwrap().unwrap();
+    let mut vault = TokenAccount::unpack(&account.data).unwrap();
+    vault.owner = Pubkey::new_unique();
+    TokenAccount::pack(vault, &mut account.data).unwrap();
+    env.ctx.set_account(&fixture.vault, &account.into());
+
+    let args = ListingArgs {
+        create_vault: true,
+        ..ListingArgs::default()
+    };
+    let listing = env.create_listing_account(&fixture.seller).await;
+    let fixture = ListingFixture {
+        listing: listing.pubkey(),
+        ..fixture
+    };
+    let ix = initialize_ix(&env.program_id, &fixture, &args);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::IncorrectAuthority as u32));
+}
+
+/// A wallet passed as the quote mint is rejected before the listing is written.
+#[tokio::test]
+async fn test_initialize_rejects_non_mint_quote() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+
+    let listing = env.create_listing_account(&fixture.seller).await;
+    let fixture = ListingFixture {
+        listing: listing.pubkey(),
+        quote_mint: fixture.seller.pubkey(),
+        ..fixture
+    };
+    let ix = initialize_ix(&env.program_id, &fixture, &ListingArgs::default());
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::MintMismatch as u32));
+}
+
+/// A base mint nobody can ever mint from is rejected with `DegenerateMint`.
+#[tokio::test]
+async fn test_initialize_rejects_degenerate_base_mint() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+
+    let base_mint = env.create_mint(6).await;
+    let payer = env.ctx.payer.pubkey();
+    let revoke = spl_token::instruction::set_authority(
+        &spl_token::id(),
+        &base_mint,
+        None,
+        spl_token::instruction::AuthorityType::MintTokens,
+        &payer,
+        &[],
+    )
+    .unwrap();
+    env.process(&[revoke], &[]).await.unwrap();
+
+    let listing = env.create_listing_account(&fixture.seller).await;
+    let vault = get_associated_token_address(&fixture.vault_authority, &base_mint);
+    let fixture = ListingFixture {
+        listing: listing.pubkey(),
+        base_mint,
+        vault,
+        ..fixture
+    };
+    let ix = initialize_ix(&env.program_id, &fixture, &ListingArgs::default());
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::DegenerateMint as u32));
+}
+
+/// With a tick size, only prices on the grid are accepted.
+#[tokio::test]
+async fn test_initialize_enforces_tick_size() {
+    let mut env = common::start().await;
+    let fixture = env
+        .setup_listing(ListingArgs {
+            price_per_token: 2_500_000,
+            tick_size: 500_000,
+            ..ListingArgs::default()
+        })
+        .await;
+    assert_eq!(env.listing(&fixture.listing).await.tick_size, 500_000);
+
+    let off_tick = ListingArgs {
+        price_per_token: 2_600_000,
+        tick_size: 500_000,
+        ..ListingArgs::default()
+    };
+    let listing = env.create_listing_account(&fixture.seller).await;
+    let fixture = ListingFixture {
+        listing: listing.pubkey(),
+        ..fixture
+    };
+    let ix = initialize_ix(&env.program_id, &fixture, &off_tick);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::PriceNotOnTick as u32));
+}
+
+/// Two sellers reusing the same client `listing_id` still get distinct canonical IDs.
+#[tokio::test]
+async fn test_canonical_id_unique_across_sellers() {
+    let mut env = common::start().await;
+    let first = env.setup_listing(ListingArgs::default()).await;
+    let second = env.setup_listing(ListingArgs::default()).await;
+
+    let first_listing = env.listing(&first.listing).await;
+    let second_listing = env.listing(&second.listing).await;
+    assert_eq!(first_listing.listing_id, second_listing.listing_id);
+    assert_ne!(first_listing.canonical_id, second_listing.canonical_id);
+    assert_ne!(first_listing.canonical_id, [0; 32]);
+}
+
+/// An X402 listing moves no SOL, so it can be initialized without the system program.
+#[tokio::test]
+async fn test_x402_initialize_without_system_program() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        fee_payment_method: 1,
+        x402_payload: Some("x402-proof".to_string()),
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_listing(args.clone()).await;
+
+    let listing = env.create_listing_account(&fixture.seller).await;
+    let fixture = ListingFixture {
+        listing: listing.pubkey(),
+        ..fixture
+    };
+    let mut ix = initialize_ix(&env.program_id, &fixture, &args);
+    ix.accounts.remove(6);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!(listing.seller, fixture.seller.pubkey());
+    assert_eq!(listing.quantity, args.quantity);
+}
+
+/// The category is stored on the listing; bytes past the known categories are rejected.
+#[tokio::test]
+async fn test_initialize_stores_category() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        category: ListingCategory::Stablecoin.as_u8(),
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_listing(args.clone()).await;
+    assert_eq!(env.listing(&fixture.listing).await.category, ListingCategory::Stablecoin.as_u8());
+
+    let listing = env.create_listing_account(&fixture.seller).await;
+    let fixture = ListingFixture {
+        listing: listing.pubkey(),
+        ..fixture
+    };
+    let args = ListingArgs {
+        category: ListingCategory::Utility.as_u8() + 1,
+        ..args
+    };
+    let ix = initialize_ix(&env.program_id, &fixture, &args);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidCategory as u32));
+}
+
+/// The seller's note is stored on the listing and quoted in the creation log;
+/// a note that is not UTF-8 is rejected.
+#[tokio::test]
+async fn test_initialize_records_note() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+    let listing = env.create_listing_account(&fixture.seller).await;
+    let fixture = ListingFixture {
+        listing: listing.pubkey(),
+        ..fixture
+    };
+
+    let mut note = [0u8; NOTE_LEN];
+    note[..19].copy_from_slice(b"serious buyers only");
+    let args = ListingArgs {
+        note,
+        ..ListingArgs::default()
+    };
+    let ix = initialize_ix(&env.program_id, &fixture, &args);
+    let logs = env.process_with_logs(&[ix], &[&fixture.seller]).await.unwrap();
+    assert_eq!(env.listing(&fixture.listing).await.note, note);
+    let created = logs.iter().find(|log| log.contains("ListingCreated")).unwrap();
+    assert!(created.ends_with(r#" note="serious buyers only""#));
+
+    let listing = env.create_listing_account(&fixture.seller).await;
+    let fixture = ListingFixture {
+        listing: listing.pubkey(),
+        ..fixture
+    };
+    let mut note = [0u8; NOTE_LEN];
+    note[0] = 0xff;
+    let ix = initialize_ix(&env.program_id, &fixture, &ListingArgs { note, ..ListingArgs::default() });
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidNote as u32));
+}
+
+/// A fee method byte past the known `FeePaymentMethod` variants is rejected.
+#[tokio::test]
+async fn test_initialize_rejects_unknown_fee_payment_method() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+
+    let listing = env.create_listing_account(&fixture.seller).await;
+    let fixture = ListingFixture {
+        listing: listing.pubkey(),
+        ..fixture
+    };
+    let args = ListingArgs {
+        fee_payment_method: FeePaymentMethod::SplToken.as_u8() + 1,
+        ..ListingArgs::default()
+    };
+    let ix = initialize_ix(&env.program_id, &fixture, &args);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidInstructionData as u32));
+}
@@ -0,0 +1,88 @@
+//! Tests for paying for a listing in installments.
+
+mod common;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use common::{Buyer, ListingArgs, ListingFixture, TestEnv};
+use escrow_program::{
+    find_fills_ledger_address, find_installment_address, EscrowInstruction, InstallmentPlan, ListingStatus,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::signature::Signer;
+
+fn pay_installment_ix(env: &TestEnv, fixture: &ListingFixture, buyer: &Buyer, quote_amount: u64) -> Instruction {
+    let buyer_key = buyer.keypair.pubkey();
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new(buyer_key, true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new(fixture.seller_quote, false),
+            AccountMeta::new(buyer.quote_account, false),
+            AccountMeta::new(buyer.base_account, false),
+            AccountMeta::new_readonly(fixture.vault_authority, false),
+            AccountMeta::new(fixture.vault, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(find_installment_address(&env.program_id, &fixture.listing, &buyer_key).0, false),
+            AccountMeta::new(find_fills_ledger_address(&env.program_id, &fixture.listing).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::PayInstallment { quote_amount }.try_to_vec().unwrap(),
+    }
+}
+
+/// Each installment delivers the base it pays for; the last one is capped at
+/// the remainder and only charged for it.
+#[tokio::test]
+async fn test_two_installments_deliver_proportionally() {
+    let mut env = common::start().await;
+    // 100 tokens at 2.0 quote each.
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            price_per_token: 2_000_000,
+            ..ListingArgs::default()
+        })
+        .await;
+    let buyer = env.setup_buyer(&fixture, 300_000_000).await;
+
+    let ix = pay_installment_ix(&env, &fixture, &buyer, 50_000_000);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&buyer.base_account).await, 25_000_000);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 50_000_000);
+
+    // Offers 200 quote but only 150 is needed for the remaining 75 tokens.
+    let ix = pay_installment_ix(&env, &fixture, &buyer, 200_000_000);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&buyer.base_account).await, 100_000_000);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 200_000_000);
+    assert_eq!(env.token_balance(&buyer.quote_account).await, 100_000_000);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Completed);
+
+    let (plan_address, _) = find_installment_address(&env.program_id, &fixture.listing, &buyer.keypair.pubkey());
+    let account = env.ctx.banks_client.get_account(plan_address).await.unwrap().unwrap();
+    let plan = InstallmentPlan::try_from_slice(&account.data).unwrap();
+    assert_eq!(plan.quote_paid, 200_000_000);
+    assert_eq!(plan.base_delivered, 100_000_000);
+}
+
+/// A fake token program is refused before any transfer, so the buyer cannot
+/// skip paying while the vault releases base.
+#[tokio::test]
+async fn test_installment_rejects_fake_token_program() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let fixture = env.setup_active_listing(args.clone()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    let mut ix = pay_installment_ix(&env, &fixture, &buyer, 50_000_000);
+    ix.accounts[7] = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+
+    assert_eq!(common::instruction_error(err), Some(InstructionError::IncorrectProgramId));
+    assert_eq!(env.token_balance(&fixture.vault).await, args.quantity);
+    assert_eq!(env.token_balance(&buyer.base_account).await, 0);
+}
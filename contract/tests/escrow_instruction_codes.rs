@@ -0,0 +1,133 @@
+//! Tests for the fixed one-byte instruction codes.
+
+use borsh::BorshSerialize;
+use escrow_program::{EscrowInstruction, InitializeListingArgs};
+use solana_program::pubkey::Pubkey;
+
+fn init_args() -> InitializeListingArgs {
+    InitializeListingArgs {
+        listing_id: 1,
+        price_per_token: 1_000_000,
+        quantity: 10,
+        allow_partial: true,
+        fee_payment_method: 0,
+        x402_payload: None,
+        required_attestation_issuer: None,
+        escrow_fee: false,
+        vault_count: 1,
+        tranche_interval: 0,
+        cancel_authority: None,
+        min_fill_bps: 0,
+        max_fill_bps: 0,
+        oracle: None,
+        spread_bps: 0,
+        min_fill_quote: 0,
+        auction_end: 0,
+        trigger_price: 0,
+        max_quote_raise: 0,
+        tick_size: 0,
+        quote_per_base_num: 0,
+        quote_per_base_den: 0,
+        taker_fee_bps: 0,
+        category: 0,
+        note: [0; 64],
+        deposit_deadline: 0,
+        auto_complete_after_fills: 0,
+        max_slippage_bps: 0,
+        proceeds_unlock_ts: 0,
+        max_oracle_staleness_secs: 0,
+    }
+}
+
+/// Every instruction serializes to its fixed leading byte and decodes back through `unpack`.
+#[test]
+fn test_instruction_codes_are_stable() {
+    let cases = vec![
+        (EscrowInstruction::InitializeListing(init_args()), 0),
+        (EscrowInstruction::DepositTokens, 1),
+        (EscrowInstruction::Purchase { quantity: 5 }, 2),
+        (EscrowInstruction::CancelListing, 3),
+        (EscrowInstruction::ReadListing, 4),
+        (EscrowInstruction::SetPriceSchedule { price_steps: vec![(1, 2)] }, 5),
+        (EscrowInstruction::PurchaseFraction { bps: 100 }, 6),
+        (EscrowInstruction::DryRunInitialize(init_args()), 7),
+        (
+            EscrowInstruction::InitializeConfig {
+                default_treasury: Pubkey::new_unique(),
+            },
+            8,
+        ),
+        (EscrowInstruction::SetTreasury { treasury: Pubkey::new_unique() }, 9),
+        (EscrowInstruction::ReleaseFeeEscrow, 10),
+        (EscrowInstruction::SweepVaultLamports, 11),
+        (EscrowInstruction::AddFeeExempt { seller: Pubkey::new_unique() }, 12),
+        (EscrowInstruction::RemoveFeeExempt { seller: Pubkey::new_unique() }, 13),
+        (EscrowInstruction::RegisterInterest, 14),
+        (EscrowInstruction::SetBasketLegs { legs: vec![] }, 15),
+        (EscrowInstruction::PurchaseCreatingSellerQuote { quantity: 5 }, 16),
+        (EscrowInstruction::RegisterClaim { desired: 5 }, 17),
+        (EscrowInstruction::SettleClaims, 18),
+        (EscrowInstruction::SetAllowedBuyer { buyer: None }, 19),
+        (EscrowInstruction::PayInstallment { quote_amount: 5 }, 20),
+        (EscrowInstruction::SetMaxActiveListings { max_active_listings: 3 }, 21),
+        (EscrowInstruction::PlaceBid { amount: 5 }, 22),
+        (EscrowInstruction::FinalizeAuction, 23),
+        (EscrowInstruction::PurchaseUpTo { max_quantity: 5 }, 24),
+        (EscrowInstruction::ForceCancel, 25),
+        (EscrowInstruction::SetFeeMint, 26),
+        (EscrowInstruction::ReconcileListing, 27),
+        (EscrowInstruction::GetTimeRemaining, 28),
+        (
+            EscrowInstruction::PurchaseWithRelayerTip {
+                quantity: 5,
+                tip_lamports: 1,
+            },
+            29,
+        ),
+        (EscrowInstruction::EnableStatusHistory, 30),
+        (EscrowInstruction::PurchaseWithDelegate { quantity: 5 }, 31),
+        (EscrowInstruction::ProposeAdmin { new_admin: Pubkey::new_unique() }, 32),
+        (EscrowInstruction::AcceptAdmin, 33),
+        (EscrowInstruction::CommitPurchase { commitment: [7; 32] }, 34),
+        (EscrowInstruction::RevealPurchase { quantity: 1, nonce: 2 }, 35),
+        (EscrowInstruction::SetMaxListingQuantity { max_listing_quantity: 5 }, 36),
+        (EscrowInstruction::UnfreezeListing, 37),
+        (EscrowInstruction::EnableSwap { counterparty: Pubkey::new_unique() }, 38),
+        (EscrowInstruction::DepositSwapQuote, 39),
+        (EscrowInstruction::SettleSwap, 40),
+        (EscrowInstruction::CancelSwap, 41),
+        (EscrowInstruction::SimulatePurchase { quantity: 9 }, 42),
+        (EscrowInstruction::SetBurnBps { burn_bps: 10 }, 43),
+        (EscrowInstruction::SetAllowlistRoot { root: Some([4; 32]) }, 44),
+        (EscrowInstruction::PurchaseWithProof { quantity: 9, proof: vec![[5; 32]] }, 45),
+        (EscrowInstruction::ExpireListing, 46),
+        (EscrowInstruction::BatchCancel, 47),
+        (EscrowInstruction::ReadGlobalStats, 48),
+        (EscrowInstruction::SetCancellationFee { cancellation_fee: 11 }, 49),
+        (EscrowInstruction::EnableProceedsVault, 50),
+        (EscrowInstruction::ClaimProceeds, 51),
+        (EscrowInstruction::PurchaseWithReceipt { quantity: 9 }, 52),
+        (EscrowInstruction::SetMinListingQuantity { min_listing_quantity: 12 }, 53),
+        (EscrowInstruction::PurchaseWithReferencePrice { quantity: 9, reference_price: 13 }, 54),
+        (EscrowInstruction::SetSplFeeAmount { spl_fee_amount: 14 }, 55),
+        (EscrowInstruction::CloseClaim, 56),
+        (EscrowInstruction::ApproveDelegate { delegate: Pubkey::new_unique(), quantity: 9, max_price: 15 }, 57),
+        (EscrowInstruction::SetNativeFeeLamports { native_fee_lamports: 16 }, 58),
+    ];
+
+    for (instruction, code) in cases {
+        let data = instruction.try_to_vec().unwrap();
+        assert_eq!(data[0], code, "{instruction:?}");
+        let decoded = EscrowInstruction::unpack(&data).unwrap();
+        assert_eq!(decoded.try_to_vec().unwrap(), data, "{instruction:?}");
+    }
+}
+
+/// Unknown codes, empty data and trailing bytes are rejected.
+#[test]
+fn test_unpack_rejects_malformed_data() {
+    assert!(EscrowInstruction::unpack(&[]).is_err());
+    assert!(EscrowInstruction::unpack(&[u8::MAX]).is_err());
+    assert!(EscrowInstruction::unpack(&[1, 0]).is_err());
+    assert!(EscrowInstruction::unpack(&[2, 0, 0]).is_err());
+}
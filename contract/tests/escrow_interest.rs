@@ -0,0 +1,51 @@
+//! Tests for the listing interest counter.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{ListingArgs, ListingFixture};
+use escrow_program::{EscrowInstruction, INTEREST_FEE_LAMPORTS};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::signature::Signer;
+
+fn register_interest_ix(program_id: &Pubkey, fixture: &ListingFixture, caller: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*caller, true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new(fixture.seller.pubkey(), false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::RegisterInterest.try_to_vec().unwrap(),
+    }
+}
+
+/// Each call bumps the counter and pays the seller, without affecting purchases.
+#[tokio::test]
+async fn test_register_interest_increments_counter() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let first = env.funded_keypair(100_000_000).await;
+    let second = env.funded_keypair(100_000_000).await;
+    let seller_before = env.lamports(&fixture.seller.pubkey()).await;
+
+    let ix = register_interest_ix(&env.program_id, &fixture, &first.pubkey());
+    env.process(&[ix], &[&first]).await.unwrap();
+    let ix = register_interest_ix(&env.program_id, &fixture, &second.pubkey());
+    env.process(&[ix], &[&second]).await.unwrap();
+
+    assert_eq!(env.listing(&fixture.listing).await.interest_count, 2);
+    assert_eq!(
+        env.lamports(&fixture.seller.pubkey()).await,
+        seller_before + 2 * INTEREST_FEE_LAMPORTS
+    );
+
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    env.purchase(&fixture, &buyer, 10_000_000).await.unwrap();
+    assert_eq!(env.listing(&fixture.listing).await.interest_count, 2);
+}
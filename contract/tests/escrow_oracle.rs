@@ -0,0 +1,174 @@
+//! Tests for listings priced from an oracle feed.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{custom_error_code, ListingArgs};
+use escrow_program::{EscrowError, EscrowInstruction, OraclePrice, MAX_ORACLE_STALENESS};
+use solana_program::{clock::Clock, instruction::AccountMeta, pubkey::Pubkey};
+use solana_sdk::account::Account;
+
+/// Store a mock oracle account publishing `price` at `publish_time`.
+fn set_oracle(env: &mut common::TestEnv, oracle: &Pubkey, price: u64, publish_time: i64) {
+    let data = OraclePrice { price, publish_time }.try_to_vec().unwrap();
+    env.ctx.set_account(
+        oracle,
+        &Account {
+            lamports: 1_000_000,
+            data,
+            owner: Pubkey::new_unique(),
+            executable: false,
+            rent_epoch: 0,
+        }
+        .into(),
+    );
+}
+
+/// The buyer pays the oracle price adjusted by the listing's spread.
+#[tokio::test]
+async fn test_purchase_uses_spread_adjusted_oracle_price() {
+    let mut env = common::start().await;
+    let oracle = Pubkey::new_unique();
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            oracle: Some(oracle),
+            spread_bps: 500,
+            ..ListingArgs::default()
+        })
+        .await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    let clock: Clock = env.ctx.banks_client.get_sysvar().await.unwrap();
+    set_oracle(&mut env, &oracle, 2_000_000, clock.unix_timestamp);
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 10_000_000);
+    ix.accounts.push(AccountMeta::new_readonly(oracle, false));
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+
+    // 10 tokens at 2.0 * 1.05 quote each.
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 21_000_000);
+    assert_eq!(env.token_balance(&buyer.quote_account).await, 79_000_000);
+    assert_eq!(env.token_balance(&buyer.base_account).await, 10_000_000);
+}
+
+/// A price published longer ago than the staleness window is rejected.
+#[tokio::test]
+async fn test_purchase_with_stale_oracle_rejected() {
+    let mut env = common::start().await;
+    let oracle = Pubkey::new_unique();
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            oracle: Some(oracle),
+            ..ListingArgs::default()
+        })
+        .await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    let clock: Clock = env.ctx.banks_client.get_sysvar().await.unwrap();
+    set_oracle(&mut env, &oracle, 1_000_000, clock.unix_timestamp - MAX_ORACLE_STALENESS - 1);
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 10_000_000);
+    ix.accounts.push(AccountMeta::new_readonly(oracle, false));
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+
+    assert_eq!(custom_error_code(err), Some(EscrowError::StaleOracle as u32));
+    assert_eq!(env.token_balance(&buyer.base_account).await, 0);
+}
+
+/// A listing's own `max_oracle_staleness_secs` replaces the default window:
+/// a price older than the default but within it fills, one past it does not.
+#[tokio::test]
+async fn test_purchase_uses_listing_oracle_staleness() {
+    let mut env = common::start().await;
+    let oracle = Pubkey::new_unique();
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            oracle: Some(oracle),
+            max_oracle_staleness_secs: 300,
+            ..ListingArgs::default()
+        })
+        .await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let clock: Clock = env.ctx.banks_client.get_sysvar().await.unwrap();
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 10_000_000);
+    ix.accounts.push(AccountMeta::new_readonly(oracle, false));
+
+    set_oracle(&mut env, &oracle, 1_000_000, clock.unix_timestamp - 301);
+    let err = env.process(&[ix.clone()], &[&buyer.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::StaleOracle as u32));
+
+    set_oracle(&mut env, &oracle, 1_000_000, clock.unix_timestamp - MAX_ORACLE_STALENESS - 1);
+    env.refresh_blockhash().await;
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&buyer.base_account).await, 10_000_000);
+}
+
+/// A limit sell only fills once the oracle price reaches the trigger.
+#[tokio::test]
+async fn test_purchase_requires_trigger_price() {
+    let mut env = common::start().await;
+    let oracle = Pubkey::new_unique();
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            oracle: Some(oracle),
+            trigger_price: 1_500_000,
+            ..ListingArgs::default()
+        })
+        .await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let clock: Clock = env.ctx.banks_client.get_sysvar().await.unwrap();
+
+    set_oracle(&mut env, &oracle, 1_000_000, clock.unix_timestamp);
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 10_000_000);
+    ix.accounts.push(AccountMeta::new_readonly(oracle, false));
+    let err = env.process(&[ix.clone()], &[&buyer.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::TriggerNotMet as u32));
+
+    set_oracle(&mut env, &oracle, 2_000_000, clock.unix_timestamp);
+    env.refresh_blockhash().await;
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&buyer.base_account).await, 10_000_000);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 20_000_000);
+}
+
+/// A reference-priced purchase fills while the oracle stays within `max_slippage_bps`
+/// of the buyer's reference and fails once it moves beyond it.
+#[tokio::test]
+async fn test_purchase_with_reference_price_enforces_slippage() {
+    let mut env = common::start().await;
+    let oracle = Pubkey::new_unique();
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            oracle: Some(oracle),
+            max_slippage_bps: 100,
+            ..ListingArgs::default()
+        })
+        .await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let clock: Clock = env.ctx.banks_client.get_sysvar().await.unwrap();
+    let reference_ix = |quantity: u64| {
+        let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, quantity);
+        ix.accounts.push(AccountMeta::new_readonly(oracle, false));
+        ix.data = EscrowInstruction::PurchaseWithReferencePrice {
+            quantity,
+            reference_price: 1_000_000,
+        }
+        .try_to_vec()
+        .unwrap();
+        ix
+    };
+    let within = reference_ix(10_000_000);
+    let beyond = reference_ix(20_000_000);
+
+    // 0.5% above the reference.
+    set_oracle(&mut env, &oracle, 1_005_000, clock.unix_timestamp);
+    env.process(&[within], &[&buyer.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&buyer.base_account).await, 10_000_000);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 10_050_000);
+
+    // 2% below the reference.
+    set_oracle(&mut env, &oracle, 980_000, clock.unix_timestamp);
+    let err = env.process(&[beyond], &[&buyer.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::SlippageExceeded as u32));
+    assert_eq!(env.token_balance(&buyer.base_account).await, 10_000_000);
+}
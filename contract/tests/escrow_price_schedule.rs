@@ -0,0 +1,76 @@
+//! Tests for step-price schedules.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{custom_error_code, ListingArgs, ListingFixture, TestEnv};
+use escrow_program::{find_price_schedule_address, EscrowError, EscrowInstruction};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    system_program,
+};
+use solana_sdk::signature::Signer;
+
+fn set_price_schedule_ix(env: &TestEnv, fixture: &ListingFixture, price_steps: Vec<(u64, u64)>) -> Instruction {
+    let (schedule, _) = find_price_schedule_address(&env.program_id, &fixture.listing);
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new(fixture.seller.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new(schedule, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::SetPriceSchedule { price_steps }
+            .try_to_vec()
+            .unwrap(),
+    }
+}
+
+/// A purchase straddling a step boundary pays the blended price.
+#[tokio::test]
+async fn test_purchase_across_price_step_boundary() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        price_per_token: 1_000_000,
+        quantity: 200_000_000,
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_listing(args).await;
+
+    // 1.0 for the first 100 tokens, 1.1 for the next 100.
+    let ix = set_price_schedule_ix(&env, &fixture, vec![(100_000_000, 1_000_000), (200_000_000, 1_100_000)]);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    env.deposit(&fixture).await.unwrap();
+
+    let (schedule, _) = find_price_schedule_address(&env.program_id, &fixture.listing);
+    let buyer = env.setup_buyer(&fixture, 1_000_000_000).await;
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 50_000_000);
+    ix.accounts.push(AccountMeta::new_readonly(schedule, false));
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 50_000_000);
+
+    // 50 tokens at 1.0 plus 50 tokens at 1.1.
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 100_000_000);
+    ix.accounts.push(AccountMeta::new_readonly(schedule, false));
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 50_000_000 + 105_000_000);
+    assert_eq!(env.token_balance(&buyer.base_account).await, 150_000_000);
+}
+
+/// Thresholds must strictly increase.
+#[tokio::test]
+async fn test_price_schedule_rejects_non_monotonic_thresholds() {
+    let mut env = common::start().await;
+    let fixture = env
+        .setup_listing(ListingArgs {
+            quantity: 200_000_000,
+            ..ListingArgs::default()
+        })
+        .await;
+
+    let ix = set_price_schedule_ix(&env, &fixture, vec![(150_000_000, 1_000_000), (100_000_000, 1_100_000)]);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidPriceSchedule as u32));
+}
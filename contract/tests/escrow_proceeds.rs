@@ -0,0 +1,162 @@
+//! Tests for accruing purchase proceeds in a quote vault.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{custom_error_code, instruction_error, ListingArgs, ListingFixture};
+use escrow_program::{EscrowError, EscrowInstruction, ListingStatus};
+use solana_program::{
+    clock::Clock,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::signature::Signer;
+use spl_associated_token_account::get_associated_token_address;
+
+fn enable_proceeds_vault_ix(env: &common::TestEnv, fixture: &ListingFixture) -> Instruction {
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new(fixture.seller.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new_readonly(fixture.vault_authority, false),
+            AccountMeta::new(get_associated_token_address(&fixture.vault_authority, &fixture.quote_mint), false),
+            AccountMeta::new_readonly(fixture.quote_mint, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        ],
+        data: EscrowInstruction::EnableProceedsVault.try_to_vec().unwrap(),
+    }
+}
+
+fn claim_proceeds_ix(env: &common::TestEnv, fixture: &ListingFixture) -> Instruction {
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(fixture.seller.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new_readonly(fixture.vault_authority, false),
+            AccountMeta::new(get_associated_token_address(&fixture.vault_authority, &fixture.quote_mint), false),
+            AccountMeta::new(fixture.seller_quote, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: EscrowInstruction::ClaimProceeds.try_to_vec().unwrap(),
+    }
+}
+
+/// Three fills accrue in the quote vault and a single `ClaimProceeds` pays the
+/// seller their sum.
+#[tokio::test]
+async fn test_proceeds_accrue_until_claimed() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let ix = enable_proceeds_vault_ix(&env, &fixture);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    let quote_vault = get_associated_token_address(&fixture.vault_authority, &fixture.quote_mint);
+
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    for quantity in [10_000_000, 20_000_000, 30_000_000] {
+        let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, quantity);
+        ix.accounts[2] = AccountMeta::new(quote_vault, false);
+        env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    }
+
+    assert_eq!(env.token_balance(&quote_vault).await, 60_000_000);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 0);
+    assert_eq!(env.listing(&fixture.listing).await.proceeds_accrued, 60_000_000);
+
+    let ix = claim_proceeds_ix(&env, &fixture);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 60_000_000);
+    assert_eq!(env.token_balance(&quote_vault).await, 0);
+    assert_eq!(env.listing(&fixture.listing).await.proceeds_accrued, 0);
+}
+
+/// A claim through anything but the SPL Token program is refused and the
+/// accrued proceeds stay in the quote vault.
+#[tokio::test]
+async fn test_claim_proceeds_rejects_fake_token_program() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let ix = enable_proceeds_vault_ix(&env, &fixture);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    let quote_vault = get_associated_token_address(&fixture.vault_authority, &fixture.quote_mint);
+
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 40_000_000);
+    ix.accounts[2] = AccountMeta::new(quote_vault, false);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+
+    let mut ix = claim_proceeds_ix(&env, &fixture);
+    ix.accounts[5] = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(instruction_error(err), Some(InstructionError::IncorrectProgramId));
+
+    assert_eq!(env.token_balance(&quote_vault).await, 40_000_000);
+    assert_eq!(env.listing(&fixture.listing).await.proceeds_accrued, 40_000_000);
+}
+
+/// Cancelling a partially filled listing returns both the unsold base and the
+/// accrued proceeds to the seller.
+#[tokio::test]
+async fn test_cancel_pays_out_accrued_proceeds() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let ix = enable_proceeds_vault_ix(&env, &fixture);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    let quote_vault = get_associated_token_address(&fixture.vault_authority, &fixture.quote_mint);
+
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 40_000_000);
+    ix.accounts[2] = AccountMeta::new(quote_vault, false);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    let seller_base_before = env.token_balance(&fixture.seller_base).await;
+
+    let mut ix = common::cancel_ix(&env.program_id, &fixture);
+    ix.accounts.push(AccountMeta::new(quote_vault, false));
+    ix.accounts.push(AccountMeta::new(fixture.seller_quote, false));
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!(listing.status(), ListingStatus::Cancelled);
+    assert_eq!(listing.proceeds_accrued, 0);
+    assert_eq!(env.token_balance(&fixture.seller_base).await - seller_base_before, listing.remaining());
+    assert_eq!(env.token_balance(&fixture.vault).await, 0);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 40_000_000);
+    assert_eq!(env.token_balance(&quote_vault).await, 0);
+}
+
+/// Proceeds behind a vesting cliff cannot be claimed until it passes.
+#[tokio::test]
+async fn test_claim_proceeds_waits_for_unlock_ts() {
+    let mut env = common::start().await;
+    let clock: Clock = env.ctx.banks_client.get_sysvar().await.unwrap();
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            proceeds_unlock_ts: clock.unix_timestamp + 100,
+            ..ListingArgs::default()
+        })
+        .await;
+    let ix = enable_proceeds_vault_ix(&env, &fixture);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    let quote_vault = get_associated_token_address(&fixture.vault_authority, &fixture.quote_mint);
+
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 40_000_000);
+    ix.accounts[2] = AccountMeta::new(quote_vault, false);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+
+    let ix = claim_proceeds_ix(&env, &fixture);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::ProceedsLocked as u32));
+    assert_eq!(env.token_balance(&quote_vault).await, 40_000_000);
+
+    env.advance_clock(101).await;
+    let ix = claim_proceeds_ix(&env, &fixture);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 40_000_000);
+    assert_eq!(env.listing(&fixture.listing).await.proceeds_accrued, 0);
+}
@@ -0,0 +1,654 @@
+//! Tests for purchasing from a listing.
+
+mod common;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use common::{custom_error_code, ListingArgs};
+use escrow_program::{
+    find_delegation_address, EscrowError, EscrowInstruction, ListingStatus, SimulatedPurchase, MAX_RELAYER_TIP_LAMPORTS,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::{
+    account::AccountSharedData,
+    signature::{Keypair, Signer},
+};
+use spl_token::state::{Account as TokenAccount, AccountState};
+
+/// A full purchase swaps quote for base and completes the listing.
+#[tokio::test]
+async fn test_purchase_full_listing() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let fixture = env.setup_active_listing(args.clone()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    env.purchase(&fixture, &buyer, args.quantity).await.unwrap();
+
+    // 100 tokens at 1.0 quote each.
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 100_000_000);
+    assert_eq!(env.token_balance(&buyer.base_account).await, args.quantity);
+    assert_eq!(env.token_balance(&fixture.vault).await, 0);
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!(listing.filled, args.quantity);
+    assert_eq!(listing.status(), ListingStatus::Completed);
+}
+
+/// Passing the vault as the seller's quote destination is rejected.
+#[tokio::test]
+async fn test_purchase_rejects_seller_quote_aliasing_vault() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 10_000_000);
+    ix.accounts[2] = AccountMeta::new(fixture.vault, false);
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+
+    assert_eq!(custom_error_code(err), Some(EscrowError::DuplicateAccount as u32));
+}
+
+/// `PurchaseFraction` buys the given share of the remaining inventory.
+#[tokio::test]
+async fn test_purchase_fraction_of_remaining() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        price_per_token: 2_000_000,
+        quantity: 80_000_000,
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_active_listing(args).await;
+    let buyer = env.setup_buyer(&fixture, 1_000_000_000).await;
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 0);
+    ix.data = EscrowInstruction::PurchaseFraction { bps: 2_500 }.try_to_vec().unwrap();
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+
+    // 25% of 80 tokens is 20 tokens, at 2.0 quote each.
+    assert_eq!(env.token_balance(&buyer.base_account).await, 20_000_000);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 40_000_000);
+    assert_eq!(env.listing(&fixture.listing).await.filled, 20_000_000);
+}
+
+/// Basis points above 10_000 are rejected.
+#[tokio::test]
+async fn test_purchase_fraction_rejects_out_of_range_bps() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 1_000_000_000).await;
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 0);
+    ix.data = EscrowInstruction::PurchaseFraction { bps: 10_001 }.try_to_vec().unwrap();
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidBasisPoints as u32));
+}
+
+/// Fills exactly at the min and max bounds succeed; fills outside them are rejected.
+#[tokio::test]
+async fn test_purchase_respects_fill_bps_bounds() {
+    let mut env = common::start().await;
+    // Fills must be between 10% and 40% of 100 tokens.
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            min_fill_bps: 1_000,
+            max_fill_bps: 4_000,
+            ..ListingArgs::default()
+        })
+        .await;
+    let buyer = env.setup_buyer(&fixture, 1_000_000_000).await;
+
+    let err = env.purchase(&fixture, &buyer, 9_999_999).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::FillBelowMinimum as u32));
+    let err = env.purchase(&fixture, &buyer, 40_000_001).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::FillAboveMaximum as u32));
+
+    env.purchase(&fixture, &buyer, 10_000_000).await.unwrap();
+    env.purchase(&fixture, &buyer, 40_000_000).await.unwrap();
+    assert_eq!(env.token_balance(&buyer.base_account).await, 50_000_000);
+}
+
+/// A fill paying less than `min_fill_quote` is rejected.
+#[tokio::test]
+async fn test_purchase_below_min_fill_quote_rejected() {
+    let mut env = common::start().await;
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            min_fill_quote: 5_000_000,
+            ..ListingArgs::default()
+        })
+        .await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    // 4.999999 tokens at 1.0 quote each.
+    let err = env.purchase(&fixture, &buyer, 4_999_999).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::BelowMinimumQuoteFill as u32));
+    assert_eq!(env.token_balance(&buyer.quote_account).await, 100_000_000);
+}
+
+/// Fills at or above `min_fill_quote` succeed, as does a final fill of a smaller remainder.
+#[tokio::test]
+async fn test_purchase_at_min_fill_quote_succeeds() {
+    let mut env = common::start().await;
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            min_fill_quote: 5_000_000,
+            ..ListingArgs::default()
+        })
+        .await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    env.purchase(&fixture, &buyer, 5_000_000).await.unwrap();
+    env.purchase(&fixture, &buyer, 92_000_000).await.unwrap();
+    env.purchase(&fixture, &buyer, 3_000_000).await.unwrap();
+    assert_eq!(env.token_balance(&buyer.base_account).await, 100_000_000);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Completed);
+}
+
+/// A listing account carrying bytes past `Listing::LEN` is rejected rather than
+/// having the trailing data silently ignored.
+#[tokio::test]
+async fn test_purchase_rejects_listing_with_trailing_bytes() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    let mut account = env.ctx.banks_client.get_account(fixture.listing).await.unwrap().unwrap();
+    account.data.extend_from_slice(&[0xAB; 8]);
+    env.ctx.set_account(&fixture.listing, &account.into());
+
+    let err = env.purchase(&fixture, &buyer, 10_000_000).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::AccountLengthMismatch as u32));
+    assert_eq!(env.token_balance(&buyer.base_account).await, 0);
+}
+
+/// `PurchaseCreatingSellerQuote` creates a missing seller quote ATA at the
+/// buyer's expense and pays the seller into it.
+#[tokio::test]
+async fn test_purchase_creates_missing_seller_quote_account() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    // Drop the seller's quote ATA so it no longer exists.
+    env.ctx.set_account(&fixture.seller_quote, &AccountSharedData::default());
+    assert!(env.ctx.banks_client.get_account(fixture.seller_quote).await.unwrap().is_none());
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 10_000_000);
+    ix.data = EscrowInstruction::PurchaseCreatingSellerQuote { quantity: 10_000_000 }
+        .try_to_vec()
+        .unwrap();
+    ix.accounts.splice(
+        10..10,
+        [
+            AccountMeta::new_readonly(fixture.seller.pubkey(), false),
+            AccountMeta::new_readonly(fixture.quote_mint, false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        ],
+    );
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 10_000_000);
+    assert_eq!(env.token_balance(&buyer.base_account).await, 10_000_000);
+}
+
+/// When the base transfer fails after the quote transfer has run, the whole
+/// purchase is rolled back and the buyer keeps their quote.
+#[tokio::test]
+async fn test_failed_base_transfer_rolls_back_quote() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    // A frozen vault passes the program's checks but fails the token transfer.
+    let mut account = env.ctx.banks_client.get_account(fixture.vault).await.unwrap().unwrap();
+    let mut vault = TokenAccount::unpack(&account.data).unwrap();
+    vault.state = AccountState::Frozen;
+    TokenAccount::pack(vault, &mut account.data).unwrap();
+    env.ctx.set_account(&fixture.vault, &account.into());
+
+    env.purchase(&fixture, &buyer, 10_000_000).await.unwrap_err();
+
+    assert_eq!(env.token_balance(&buyer.quote_account).await, 100_000_000);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 0);
+    assert_eq!(env.token_balance(&buyer.base_account).await, 0);
+    assert_eq!(env.listing(&fixture.listing).await.filled, 0);
+}
+
+/// A look-alike vault with the right owner and mint, but not at the vault
+/// authority's ATA address, is rejected.
+#[tokio::test]
+async fn test_purchase_rejects_look_alike_vault() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    let account = env.ctx.banks_client.get_account(fixture.vault).await.unwrap().unwrap();
+    let look_alike = Pubkey::new_unique();
+    env.ctx.set_account(&look_alike, &account.into());
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 10_000_000);
+    ix.accounts[6] = AccountMeta::new(look_alike, false);
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+
+    assert_eq!(custom_error_code(err), Some(EscrowError::MintMismatch as u32));
+    assert_eq!(env.token_balance(&buyer.quote_account).await, 100_000_000);
+}
+
+/// A buyer quote account in a different mint from the seller's is rejected before any transfer.
+#[tokio::test]
+async fn test_purchase_rejects_mismatched_quote_mints() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    let other_mint = env.create_mint(6).await;
+    let other_quote = env.create_ata(&buyer.keypair.pubkey(), &other_mint).await;
+    env.mint_to(&other_mint, &other_quote, 100_000_000).await;
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 10_000_000);
+    ix.accounts[3] = AccountMeta::new(other_quote, false);
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+
+    assert_eq!(custom_error_code(err), Some(EscrowError::MintMismatch as u32));
+    assert_eq!(env.token_balance(&other_quote).await, 100_000_000);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 0);
+}
+
+/// A vault carrying a close authority is refused.
+#[tokio::test]
+async fn test_purchase_rejects_vault_with_close_authority() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    let mut account = env.ctx.banks_client.get_account(fixture.vault).await.unwrap().unwrap();
+    let mut vault = TokenAccount::unpack(&account.data).unwrap();
+    vault.close_authority = COption::Some(Pubkey::new_unique());
+    TokenAccount::pack(vault, &mut account.data).unwrap();
+    env.ctx.set_account(&fixture.vault, &account.into());
+
+    let err = env.purchase(&fixture, &buyer, 10_000_000).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::VaultCloseAuthoritySet as u32));
+}
+
+/// `PurchaseUpTo` clamps an oversized request to what remains and reports the fill.
+#[tokio::test]
+async fn test_purchase_up_to_clamps_to_remaining() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 200_000_000).await;
+    env.purchase(&fixture, &buyer, 70_000_000).await.unwrap();
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 0);
+    ix.data = EscrowInstruction::PurchaseUpTo {
+        max_quantity: 50_000_000,
+    }
+    .try_to_vec()
+    .unwrap();
+    let data = env.simulate_return_data(&[ix.clone()], &[&buyer.keypair]).await.unwrap();
+    assert_eq!(u64::from_le_bytes(data.try_into().unwrap()), 30_000_000);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+
+    assert_eq!(env.token_balance(&buyer.base_account).await, 100_000_000);
+    assert_eq!(env.token_balance(&buyer.quote_account).await, 100_000_000);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Completed);
+}
+
+/// The buyer's tip lands with the relayer paying the transaction fee.
+#[tokio::test]
+async fn test_purchase_with_relayer_tip_pays_fee_payer() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let relayer = env.ctx.payer.pubkey();
+    let tip = 1_000_000;
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 10_000_000);
+    ix.data = EscrowInstruction::PurchaseWithRelayerTip {
+        quantity: 10_000_000,
+        tip_lamports: tip,
+    }
+    .try_to_vec()
+    .unwrap();
+    ix.accounts.push(AccountMeta::new(relayer, false));
+    let relayer_before = env.lamports(&relayer).await;
+    env.process(&[ix.clone()], &[&buyer.keypair]).await.unwrap();
+
+    assert_eq!(env.token_balance(&buyer.base_account).await, 10_000_000);
+    // The relayer nets the tip minus the transaction fee it paid.
+    assert!(env.lamports(&relayer).await > relayer_before);
+
+    ix.data = EscrowInstruction::PurchaseWithRelayerTip {
+        quantity: 10_000_000,
+        tip_lamports: MAX_RELAYER_TIP_LAMPORTS + 1,
+    }
+    .try_to_vec()
+    .unwrap();
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::RelayerTipTooLarge as u32));
+}
+
+/// Reaching `max_quote_raise` completes the listing; the seller then reclaims the leftovers.
+#[tokio::test]
+async fn test_purchase_completes_at_raise_cap() {
+    let mut env = common::start().await;
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            max_quote_raise: 30_000_000,
+            ..ListingArgs::default()
+        })
+        .await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    env.purchase(&fixture, &buyer, 20_000_000).await.unwrap();
+    env.refresh_blockhash().await;
+    let err = env.purchase(&fixture, &buyer, 20_000_000).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::RaiseCapExceeded as u32));
+
+    env.purchase(&fixture, &buyer, 10_000_000).await.unwrap();
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!(listing.quote_received, 30_000_000);
+    assert_eq!(listing.status(), ListingStatus::Completed);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 30_000_000);
+
+    let ix = common::cancel_ix(&env.program_id, &fixture);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    assert_eq!(env.token_balance(&fixture.seller_base).await, 70_000_000);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Cancelled);
+}
+
+/// Cheap checks reject a purchase before its token accounts are ever unpacked.
+#[tokio::test]
+async fn test_purchase_rejects_before_unpacking_token_accounts() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let fixture = env.setup_active_listing(args.clone()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    // None of these placeholder accounts could be unpacked as a token account.
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, args.quantity + 1);
+    for index in 2..=4 {
+        ix.accounts[index] = AccountMeta::new(Pubkey::new_unique(), false);
+    }
+    let err = env.process(&[ix.clone()], &[&buyer.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InsufficientQuantity as u32));
+
+    ix.accounts[9] = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+    assert_eq!(common::instruction_error(err), Some(InstructionError::IncorrectProgramId));
+}
+
+/// `ApproveDelegate` for `quantity` tokens at up to 1.0 quote each.
+fn approve_delegate_ix(
+    env: &common::TestEnv,
+    fixture: &common::ListingFixture,
+    buyer: &common::Buyer,
+    delegate: &Pubkey,
+    quantity: u64,
+) -> Instruction {
+    let buyer_key = buyer.keypair.pubkey();
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new(buyer_key, true),
+            AccountMeta::new_readonly(fixture.listing, false),
+            AccountMeta::new(find_delegation_address(&env.program_id, &fixture.listing, &buyer_key).0, false),
+            AccountMeta::new(buyer.quote_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::ApproveDelegate {
+            delegate: *delegate,
+            quantity,
+            max_price: 1_000_000,
+        }
+        .try_to_vec()
+        .unwrap(),
+    }
+}
+
+/// `ApproveDelegate` for one delegate, 10 tokens at up to 1.0 quote each.
+async fn approve_delegate(
+    env: &mut common::TestEnv,
+    fixture: &common::ListingFixture,
+    buyer: &common::Buyer,
+) -> Keypair {
+    let delegate = env.funded_keypair(1_000_000_000).await;
+    let ix = approve_delegate_ix(env, fixture, buyer, &delegate.pubkey(), 10_000_000);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    delegate
+}
+
+fn delegated_purchase_ix(
+    env: &common::TestEnv,
+    fixture: &common::ListingFixture,
+    buyer: &common::Buyer,
+    submitter: &Pubkey,
+    quantity: u64,
+) -> Instruction {
+    let buyer_key = buyer.keypair.pubkey();
+    let mut ix = common::purchase_ix(&env.program_id, fixture, buyer, quantity);
+    ix.accounts[0] = AccountMeta::new_readonly(buyer_key, false);
+    ix.accounts.push(AccountMeta::new(*submitter, true));
+    ix.accounts.push(AccountMeta::new(find_delegation_address(&env.program_id, &fixture.listing, &buyer_key).0, false));
+    ix.data = EscrowInstruction::PurchaseWithDelegate { quantity }.try_to_vec().unwrap();
+    ix
+}
+
+/// The buyer's recorded delegate buys for them without the buyer signing, and
+/// the delegation's quantity caps the spend.
+#[tokio::test]
+async fn test_purchase_with_delegate_pulls_approved_quote() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let delegate = approve_delegate(&mut env, &fixture, &buyer).await;
+
+    let over = delegated_purchase_ix(&env, &fixture, &buyer, &delegate.pubkey(), 20_000_000);
+    let err = env.process(&[over], &[&delegate]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InsufficientDelegation as u32));
+
+    let within = delegated_purchase_ix(&env, &fixture, &buyer, &delegate.pubkey(), 6_000_000);
+    env.process(&[within], &[&delegate]).await.unwrap();
+    assert_eq!(env.token_balance(&buyer.base_account).await, 6_000_000);
+    assert_eq!(env.token_balance(&buyer.quote_account).await, 94_000_000);
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 6_000_000);
+
+    // 4 of the 10 delegated tokens are left.
+    let rest = delegated_purchase_ix(&env, &fixture, &buyer, &delegate.pubkey(), 5_000_000);
+    let err = env.process(&[rest], &[&delegate]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InsufficientDelegation as u32));
+}
+
+/// Anyone but the recorded delegate or the buyer is refused, and a buyer who
+/// only approved the vault authority has no delegation to spend.
+#[tokio::test]
+async fn test_purchase_with_delegate_requires_recorded_delegate() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    approve_delegate(&mut env, &fixture, &buyer).await;
+
+    let stranger = env.funded_keypair(1_000_000_000).await;
+    let ix = delegated_purchase_ix(&env, &fixture, &buyer, &stranger.pubkey(), 1_000_000);
+    let err = env.process(&[ix], &[&stranger]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::IncorrectAuthority as u32));
+
+    let other = env.setup_buyer(&fixture, 100_000_000).await;
+    let approve = spl_token::instruction::approve(
+        &spl_token::id(),
+        &other.quote_account,
+        &fixture.vault_authority,
+        &other.keypair.pubkey(),
+        &[],
+        10_000_000,
+    )
+    .unwrap();
+    env.process(&[approve], &[&other.keypair]).await.unwrap();
+    let ix = delegated_purchase_ix(&env, &fixture, &other, &stranger.pubkey(), 1_000_000);
+    let err = env.process(&[ix], &[&stranger]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidDelegation as u32));
+    assert_eq!(env.token_balance(&other.quote_account).await, 100_000_000);
+}
+
+/// `ApproveDelegate` neither replaces nor revokes an approval it did not
+/// make, such as a pending claim's.
+#[tokio::test]
+async fn test_approve_delegate_keeps_other_quote_delegate() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let approve = spl_token::instruction::approve(
+        &spl_token::id(),
+        &buyer.quote_account,
+        &fixture.vault_authority,
+        &buyer.keypair.pubkey(),
+        &[],
+        40_000_000,
+    )
+    .unwrap();
+    env.process(&[approve], &[&buyer.keypair]).await.unwrap();
+
+    let delegate = Pubkey::new_unique();
+    let ix = approve_delegate_ix(&env, &fixture, &buyer, &delegate, 10_000_000);
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::QuoteDelegateInUse as u32));
+
+    let ix = approve_delegate_ix(&env, &fixture, &buyer, &delegate, 0);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    let quote_account = env.ctx.banks_client.get_account(buyer.quote_account).await.unwrap().unwrap();
+    let quote_account = TokenAccount::unpack(&quote_account.data).unwrap();
+    assert_eq!(quote_account.delegate, COption::Some(fixture.vault_authority));
+    assert_eq!(quote_account.delegated_amount, 40_000_000);
+}
+
+/// Each non-active status rejects purchases with its own error.
+#[tokio::test]
+async fn test_purchase_reports_listing_status() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+
+    let unfunded = env.setup_listing(args.clone()).await;
+    let buyer = env.setup_buyer(&unfunded, 100_000_000).await;
+    let err = env.purchase(&unfunded, &buyer, 10_000_000).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::ListingNotFunded as u32));
+
+    let cancelled = env.setup_active_listing(args.clone()).await;
+    env.cancel(&cancelled).await.unwrap();
+    let buyer = env.setup_buyer(&cancelled, 100_000_000).await;
+    let err = env.purchase(&cancelled, &buyer, 10_000_000).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::ListingCancelled as u32));
+
+    let completed = env.setup_active_listing(args.clone()).await;
+    let buyer = env.setup_buyer(&completed, 200_000_000).await;
+    env.purchase(&completed, &buyer, args.quantity).await.unwrap();
+    let err = env.purchase(&completed, &buyer, 10_000_000).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::ListingAlreadyCompleted as u32));
+}
+
+/// `SimulatePurchase` previews exactly what the matching purchase then moves.
+#[tokio::test]
+async fn test_simulate_purchase_matches_purchase() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    env.initialize_config(treasury).await;
+    let mut note = [0u8; 64];
+    note[..8].copy_from_slice(b"min 10k!");
+    let fixture = env
+        .setup_active_listing(ListingArgs {
+            price_per_token: 10_000,
+            quantity: 100_000,
+            treasury: Some(treasury),
+            taker_fee_bps: 250,
+            note,
+            ..ListingArgs::default()
+        })
+        .await;
+    let treasury_quote = env.create_ata(&treasury, &fixture.quote_mint).await;
+    let buyer = env.setup_buyer(&fixture, 1_000).await;
+
+    let program_id = env.program_id;
+    let listing = fixture.listing;
+    let simulate_ix = |quantity| Instruction {
+        program_id,
+        accounts: vec![AccountMeta::new_readonly(listing, false)],
+        data: EscrowInstruction::SimulatePurchase { quantity }.try_to_vec().unwrap(),
+    };
+    let data = env.simulate_return_data(&[simulate_ix(33_333)], &[]).await.unwrap();
+    let simulated = SimulatedPurchase::try_from_slice(&data).unwrap();
+    // 33_333 base units cost 333 quote (rounded down) plus a 2.5% taker fee of 8.
+    assert_eq!(simulated.quote_in, 341);
+    assert_eq!(simulated.note, note);
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 33_333);
+    ix.accounts.push(AccountMeta::new(treasury_quote, false));
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+
+    assert_eq!(simulated.base_out, env.token_balance(&buyer.base_account).await);
+    assert_eq!(simulated.quote_in, 1_000 - env.token_balance(&buyer.quote_account).await);
+    assert_eq!(simulated.taker_fee, env.token_balance(&treasury_quote).await);
+    assert!(!simulated.would_complete);
+
+    let data = env.simulate_return_data(&[simulate_ix(66_667)], &[]).await.unwrap();
+    assert!(SimulatedPurchase::try_from_slice(&data).unwrap().would_complete);
+}
+
+/// With `auto_complete_after_fills`, the listing completes after that many
+/// purchases with inventory still in the vault, which the seller then reclaims.
+#[tokio::test]
+async fn test_auto_complete_after_fills() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        auto_complete_after_fills: 3,
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_active_listing(args.clone()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    for quantity in [10_000_000, 11_000_000, 12_000_000] {
+        env.purchase(&fixture, &buyer, quantity).await.unwrap();
+    }
+
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!(listing.status(), ListingStatus::Completed);
+    assert_eq!(listing.fill_count, 3);
+    assert_eq!(env.token_balance(&fixture.vault).await, args.quantity - 33_000_000);
+
+    let err = env.purchase(&fixture, &buyer, 13_000_000).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::ListingAlreadyCompleted as u32));
+
+    env.cancel(&fixture).await.unwrap();
+    assert_eq!(env.token_balance(&fixture.vault).await, 0);
+    assert_eq!(env.token_balance(&fixture.seller_base).await, args.quantity - 33_000_000);
+}
+
+/// `auto_complete_after_fills: 1` makes a partial-fill listing single-fill: the
+/// first purchase completes it and the seller reclaims the rest.
+#[tokio::test]
+async fn test_single_fill_listing_completes_after_partial_fill() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        allow_partial: true,
+        auto_complete_after_fills: 1,
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_active_listing(args.clone()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    env.purchase(&fixture, &buyer, 25_000_000).await.unwrap();
+
+    let listing = env.listing(&fixture.listing).await;
+    assert_eq!(listing.status(), ListingStatus::Completed);
+    assert_eq!(listing.filled, 25_000_000);
+    let err = env.purchase(&fixture, &buyer, 5_000_000).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::ListingAlreadyCompleted as u32));
+
+    env.cancel(&fixture).await.unwrap();
+    assert_eq!(env.token_balance(&fixture.seller_base).await, args.quantity - 25_000_000);
+}
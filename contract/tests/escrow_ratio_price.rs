@@ -0,0 +1,91 @@
+//! Tests for listings priced by a raw quote/base ratio.
+
+mod common;
+
+use common::{custom_error_code, ListingArgs, ListingFixture};
+use escrow_program::EscrowError;
+use solana_sdk::signature::Signer;
+
+/// A 9-decimal base sold for a 6-decimal quote at 1.5 quote per whole token.
+#[tokio::test]
+async fn test_ratio_price_base_more_decimals_than_quote() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        price_per_token: 0,
+        quantity: 2_000_000_000,
+        base_decimals: 9,
+        quote_decimals: 6,
+        quote_per_base_num: 1_500_000,
+        quote_per_base_den: 1_000_000_000,
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_active_listing(args).await;
+    let buyer = env.setup_buyer(&fixture, 10_000_000).await;
+
+    env.purchase(&fixture, &buyer, 500_000_000).await.unwrap();
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 750_000);
+    assert_eq!(env.token_balance(&buyer.base_account).await, 500_000_000);
+    // The stored display price is the same 1.5 quote per whole token.
+    assert_eq!(env.listing(&fixture.listing).await.price_per_token, 1_500_000);
+}
+
+/// A 0-decimal base sold for a 9-decimal quote at 2.5 quote per token.
+#[tokio::test]
+async fn test_ratio_price_base_fewer_decimals_than_quote() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        price_per_token: 0,
+        quantity: 10,
+        base_decimals: 0,
+        quote_decimals: 9,
+        quote_per_base_num: 5_000_000_000,
+        quote_per_base_den: 2,
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_active_listing(args).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000_000).await;
+
+    env.purchase(&fixture, &buyer, 3).await.unwrap();
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 7_500_000_000);
+    assert_eq!(env.token_balance(&buyer.base_account).await, 3);
+}
+
+/// Fractional quote amounts round down, as with `price_per_token`.
+#[tokio::test]
+async fn test_ratio_price_rounds_down() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        price_per_token: 0,
+        quantity: 1_000,
+        base_decimals: 2,
+        quote_decimals: 8,
+        quote_per_base_num: 10,
+        quote_per_base_den: 3,
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_active_listing(args).await;
+    let buyer = env.setup_buyer(&fixture, 1_000_000).await;
+
+    env.purchase(&fixture, &buyer, 100).await.unwrap();
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 333);
+}
+
+/// Mixing both pricing modes is rejected.
+#[tokio::test]
+async fn test_ratio_price_rejects_price_per_token() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+    let mixed = ListingArgs {
+        quote_per_base_num: 1,
+        quote_per_base_den: 1,
+        ..ListingArgs::default()
+    };
+    let listing = env.create_listing_account(&fixture.seller).await;
+    let fixture = ListingFixture {
+        listing: listing.pubkey(),
+        ..fixture
+    };
+    let ix = common::initialize_ix(&env.program_id, &fixture, &mixed);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidInstructionData as u32));
+}
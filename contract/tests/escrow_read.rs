@@ -0,0 +1,85 @@
+//! Tests for reading listings through program return data.
+
+mod common;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use common::ListingArgs;
+use escrow_program::{EscrowInstruction, Listing, ListingStatus};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::{get_return_data, invoke, set_return_data},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use solana_program_test::processor;
+use solana_sdk::signature::Signer;
+
+/// Minimal composing program: reads a listing from the escrow via CPI, decodes
+/// it, and re-exposes the bytes as its own return data.
+fn listing_reader_process_instruction(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    _instruction_data: &[u8],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let escrow_program_info = next_account_info(account_info_iter)?;
+    let listing_info = next_account_info(account_info_iter)?;
+
+    let ix = Instruction {
+        program_id: *escrow_program_info.key,
+        accounts: vec![AccountMeta::new_readonly(*listing_info.key, false)],
+        data: EscrowInstruction::ReadListing.try_to_vec().unwrap(),
+    };
+    invoke(&ix, &[listing_info.clone(), escrow_program_info.clone()])?;
+
+    let (returning_program, data) = get_return_data().ok_or(ProgramError::InvalidAccountData)?;
+    if returning_program != *escrow_program_info.key {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Listing::try_from_slice(&data).map_err(|_| ProgramError::InvalidAccountData)?;
+    set_return_data(&data);
+    Ok(())
+}
+
+/// A composing program can read the full listing through `ReadListing`.
+#[tokio::test]
+async fn test_read_listing_via_cpi() {
+    let (mut program_test, program_id) = common::escrow_program_test();
+    let reader_id = Pubkey::new_unique();
+    program_test.add_program(
+        "listing_reader",
+        reader_id,
+        processor!(listing_reader_process_instruction),
+    );
+    let mut env = common::start_with(program_test, program_id).await;
+
+    let args = ListingArgs {
+        listing_id: 42,
+        price_per_token: 2_500_000,
+        quantity: 7_000_000,
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_active_listing(args.clone()).await;
+
+    let ix = Instruction {
+        program_id: reader_id,
+        accounts: vec![
+            AccountMeta::new_readonly(program_id, false),
+            AccountMeta::new_readonly(fixture.listing, false),
+        ],
+        data: vec![],
+    };
+    let data = env.simulate_return_data(&[ix], &[]).await.unwrap();
+    let listing = Listing::try_from_slice(&data).unwrap();
+
+    assert_eq!(listing.seller, fixture.seller.pubkey());
+    assert_eq!(listing.base_mint, fixture.base_mint);
+    assert_eq!(listing.quote_mint, fixture.quote_mint);
+    assert_eq!(listing.vault_authority, fixture.vault_authority);
+    assert_eq!(listing.listing_id, args.listing_id);
+    assert_eq!(listing.price_per_token, args.price_per_token);
+    assert_eq!(listing.quantity, args.quantity);
+    assert_eq!(listing.status(), ListingStatus::Active);
+}
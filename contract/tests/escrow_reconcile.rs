@@ -0,0 +1,121 @@
+//! Tests for reconciling recorded fills against vault balances.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{custom_error_code, ListingArgs, ListingFixture, TestEnv};
+use escrow_program::{find_config_address, EscrowError, EscrowInstruction, ListingStatus};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_sdk::signature::{Keypair, Signer};
+use spl_token::state::Account as TokenAccount;
+
+fn reconcile_ix(program_id: &Pubkey, fixture: &ListingFixture) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new_readonly(fixture.vault, false),
+        ],
+        data: EscrowInstruction::ReconcileListing.try_to_vec().unwrap(),
+    }
+}
+
+fn unfreeze_ix(program_id: &Pubkey, admin: &Pubkey, fixture: &ListingFixture) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new_readonly(fixture.vault, false),
+        ],
+        data: EscrowInstruction::UnfreezeListing.try_to_vec().unwrap(),
+    }
+}
+
+/// Overwrite the vault's token balance, simulating base moved outside a fill.
+async fn set_vault_amount(env: &mut TestEnv, fixture: &ListingFixture, amount: u64) {
+    let mut account = env.ctx.banks_client.get_account(fixture.vault).await.unwrap().unwrap();
+    let mut vault = TokenAccount::unpack(&account.data).unwrap();
+    vault.amount = amount;
+    TokenAccount::pack(vault, &mut account.data).unwrap();
+    env.ctx.set_account(&fixture.vault, &account.into());
+}
+
+/// Freeze an active listing by draining 40 tokens from its vault, returning the config admin.
+async fn frozen_listing(env: &mut TestEnv, fixture: &ListingFixture) -> Keypair {
+    let admin = env.initialize_config(Pubkey::new_unique()).await;
+    set_vault_amount(env, fixture, 60_000_000).await;
+    let ix = reconcile_ix(&env.program_id, fixture);
+    env.process(&[ix], &[]).await.unwrap();
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Frozen);
+    admin
+}
+
+/// A vault matching the recorded fills leaves the listing active.
+#[tokio::test]
+async fn test_reconcile_consistent_listing_stays_active() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    env.purchase(&fixture, &buyer, 30_000_000).await.unwrap();
+
+    let ix = reconcile_ix(&env.program_id, &fixture);
+    env.process(&[ix], &[]).await.unwrap();
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Active);
+}
+
+/// Base drained from the vault outside a fill freezes the listing and blocks purchases.
+#[tokio::test]
+async fn test_reconcile_freezes_drained_listing() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    let mut account = env.ctx.banks_client.get_account(fixture.vault).await.unwrap().unwrap();
+    let mut vault = TokenAccount::unpack(&account.data).unwrap();
+    vault.amount -= 40_000_000;
+    TokenAccount::pack(vault, &mut account.data).unwrap();
+    env.ctx.set_account(&fixture.vault, &account.into());
+
+    let ix = reconcile_ix(&env.program_id, &fixture);
+    env.process(&[ix], &[]).await.unwrap();
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Frozen);
+
+    let err = env.purchase(&fixture, &buyer, 10_000_000).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::ListingFrozen as u32));
+    assert_eq!(env.token_balance(&buyer.quote_account).await, 100_000_000);
+}
+
+/// Once the shortfall is topped back up, the admin returns the listing to `Active`.
+#[tokio::test]
+async fn test_unfreeze_reconciled_listing() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let admin = frozen_listing(&mut env, &fixture).await;
+
+    set_vault_amount(&mut env, &fixture, 100_000_000).await;
+    let ix = unfreeze_ix(&env.program_id, &admin.pubkey(), &fixture);
+    env.process(&[ix], &[&admin]).await.unwrap();
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Active);
+
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    env.purchase(&fixture, &buyer, 10_000_000).await.unwrap();
+}
+
+/// Unfreezing is refused while the vault still holds less than what remains unfilled.
+#[tokio::test]
+async fn test_unfreeze_rejects_diverged_listing() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let admin = frozen_listing(&mut env, &fixture).await;
+
+    let ix = unfreeze_ix(&env.program_id, &admin.pubkey(), &fixture);
+    let err = env.process(&[ix], &[&admin]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::ReconciliationFailed as u32));
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Frozen);
+}
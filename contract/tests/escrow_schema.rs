@@ -0,0 +1,91 @@
+//! Tests for the exported `Listing` schema.
+
+use escrow_program::{listing_schema, Listing, LISTING_FIELDS};
+
+const EXPECTED_FIELDS: &[&str] = &[
+    "seller",
+    "base_mint",
+    "quote_mint",
+    "vault_authority",
+    "price_per_token",
+    "quantity",
+    "filled",
+    "listing_id",
+    "flags",
+    "vault_bump",
+    "status",
+    "base_decimals",
+    "fee_payment_method",
+    "fee_amount_paid",
+    "x402_payload_hash",
+    "fee_treasury",
+    "required_attestation_issuer",
+    "vault_count",
+    "tranche_interval",
+    "activated_at",
+    "cancel_authority",
+    "min_fill_bps",
+    "max_fill_bps",
+    "oracle",
+    "spread_bps",
+    "interest_count",
+    "min_fill_quote",
+    "allowed_buyer",
+    "auction_end",
+    "high_bid",
+    "high_bidder",
+    "fee_mint",
+    "trigger_price",
+    "max_quote_raise",
+    "quote_received",
+    "tick_size",
+    "canonical_id",
+    "quote_per_base_num",
+    "quote_per_base_den",
+    "taker_fee_bps",
+    "category",
+    "note",
+    "allowlist_root",
+    "deposit_deadline",
+    "proceeds_accrued",
+    "auto_complete_after_fills",
+    "fill_count",
+    "max_slippage_bps",
+    "claim_count",
+    "claims_desired",
+    "claims_close_at",
+    "claims_supply",
+    "cancellation_fee",
+    "proceeds_unlock_ts",
+    "max_oracle_staleness_secs",
+];
+
+fn type_size(ty: &str) -> usize {
+    match ty {
+        "u8" => 1,
+        "u16" => 2,
+        "i32" | "u32" => 4,
+        "u64" | "i64" => 8,
+        "pubkey" | "[u8; 32]" => 32,
+        "[u8; 64]" => 64,
+        other => panic!("unexpected type {other}"),
+    }
+}
+
+/// The schema lists every `Listing` field in order, and its sizes add up to `Listing::LEN`.
+#[test]
+fn test_listing_schema_matches_layout() {
+    let names: Vec<_> = LISTING_FIELDS.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, EXPECTED_FIELDS);
+    let size: usize = LISTING_FIELDS.iter().map(|(_, ty)| type_size(ty)).sum();
+    assert_eq!(size, Listing::LEN);
+
+    let schema = listing_schema();
+    assert!(schema.starts_with(&format!(r#"{{"name":"Listing","size":{},"fields":["#, Listing::LEN)));
+    let mut offset = 0;
+    for name in EXPECTED_FIELDS {
+        let entry = format!(r#"{{"name":"{name}","#);
+        let position = schema[offset..].find(&entry).unwrap_or_else(|| panic!("{name} missing or out of order"));
+        offset += position + entry.len();
+    }
+}
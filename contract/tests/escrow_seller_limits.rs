@@ -0,0 +1,162 @@
+//! Tests for the admin caps on seller listings.
+
+mod common;
+
+use borsh::BorshDeserialize;
+use common::{custom_error_code, ListingArgs, ListingFixture};
+use escrow_program::{find_seller_stats_address, EscrowError, SellerStats};
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey};
+use solana_sdk::signature::Signer;
+use spl_associated_token_account::get_associated_token_address;
+
+/// A fresh listing account for `fixture`'s seller and mints under a new listing id.
+async fn next_listing(env: &mut common::TestEnv, fixture: &ListingFixture, listing_id: u64) -> ListingFixture {
+    let seller = fixture.seller.insecure_clone();
+    let listing = env.create_listing_account(&seller).await;
+    let (vault_authority, _) = common::vault_authority_address(&env.program_id, &seller.pubkey(), listing_id);
+    let vault = get_associated_token_address(&vault_authority, &fixture.base_mint);
+    ListingFixture {
+        seller,
+        listing: listing.pubkey(),
+        listing_id,
+        vault_authority,
+        vault,
+        base_mint: fixture.base_mint,
+        quote_mint: fixture.quote_mint,
+        seller_base: fixture.seller_base,
+        seller_quote: fixture.seller_quote,
+    }
+}
+
+async fn active_listings(env: &mut common::TestEnv, seller: &Pubkey) -> u32 {
+    let (stats, _) = find_seller_stats_address(&env.program_id, seller);
+    let account = env.ctx.banks_client.get_account(stats).await.unwrap().unwrap();
+    SellerStats::try_from_slice(&account.data).unwrap().active_listings
+}
+
+/// Sellers can open listings up to the cap; one more is rejected until a
+/// listing is cancelled and frees its slot.
+#[tokio::test]
+async fn test_listings_beyond_cap_rejected() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    env.set_max_active_listings(&admin, 2).await.unwrap();
+
+    let seller = env.funded_keypair(1_000_000_000).await;
+    let args = ListingArgs {
+        price_per_token: 10_000,
+        quantity: 100_000,
+        treasury: Some(treasury),
+        seller_stats: true,
+        ..ListingArgs::default()
+    };
+    let first = env.setup_listing_for(seller, args.clone()).await;
+    let second = next_listing(&mut env, &first, 2).await;
+    let second_args = ListingArgs {
+        listing_id: 2,
+        ..args.clone()
+    };
+    let ix = common::initialize_ix(&env.program_id, &second, &second_args);
+    env.process(&[ix], &[&second.seller]).await.unwrap();
+    assert_eq!(active_listings(&mut env, &first.seller.pubkey()).await, 2);
+
+    let third = next_listing(&mut env, &first, 3).await;
+    let third_args = ListingArgs {
+        listing_id: 3,
+        ..args
+    };
+    let ix = common::initialize_ix(&env.program_id, &third, &third_args);
+    let err = env.process(&[ix], &[&third.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::TooManyActiveListings as u32));
+
+    let (stats, _) = find_seller_stats_address(&env.program_id, &second.seller.pubkey());
+    let mut ix = common::cancel_ix(&env.program_id, &second);
+    ix.accounts.push(AccountMeta::new(stats, false));
+    env.process(&[ix], &[&second.seller]).await.unwrap();
+    assert_eq!(active_listings(&mut env, &first.seller.pubkey()).await, 1);
+
+    env.refresh_blockhash().await;
+    let ix = common::initialize_ix(&env.program_id, &third, &third_args);
+    env.process(&[ix], &[&third.seller]).await.unwrap();
+    assert_eq!(active_listings(&mut env, &first.seller.pubkey()).await, 2);
+}
+
+/// Listings below and at `max_listing_quantity` are accepted; one unit above is rejected.
+#[tokio::test]
+async fn test_listing_quantity_cap() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    env.set_max_listing_quantity(&admin, 100_000).await.unwrap();
+
+    let args = ListingArgs {
+        price_per_token: 10_000,
+        quantity: 99_999,
+        treasury: Some(treasury),
+        ..ListingArgs::default()
+    };
+    let below = env.setup_listing(args.clone()).await;
+    let at_cap = ListingArgs {
+        quantity: 100_000,
+        ..args.clone()
+    };
+    env.setup_listing(at_cap).await;
+
+    let above = next_listing(&mut env, &below, 2).await;
+    let above_args = ListingArgs {
+        listing_id: 2,
+        quantity: 100_001,
+        ..args
+    };
+    let ix = common::initialize_ix(&env.program_id, &above, &above_args);
+    let err = env.process(&[ix], &[&above.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::QuantityTooLarge as u32));
+}
+
+/// Fungible listings at `min_listing_quantity` are accepted; one unit below is rejected.
+#[tokio::test]
+async fn test_listing_quantity_minimum() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    env.set_min_listing_quantity(&admin, 100).await.unwrap();
+
+    let args = ListingArgs {
+        price_per_token: 1_000_000,
+        quantity: 100,
+        treasury: Some(treasury),
+        ..ListingArgs::default()
+    };
+    let at_minimum = env.setup_listing(args.clone()).await;
+
+    let below = next_listing(&mut env, &at_minimum, 2).await;
+    let below_args = ListingArgs {
+        listing_id: 2,
+        quantity: 99,
+        ..args
+    };
+    let ix = common::initialize_ix(&env.program_id, &below, &below_args);
+    let err = env.process(&[ix], &[&below.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::QuantityBelowMinimum as u32));
+}
+
+/// A single-unit NFT (0 decimals, supply 1) is listed despite `min_listing_quantity`.
+#[tokio::test]
+async fn test_listing_quantity_minimum_exempts_nft() {
+    let mut env = common::start().await;
+    let treasury = Pubkey::new_unique();
+    let admin = env.initialize_config(treasury).await;
+    env.set_min_listing_quantity(&admin, 100).await.unwrap();
+
+    let fixture = env
+        .setup_listing(ListingArgs {
+            price_per_token: 100_000_000,
+            quantity: 1,
+            base_decimals: 0,
+            treasury: Some(treasury),
+            ..ListingArgs::default()
+        })
+        .await;
+    assert_eq!(env.listing(&fixture.listing).await.quantity, 1);
+}
@@ -0,0 +1,261 @@
+//! Tests for the listing status history.
+
+mod common;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use common::{ListingArgs, ListingFixture, TestEnv};
+use escrow_program::{
+    find_config_address, find_status_history_address, EscrowInstruction, ListingStatus, StatusHistory,
+    MAX_STATUS_ENTRIES,
+};
+use solana_program::{
+    clock::Clock,
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::signature::Signer;
+use spl_token::state::Account as TokenAccount;
+
+fn enable_ix(env: &TestEnv, fixture: &ListingFixture, history: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new(fixture.seller.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new(*history, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::EnableStatusHistory.try_to_vec().unwrap(),
+    }
+}
+
+/// Deposit and the completing purchase each append to the history started while awaiting deposit.
+#[tokio::test]
+async fn test_status_history_records_full_lifecycle() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let fixture = env.setup_listing(args.clone()).await;
+    let (history, _) = find_status_history_address(&env.program_id, &fixture.listing);
+
+    let ix = enable_ix(&env, &fixture, &history);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    assert!(env.listing(&fixture.listing).await.has_status_history());
+
+    env.create_ata(&fixture.vault_authority, &fixture.base_mint).await;
+    let mut ix = common::deposit_ix(&env.program_id, &fixture);
+    ix.accounts.push(AccountMeta::new(history, false));
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, args.quantity);
+    ix.accounts.push(AccountMeta::new(history, false));
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+
+    let account = env.ctx.banks_client.get_account(history).await.unwrap().unwrap();
+    let recorded = StatusHistory::deserialize(&mut &account.data[..]).unwrap();
+    assert_eq!(recorded.listing, fixture.listing);
+    let statuses: Vec<_> = recorded.entries.iter().map(|entry| entry.status).collect();
+    assert_eq!(
+        statuses,
+        vec![ListingStatus::AwaitingDeposit, ListingStatus::Active, ListingStatus::Completed]
+    );
+    assert!(recorded.entries.windows(2).all(|pair| pair[0].ts <= pair[1].ts));
+}
+
+/// Once enabled, status-changing instructions fail without the history account.
+#[tokio::test]
+async fn test_status_history_required_after_enable() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let (history, _) = find_status_history_address(&env.program_id, &fixture.listing);
+    let ix = enable_ix(&env, &fixture, &history);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    let ix = common::cancel_ix(&env.program_id, &fixture);
+    assert!(env.process(&[ix], &[&fixture.seller]).await.is_err());
+
+    let mut ix = common::cancel_ix(&env.program_id, &fixture);
+    ix.accounts.push(AccountMeta::new(history, false));
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Cancelled);
+}
+
+/// Cancelling a never-funded listing records `Cancelled` before its rent is refunded.
+#[tokio::test]
+async fn test_status_history_records_unfunded_cancel() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+    let (history, _) = find_status_history_address(&env.program_id, &fixture.listing);
+    let ix = enable_ix(&env, &fixture, &history);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    let mut ix = common::cancel_ix(&env.program_id, &fixture);
+    ix.accounts.push(AccountMeta::new(history, false));
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    assert!(env.ctx.banks_client.get_account(fixture.listing).await.unwrap().is_none());
+    let account = env.ctx.banks_client.get_account(history).await.unwrap().unwrap();
+    let recorded = StatusHistory::deserialize(&mut &account.data[..]).unwrap();
+    let statuses: Vec<_> = recorded.entries.iter().map(|entry| entry.status).collect();
+    assert_eq!(statuses, vec![ListingStatus::AwaitingDeposit, ListingStatus::Cancelled]);
+}
+
+/// The recorded statuses in the listing's history.
+async fn recorded_statuses(env: &mut TestEnv, history: Pubkey) -> Vec<ListingStatus> {
+    let account = env.ctx.banks_client.get_account(history).await.unwrap().unwrap();
+    let recorded = StatusHistory::deserialize(&mut &account.data[..]).unwrap();
+    recorded.entries.iter().map(|entry| entry.status).collect()
+}
+
+/// Expiring a never-funded listing records `Cancelled` before its rent is refunded.
+#[tokio::test]
+async fn test_status_history_records_expiry() {
+    let mut env = common::start().await;
+    let clock: Clock = env.ctx.banks_client.get_sysvar().await.unwrap();
+    let fixture = env
+        .setup_listing(ListingArgs {
+            deposit_deadline: clock.unix_timestamp + 100,
+            ..ListingArgs::default()
+        })
+        .await;
+    let (history, _) = find_status_history_address(&env.program_id, &fixture.listing);
+    let ix = enable_ix(&env, &fixture, &history);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    env.advance_clock(101).await;
+    let caller = env.funded_keypair(1_000_000_000).await;
+    let ix = Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(caller.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new(fixture.seller.pubkey(), false),
+            AccountMeta::new(history, false),
+        ],
+        data: EscrowInstruction::ExpireListing.try_to_vec().unwrap(),
+    };
+    env.process(&[ix], &[&caller]).await.unwrap();
+
+    assert!(env.ctx.banks_client.get_account(fixture.listing).await.unwrap().is_none());
+    assert_eq!(
+        recorded_statuses(&mut env, history).await,
+        vec![ListingStatus::AwaitingDeposit, ListingStatus::Cancelled]
+    );
+}
+
+/// Calling off a swap the seller never funded records `Cancelled` before its
+/// rent is refunded.
+#[tokio::test]
+async fn test_status_history_records_unfunded_swap_cancel() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs { allow_partial: false, ..ListingArgs::default() }).await;
+    let counterparty = env.setup_buyer(&fixture, 100_000_000).await;
+    let quote_vault = env.create_ata(&fixture.vault_authority, &fixture.quote_mint).await;
+    let (history, _) = find_status_history_address(&env.program_id, &fixture.listing);
+    let ix = enable_ix(&env, &fixture, &history);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    let ix = Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(fixture.seller.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+        ],
+        data: EscrowInstruction::EnableSwap { counterparty: counterparty.keypair.pubkey() }.try_to_vec().unwrap(),
+    };
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    let ix = Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(fixture.seller.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new(fixture.seller.pubkey(), false),
+            AccountMeta::new_readonly(fixture.vault_authority, false),
+            AccountMeta::new(fixture.vault, false),
+            AccountMeta::new(fixture.seller_base, false),
+            AccountMeta::new(quote_vault, false),
+            AccountMeta::new(counterparty.quote_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(history, false),
+        ],
+        data: EscrowInstruction::CancelSwap.try_to_vec().unwrap(),
+    };
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    assert!(env.ctx.banks_client.get_account(fixture.listing).await.unwrap().is_none());
+    assert_eq!(
+        recorded_statuses(&mut env, history).await,
+        vec![ListingStatus::AwaitingDeposit, ListingStatus::Cancelled]
+    );
+}
+
+/// Overwrite the vault's token balance, simulating base moved outside a fill.
+async fn set_vault_amount(env: &mut TestEnv, fixture: &ListingFixture, amount: u64) {
+    let mut account = env.ctx.banks_client.get_account(fixture.vault).await.unwrap().unwrap();
+    let mut vault = TokenAccount::unpack(&account.data).unwrap();
+    vault.amount = amount;
+    TokenAccount::pack(vault, &mut account.data).unwrap();
+    env.ctx.set_account(&fixture.vault, &account.into());
+}
+
+/// Freeze/unfreeze cycles past `MAX_STATUS_ENTRIES` keep the first entry and drop
+/// the oldest later one.
+#[tokio::test]
+async fn test_status_history_keeps_first_entry_when_full() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+    let (history, _) = find_status_history_address(&env.program_id, &fixture.listing);
+    let ix = enable_ix(&env, &fixture, &history);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    env.create_ata(&fixture.vault_authority, &fixture.base_mint).await;
+    let mut ix = common::deposit_ix(&env.program_id, &fixture);
+    ix.accounts.push(AccountMeta::new(history, false));
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    let admin = env.initialize_config(Pubkey::new_unique()).await;
+    for _ in 0..2 {
+        set_vault_amount(&mut env, &fixture, 60_000_000).await;
+        let ix = Instruction {
+            program_id: env.program_id,
+            accounts: vec![
+                AccountMeta::new(fixture.listing, false),
+                AccountMeta::new(history, false),
+                AccountMeta::new_readonly(fixture.vault, false),
+            ],
+            data: EscrowInstruction::ReconcileListing.try_to_vec().unwrap(),
+        };
+        env.process(&[ix], &[]).await.unwrap();
+
+        set_vault_amount(&mut env, &fixture, 100_000_000).await;
+        let ix = Instruction {
+            program_id: env.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(admin.pubkey(), true),
+                AccountMeta::new_readonly(find_config_address(&env.program_id).0, false),
+                AccountMeta::new(fixture.listing, false),
+                AccountMeta::new(history, false),
+                AccountMeta::new_readonly(fixture.vault, false),
+            ],
+            data: EscrowInstruction::UnfreezeListing.try_to_vec().unwrap(),
+        };
+        env.process(&[ix], &[&admin]).await.unwrap();
+        env.refresh_blockhash().await;
+    }
+
+    let account = env.ctx.banks_client.get_account(history).await.unwrap().unwrap();
+    let recorded = StatusHistory::deserialize(&mut &account.data[..]).unwrap();
+    assert_eq!(recorded.entries.len(), MAX_STATUS_ENTRIES);
+    let statuses: Vec<_> = recorded.entries.iter().map(|entry| entry.status).collect();
+    assert_eq!(
+        statuses,
+        vec![
+            ListingStatus::AwaitingDeposit,
+            ListingStatus::Active,
+            ListingStatus::Frozen,
+            ListingStatus::Active
+        ]
+    );
+}
@@ -0,0 +1,188 @@
+//! Tests for two-sided swaps where both parties escrow before settlement.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{custom_error_code, instruction_error, Buyer, ListingArgs, ListingFixture, TestEnv};
+use escrow_program::{find_fills_ledger_address, EscrowError, EscrowInstruction, ListingStatus};
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::signature::Signer;
+
+fn enable_swap_ix(env: &TestEnv, fixture: &ListingFixture, counterparty: &Buyer) -> Instruction {
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(fixture.seller.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+        ],
+        data: EscrowInstruction::EnableSwap { counterparty: counterparty.keypair.pubkey() }.try_to_vec().unwrap(),
+    }
+}
+
+fn deposit_quote_ix(
+    env: &TestEnv,
+    fixture: &ListingFixture,
+    quote_vault: &Pubkey,
+    counterparty: &Buyer,
+) -> Instruction {
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(counterparty.keypair.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new(counterparty.quote_account, false),
+            AccountMeta::new(*quote_vault, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: EscrowInstruction::DepositSwapQuote.try_to_vec().unwrap(),
+    }
+}
+
+fn settle_ix(env: &TestEnv, fixture: &ListingFixture, quote_vault: &Pubkey, counterparty: &Buyer) -> Instruction {
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new(counterparty.keypair.pubkey(), true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new_readonly(fixture.vault_authority, false),
+            AccountMeta::new(fixture.vault, false),
+            AccountMeta::new(*quote_vault, false),
+            AccountMeta::new(fixture.seller_quote, false),
+            AccountMeta::new(counterparty.base_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(find_fills_ledger_address(&env.program_id, &fixture.listing).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::SettleSwap.try_to_vec().unwrap(),
+    }
+}
+
+fn cancel_swap_ix(
+    env: &TestEnv,
+    fixture: &ListingFixture,
+    quote_vault: &Pubkey,
+    counterparty: &Buyer,
+    signer: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*signer, true),
+            AccountMeta::new(fixture.listing, false),
+            AccountMeta::new(fixture.seller.pubkey(), false),
+            AccountMeta::new_readonly(fixture.vault_authority, false),
+            AccountMeta::new(fixture.vault, false),
+            AccountMeta::new(fixture.seller_base, false),
+            AccountMeta::new(*quote_vault, false),
+            AccountMeta::new(counterparty.quote_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: EscrowInstruction::CancelSwap.try_to_vec().unwrap(),
+    }
+}
+
+/// An unfunded 100-token swap at 1.0 quote each with a counterparty holding
+/// `quote_amount`, plus the quote vault.
+async fn setup_swap(env: &mut TestEnv, quote_amount: u64) -> (ListingFixture, Buyer, Pubkey) {
+    let fixture = env.setup_listing(ListingArgs { allow_partial: false, ..ListingArgs::default() }).await;
+    let counterparty = env.setup_buyer(&fixture, quote_amount).await;
+    let quote_vault = env.create_ata(&fixture.vault_authority, &fixture.quote_mint).await;
+    let ix = enable_swap_ix(env, &fixture, &counterparty);
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    (fixture, counterparty, quote_vault)
+}
+
+/// With both sides deposited, settlement swaps base and quote in one step.
+#[tokio::test]
+async fn test_swap_settles_after_both_deposits() {
+    let mut env = common::start().await;
+    let (fixture, counterparty, quote_vault) = setup_swap(&mut env, 150_000_000).await;
+
+    let ix = deposit_quote_ix(&env, &fixture, &quote_vault, &counterparty);
+    env.process(&[ix], &[&counterparty.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&quote_vault).await, 100_000_000);
+    env.deposit(&fixture).await.unwrap();
+
+    let err = env.purchase(&fixture, &counterparty, 10_000_000).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidSwap as u32));
+
+    let ix = settle_ix(&env, &fixture, &quote_vault, &counterparty);
+    env.process(&[ix], &[&counterparty.keypair]).await.unwrap();
+
+    assert_eq!(env.token_balance(&fixture.seller_quote).await, 100_000_000);
+    assert_eq!(env.token_balance(&counterparty.base_account).await, 100_000_000);
+    assert_eq!(env.token_balance(&counterparty.quote_account).await, 50_000_000);
+    assert_eq!(env.token_balance(&quote_vault).await, 0);
+    assert_eq!(env.token_balance(&fixture.vault).await, 0);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Completed);
+}
+
+/// Settlement waits for the seller's side; the counterparty can back out and
+/// reclaim the quote, which closes the never-funded listing.
+#[tokio::test]
+async fn test_swap_cancel_after_one_deposit_refunds_it() {
+    let mut env = common::start().await;
+    let (fixture, counterparty, quote_vault) = setup_swap(&mut env, 100_000_000).await;
+
+    let ix = deposit_quote_ix(&env, &fixture, &quote_vault, &counterparty);
+    env.process(&[ix], &[&counterparty.keypair]).await.unwrap();
+    let ix = settle_ix(&env, &fixture, &quote_vault, &counterparty);
+    let err = env.process(&[ix], &[&counterparty.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::InvalidListingStatus as u32));
+
+    let ix = cancel_swap_ix(&env, &fixture, &quote_vault, &counterparty, &counterparty.keypair.pubkey());
+    env.process(&[ix], &[&counterparty.keypair]).await.unwrap();
+
+    assert_eq!(env.token_balance(&counterparty.quote_account).await, 100_000_000);
+    assert_eq!(env.token_balance(&quote_vault).await, 0);
+    assert!(env.ctx.banks_client.get_account(fixture.listing).await.unwrap().is_none());
+}
+
+/// A funded swap the seller calls off returns the base to the seller.
+#[tokio::test]
+async fn test_seller_cancels_funded_swap() {
+    let mut env = common::start().await;
+    let (fixture, counterparty, quote_vault) = setup_swap(&mut env, 100_000_000).await;
+    env.deposit(&fixture).await.unwrap();
+
+    let ix = cancel_swap_ix(&env, &fixture, &quote_vault, &counterparty, &fixture.seller.pubkey());
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    assert_eq!(env.token_balance(&fixture.seller_base).await, 100_000_000);
+    assert_eq!(env.token_balance(&fixture.vault).await, 0);
+    assert_eq!(env.listing(&fixture.listing).await.status(), ListingStatus::Cancelled);
+}
+
+/// Settlement is limited to the two parties and the real SPL Token program;
+/// a stranger or a fake program cannot release either vault.
+#[tokio::test]
+async fn test_settle_swap_rejects_stranger_and_fake_token_program() {
+    let mut env = common::start().await;
+    let (fixture, counterparty, quote_vault) = setup_swap(&mut env, 100_000_000).await;
+    let ix = deposit_quote_ix(&env, &fixture, &quote_vault, &counterparty);
+    env.process(&[ix], &[&counterparty.keypair]).await.unwrap();
+    env.deposit(&fixture).await.unwrap();
+
+    let stranger = env.funded_keypair(1_000_000_000).await;
+    let mut ix = settle_ix(&env, &fixture, &quote_vault, &counterparty);
+    ix.accounts[0] = AccountMeta::new(stranger.pubkey(), true);
+    let err = env.process(&[ix], &[&stranger]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::IncorrectAuthority as u32));
+
+    let mut ix = settle_ix(&env, &fixture, &quote_vault, &counterparty);
+    ix.accounts[7] = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+    let err = env.process(&[ix], &[&counterparty.keypair]).await.unwrap_err();
+    assert_eq!(instruction_error(err), Some(InstructionError::IncorrectProgramId));
+
+    let mut ix = cancel_swap_ix(&env, &fixture, &quote_vault, &counterparty, &fixture.seller.pubkey());
+    ix.accounts[8] = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(instruction_error(err), Some(InstructionError::IncorrectProgramId));
+
+    assert_eq!(env.token_balance(&fixture.vault).await, 100_000_000);
+    assert_eq!(env.token_balance(&quote_vault).await, 100_000_000);
+}
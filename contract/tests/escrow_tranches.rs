@@ -0,0 +1,55 @@
+//! Tests for listings split across time-unlocked tranche vaults.
+
+mod common;
+
+use common::{custom_error_code, ListingArgs};
+use escrow_program::{find_tranche_vault_authority, EscrowError};
+use solana_program::instruction::AccountMeta;
+use solana_sdk::signature::Signer;
+use spl_associated_token_account::get_associated_token_address;
+
+/// The second tranche is locked until its interval elapses, then purchases can draw from it.
+#[tokio::test]
+async fn test_second_tranche_unlocks_after_interval() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        vault_count: 2,
+        tranche_interval: 3_600,
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_listing(args.clone()).await;
+
+    let (second_authority, _) =
+        find_tranche_vault_authority(&env.program_id, &fixture.seller.pubkey(), fixture.listing_id, 1);
+    let second_vault = get_associated_token_address(&second_authority, &fixture.base_mint);
+    env.create_ata(&fixture.vault_authority, &fixture.base_mint).await;
+    env.create_ata(&second_authority, &fixture.base_mint).await;
+
+    let mut ix = common::deposit_ix(&env.program_id, &fixture);
+    ix.accounts.push(AccountMeta::new_readonly(second_authority, false));
+    ix.accounts.push(AccountMeta::new(second_vault, false));
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+    assert_eq!(env.token_balance(&fixture.vault).await, 50_000_000);
+    assert_eq!(env.token_balance(&second_vault).await, 50_000_000);
+
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    let program_id = env.program_id;
+    let second_tranche_purchase = |quantity: u64| {
+        let mut ix = common::purchase_ix(&program_id, &fixture, &buyer, quantity);
+        ix.accounts[5] = AccountMeta::new_readonly(second_authority, false);
+        ix.accounts[6] = AccountMeta::new(second_vault, false);
+        ix
+    };
+
+    let ix = second_tranche_purchase(10_000_000);
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::TrancheLocked as u32));
+
+    env.advance_clock(3_600).await;
+
+    let ix = second_tranche_purchase(10_000_000);
+    env.process(&[ix], &[&buyer.keypair]).await.unwrap();
+    assert_eq!(env.token_balance(&second_vault).await, 40_000_000);
+    assert_eq!(env.token_balance(&fixture.vault).await, 50_000_000);
+    assert_eq!(env.token_balance(&buyer.base_account).await, 10_000_000);
+}
@@ -0,0 +1,137 @@
+//! Tests for vault authority and vault account validation.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{custom_error_code, ListingArgs};
+use escrow_program::{EscrowError, EscrowInstruction};
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    system_instruction, system_program,
+};
+use solana_program::program_pack::Pack;
+use solana_sdk::signature::{Keypair, Signer};
+use spl_token::state::{Account as TokenAccount, AccountState};
+
+/// A listing whose stored vault authority no longer matches its seeds is
+/// rejected with `IncorrectAuthority` before any transfer is attempted.
+#[tokio::test]
+async fn test_purchase_rejects_tampered_vault_authority() {
+    let mut env = common::start().await;
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+
+    // Point the listing at an attacker-controlled authority that owns a funded vault.
+    let impostor = Keypair::new();
+    let impostor_vault = env.create_ata(&impostor.pubkey(), &fixture.base_mint).await;
+    env.mint_to(&fixture.base_mint, &impostor_vault, 10_000_000).await;
+    let mut listing = env.listing(&fixture.listing).await;
+    listing.vault_authority = impostor.pubkey();
+    env.set_listing(&fixture.listing, &listing).await;
+
+    let mut ix = common::purchase_ix(&env.program_id, &fixture, &buyer, 10_000_000);
+    ix.accounts[5] = AccountMeta::new_readonly(impostor.pubkey(), false);
+    ix.accounts[6] = AccountMeta::new(impostor_vault, false);
+    let err = env.process(&[ix], &[&buyer.keypair]).await.unwrap_err();
+
+    assert_eq!(custom_error_code(err), Some(EscrowError::IncorrectAuthority as u32));
+}
+
+/// Lamports sent to the vault authority PDA can be swept back to the seller once
+/// the listing is completed.
+#[tokio::test]
+async fn test_sweep_vault_lamports_on_completed_listing() {
+    let mut env = common::start().await;
+    let args = ListingArgs::default();
+    let fixture = env.setup_active_listing(args.clone()).await;
+    let buyer = env.setup_buyer(&fixture, 100_000_000).await;
+    env.purchase(&fixture, &buyer, args.quantity).await.unwrap();
+
+    let payer = env.ctx.payer.pubkey();
+    let ix = system_instruction::transfer(&payer, &fixture.vault_authority, 5_000_000);
+    env.process(&[ix], &[]).await.unwrap();
+    assert_eq!(env.lamports(&fixture.vault_authority).await, 5_000_000);
+
+    let seller_before = env.lamports(&fixture.seller.pubkey()).await;
+    let ix = Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new(fixture.seller.pubkey(), true),
+            AccountMeta::new_readonly(fixture.listing, false),
+            AccountMeta::new(fixture.vault_authority, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::SweepVaultLamports.try_to_vec().unwrap(),
+    };
+    env.process(&[ix], &[&fixture.seller]).await.unwrap();
+
+    assert_eq!(env.lamports(&fixture.vault_authority).await, 0);
+    assert_eq!(env.lamports(&fixture.seller.pubkey()).await, seller_before + 5_000_000);
+}
+
+/// Depositing into a frozen vault is refused before any tokens move.
+#[tokio::test]
+async fn test_deposit_rejects_frozen_vault() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+    env.create_ata(&fixture.vault_authority, &fixture.base_mint).await;
+
+    let mut account = env.ctx.banks_client.get_account(fixture.vault).await.unwrap().unwrap();
+    let mut vault = TokenAccount::unpack(&account.data).unwrap();
+    vault.state = AccountState::Frozen;
+    TokenAccount::pack(vault, &mut account.data).unwrap();
+    env.ctx.set_account(&fixture.vault, &account.into());
+
+    let err = env.deposit(&fixture).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::VaultAccountUnsafe as u32));
+    assert_eq!(env.token_balance(&fixture.seller_base).await, ListingArgs::default().quantity);
+}
+
+/// Deposits only go to the vault authority's canonical base-mint ATA: neither
+/// its ATA for another mint nor a non-ATA account for the base mint is accepted.
+#[tokio::test]
+async fn test_deposit_rejects_non_canonical_vault() {
+    let mut env = common::start().await;
+    let fixture = env.setup_listing(ListingArgs::default()).await;
+
+    let other_mint = env.create_mint(6).await;
+    let other_vault = env.create_ata(&fixture.vault_authority, &other_mint).await;
+    let mut ix = common::deposit_ix(&env.program_id, &fixture);
+    ix.accounts[4] = AccountMeta::new(other_vault, false);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::MintMismatch as u32));
+
+    // A copy of the real vault at another address has the right mint and owner.
+    env.create_ata(&fixture.vault_authority, &fixture.base_mint).await;
+    let account = env.ctx.banks_client.get_account(fixture.vault).await.unwrap().unwrap();
+    let impostor_vault = Pubkey::new_unique();
+    env.ctx.set_account(&impostor_vault, &account.into());
+    let mut ix = common::deposit_ix(&env.program_id, &fixture);
+    ix.accounts[4] = AccountMeta::new(impostor_vault, false);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::MintMismatch as u32));
+    assert_eq!(env.token_balance(&fixture.seller_base).await, ListingArgs::default().quantity);
+}
+
+/// A token program other than SPL Token is refused on seller-signed deposits
+/// and on every vault-signed transfer, here the return of inventory on cancel.
+#[tokio::test]
+async fn test_rejects_fake_token_program() {
+    let mut env = common::start().await;
+    let fake_token_program = Pubkey::new_unique();
+
+    let unfunded = env.setup_listing(ListingArgs::default()).await;
+    env.create_ata(&unfunded.vault_authority, &unfunded.base_mint).await;
+    let mut ix = common::deposit_ix(&env.program_id, &unfunded);
+    ix.accounts[5] = AccountMeta::new_readonly(fake_token_program, false);
+    let err = env.process(&[ix], &[&unfunded.seller]).await.unwrap_err();
+    assert_eq!(common::instruction_error(err), Some(InstructionError::IncorrectProgramId));
+
+    let fixture = env.setup_active_listing(ListingArgs::default()).await;
+    let mut ix = common::cancel_ix(&env.program_id, &fixture);
+    ix.accounts[5] = AccountMeta::new_readonly(fake_token_program, false);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(common::instruction_error(err), Some(InstructionError::IncorrectProgramId));
+    assert_eq!(env.token_balance(&fixture.vault).await, ListingArgs::default().quantity);
+}
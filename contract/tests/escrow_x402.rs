@@ -6,37 +6,31 @@ use solana_program::{
     pubkey::Pubkey,
     system_program,
 };
-use solana_program_test::{processor, ProgramTest};
+use spl_associated_token_account::get_associated_token_address;
 use solana_sdk::{
     account::Account,
     signature::{Keypair, Signer},
     transaction::Transaction,
 };
 
-// Re-export the program module
-use escrow_program::{EscrowInstruction, Listing, ListingStatus};
+mod common;
 
-/// Helper function to create a program test environment
-fn program_test() -> ProgramTest {
-    let program_id = Pubkey::new_unique();
-    let mut program_test = ProgramTest::new(
-        "escrow_program",
-        program_id,
-        processor!(escrow_program::process_instruction),
-    );
-    program_test
-}
+// Re-export the program module
+use escrow_program::{
+    find_config_address, EscrowError, EscrowInstruction, InitializeListingArgs, Listing, ListingStatus,
+    MAX_X402_PAYLOAD_LEN,
+};
+use common::{add_mint_account, custom_error_code, escrow_program_test as program_test, ListingArgs, ListingFixture};
 
 /// Test initializing a listing with NativeSol fee payment (backward compatibility)
 #[tokio::test]
 async fn test_initialize_listing_native_sol_fee() {
-    let program_test = program_test();
-    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (mut program_test, program_id) = program_test();
 
     let seller = Keypair::new();
     let listing = Keypair::new();
-    let base_mint = Pubkey::new_unique();
-    let quote_mint = Pubkey::new_unique();
+    let base_mint = add_mint_account(&mut program_test, 6);
+    let quote_mint = add_mint_account(&mut program_test, 6);
     
     let listing_id = 12345u64;
     let price_per_token = 1_000_000u64; // 1 USDC per token
@@ -46,20 +40,22 @@ async fn test_initialize_listing_native_sol_fee() {
     let x402_payload: Option<String> = None;
 
     // Create the instruction data
-    let instruction_data = EscrowInstruction::InitializeListing {
+    let instruction_data = EscrowInstruction::InitializeListing(InitializeListingArgs {
         listing_id,
         price_per_token,
         quantity,
         allow_partial,
         fee_payment_method,
         x402_payload,
-    };
+        ..Default::default()
+    });
 
     let listing_id_bytes = listing_id.to_le_bytes();
-    let seeds: &[&[u8]] = &[b"vault", seller.pubkey().as_ref(), &listing_id_bytes];
-    let (vault_authority, _bump) = Pubkey::find_program_address(seeds, &program_test.program_id);
+    let seller_pubkey = seller.pubkey();
+    let seeds: &[&[u8]] = &[b"vault", seller_pubkey.as_ref(), &listing_id_bytes];
+    let (vault_authority, _bump) = Pubkey::find_program_address(seeds, &program_id);
     
-    let vault_token_account = Pubkey::new_unique();
+    let vault_token_account = get_associated_token_address(&vault_authority, &base_mint);
 
     let accounts = vec![
         AccountMeta::new(seller.pubkey(), true),
@@ -69,10 +65,11 @@ async fn test_initialize_listing_native_sol_fee() {
         AccountMeta::new_readonly(base_mint, false),
         AccountMeta::new_readonly(quote_mint, false),
         AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(find_config_address(&program_id).0, false),
     ];
 
     let instruction = Instruction {
-        program_id: program_test.program_id,
+        program_id,
         accounts,
         data: instruction_data.try_to_vec().unwrap(),
     };
@@ -85,23 +82,19 @@ async fn test_initialize_listing_native_sol_fee() {
         executable: false,
         rent_epoch: 0,
     };
-    banks_client
-        .set_account(&seller.pubkey(), &seller_account)
-        .await
-        .unwrap();
+    program_test.add_account(seller.pubkey(), seller_account);
 
     // Create listing account with required space
     let listing_account = Account {
         lamports: 1_000_000,
         data: vec![0; Listing::LEN],
-        owner: program_test.program_id,
+        owner: program_id,
         executable: false,
         rent_epoch: 0,
     };
-    banks_client
-        .set_account(&listing.pubkey(), &listing_account)
-        .await
-        .unwrap();
+    program_test.add_account(listing.pubkey(), listing_account);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
 
     let transaction = Transaction::new_signed_with_payer(
         &[instruction],
@@ -138,13 +131,12 @@ async fn test_initialize_listing_native_sol_fee() {
 /// Test initializing a listing with X402 fee payment and valid payload
 #[tokio::test]
 async fn test_initialize_listing_x402_fee_valid_payload() {
-    let program_test = program_test();
-    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (mut program_test, program_id) = program_test();
 
     let seller = Keypair::new();
     let listing = Keypair::new();
-    let base_mint = Pubkey::new_unique();
-    let quote_mint = Pubkey::new_unique();
+    let base_mint = add_mint_account(&mut program_test, 6);
+    let quote_mint = add_mint_account(&mut program_test, 6);
     
     let listing_id = 67890u64;
     let price_per_token = 2_000_000u64; // 2 USDC per token
@@ -154,20 +146,22 @@ async fn test_initialize_listing_x402_fee_valid_payload() {
     let x402_payload = Some("x402-payment-proof-base64-encoded-data-12345".to_string());
 
     // Create the instruction data
-    let instruction_data = EscrowInstruction::InitializeListing {
+    let instruction_data = EscrowInstruction::InitializeListing(InitializeListingArgs {
         listing_id,
         price_per_token,
         quantity,
         allow_partial,
         fee_payment_method,
         x402_payload,
-    };
+        ..Default::default()
+    });
 
     let listing_id_bytes = listing_id.to_le_bytes();
-    let seeds: &[&[u8]] = &[b"vault", seller.pubkey().as_ref(), &listing_id_bytes];
-    let (vault_authority, _bump) = Pubkey::find_program_address(seeds, &program_test.program_id);
+    let seller_pubkey = seller.pubkey();
+    let seeds: &[&[u8]] = &[b"vault", seller_pubkey.as_ref(), &listing_id_bytes];
+    let (vault_authority, _bump) = Pubkey::find_program_address(seeds, &program_id);
     
-    let vault_token_account = Pubkey::new_unique();
+    let vault_token_account = get_associated_token_address(&vault_authority, &base_mint);
 
     let accounts = vec![
         AccountMeta::new(seller.pubkey(), true),
@@ -177,10 +171,11 @@ async fn test_initialize_listing_x402_fee_valid_payload() {
         AccountMeta::new_readonly(base_mint, false),
         AccountMeta::new_readonly(quote_mint, false),
         AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(find_config_address(&program_id).0, false),
     ];
 
     let instruction = Instruction {
-        program_id: program_test.program_id,
+        program_id,
         accounts,
         data: instruction_data.try_to_vec().unwrap(),
     };
@@ -193,23 +188,19 @@ async fn test_initialize_listing_x402_fee_valid_payload() {
         executable: false,
         rent_epoch: 0,
     };
-    banks_client
-        .set_account(&seller.pubkey(), &seller_account)
-        .await
-        .unwrap();
+    program_test.add_account(seller.pubkey(), seller_account);
 
     // Create listing account with required space
     let listing_account = Account {
         lamports: 1_000_000,
         data: vec![0; Listing::LEN],
-        owner: program_test.program_id,
+        owner: program_id,
         executable: false,
         rent_epoch: 0,
     };
-    banks_client
-        .set_account(&listing.pubkey(), &listing_account)
-        .await
-        .unwrap();
+    program_test.add_account(listing.pubkey(), listing_account);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
 
     let transaction = Transaction::new_signed_with_payer(
         &[instruction],
@@ -248,13 +239,12 @@ async fn test_initialize_listing_x402_fee_valid_payload() {
 /// Test initializing a listing with X402 fee payment but missing payload (should fail)
 #[tokio::test]
 async fn test_initialize_listing_x402_fee_missing_payload() {
-    let program_test = program_test();
-    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (mut program_test, program_id) = program_test();
 
     let seller = Keypair::new();
     let listing = Keypair::new();
-    let base_mint = Pubkey::new_unique();
-    let quote_mint = Pubkey::new_unique();
+    let base_mint = add_mint_account(&mut program_test, 6);
+    let quote_mint = add_mint_account(&mut program_test, 6);
     
     let listing_id = 11111u64;
     let price_per_token = 1_500_000u64;
@@ -264,20 +254,22 @@ async fn test_initialize_listing_x402_fee_missing_payload() {
     let x402_payload: Option<String> = None; // Missing payload!
 
     // Create the instruction data
-    let instruction_data = EscrowInstruction::InitializeListing {
+    let instruction_data = EscrowInstruction::InitializeListing(InitializeListingArgs {
         listing_id,
         price_per_token,
         quantity,
         allow_partial,
         fee_payment_method,
         x402_payload,
-    };
+        ..Default::default()
+    });
 
     let listing_id_bytes = listing_id.to_le_bytes();
-    let seeds: &[&[u8]] = &[b"vault", seller.pubkey().as_ref(), &listing_id_bytes];
-    let (vault_authority, _bump) = Pubkey::find_program_address(seeds, &program_test.program_id);
+    let seller_pubkey = seller.pubkey();
+    let seeds: &[&[u8]] = &[b"vault", seller_pubkey.as_ref(), &listing_id_bytes];
+    let (vault_authority, _bump) = Pubkey::find_program_address(seeds, &program_id);
     
-    let vault_token_account = Pubkey::new_unique();
+    let vault_token_account = get_associated_token_address(&vault_authority, &base_mint);
 
     let accounts = vec![
         AccountMeta::new(seller.pubkey(), true),
@@ -287,10 +279,11 @@ async fn test_initialize_listing_x402_fee_missing_payload() {
         AccountMeta::new_readonly(base_mint, false),
         AccountMeta::new_readonly(quote_mint, false),
         AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(find_config_address(&program_id).0, false),
     ];
 
     let instruction = Instruction {
-        program_id: program_test.program_id,
+        program_id,
         accounts,
         data: instruction_data.try_to_vec().unwrap(),
     };
@@ -303,23 +296,19 @@ async fn test_initialize_listing_x402_fee_missing_payload() {
         executable: false,
         rent_epoch: 0,
     };
-    banks_client
-        .set_account(&seller.pubkey(), &seller_account)
-        .await
-        .unwrap();
+    program_test.add_account(seller.pubkey(), seller_account);
 
     // Create listing account with required space
     let listing_account = Account {
         lamports: 1_000_000,
         data: vec![0; Listing::LEN],
-        owner: program_test.program_id,
+        owner: program_id,
         executable: false,
         rent_epoch: 0,
     };
-    banks_client
-        .set_account(&listing.pubkey(), &listing_account)
-        .await
-        .unwrap();
+    program_test.add_account(listing.pubkey(), listing_account);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
 
     let transaction = Transaction::new_signed_with_payer(
         &[instruction],
@@ -338,13 +327,12 @@ async fn test_initialize_listing_x402_fee_missing_payload() {
 /// Test initializing a listing with X402 fee and empty payload string (should fail)
 #[tokio::test]
 async fn test_initialize_listing_x402_fee_empty_payload() {
-    let program_test = program_test();
-    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (mut program_test, program_id) = program_test();
 
     let seller = Keypair::new();
     let listing = Keypair::new();
-    let base_mint = Pubkey::new_unique();
-    let quote_mint = Pubkey::new_unique();
+    let base_mint = add_mint_account(&mut program_test, 6);
+    let quote_mint = add_mint_account(&mut program_test, 6);
     
     let listing_id = 22222u64;
     let price_per_token = 3_000_000u64;
@@ -354,20 +342,22 @@ async fn test_initialize_listing_x402_fee_empty_payload() {
     let x402_payload = Some("".to_string()); // Empty payload string!
 
     // Create the instruction data
-    let instruction_data = EscrowInstruction::InitializeListing {
+    let instruction_data = EscrowInstruction::InitializeListing(InitializeListingArgs {
         listing_id,
         price_per_token,
         quantity,
         allow_partial,
         fee_payment_method,
         x402_payload,
-    };
+        ..Default::default()
+    });
 
     let listing_id_bytes = listing_id.to_le_bytes();
-    let seeds: &[&[u8]] = &[b"vault", seller.pubkey().as_ref(), &listing_id_bytes];
-    let (vault_authority, _bump) = Pubkey::find_program_address(seeds, &program_test.program_id);
+    let seller_pubkey = seller.pubkey();
+    let seeds: &[&[u8]] = &[b"vault", seller_pubkey.as_ref(), &listing_id_bytes];
+    let (vault_authority, _bump) = Pubkey::find_program_address(seeds, &program_id);
     
-    let vault_token_account = Pubkey::new_unique();
+    let vault_token_account = get_associated_token_address(&vault_authority, &base_mint);
 
     let accounts = vec![
         AccountMeta::new(seller.pubkey(), true),
@@ -377,10 +367,11 @@ async fn test_initialize_listing_x402_fee_empty_payload() {
         AccountMeta::new_readonly(base_mint, false),
         AccountMeta::new_readonly(quote_mint, false),
         AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(find_config_address(&program_id).0, false),
     ];
 
     let instruction = Instruction {
-        program_id: program_test.program_id,
+        program_id,
         accounts,
         data: instruction_data.try_to_vec().unwrap(),
     };
@@ -393,23 +384,19 @@ async fn test_initialize_listing_x402_fee_empty_payload() {
         executable: false,
         rent_epoch: 0,
     };
-    banks_client
-        .set_account(&seller.pubkey(), &seller_account)
-        .await
-        .unwrap();
+    program_test.add_account(seller.pubkey(), seller_account);
 
     // Create listing account with required space
     let listing_account = Account {
         lamports: 1_000_000,
         data: vec![0; Listing::LEN],
-        owner: program_test.program_id,
+        owner: program_id,
         executable: false,
         rent_epoch: 0,
     };
-    banks_client
-        .set_account(&listing.pubkey(), &listing_account)
-        .await
-        .unwrap();
+    program_test.add_account(listing.pubkey(), listing_account);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
 
     let transaction = Transaction::new_signed_with_payer(
         &[instruction],
@@ -428,13 +415,12 @@ async fn test_initialize_listing_x402_fee_empty_payload() {
 /// Test that fee calculation is correct (1% of trade value)
 #[tokio::test]
 async fn test_x402_fee_calculation() {
-    let program_test = program_test();
-    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (mut program_test, program_id) = program_test();
 
     let seller = Keypair::new();
     let listing = Keypair::new();
-    let base_mint = Pubkey::new_unique();
-    let quote_mint = Pubkey::new_unique();
+    let base_mint = add_mint_account(&mut program_test, 6);
+    let quote_mint = add_mint_account(&mut program_test, 6);
     
     let listing_id = 99999u64;
     let price_per_token = 10_000_000u64; // 10 USDC per token
@@ -447,20 +433,22 @@ async fn test_x402_fee_calculation() {
     let x402_payload = Some("valid-x402-proof-for-fee-test".to_string());
 
     // Create the instruction data
-    let instruction_data = EscrowInstruction::InitializeListing {
+    let instruction_data = EscrowInstruction::InitializeListing(InitializeListingArgs {
         listing_id,
         price_per_token,
         quantity,
         allow_partial,
         fee_payment_method,
         x402_payload,
-    };
+        ..Default::default()
+    });
 
     let listing_id_bytes = listing_id.to_le_bytes();
-    let seeds: &[&[u8]] = &[b"vault", seller.pubkey().as_ref(), &listing_id_bytes];
-    let (vault_authority, _bump) = Pubkey::find_program_address(seeds, &program_test.program_id);
+    let seller_pubkey = seller.pubkey();
+    let seeds: &[&[u8]] = &[b"vault", seller_pubkey.as_ref(), &listing_id_bytes];
+    let (vault_authority, _bump) = Pubkey::find_program_address(seeds, &program_id);
     
-    let vault_token_account = Pubkey::new_unique();
+    let vault_token_account = get_associated_token_address(&vault_authority, &base_mint);
 
     let accounts = vec![
         AccountMeta::new(seller.pubkey(), true),
@@ -470,10 +458,11 @@ async fn test_x402_fee_calculation() {
         AccountMeta::new_readonly(base_mint, false),
         AccountMeta::new_readonly(quote_mint, false),
         AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(find_config_address(&program_id).0, false),
     ];
 
     let instruction = Instruction {
-        program_id: program_test.program_id,
+        program_id,
         accounts,
         data: instruction_data.try_to_vec().unwrap(),
     };
@@ -486,23 +475,19 @@ async fn test_x402_fee_calculation() {
         executable: false,
         rent_epoch: 0,
     };
-    banks_client
-        .set_account(&seller.pubkey(), &seller_account)
-        .await
-        .unwrap();
+    program_test.add_account(seller.pubkey(), seller_account);
 
     // Create listing account with required space
     let listing_account = Account {
         lamports: 1_000_000,
         data: vec![0; Listing::LEN],
-        owner: program_test.program_id,
+        owner: program_id,
         executable: false,
         rent_epoch: 0,
     };
-    banks_client
-        .set_account(&listing.pubkey(), &listing_account)
-        .await
-        .unwrap();
+    program_test.add_account(listing.pubkey(), listing_account);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
 
     let transaction = Transaction::new_signed_with_payer(
         &[instruction],
@@ -523,9 +508,35 @@ async fn test_x402_fee_calculation() {
     
     let listing_data = Listing::try_from_slice(&listing_account.data).unwrap();
     
-    // Verify fee calculation: (10_000_000 * 1_000_000_000) / 100 = 100_000_000_000
+    // Verify fee calculation: (10_000_000 * 1_000_000_000) / 100 = 100_000_000_000_000
     let expected_fee = (price_per_token as u128 * quantity as u128 / 100) as u64;
     assert_eq!(listing_data.fee_amount_paid, expected_fee);
-    assert_eq!(listing_data.fee_amount_paid, 100_000_000_000u64);
+    assert_eq!(listing_data.fee_amount_paid, 100_000_000_000_000u64);
 }
 
+/// A payload of exactly `MAX_X402_PAYLOAD_LEN` bytes is accepted; one byte more
+/// fails with `X402PayloadTooLarge`.
+#[tokio::test]
+async fn test_x402_payload_size_limit() {
+    let mut env = common::start().await;
+    let args = ListingArgs {
+        fee_payment_method: 1,
+        x402_payload: Some("p".repeat(MAX_X402_PAYLOAD_LEN)),
+        ..ListingArgs::default()
+    };
+    let fixture = env.setup_listing(args.clone()).await;
+    assert_eq!(env.listing(&fixture.listing).await.fee_payment_method, 1);
+
+    let listing = env.create_listing_account(&fixture.seller).await;
+    let fixture = ListingFixture {
+        listing: listing.pubkey(),
+        ..fixture
+    };
+    let args = ListingArgs {
+        x402_payload: Some("p".repeat(MAX_X402_PAYLOAD_LEN + 1)),
+        ..args
+    };
+    let ix = common::initialize_ix(&env.program_id, &fixture, &args);
+    let err = env.process(&[ix], &[&fixture.seller]).await.unwrap_err();
+    assert_eq!(custom_error_code(err), Some(EscrowError::X402PayloadTooLarge as u32));
+}
@@ -33,6 +33,7 @@ import {
   LISTING_ACCOUNT_SIZE,
   decodeListingAccount,
   formatPublicKey,
+  resolveFeeAccounts,
   type FeeMethodLabel,
   type ListingStatusLabel,
 } from "@/lib/escrow";
@@ -140,6 +141,26 @@ const encodeU64LE = (value: bigint): Uint8Array => {
   return new Uint8Array(buffer);
 };
 
+const encodeU16LE = (value: number): Uint8Array => {
+  if (!Number.isInteger(value) || value < 0 || value > 0xffff) {
+    throw new Error("Value out of range for u16");
+  }
+  const buffer = new ArrayBuffer(2);
+  const view = new DataView(buffer);
+  view.setUint16(0, value, true);
+  return new Uint8Array(buffer);
+};
+
+const encodeI32LE = (value: number): Uint8Array => {
+  if (!Number.isInteger(value) || value < -0x80000000 || value > 0x7fffffff) {
+    throw new Error("Value out of range for i32");
+  }
+  const buffer = new ArrayBuffer(4);
+  const view = new DataView(buffer);
+  view.setInt32(0, value, true);
+  return new Uint8Array(buffer);
+};
+
 const encodeU32LE = (value: number): Uint8Array => {
   if (!Number.isInteger(value) || value < 0 || value > 0xffffffff) {
     throw new Error("Value out of range for u32");
@@ -150,6 +171,16 @@ const encodeU32LE = (value: number): Uint8Array => {
   return new Uint8Array(buffer);
 };
 
+const encodeNote = (note: string): Uint8Array => {
+  const bytes = textEncoder.encode(note);
+  if (bytes.length > 64) {
+    throw new Error("Note must be at most 64 bytes");
+  }
+  const padded = new Uint8Array(64);
+  padded.set(bytes);
+  return padded;
+};
+
 const decimalToBigInt = (input: string, decimals: number): bigint => {
   const sanitized = input.trim();
   if (sanitized.length === 0) {
@@ -174,6 +205,30 @@ const encodeInitializeListing = (
     allowPartial: boolean;
     feePaymentMethod: number;
     x402Payload?: string;
+    requiredAttestationIssuer?: PublicKey;
+    escrowFee?: boolean;
+    vaultCount?: number;
+    trancheInterval?: bigint;
+    cancelAuthority?: PublicKey;
+    minFillBps?: number;
+    maxFillBps?: number;
+    oracle?: PublicKey;
+    spreadBps?: number;
+    minFillQuote?: bigint;
+    auctionEnd?: bigint;
+    triggerPrice?: bigint;
+    maxQuoteRaise?: bigint;
+    tickSize?: bigint;
+    quotePerBaseNum?: bigint;
+    quotePerBaseDen?: bigint;
+    takerFeeBps?: number;
+    category?: number;
+    note?: string;
+    depositDeadline?: bigint;
+    autoCompleteAfterFills?: number;
+    maxSlippageBps?: number;
+    proceedsUnlockTs?: bigint;
+    maxOracleStalenessSecs?: number;
   }
 ): Uint8Array => {
   const variant = Uint8Array.of(0); // InitializeListing discriminant
@@ -196,6 +251,18 @@ const encodeInitializeListing = (
     optionBytes = Uint8Array.of(0);
   }
 
+  const attestationIssuerBytes = params.requiredAttestationIssuer
+    ? concatUint8Arrays([Uint8Array.of(1), params.requiredAttestationIssuer.toBytes()])
+    : Uint8Array.of(0);
+
+  const cancelAuthorityBytes = params.cancelAuthority
+    ? concatUint8Arrays([Uint8Array.of(1), params.cancelAuthority.toBytes()])
+    : Uint8Array.of(0);
+
+  const oracleBytes = params.oracle
+    ? concatUint8Arrays([Uint8Array.of(1), params.oracle.toBytes()])
+    : Uint8Array.of(0);
+
   return concatUint8Arrays([
     variant,
     listingIdBytes,
@@ -204,6 +271,30 @@ const encodeInitializeListing = (
     allowPartialByte,
     feeMethodByte,
     optionBytes,
+    attestationIssuerBytes,
+    Uint8Array.of(params.escrowFee ? 1 : 0),
+    Uint8Array.of(params.vaultCount ?? 1),
+    encodeU64LE(params.trancheInterval ?? 0n),
+    cancelAuthorityBytes,
+    encodeU16LE(params.minFillBps ?? 0),
+    encodeU16LE(params.maxFillBps ?? 0),
+    oracleBytes,
+    encodeI32LE(params.spreadBps ?? 0),
+    encodeU64LE(params.minFillQuote ?? 0n),
+    encodeU64LE(params.auctionEnd ?? 0n),
+    encodeU64LE(params.triggerPrice ?? 0n),
+    encodeU64LE(params.maxQuoteRaise ?? 0n),
+    encodeU64LE(params.tickSize ?? 0n),
+    encodeU64LE(params.quotePerBaseNum ?? 0n),
+    encodeU64LE(params.quotePerBaseDen ?? 0n),
+    encodeU16LE(params.takerFeeBps ?? 0),
+    Uint8Array.of(params.category ?? 0),
+    encodeNote(params.note ?? ""),
+    encodeU64LE(params.depositDeadline ?? 0n),
+    encodeU16LE(params.autoCompleteAfterFills ?? 0),
+    encodeU16LE(params.maxSlippageBps ?? 0),
+    encodeU64LE(params.proceedsUnlockTs ?? 0n),
+    encodeU32LE(params.maxOracleStalenessSecs ?? 0),
   ]);
 };
 
@@ -420,6 +511,8 @@ export default function MarketPage() {
           x402Payload,
         });
 
+        const feeAccounts = await resolveFeeAccounts(connection, quoteMintKey, publicKey);
+
         const createListingAccountIx = SystemProgram.createAccount({
           fromPubkey: publicKey,
           newAccountPubkey: listingAccount.publicKey,
@@ -434,10 +527,14 @@ export default function MarketPage() {
             { pubkey: publicKey, isSigner: true, isWritable: true },
             { pubkey: listingAccount.publicKey, isSigner: false, isWritable: true },
             { pubkey: vaultAuthority, isSigner: false, isWritable: false },
-            { pubkey: vaultTokenAccount, isSigner: false, isWritable: false },
+            { pubkey: vaultTokenAccount, isSigner: false, isWritable: true },
             { pubkey: baseMintKey, isSigner: false, isWritable: false },
             { pubkey: quoteMintKey, isSigner: false, isWritable: false },
             { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+            ...feeAccounts,
+            // Lets the program create the vault ATA up front (idempotently).
+            { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+            { pubkey: ASSOCIATED_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
           ],
           data: Buffer.from(instructionData),
         });
@@ -1,10 +1,12 @@
-import { PublicKey } from "@solana/web3.js";
+import { PublicKey, type AccountMeta, type Connection } from "@solana/web3.js";
+import { Buffer } from "buffer";
 
 export const ESCROW_PROGRAM_ID = new PublicKey("8DbZKwhFKq1Zi7HGSKfs6AsqS5CLWNCPZkQFuMKsntVt");
-export const LISTING_ACCOUNT_SIZE = 205;
+export const LISTING_ACCOUNT_SIZE = 733;
 
-export type ListingStatusLabel = "Awaiting Deposit" | "Active" | "Completed" | "Cancelled" | "Unknown";
-export type FeeMethodLabel = "SOL" | "x402" | "Unknown";
+export type ListingStatusLabel = "Awaiting Deposit" | "Active" | "Completed" | "Cancelled" | "Frozen" | "Unknown";
+export type FeeMethodLabel = "SOL" | "x402" | "SPL" | "Unknown";
+export type CategoryLabel = "Uncategorized" | "Governance" | "Meme" | "Stablecoin" | "Utility" | "Unknown";
 
 export interface ListingAccount {
   pubkey: PublicKey;
@@ -17,12 +19,56 @@ export interface ListingAccount {
   filled: bigint;
   listingId: bigint;
   allowPartial: boolean;
+  feeEscrowed: boolean;
+  isBasket: boolean;
+  countsTowardLimit: boolean;
+  hasStatusHistory: boolean;
   vaultBump: number;
   status: ListingStatusLabel;
   baseDecimals: number;
   feePaymentMethod: FeeMethodLabel;
   feeAmountPaid: bigint;
   x402PayloadHash: string;
+  feeTreasury: PublicKey;
+  requiredAttestationIssuer: PublicKey | null;
+  vaultCount: number;
+  trancheInterval: bigint;
+  activatedAt: bigint;
+  cancelAuthority: PublicKey | null;
+  minFillBps: number;
+  maxFillBps: number;
+  oracle: PublicKey | null;
+  spreadBps: number;
+  interestCount: bigint;
+  minFillQuote: bigint;
+  allowedBuyer: PublicKey | null;
+  auctionEnd: bigint;
+  highBid: bigint;
+  highBidder: PublicKey | null;
+  feeMint: PublicKey | null;
+  triggerPrice: bigint;
+  maxQuoteRaise: bigint;
+  quoteReceived: bigint;
+  tickSize: bigint;
+  canonicalId: string;
+  quotePerBaseNum: bigint;
+  quotePerBaseDen: bigint;
+  takerFeeBps: number;
+  category: CategoryLabel;
+  note: string;
+  hasAllowlist: boolean;
+  depositDeadline: bigint;
+  proceedsAccrued: bigint;
+  autoCompleteAfterFills: number;
+  fillCount: number;
+  maxSlippageBps: number;
+  claimCount: number;
+  claimsDesired: bigint;
+  claimsCloseAt: bigint;
+  claimsSupply: bigint;
+  cancellationFee: bigint;
+  proceedsUnlockTs: bigint;
+  maxOracleStalenessSecs: number;
 }
 
 const statusFromByte = (value: number): ListingStatusLabel => {
@@ -35,6 +81,8 @@ const statusFromByte = (value: number): ListingStatusLabel => {
       return "Completed";
     case 3:
       return "Cancelled";
+    case 4:
+      return "Frozen";
     default:
       return "Unknown";
   }
@@ -46,6 +94,25 @@ const feeMethodFromByte = (value: number): FeeMethodLabel => {
       return "SOL";
     case 1:
       return "x402";
+    case 2:
+      return "SPL";
+    default:
+      return "Unknown";
+  }
+};
+
+const categoryFromByte = (value: number): CategoryLabel => {
+  switch (value) {
+    case 0:
+      return "Uncategorized";
+    case 1:
+      return "Governance";
+    case 2:
+      return "Meme";
+    case 3:
+      return "Stablecoin";
+    case 4:
+      return "Utility";
     default:
       return "Unknown";
   }
@@ -80,6 +147,50 @@ export const decodeListingAccount = (data: Uint8Array, pubkey: PublicKey): Listi
   const feeAmountPaid = readBigUInt64LE(view, 165);
   const hashBytes = Array.from(data.subarray(173, 205));
   const x402PayloadHash = hashBytes.map((value) => value.toString(16).padStart(2, "0")).join("");
+  const feeTreasury = new PublicKey(data.subarray(205, 237));
+  const attestationIssuer = new PublicKey(data.subarray(237, 269));
+  const vaultCount = data[269];
+  const trancheInterval = view.getBigInt64(270, true);
+  const activatedAt = view.getBigInt64(278, true);
+  const cancelAuthority = new PublicKey(data.subarray(286, 318));
+  const minFillBps = view.getUint16(318, true);
+  const maxFillBps = view.getUint16(320, true);
+  const oracle = new PublicKey(data.subarray(322, 354));
+  const spreadBps = view.getInt32(354, true);
+  const interestCount = readBigUInt64LE(view, 358);
+  const minFillQuote = readBigUInt64LE(view, 366);
+  const allowedBuyer = new PublicKey(data.subarray(374, 406));
+  const auctionEnd = view.getBigInt64(406, true);
+  const highBid = readBigUInt64LE(view, 414);
+  const highBidder = new PublicKey(data.subarray(422, 454));
+  const feeMint = new PublicKey(data.subarray(454, 486));
+  const triggerPrice = readBigUInt64LE(view, 486);
+  const maxQuoteRaise = readBigUInt64LE(view, 494);
+  const quoteReceived = readBigUInt64LE(view, 502);
+  const tickSize = readBigUInt64LE(view, 510);
+  const canonicalId = Array.from(data.subarray(518, 550))
+    .map((value) => value.toString(16).padStart(2, "0"))
+    .join("");
+  const quotePerBaseNum = readBigUInt64LE(view, 550);
+  const quotePerBaseDen = readBigUInt64LE(view, 558);
+  const takerFeeBps = view.getUint16(566, true);
+  const categoryByte = data[568];
+  const noteBytes = data.subarray(569, 633);
+  const noteEnd = noteBytes.indexOf(0);
+  const hasAllowlist = data.subarray(633, 665).some((value) => value !== 0);
+  const depositDeadline = view.getBigInt64(665, true);
+  const proceedsAccrued = readBigUInt64LE(view, 673);
+  const autoCompleteAfterFills = view.getUint16(681, true);
+  const fillCount = view.getUint16(683, true);
+  const maxSlippageBps = view.getUint16(685, true);
+  const claimCount = view.getUint16(687, true);
+  const claimsDesired = readBigUInt64LE(view, 689);
+  const claimsCloseAt = view.getBigInt64(697, true);
+  const claimsSupply = readBigUInt64LE(view, 705);
+  const cancellationFee = readBigUInt64LE(view, 713);
+  const proceedsUnlockTs = view.getBigInt64(721, true);
+  const maxOracleStalenessSecs = view.getUint32(729, true);
+  const note = new TextDecoder().decode(noteEnd === -1 ? noteBytes : noteBytes.subarray(0, noteEnd));
 
   return {
     pubkey,
@@ -92,15 +203,135 @@ export const decodeListingAccount = (data: Uint8Array, pubkey: PublicKey): Listi
     filled,
     listingId,
     allowPartial: (flags & 0b0000_0001) === 1,
+    feeEscrowed: (flags & 0b0000_0100) !== 0,
+    isBasket: (flags & 0b0000_1000) !== 0,
+    countsTowardLimit: (flags & 0b0001_0000) !== 0,
+    hasStatusHistory: (flags & 0b0010_0000) !== 0,
     vaultBump,
     status: statusFromByte(statusByte),
     baseDecimals,
     feePaymentMethod: feeMethodFromByte(feeMethodByte),
     feeAmountPaid,
     x402PayloadHash,
+    feeTreasury,
+    requiredAttestationIssuer: attestationIssuer.equals(PublicKey.default) ? null : attestationIssuer,
+    vaultCount,
+    trancheInterval,
+    activatedAt,
+    cancelAuthority: cancelAuthority.equals(PublicKey.default) ? null : cancelAuthority,
+    minFillBps,
+    maxFillBps,
+    oracle: oracle.equals(PublicKey.default) ? null : oracle,
+    spreadBps,
+    interestCount,
+    minFillQuote,
+    allowedBuyer: allowedBuyer.equals(PublicKey.default) ? null : allowedBuyer,
+    auctionEnd,
+    highBid,
+    highBidder: highBidder.equals(PublicKey.default) ? null : highBidder,
+    feeMint: feeMint.equals(PublicKey.default) ? null : feeMint,
+    triggerPrice,
+    maxQuoteRaise,
+    quoteReceived,
+    tickSize,
+    canonicalId,
+    quotePerBaseNum,
+    quotePerBaseDen,
+    takerFeeBps,
+    category: categoryFromByte(categoryByte),
+    note,
+    hasAllowlist,
+    depositDeadline,
+    proceedsAccrued,
+    autoCompleteAfterFills,
+    fillCount,
+    maxSlippageBps,
+    claimCount,
+    claimsDesired,
+    claimsCloseAt,
+    claimsSupply,
+    cancellationFee,
+    proceedsUnlockTs,
+    maxOracleStalenessSecs,
   };
 };
 
+export const findConfigAddress = (): PublicKey => {
+  const [address] = PublicKey.findProgramAddressSync([Buffer.from("config")], ESCROW_PROGRAM_ID);
+  return address;
+};
+
+export const findTreasuryAddress = (quoteMint: PublicKey): PublicKey => {
+  const [address] = PublicKey.findProgramAddressSync(
+    [Buffer.from("treasury"), quoteMint.toBuffer()],
+    ESCROW_PROGRAM_ID
+  );
+  return address;
+};
+
+export const findFeeExemptAddress = (seller: PublicKey): PublicKey => {
+  const [address] = PublicKey.findProgramAddressSync(
+    [Buffer.from("fee_exempt"), seller.toBuffer()],
+    ESCROW_PROGRAM_ID
+  );
+  return address;
+};
+
+export const findSellerStatsAddress = (seller: PublicKey): PublicKey => {
+  const [address] = PublicKey.findProgramAddressSync(
+    [Buffer.from("seller_stats"), seller.toBuffer()],
+    ESCROW_PROGRAM_ID
+  );
+  return address;
+};
+
+export const findStatusHistoryAddress = (listing: PublicKey): PublicKey => {
+  const [address] = PublicKey.findProgramAddressSync(
+    [Buffer.from("status_history"), listing.toBuffer()],
+    ESCROW_PROGRAM_ID
+  );
+  return address;
+};
+
+/**
+ * Trailing InitializeListing accounts: the config PDA, plus the quote mint's
+ * treasury route, resolved treasury wallet and the seller's fee exemption PDA
+ * once the config exists, and the seller stats PDA when active listings are capped.
+ */
+export const resolveFeeAccounts = async (
+  connection: Connection,
+  quoteMint: PublicKey,
+  seller: PublicKey
+): Promise<AccountMeta[]> => {
+  const config = findConfigAddress();
+  const configInfo = await connection.getAccountInfo(config);
+  const keys: AccountMeta[] = [{ pubkey: config, isSigner: false, isWritable: false }];
+  if (!configInfo || configInfo.data.length === 0) {
+    return keys;
+  }
+
+  const route = findTreasuryAddress(quoteMint);
+  const routeInfo = await connection.getAccountInfo(route);
+  // Config layout: admin (32) | default_treasury (32) | max_active_listings (u32) | fee_mint (32)
+  // | pending_admin (32) | max_listing_quantity (u64) | burn_bps (u16) | cancellation_fee (u64)
+  // | min_listing_quantity (u64) | spl_fee_amount (u64) | native_fee_lamports (u64);
+  // route layout: quote_mint (32) | treasury (32).
+  const treasury =
+    routeInfo && routeInfo.data.length >= 64
+      ? new PublicKey(routeInfo.data.subarray(32, 64))
+      : new PublicKey(configInfo.data.subarray(32, 64));
+
+  keys.push(
+    { pubkey: route, isSigner: false, isWritable: false },
+    { pubkey: treasury, isSigner: false, isWritable: true },
+    { pubkey: findFeeExemptAddress(seller), isSigner: false, isWritable: false }
+  );
+  if (configInfo.data.length >= 68 && configInfo.data.readUInt32LE(64) > 0) {
+    keys.push({ pubkey: findSellerStatsAddress(seller), isSigner: false, isWritable: true });
+  }
+  return keys;
+};
+
 export const formatPublicKey = (key: PublicKey, start = 4, end = 4) => {
   const base58 = key.toBase58();
   if (base58.length <= start + end) {